@@ -15,7 +15,7 @@ use std::{
 
 use driver_ipc::{
     sync::{DriverClient, EventsSubscription},
-    Dimen, EventCommand, Id, Mode, Monitor, RefreshRate,
+    Blanking, Dimen, EventCommand, Id, Mode, Monitor, RefreshRate,
 };
 use pyo3::prelude::*;
 use pyo3::{
@@ -174,7 +174,11 @@ impl PyTypedList {
                 if let Ok(mode) = item_b.downcast_exact::<PyMode>().map(Bound::borrow) {
                     for (i, item) in inner.iter().enumerate() {
                         let item = item.downcast_exact::<PyMode>()?.borrow();
-                        if item.width == mode.width && item.height == mode.height && i != index {
+                        if item.width == mode.width
+                            && item.height == mode.height
+                            && item.interlaced == mode.interlaced
+                            && i != index
+                        {
                             return Err(PyRuntimeError::new_err(format!(
                                 "modes list already contains a Mode {}x{}",
                                 item.width, item.height,
@@ -416,7 +420,7 @@ impl PyDriverClient {
         let event_subscription = self.client.add_event_receiver(move |data| match data {
             Ok(cmd) => {
                 let EventCommand::Changed(data) = cmd else {
-                    unreachable!()
+                    return;
                 };
 
                 Python::with_gil(|py| {
@@ -691,7 +695,7 @@ impl Debug for PyMonitor {
 }
 
 /// A monitor mode which represents a resolution and associated refresh rates.
-/// Each mode must be unique from other modes (a unique width x height),
+/// Each mode must be unique from other modes (a unique width x height and scan),
 /// and the refresh rates must also be unique per mode
 #[pyclass]
 #[pyo3(name = "Mode")]
@@ -708,6 +712,12 @@ struct PyMode {
     /// Sig: refresh_rates: list[int]
     #[pyo3(get)]
     refresh_rates: Py<PyTypedList>,
+    /// Whether the mode is interlaced. The refresh rates are field rates then
+    /// Sig: interlaced: bool
+    #[pyo3(get, set)]
+    interlaced: bool,
+    // custom blanking, kept as is so it survives a round trip through python
+    blanking: Option<Blanking>,
 }
 
 impl Clone for PyMode {
@@ -716,6 +726,8 @@ impl Clone for PyMode {
             width: self.width,
             height: self.height,
             refresh_rates: self.refresh_rates.clone_ref(py),
+            interlaced: self.interlaced,
+            blanking: self.blanking,
         })
     }
 }
@@ -735,6 +747,8 @@ impl Debug for PyMode {
                 width,
                 height,
                 refresh_rates,
+                interlaced,
+                blanking,
             } = self;
 
             let refresh_rates = refresh_rates
@@ -747,6 +761,8 @@ impl Debug for PyMode {
                 .field("width", &width)
                 .field("height", &height)
                 .field("refresh_rates", &refresh_rates)
+                .field("interlaced", &interlaced)
+                .field("blanking", &blanking)
                 .finish()
         })
     }
@@ -760,6 +776,8 @@ impl PyMode {
             width: 0,
             height: 0,
             refresh_rates: PyTypedList::new(py, ListType::RefreshRate).try_into()?,
+            interlaced: false,
+            blanking: None,
         };
 
         Ok(inst)
@@ -808,6 +826,8 @@ fn state_to_pylist(py: Python, monitors: &[Monitor]) -> PyResult<Py<PyList>> {
                     ListType::RefreshRate,
                 )
                 .try_into()?,
+                interlaced: mode.interlaced,
+                blanking: mode.blanking,
             }
             .try_into()?;
 
@@ -859,6 +879,8 @@ fn pytypedlist_to_state(py: Python, monitors: &Py<PyTypedList>) -> PyResult<Vec<
                 width: mode.width,
                 height: mode.height,
                 refresh_rates,
+                interlaced: mode.interlaced,
+                blanking: mode.blanking,
             });
         }
 
@@ -956,7 +978,10 @@ fn pylist_append_pyobject(
                 let m = mode.borrow();
                 for item in inner.iter() {
                     let item = item.downcast_exact::<PyMode>().unwrap().borrow();
-                    if item.width == m.width && item.height == m.height {
+                    if item.width == m.width
+                        && item.height == m.height
+                        && item.interlaced == m.interlaced
+                    {
                         return Err(PyRuntimeError::new_err(format!(
                             "modes list already contains a Mode {}x{}",
                             m.width, m.height,
@@ -974,7 +999,7 @@ fn pylist_append_pyobject(
                     if let Ok(mode) = item.downcast_exact::<PyMode>() {
                         let b = mode.borrow();
 
-                        if !used.insert((b.width, b.height)) {
+                        if !used.insert((b.width, b.height, b.interlaced)) {
                             return Err(PyRuntimeError::new_err(format!(
                                 "list of Mode already contains a Mode {}x{}",
                                 b.width, b.height
@@ -983,7 +1008,10 @@ fn pylist_append_pyobject(
 
                         for item in inner.iter() {
                             let item = item.downcast_exact::<PyMode>().unwrap().borrow();
-                            if item.width == b.width && item.height == b.height {
+                            if item.width == b.width
+                                && item.height == b.height
+                                && item.interlaced == b.interlaced
+                            {
                                 return Err(PyRuntimeError::new_err(format!(
                                     "modes list already contains a Mode {}x{}",
                                     b.width, b.height,
@@ -84,7 +84,52 @@ impl Client {
         Ok(Self { shared, command_rx })
     }
 
+    /// Find the pipe names of all running driver instances, which can be
+    /// passed to [Client::connect_to].
+    ///
+    /// See [config::pipe_name] for how the pipes are named.
+    pub fn discover() -> Result<Vec<String>, error::DiscoverError> {
+        use windows::{
+            core::w,
+            Win32::{
+                Foundation::ERROR_NO_MORE_FILES,
+                Storage::FileSystem::{FindClose, FindFirstFileW, FindNextFileW, WIN32_FIND_DATAW},
+            },
+        };
+
+        let mut data = WIN32_FIND_DATAW::default();
+        let handle = unsafe { FindFirstFileW(w!(r"\\.\pipe\*"), &mut data)? };
+
+        let mut names = Vec::new();
+        let result = loop {
+            let len = data
+                .cFileName
+                .iter()
+                .position(|&c| c == 0)
+                .unwrap_or(data.cFileName.len());
+            let name = String::from_utf16_lossy(&data.cFileName[..len]);
+
+            if config::is_driver_pipe(&name) {
+                names.push(name);
+            }
+
+            match unsafe { FindNextFileW(handle, &mut data) } {
+                Ok(()) => (),
+                Err(e) if e.code() == ERROR_NO_MORE_FILES.to_hresult() => break Ok(names),
+                Err(e) => break Err(e.into()),
+            }
+        };
+
+        _ = unsafe { FindClose(handle) };
+
+        result
+    }
+
     /// Send new state to the driver.
+    ///
+    /// The driver doesn't answer, an invalid state or one over its limits is
+    /// rejected through [Client::receive_errors]. Check it with
+    /// [config::validate] and [config::check_limits] first.
     pub async fn notify(&self, monitors: &[Monitor]) -> Result<(), error::SendError> {
         let command = DriverCommand::Notify(monitors.to_owned());
 
@@ -108,23 +153,208 @@ impl Client {
         Ok(())
     }
 
+    /// Set the catalogue of modes the driver gives monitors without modes.
+    ///
+    /// Monitors the driver already knows are not changed. The driver doesn't
+    /// answer, a catalogue with modes no timing can be built for is rejected
+    /// through [Client::receive_errors]. Check it with
+    /// [DefaultModes::validate](defaults::DefaultModes::validate) first.
+    pub async fn set_default_modes(
+        &self,
+        defaults: &defaults::DefaultModes,
+    ) -> Result<(), error::SendError> {
+        let command = DriverCommand::SetDefaultModes(defaults.clone());
+
+        send_command(&self.shared.client, &command).await?;
+        Ok(())
+    }
+
+    /// Select the gpu which renders the monitors, see
+    /// [Client::request_render_adapters].
+    ///
+    /// The driver keeps the os's choice if nothing matches. `None` stops
+    /// selecting a gpu, the current one stays in use until the driver restarts.
+    pub async fn set_render_adapter(
+        &self,
+        preference: Option<&render_adapter::Preference>,
+    ) -> Result<(), error::SendError> {
+        let command = DriverCommand::SetRenderAdapter(preference.cloned());
+
+        send_command(&self.shared.client, &command).await?;
+        Ok(())
+    }
+
+    /// Change the level of the driver log.
+    ///
+    /// With a `module_filter`, like `virtual_display_driver::ipc`, the level
+    /// only applies to that module and its submodules, every other module
+    /// keeps the current level. The level is reset when the driver restarts.
+    pub async fn set_log_level(
+        &self,
+        level: LogLevel,
+        module_filter: Option<&str>,
+    ) -> Result<(), error::SendError> {
+        let command = DriverCommand::SetLogLevel {
+            level,
+            module_filter: module_filter.map(ToOwned::to_owned),
+        };
+
+        send_command(&self.shared.client, &command).await?;
+        Ok(())
+    }
+
+    /// Start or stop exporting the frames of the monitor with the specified
+    /// ID into shared memory, see the `frame-export` crate.
+    ///
+    /// The export continues across mode changes and while the monitor is
+    /// off, until it's stopped or the driver restarts.
+    pub async fn set_frame_export(&self, id: Id, enabled: bool) -> Result<(), error::SendError> {
+        let command = DriverCommand::SetFrameExport { id, enabled };
+
+        send_command(&self.shared.client, &command).await?;
+        Ok(())
+    }
+
     /// Request the current state of the driver.
     ///
     /// Returns [IpcError::Timeout] if the driver does not respond within 5
     /// seconds.
     pub async fn request_state(&self) -> Result<Vec<Monitor>, error::RequestError> {
+        self.request(&RequestCommand::State, |reply| match reply {
+            ReplyCommand::State(monitors) => Some(monitors),
+            _ => None,
+        })
+        .await
+    }
+
+    /// Request the EDID the monitor with the specified ID was plugged in
+    /// with.
+    ///
+    /// Returns `None` if the driver does not know the monitor, or it is not
+    /// plugged in. The EDID can be decoded with
+    /// [Edid::parse](crate::edid::Edid::parse).
+    ///
+    /// Returns [IpcError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub async fn request_edid(&self, id: Id) -> Result<Option<Vec<u8>>, error::RequestError> {
+        self.request(&RequestCommand::GetEdid(id), |reply| match reply {
+            ReplyCommand::Edid(reply_id, edid) if reply_id == id => Some(edid),
+            _ => None,
+        })
+        .await
+    }
+
+    /// Request the limits the driver was configured with.
+    ///
+    /// The driver rejects [Client::notify] with more enabled monitors than
+    /// [Limits::max_monitors], or with enabled monitors whose ids aren't below
+    /// it, see [Client::receive_errors].
+    ///
+    /// Returns [IpcError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub async fn request_limits(&self) -> Result<Limits, error::RequestError> {
+        self.request(&RequestCommand::Limits, |reply| match reply {
+            ReplyCommand::Limits(limits) => Some(limits),
+            _ => None,
+        })
+        .await
+    }
+
+    /// Request the catalogue of modes the driver gives monitors without
+    /// modes, see [Client::set_default_modes].
+    ///
+    /// Returns [IpcError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub async fn request_default_modes(
+        &self,
+    ) -> Result<defaults::DefaultModes, error::RequestError> {
+        self.request(&RequestCommand::DefaultModes, |reply| match reply {
+            ReplyCommand::DefaultModes(defaults) => Some(defaults),
+            _ => None,
+        })
+        .await
+    }
+
+    /// Request the gpus which can render the monitors.
+    ///
+    /// Returns [IpcError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub async fn request_render_adapters(
+        &self,
+    ) -> Result<Vec<render_adapter::RenderAdapter>, error::RequestError> {
+        self.request(&RequestCommand::ListRenderAdapters, |reply| match reply {
+            ReplyCommand::RenderAdapters(adapters) => Some(adapters),
+            _ => None,
+        })
+        .await
+    }
+
+    /// Request the log entries the driver keeps in memory, oldest first.
+    ///
+    /// Only entries after the one with sequence number `since` (all if
+    /// `None`) which are at least as severe as `min_level` are returned.
+    ///
+    /// Returns [IpcError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub async fn request_recent_logs(
+        &self,
+        since: Option<u64>,
+        min_level: LogLevel,
+    ) -> Result<Vec<LogEntry>, error::RequestError> {
+        self.request(
+            &RequestCommand::RecentLogs { since, min_level },
+            |reply| match reply {
+                ReplyCommand::RecentLogs(entries) => Some(entries),
+                _ => None,
+            },
+        )
+        .await
+    }
+
+    /// Request the report the driver wrote when it last panicked, `None` if
+    /// it didn't panic yet.
+    ///
+    /// Returns [IpcError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub async fn request_crash_report(&self) -> Result<Option<CrashReport>, error::RequestError> {
+        self.request(&RequestCommand::CrashReport, |reply| match reply {
+            ReplyCommand::CrashReport(report) => Some(report),
+            _ => None,
+        })
+        .await
+    }
+
+    /// Request the frame statistics of the enabled monitors.
+    ///
+    /// Returns [IpcError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub async fn request_frame_stats(&self) -> Result<Vec<FrameStats>, error::RequestError> {
+        self.request(&RequestCommand::FrameStats, |reply| match reply {
+            ReplyCommand::FrameStats(stats) => Some(stats),
+            _ => None,
+        })
+        .await
+    }
+
+    /// Send a request and wait for the first reply `filter` accepts.
+    async fn request<T>(
+        &self,
+        command: &RequestCommand,
+        mut filter: impl FnMut(ReplyCommand) -> Option<T>,
+    ) -> Result<T, error::RequestError> {
         use broadcast::error::RecvError;
 
         let mut rx = self.command_rx.resubscribe();
 
-        send_command(&self.shared.client, &RequestCommand::State).await?;
+        send_command(&self.shared.client, command).await?;
 
         let fut = async {
             loop {
                 match rx.recv().await {
-                    Ok(Ok(ClientCommand::Reply(ReplyCommand::State(monitors)))) => {
-                        break Ok(monitors)
-                    }
+                    Ok(Ok(ClientCommand::Reply(reply))) => match filter(reply) {
+                        Some(value) => break Ok(value),
+                        None => continue,
+                    },
                     Ok(Err(e)) => break Err(error::RequestError::Receive(e.0.clone())),
                     Ok(_) => continue,
                     Err(RecvError::Lagged(_n)) => continue,
@@ -169,10 +399,66 @@ impl Client {
         })
     }
 
+    /// Subscribe to the driver log.
+    ///
+    /// The returned stream receives every entry the driver logs from now on
+    /// which is at least as severe as `min_level`, as [EventCommand::Log].
+    /// Other events are left out. The subscription ends when all copies of
+    /// this client are dropped.
+    pub async fn subscribe_logs(
+        &self,
+        min_level: LogLevel,
+    ) -> Result<impl Stream<Item = Result<EventCommand, error::ReceiveError>>, error::SendError>
+    {
+        // subscribe before sending, so no entry is missed
+        let stream = self.receive_events();
+
+        let command = RequestCommand::SubscribeLogs { min_level };
+        send_command(&self.shared.client, &command).await?;
+
+        Ok(stream.filter(|event| matches!(event, Ok(EventCommand::Log { .. }) | Err(_))))
+    }
+
+    /// Subscribe to the hardware cursor of monitor `id`, or of all monitors
+    /// if `None`.
+    ///
+    /// The returned stream receives the current cursor, and then every move
+    /// or change of it, as [EventCommand::Cursor]. Other events are left out.
+    /// The subscription ends when all copies of this client are dropped.
+    pub async fn subscribe_cursor(
+        &self,
+        id: Option<Id>,
+    ) -> Result<impl Stream<Item = Result<EventCommand, error::ReceiveError>>, error::SendError>
+    {
+        // subscribe before sending, so the current cursor isn't missed
+        let stream = self.receive_events();
+
+        let command = RequestCommand::SubscribeCursor { id };
+        send_command(&self.shared.client, &command).await?;
+
+        Ok(stream.filter(|event| matches!(event, Ok(EventCommand::Cursor(_)) | Err(_))))
+    }
+
+    /// Receive the errors of driver commands the driver rejected.
+    ///
+    /// Only errors after calling this method are received. A rejected
+    /// command doesn't change anything in the driver.
+    pub fn receive_errors(&self) -> impl Stream<Item = DriverError> {
+        use tokio_stream::wrappers::*;
+
+        let stream = BroadcastStream::new(self.command_rx.resubscribe());
+
+        stream.filter_map(|cmd| match cmd {
+            Ok(Ok(ClientCommand::Reply(ReplyCommand::Error(e)))) => Some(e),
+            _ => None,
+        })
+    }
+
     /// Write `monitors` to the registry for current user.
     ///
-    /// Next time the driver is started, it will load this state from the
-    /// registry. This might be after a reboot or a driver restart.
+    /// The user session service applies this state when the user logs on.
+    /// The driver itself starts with the machine-wide
+    /// [`Config`](crate::config::Config) of its device registry key.
     pub fn persist(monitors: &[Monitor]) -> Result<(), error::PersistError> {
         use winreg::*;
 
@@ -346,7 +632,8 @@ pub mod error {
         PipeBroken(#[from] io::Error),
     }
 
-    /// Error returned from [Client::request_state].
+    /// Error returned from [Client::request_state], [Client::request_edid]
+    /// and [Client::request_limits].
     #[derive(Debug, Error)]
     pub enum RequestError {
         #[error("Failed to send message (pipe broken): {0}")]
@@ -362,6 +649,11 @@ pub mod error {
     #[error("Failed to receive event: {0}")]
     pub struct ReceiveError(#[from] pub Arc<io::Error>);
 
+    /// Error returned from [Client::discover].
+    #[derive(Debug, Error)]
+    #[error("Failed to list pipes: {0}")]
+    pub struct DiscoverError(#[from] pub windows::core::Error);
+
     /// Error returned from [Client::persist].
     #[derive(Debug, Error)]
     pub enum PersistError {
@@ -459,6 +751,107 @@ mod test {
         );
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn request_edid() {
+        const PIPE_NAME: &str = "virtualdisplaydriver-test-request_edid";
+
+        let mut server = MockServer::new(PIPE_NAME);
+
+        let client = Client::connect_to(PIPE_NAME)
+            .await
+            .expect("Failed to connect to pipe");
+
+        let mons = [Monitor {
+            id: 5,
+            enabled: true,
+            name: None,
+            modes: vec![],
+        }];
+
+        tokio::join!(client.notify(&mons), server.pump())
+            .0
+            .expect("Failed to notify");
+
+        server.check_next(|cmd| {
+            assert!(matches!(
+                cmd,
+                ServerCommand::Request(RequestCommand::GetEdid(5))
+            ));
+        });
+
+        let (edid, _) = tokio::join!(client.request_edid(5), server.pump());
+        let edid = edid.expect("Failed to request edid");
+        assert_eq!(edid, Some(5u32.to_le_bytes().to_vec()));
+
+        let (edid, _) = tokio::join!(client.request_edid(6), server.pump());
+        let edid = edid.expect("Failed to request edid");
+        assert_eq!(edid, None);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn default_modes() {
+        const PIPE_NAME: &str = "virtualdisplaydriver-test-default_modes";
+
+        let mut server = MockServer::new(PIPE_NAME);
+
+        let client = Client::connect_to(PIPE_NAME)
+            .await
+            .expect("Failed to connect to pipe");
+
+        let (defaults, _) = tokio::join!(client.request_default_modes(), server.pump());
+        let defaults = defaults.expect("Failed to request default modes");
+        assert_eq!(defaults, defaults::DefaultModes::default());
+
+        let uhd = defaults::DefaultModes {
+            max_width: 3840,
+            max_height: 2160,
+            ..defaults
+        };
+        tokio::join!(client.set_default_modes(&uhd), server.pump())
+            .0
+            .expect("Failed to set default modes");
+
+        let (defaults, _) = tokio::join!(client.request_default_modes(), server.pump());
+        let defaults = defaults.expect("Failed to request default modes");
+        assert_eq!(defaults, uhd);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn set_frame_export() {
+        const PIPE_NAME: &str = "virtualdisplaydriver-test-set_frame_export";
+
+        let mut server = MockServer::new(PIPE_NAME);
+
+        let client = Client::connect_to(PIPE_NAME)
+            .await
+            .expect("Failed to connect to pipe");
+
+        server.check_next(|cmd| {
+            assert!(matches!(
+                cmd,
+                ServerCommand::Driver(DriverCommand::SetFrameExport {
+                    id: 3,
+                    enabled: true
+                })
+            ));
+        });
+
+        tokio::join!(client.set_frame_export(3, true), server.pump())
+            .0
+            .expect("Failed to set frame export");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn discover() {
+        const PIPE_NAME: &str = "virtualdisplaydriver-test-discover";
+
+        let _server = MockServer::new(PIPE_NAME);
+
+        let names = Client::discover().expect("Failed to discover");
+        assert!(names.iter().any(|name| name == PIPE_NAME));
+        assert!(names.iter().all(|name| config::is_driver_pipe(name)));
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn general_test_1() {
         const PIPE_NAME: &str = "virtualdisplaydriver-test-general_test_1";
@@ -494,6 +887,8 @@ mod test {
                 width: 1920,
                 height: 1080,
                 refresh_rates: vec![60],
+                interlaced: false,
+                blanking: None,
             }],
         }];
 
@@ -523,6 +918,8 @@ mod test {
                     width: 100,
                     height: 200,
                     refresh_rates: vec![80, 90],
+                    interlaced: false,
+                    blanking: None,
                 }],
             },
             Monitor {
@@ -533,6 +930,17 @@ mod test {
                     width: 300,
                     height: 400,
                     refresh_rates: vec![50],
+                    interlaced: true,
+                    blanking: Some(Blanking {
+                        h_front_porch: 8,
+                        h_sync: 32,
+                        h_back_porch: 40,
+                        v_front_porch: 3,
+                        v_sync: 5,
+                        v_back_porch: 10,
+                        h_sync_positive: true,
+                        v_sync_positive: false,
+                    }),
                 }],
             },
         ];
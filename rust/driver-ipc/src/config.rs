@@ -0,0 +1,697 @@
+//! Startup configuration of the driver.
+//!
+//! The driver loads it from its device registry key when it initializes the
+//! adapter. Parsing and validation live here, so they can be tested anywhere,
+//! reading the registry is up to the driver.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    defaults::DefaultModes, render_adapter::Preference, DriverError, Limits, Monitor,
+    DEFAULT_PIPE_NAME,
+};
+
+use self::error::ConfigError;
+
+/// Maximum amount of enabled monitors if the config doesn't set one
+pub const DEFAULT_MAX_MONITORS: u32 = 16;
+
+/// Largest width and height of hardware cursor shapes, in pixels
+pub const MAX_CURSOR_SIZE: u32 = 1024;
+
+/// Monitors and settings the driver starts with
+///
+/// Serialized as json. A bare list of monitors, like the one written by
+/// `Client::persist`, is accepted as well.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct Config {
+    pub monitors: Vec<Monitor>,
+    /// Replaces the default modes of monitors without any if set
+    pub default_modes: Option<DefaultModes>,
+    pub adapter: Adapter,
+    /// Name of the driver instance, which its pipe is named after, see
+    /// [`pipe_name`]. Every device but one needs its own.
+    pub instance: Option<String>,
+    /// Gpu which renders the monitors, the os picks one if unset or nothing
+    /// matches
+    pub render_adapter: Option<Preference>,
+    /// Rotating files the driver writes its log to, besides the event log
+    pub log_file: Option<LogFile>,
+    /// Throttles monitors nobody captures to save power, they render at full
+    /// speed if unset
+    pub eco: Option<Eco>,
+    /// Shapes the hardware cursor of the monitors supports
+    pub cursor: HardwareCursor,
+}
+
+/// Identity and limits of the adapter, which are fixed once it is initialized
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Adapter {
+    /// Maximum amount of enabled monitors
+    pub max_monitors: u32,
+    pub friendly_name: String,
+    pub manufacturer: String,
+    pub model: String,
+}
+
+impl Default for Adapter {
+    fn default() -> Self {
+        Self {
+            max_monitors: DEFAULT_MAX_MONITORS,
+            friendly_name: "Virtual Display Driver Adapter".to_owned(),
+            manufacturer: "Cherry".to_owned(),
+            model: "Pro".to_owned(),
+        }
+    }
+}
+
+impl Adapter {
+    /// The limits clients are told about
+    #[must_use]
+    pub fn limits(&self) -> Limits {
+        Limits {
+            max_monitors: self.max_monitors,
+        }
+    }
+}
+
+/// Where the driver writes its log files and how many it keeps
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct LogFile {
+    /// `%ProgramData%\VirtualDisplayDriver\logs` if unset
+    pub dir: Option<PathBuf>,
+    /// Size in bytes after which a file is rotated
+    pub max_size: u64,
+    /// Amount of files kept, including the current one
+    pub max_files: u32,
+    /// Write one json object per line instead of text, for log collectors
+    pub json: bool,
+}
+
+impl Default for LogFile {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            max_size: 1024 * 1024,
+            max_files: 5,
+            json: false,
+        }
+    }
+}
+
+/// How much monitors whose frames aren't exported are throttled
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Eco {
+    /// Frames per second the driver takes from such a monitor at most
+    pub max_fps: u32,
+}
+
+impl Default for Eco {
+    fn default() -> Self {
+        Self { max_fps: 10 }
+    }
+}
+
+/// Shapes the hardware cursor of a monitor supports, larger ones or unsupported xor cursors are
+/// drawn into the frames by the os instead
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct HardwareCursor {
+    /// Width in pixels of the largest shape (`MaxX`)
+    pub max_width: u32,
+    /// Height in pixels of the largest shape (`MaxY`)
+    pub max_height: u32,
+    /// How cursors which invert the screen below them are handled
+    pub xor: XorCursor,
+}
+
+impl Default for HardwareCursor {
+    fn default() -> Self {
+        Self {
+            max_width: 512,
+            max_height: 512,
+            xor: XorCursor::None,
+        }
+    }
+}
+
+/// Support of cursors which invert the screen below them
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum XorCursor {
+    /// The os draws them into the frames
+    None,
+    /// They're sent as masked color shapes
+    Full,
+    /// The os converts them into alpha shapes, which approximate them
+    Emulation,
+}
+
+// only lives while parsing, so the size doesn't matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Format {
+    Config {
+        #[serde(default)]
+        monitors: Vec<Monitor>,
+        #[serde(default)]
+        default_modes: Option<DefaultModes>,
+        #[serde(default)]
+        adapter: Adapter,
+        #[serde(default)]
+        instance: Option<String>,
+        #[serde(default)]
+        render_adapter: Option<Preference>,
+        #[serde(default)]
+        log_file: Option<LogFile>,
+        #[serde(default)]
+        eco: Option<Eco>,
+        #[serde(default)]
+        cursor: HardwareCursor,
+    },
+    Monitors(Vec<Monitor>),
+}
+
+impl Config {
+    /// Parse and [validate](Config::validate) a configuration
+    pub fn from_json(json: &str) -> Result<Self, ConfigError> {
+        let config = match serde_json::from_str(json)? {
+            Format::Config {
+                monitors,
+                default_modes,
+                adapter,
+                instance,
+                render_adapter,
+                log_file,
+                eco,
+                cursor,
+            } => Self {
+                monitors,
+                default_modes,
+                adapter,
+                instance,
+                render_adapter,
+                log_file,
+                eco,
+                cursor,
+            },
+
+            Format::Monitors(monitors) => Self {
+                monitors,
+                ..Default::default()
+            },
+        };
+
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Check the instance, the adapter, the log file, eco, the cursor, the default modes and the
+    /// monitors, see [`validate`] and [`check_limits`]
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if let Some(instance) = &self.instance {
+            let valid = !instance.is_empty()
+                && instance
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+            if !valid {
+                return Err(ConfigError::InvalidInstance(instance.clone()));
+            }
+        }
+
+        if self.adapter.max_monitors == 0 {
+            return Err(ConfigError::NoMonitors);
+        }
+
+        if let Some(log_file) = &self.log_file {
+            if log_file.max_size == 0 || log_file.max_files == 0 {
+                return Err(ConfigError::InvalidLogFile);
+            }
+        }
+
+        if self.eco.is_some_and(|eco| eco.max_fps == 0) {
+            return Err(ConfigError::InvalidEco);
+        }
+
+        let cursor = (self.cursor.max_width, self.cursor.max_height);
+        if !(1..=MAX_CURSOR_SIZE).contains(&cursor.0) || !(1..=MAX_CURSOR_SIZE).contains(&cursor.1)
+        {
+            return Err(ConfigError::InvalidCursor(cursor.0, cursor.1));
+        }
+
+        if let Some(defaults) = &self.default_modes {
+            defaults
+                .validate()
+                .map_err(ConfigError::InvalidDefaultMode)?;
+        }
+
+        validate(&self.monitors)?;
+        check_limits(&self.monitors, self.adapter.limits())?;
+
+        Ok(())
+    }
+}
+
+/// Name of the pipe of a driver instance, [`DEFAULT_PIPE_NAME`] for the one
+/// without a name
+#[must_use]
+pub fn pipe_name(instance: Option<&str>) -> String {
+    match instance {
+        Some(instance) => format!("{DEFAULT_PIPE_NAME}-{instance}"),
+        None => DEFAULT_PIPE_NAME.to_owned(),
+    }
+}
+
+/// Whether the pipe `name` is one of a driver instance, see [`pipe_name`]
+#[must_use]
+pub fn is_driver_pipe(name: &str) -> bool {
+    match name.strip_prefix(DEFAULT_PIPE_NAME) {
+        Some("") => true,
+        Some(instance) => instance.strip_prefix('-').is_some_and(|i| !i.is_empty()),
+        None => false,
+    }
+}
+
+/// Check that the enabled `monitors` are within `limits`. Their ids are the
+/// connectors of the adapter, so they must be below the maximum too.
+pub fn check_limits(monitors: &[Monitor], limits: Limits) -> Result<(), DriverError> {
+    let enabled = monitors.iter().filter(|m| m.enabled).count();
+    let requested = u32::try_from(enabled).unwrap_or(u32::MAX);
+
+    if requested > limits.max_monitors {
+        return Err(DriverError::TooManyMonitors {
+            max: limits.max_monitors,
+            requested,
+        });
+    }
+
+    if let Some(monitor) = monitors
+        .iter()
+        .find(|m| m.enabled && m.id >= limits.max_monitors)
+    {
+        return Err(DriverError::IdTooHigh {
+            max: limits.max_monitors,
+            id: monitor.id,
+        });
+    }
+
+    Ok(())
+}
+
+/// Check that `monitors` can be applied as a whole: unique monitor ids,
+/// unique modes (width+height+interlaced) per monitor, unique refresh rates
+/// per mode, and a timing for every refresh rate. The driver rejects
+/// notifications which fail this with the same error.
+pub fn validate(monitors: &[Monitor]) -> Result<(), DriverError> {
+    for (i, monitor) in monitors.iter().enumerate() {
+        if monitors[..i].iter().any(|m| m.id == monitor.id) {
+            return Err(DriverError::DuplicateId(monitor.id));
+        }
+
+        for (j, mode) in monitor.modes.iter().enumerate() {
+            let duplicate_mode = monitor.modes[..j].iter().any(|m| {
+                m.width == mode.width && m.height == mode.height && m.interlaced == mode.interlaced
+            });
+            if duplicate_mode {
+                return Err(DriverError::DuplicateMode(
+                    monitor.id,
+                    mode.width,
+                    mode.height,
+                ));
+            }
+
+            for (k, &refresh_rate) in mode.refresh_rates.iter().enumerate() {
+                if mode.refresh_rates[..k].contains(&refresh_rate) {
+                    return Err(DriverError::DuplicateRefreshRate(
+                        monitor.id,
+                        mode.width,
+                        mode.height,
+                        refresh_rate,
+                    ));
+                }
+            }
+
+            mode.validate()
+                .map_err(|e| DriverError::InvalidMode(monitor.id, e))?;
+        }
+    }
+
+    Ok(())
+}
+
+pub mod error {
+    use thiserror::Error;
+
+    use crate::{timing::error::TimingError, DriverError};
+
+    /// Error returned from [`Config::from_json`](super::Config::from_json) and
+    /// [`Config::validate`](super::Config::validate).
+    #[derive(Debug, Error)]
+    pub enum ConfigError {
+        #[error("Failed to parse config: {0}")]
+        Parse(#[from] serde_json::Error),
+        #[error("Invalid default mode: {0}")]
+        InvalidDefaultMode(TimingError),
+        #[error("Invalid instance name {0:?}, only letters, digits, - and _ are allowed")]
+        InvalidInstance(String),
+        #[error("The adapter must support at least 1 monitor")]
+        NoMonitors,
+        #[error("Log files must have a size and at least 1 of them must be kept")]
+        InvalidLogFile,
+        #[error("Eco must allow at least 1 frame per second")]
+        InvalidEco,
+        #[error("Cursor shapes of {0}x{1} are not supported, they must be 1 to {max} pixels wide and high", max = super::MAX_CURSOR_SIZE)]
+        InvalidCursor(u32, u32),
+        #[error(transparent)]
+        Monitors(#[from] DriverError),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{timing::error::TimingError, Mode};
+
+    fn mode(width: u32, height: u32, refresh_rates: &[u32]) -> Mode {
+        Mode {
+            width,
+            height,
+            refresh_rates: refresh_rates.to_vec(),
+            interlaced: false,
+            blanking: None,
+        }
+    }
+
+    fn monitor(id: u32, modes: Vec<Mode>) -> Monitor {
+        Monitor {
+            id,
+            name: None,
+            enabled: true,
+            modes,
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let config = Config::from_json(
+            r#"{
+                "monitors": [
+                    {"id": 0, "name": null, "enabled": true, "modes": [{"width": 1920, "height": 1080, "refresh_rates": [60]}]}
+                ],
+                "default_modes": {"max_width": 3840}
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.monitors, [monitor(0, vec![mode(1920, 1080, &[60])])]);
+        assert_eq!(
+            config.default_modes,
+            Some(DefaultModes {
+                max_width: 3840,
+                ..Default::default()
+            })
+        );
+
+        assert_eq!(config.adapter, Adapter::default());
+
+        assert_eq!(Config::from_json("{}").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn adapter() {
+        let config =
+            Config::from_json(r#"{"adapter": {"max_monitors": 2, "manufacturer": "Acme"}}"#)
+                .unwrap();
+
+        assert_eq!(
+            config.adapter,
+            Adapter {
+                max_monitors: 2,
+                manufacturer: "Acme".to_owned(),
+                ..Default::default()
+            }
+        );
+        assert_eq!(config.adapter.limits(), Limits { max_monitors: 2 });
+
+        assert!(matches!(
+            Config::from_json(r#"{"adapter": {"max_monitors": 0}}"#),
+            Err(ConfigError::NoMonitors)
+        ));
+    }
+
+    #[test]
+    fn render_adapter() {
+        let config = Config::from_json(r#"{"render_adapter": {"Description": "nvidia"}}"#).unwrap();
+        assert_eq!(
+            config.render_adapter,
+            Some(Preference::Description("nvidia".to_owned()))
+        );
+
+        let config = Config::from_json(r#"{"render_adapter": {"Luid": 4294989432}}"#).unwrap();
+        assert_eq!(
+            config.render_adapter,
+            Some(Preference::Luid(crate::render_adapter::luid(0x5678, 1)))
+        );
+    }
+
+    #[test]
+    fn log_file() {
+        let config = Config::from_json(r#"{"log_file": {"max_files": 2, "json": true}}"#).unwrap();
+        assert_eq!(
+            config.log_file,
+            Some(LogFile {
+                max_files: 2,
+                json: true,
+                ..Default::default()
+            })
+        );
+
+        let config = Config::from_json(r#"{"log_file": {"dir": "D:\\logs"}}"#).unwrap();
+        assert_eq!(
+            config.log_file.and_then(|f| f.dir),
+            Some(PathBuf::from(r"D:\logs"))
+        );
+
+        for json in [
+            r#"{"log_file": {"max_size": 0}}"#,
+            r#"{"log_file": {"max_files": 0}}"#,
+        ] {
+            assert!(matches!(
+                Config::from_json(json),
+                Err(ConfigError::InvalidLogFile)
+            ));
+        }
+    }
+
+    #[test]
+    fn eco() {
+        let config = Config::from_json(r#"{"eco": {}}"#).unwrap();
+        assert_eq!(config.eco, Some(Eco { max_fps: 10 }));
+
+        let config = Config::from_json(r#"{"eco": {"max_fps": 1}}"#).unwrap();
+        assert_eq!(config.eco, Some(Eco { max_fps: 1 }));
+
+        assert_eq!(Config::from_json("{}").unwrap().eco, None);
+
+        assert!(matches!(
+            Config::from_json(r#"{"eco": {"max_fps": 0}}"#),
+            Err(ConfigError::InvalidEco)
+        ));
+    }
+
+    #[test]
+    fn cursor() {
+        let config = Config::from_json("{}").unwrap();
+        assert_eq!(config.cursor, HardwareCursor::default());
+
+        let config =
+            Config::from_json(r#"{"cursor": {"max_width": 64, "xor": "Emulation"}}"#).unwrap();
+        assert_eq!(
+            config.cursor,
+            HardwareCursor {
+                max_width: 64,
+                max_height: 512,
+                xor: XorCursor::Emulation,
+            }
+        );
+
+        for json in [
+            r#"{"cursor": {"max_height": 0}}"#,
+            r#"{"cursor": {"max_width": 4096}}"#,
+        ] {
+            assert!(matches!(
+                Config::from_json(json),
+                Err(ConfigError::InvalidCursor(..))
+            ));
+        }
+    }
+
+    #[test]
+    fn instance() {
+        let config = Config::from_json(r#"{"instance": "render-1"}"#).unwrap();
+        assert_eq!(config.instance.as_deref(), Some("render-1"));
+
+        for json in [r#"{"instance": ""}"#, r#"{"instance": "a\\b"}"#] {
+            assert!(matches!(
+                Config::from_json(json),
+                Err(ConfigError::InvalidInstance(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn pipe_names() {
+        assert_eq!(pipe_name(None), DEFAULT_PIPE_NAME);
+        assert_eq!(pipe_name(Some("render-1")), "virtualdisplaydriver-render-1");
+
+        assert!(is_driver_pipe(&pipe_name(None)));
+        assert!(is_driver_pipe(&pipe_name(Some("render-1"))));
+
+        for name in [
+            "",
+            "virtualdisplaydriver-",
+            "virtualdisplaydriverfoo",
+            "foo",
+        ] {
+            assert!(!is_driver_pipe(name));
+        }
+    }
+
+    #[test]
+    fn limits() {
+        let limits = Limits { max_monitors: 2 };
+
+        let mut monitors = vec![monitor(0, vec![]), monitor(1, vec![])];
+        assert!(check_limits(&monitors, limits).is_ok());
+
+        // only enabled monitors count
+        let mut disabled = monitor(2, vec![]);
+        disabled.enabled = false;
+        monitors.push(disabled);
+        assert!(check_limits(&monitors, limits).is_ok());
+
+        // ids are connectors, which only exist up to the limit
+        let mut high_id = monitors.clone();
+        high_id[1].id = 2;
+        high_id[2].id = 1;
+        assert_eq!(
+            check_limits(&high_id, limits),
+            Err(DriverError::IdTooHigh { max: 2, id: 2 })
+        );
+
+        monitors.push(monitor(3, vec![]));
+        assert_eq!(
+            check_limits(&monitors, limits),
+            Err(DriverError::TooManyMonitors {
+                max: 2,
+                requested: 3
+            })
+        );
+
+        let config = Config {
+            monitors,
+            adapter: Adapter {
+                max_monitors: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Monitors(DriverError::TooManyMonitors { .. }))
+        ));
+
+        let config = Config {
+            monitors: high_id,
+            ..config
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Monitors(DriverError::IdTooHigh {
+                max: 2,
+                id: 2
+            }))
+        ));
+    }
+
+    #[test]
+    fn parse_persisted_monitors() {
+        let monitors = vec![
+            monitor(0, vec![mode(1920, 1080, &[60])]),
+            monitor(1, vec![]),
+        ];
+        let json = serde_json::to_string(&monitors).unwrap();
+
+        let config = Config::from_json(&json).unwrap();
+        assert_eq!(config.monitors, monitors);
+        assert_eq!(config.default_modes, None);
+    }
+
+    #[test]
+    fn default_modes() {
+        let config = |json| Config::from_json(&format!(r#"{{"default_modes": {json}}}"#));
+
+        assert!(config(r#"{"max_width": 3840, "refresh_rates": [144, 60]}"#).is_ok());
+        // no refresh rates fall back to 60hz
+        assert!(config(r#"{"refresh_rates": []}"#).is_ok());
+
+        assert!(matches!(
+            config(r#"{"refresh_rates": [60, 0]}"#),
+            Err(ConfigError::InvalidDefaultMode(TimingError::Zero(
+                1920, 1080, 0
+            )))
+        ));
+        assert!(matches!(
+            config(r#"{"refresh_rates": [3000]}"#),
+            Err(ConfigError::InvalidDefaultMode(TimingError::Unsupported(
+                1920, 1080, 3000
+            )))
+        ));
+    }
+
+    #[test]
+    fn parse_errors() {
+        for json in ["", "[", r#"{"monitors": 5}"#, r#"[{"id": 0}]"#] {
+            assert!(matches!(
+                Config::from_json(json),
+                Err(ConfigError::Parse(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        let hd = || mode(1920, 1080, &[60]);
+
+        let check = |monitors: Vec<Monitor>| validate(&monitors).unwrap_err();
+
+        assert!(matches!(
+            check(vec![monitor(0, vec![]), monitor(0, vec![])]),
+            DriverError::DuplicateId(0)
+        ));
+        assert!(matches!(
+            check(vec![monitor(0, vec![hd(), hd()])]),
+            DriverError::DuplicateMode(0, 1920, 1080)
+        ));
+        assert!(matches!(
+            check(vec![monitor(1, vec![mode(1920, 1080, &[60, 30, 60])])]),
+            DriverError::DuplicateRefreshRate(1, 1920, 1080, 60)
+        ));
+        assert!(matches!(
+            check(vec![monitor(2, vec![mode(0, 1080, &[60])])]),
+            DriverError::InvalidMode(2, TimingError::Zero(0, 1080, 60))
+        ));
+
+        // interlaced is a different mode
+        let mut interlaced = hd();
+        interlaced.interlaced = true;
+        assert!(validate(&[monitor(0, vec![hd(), interlaced])]).is_ok());
+    }
+}
@@ -1,8 +1,19 @@
+use std::{fmt, str::FromStr};
+
+use log::LevelFilter;
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    defaults::DefaultModes,
+    render_adapter::{Preference, RenderAdapter},
+    timing::error::TimingError,
+};
+
 pub type Id = u32;
 pub type Dimen = u32;
 pub type RefreshRate = u32;
+/// Key-values a log entry was logged with, sorted by key
+pub type LogFields = serde_json::Map<String, serde_json::Value>;
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, PartialOrd)]
 pub struct Monitor {
@@ -18,6 +29,28 @@ pub struct Mode {
     pub width: Dimen,
     pub height: Dimen,
     pub refresh_rates: Vec<RefreshRate>,
+    // interlaced scan, refresh rates are field rates
+    #[serde(default)]
+    pub interlaced: bool,
+    // custom blanking instead of the generated vesa timing
+    #[serde(default)]
+    pub blanking: Option<Blanking>,
+}
+
+/// Blanking of a mode with a custom timing
+///
+/// Horizontal values are in pixels, vertical values in lines. For interlaced
+/// modes the vertical values are per field.
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Hash)]
+pub struct Blanking {
+    pub h_front_porch: u32,
+    pub h_sync: u32,
+    pub h_back_porch: u32,
+    pub v_front_porch: u32,
+    pub v_sync: u32,
+    pub v_back_porch: u32,
+    pub h_sync_positive: bool,
+    pub v_sync_positive: bool,
 }
 
 #[non_exhaustive]
@@ -32,6 +65,20 @@ pub enum DriverCommand {
     Remove(Vec<Id>),
     // Remove all monitors from system
     RemoveAll,
+    // Replace the catalogue of modes monitors without modes get
+    SetDefaultModes(DefaultModes),
+    // Select the gpu which renders the monitors, `None` leaves the choice to the os
+    SetRenderAdapter(Option<Preference>),
+    // Change the level of the driver log, optionally only for one module (and its submodules)
+    SetLogLevel {
+        level: LogLevel,
+        module_filter: Option<String>,
+    },
+    // Start or stop copying the frames of a monitor into shared memory, see the frame-export crate
+    SetFrameExport {
+        id: Id,
+        enabled: bool,
+    },
 }
 
 /// Request command sent from client->server
@@ -40,6 +87,35 @@ pub enum DriverCommand {
 pub enum RequestCommand {
     // Request information on the current system monitor state
     State,
+    // Request the edid a monitor was plugged in with
+    GetEdid(Id),
+    // Request the limits the driver was configured with
+    Limits,
+    // Request the catalogue of modes monitors without modes get
+    DefaultModes,
+    // Request the gpus which can render the monitors
+    ListRenderAdapters,
+    // Request the log entries the driver keeps in memory which come after the entry with sequence
+    // number `since` (all if `None`) and are at least as severe as `min_level`
+    RecentLogs {
+        since: Option<u64>,
+        min_level: LogLevel,
+    },
+    // Send every entry the driver logs from now on which is at least as severe as `min_level` to
+    // this client as an `EventCommand::Log`, until it disconnects
+    SubscribeLogs {
+        min_level: LogLevel,
+    },
+    // Request the report the driver wrote when it last panicked
+    CrashReport,
+    // Request the frame statistics of the enabled monitors
+    FrameStats,
+    // Send the hardware cursor of monitor `id` (all if `None`) to this client as an
+    // `EventCommand::Cursor` whenever it moves or changes, starting with the current one, until
+    // it disconnects
+    SubscribeCursor {
+        id: Option<Id>,
+    },
 }
 
 /// Reply command sent from server->client
@@ -48,6 +124,202 @@ pub enum RequestCommand {
 pub enum ReplyCommand {
     // Reply to previous current system monitor state request
     State(Vec<Monitor>),
+    // Reply to previous edid request, `None` if the monitor does not exist or is not plugged in
+    Edid(Id, Option<Vec<u8>>),
+    // Reply to previous limits request
+    Limits(Limits),
+    // Reply to previous default modes request
+    DefaultModes(DefaultModes),
+    // Reply to previous render adapter list request
+    RenderAdapters(Vec<RenderAdapter>),
+    // Reply to previous recent logs request, oldest first
+    RecentLogs(Vec<LogEntry>),
+    // Reply to previous crash report request, `None` if the driver didn't panic yet
+    CrashReport(Option<CrashReport>),
+    // Reply to previous frame statistics request
+    FrameStats(Vec<FrameStats>),
+    // A driver command of this client was rejected, nothing was changed
+    Error(DriverError),
+}
+
+/// Limits of the driver, set by its [`Config`](crate::config::Config)
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Limits {
+    // Maximum amount of enabled monitors
+    pub max_monitors: u32,
+}
+
+/// Reason the driver rejected a driver command
+#[non_exhaustive]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, thiserror::Error)]
+pub enum DriverError {
+    #[error("{requested} monitors are enabled, but the driver supports at most {max}")]
+    TooManyMonitors { max: u32, requested: u32 },
+    #[error("Monitor {id} can't be enabled, the driver supports monitor ids below {max}")]
+    IdTooHigh { max: u32, id: Id },
+    #[error("Duplicate monitor id {0}")]
+    DuplicateId(Id),
+    #[error("Duplicate mode {1}x{2} on monitor {0}")]
+    DuplicateMode(Id, Dimen, Dimen),
+    #[error("Duplicate refresh rate {3} on mode {1}x{2} for monitor {0}")]
+    DuplicateRefreshRate(Id, Dimen, Dimen, RefreshRate),
+    #[error("Invalid mode on monitor {0}: {1}")]
+    InvalidMode(Id, TimingError),
+    #[error("Invalid default mode: {0}")]
+    InvalidDefaultMode(TimingError),
+}
+
+/// Level of the driver log, every level includes the ones before it
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Off => LevelFilter::Off,
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+impl From<LevelFilter> for LogLevel {
+    fn from(level: LevelFilter) -> Self {
+        match level {
+            LevelFilter::Off => LogLevel::Off,
+            LevelFilter::Error => LogLevel::Error,
+            LevelFilter::Warn => LogLevel::Warn,
+            LevelFilter::Info => LogLevel::Info,
+            LevelFilter::Debug => LogLevel::Debug,
+            LevelFilter::Trace => LogLevel::Trace,
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        LevelFilter::from(*self).fmt(f)
+    }
+}
+
+/// Parses the level names case insensitively, e.g. `debug` or `WARN`
+impl FromStr for LogLevel {
+    type Err = ParseLogLevelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<LevelFilter>()
+            .map(Into::into)
+            .map_err(|_| ParseLogLevelError(s.to_owned()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid log level `{0}`, expected one of off, error, warn, info, debug or trace")]
+pub struct ParseLogLevelError(pub String);
+
+/// An entry of the driver log
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct LogEntry {
+    // Increases by one with every entry, pass it as `since` to continue after this entry
+    pub seq: u64,
+    // Milliseconds since the unix epoch
+    pub timestamp: u64,
+    pub level: LogLevel,
+    // Module the entry was logged from
+    pub target: String,
+    pub message: String,
+    #[serde(default)]
+    pub fields: LogFields,
+}
+
+/// Report the driver writes when it panics, to attach to a bug report
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct CrashReport {
+    // Milliseconds since the unix epoch
+    pub timestamp: u64,
+    // Version of the driver and the git commit it was built from
+    pub version: String,
+    pub git_sha: String,
+    // Name of the thread which panicked, if it has one
+    pub thread: Option<String>,
+    // Panic message
+    pub message: String,
+    // Source location of the panic, e.g. `src/ipc.rs:42:5`
+    pub location: Option<String>,
+    // Stack frames, innermost first. Frames without symbols are given as addresses
+    pub backtrace: Vec<String>,
+    // Monitors at the time of the panic, `None` if they couldn't be retrieved
+    pub monitors: Option<Vec<Monitor>>,
+    // Most recent log entries, oldest first
+    pub logs: Vec<LogEntry>,
+}
+
+/// Statistics of the frames the os rendered to a monitor, since it arrived
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct FrameStats {
+    pub id: Id,
+    // Frames acquired from the swap chain
+    pub frames: u64,
+    // Frames acquired per second, over the last second
+    pub fps: f64,
+    // Waits for the next frame which timed out
+    pub timeouts: u64,
+    // Times no frame was ready yet (`E_PENDING`) and the driver waited for one
+    pub pending_waits: u64,
+    // Times the swap chain was abandoned (e.g. `DXGI_ERROR_ACCESS_LOST`) and had to be replaced
+    pub restarts: u64,
+    // Milliseconds since the unix epoch of the last frame, `None` if there was none yet
+    pub last_frame: Option<u64>,
+}
+
+/// Hardware cursor of a monitor
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Cursor {
+    pub id: Id,
+    pub visible: bool,
+    // Position of the top left corner of the shape, relative to the monitor. Negative if the
+    // cursor is partly left of or above the monitor
+    pub x: i32,
+    pub y: i32,
+    // Only sent if it changed since the last event of the monitor, and in the first one
+    pub shape: Option<CursorShape>,
+}
+
+/// Image of a hardware cursor
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct CursorShape {
+    // Changes with every new shape of the monitor
+    pub id: u32,
+    pub kind: CursorKind,
+    pub width: u32,
+    pub height: u32,
+    // Bytes from the start of one row of `data` to the next
+    pub pitch: u32,
+    // Position of the hot spot, relative to the top left corner
+    pub x_hot: u32,
+    pub y_hot: u32,
+    // 32 bit pixels of blue, green, red and alpha, in this order
+    pub data: Vec<u8>,
+}
+
+/// How the pixels of a cursor shape are drawn
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum CursorKind {
+    // Blended with the screen by their alpha
+    Alpha,
+    // Pixels with an alpha of 0xff replace the screen, the color of ones with an alpha of 0 is
+    // xored with it
+    MaskedColor,
 }
 
 /// An event happened
@@ -56,6 +328,21 @@ pub enum ReplyCommand {
 pub enum EventCommand {
     // Monitor state was changed while client was connected
     Changed(Vec<Monitor>),
+    // The driver adapter finished initializing, monitors sent before now have arrived
+    AdapterReady,
+    // The driver logged an entry, only sent to clients which subscribed to the log
+    Log {
+        level: LogLevel,
+        target: String,
+        message: String,
+        // Milliseconds since the unix epoch
+        timestamp: u64,
+        #[serde(default)]
+        fields: LogFields,
+    },
+    // The hardware cursor of a monitor moved or changed, only sent to clients which subscribed to
+    // the cursor
+    Cursor(Cursor),
 }
 
 /// An untagged enum of commands to be used with deserialization.
@@ -79,3 +366,294 @@ pub enum ClientCommand {
     Reply(ReplyCommand),
     Event(EventCommand),
 }
+
+#[cfg(test)]
+mod test {
+    use serde::de::DeserializeOwned;
+
+    use super::*;
+    use crate::render_adapter;
+
+    // commands are sent as json and received as the untagged server or client command
+    fn round_trip<T: DeserializeOwned>(value: &impl Serialize) -> T {
+        let json = serde_json::to_string(value).expect("Failed to serialize");
+        serde_json::from_str(&json).unwrap_or_else(|e| panic!("Failed to deserialize {json}: {e}"))
+    }
+
+    fn reply(reply: &ReplyCommand) -> ReplyCommand {
+        match round_trip(reply) {
+            ClientCommand::Reply(reply) => reply,
+            cmd => panic!("Expected a reply, got {cmd:?}"),
+        }
+    }
+
+    fn event(event: &EventCommand) -> EventCommand {
+        match round_trip(event) {
+            ClientCommand::Event(event) => event,
+            cmd => panic!("Expected an event, got {cmd:?}"),
+        }
+    }
+
+    fn log_entry(seq: u64, level: LogLevel) -> LogEntry {
+        LogEntry {
+            seq,
+            timestamp: 1_700_000_000_000 + seq,
+            level,
+            target: "virtual_display_driver::ipc".to_owned(),
+            message: format!("entry {seq}"),
+            fields: [
+                ("monitor_id".to_owned(), 3.into()),
+                ("ratio".to_owned(), 1.5.into()),
+                ("name".to_owned(), "a".into()),
+            ]
+            .into_iter()
+            .collect(),
+        }
+    }
+
+    fn cursor_shape() -> CursorShape {
+        CursorShape {
+            id: 1,
+            kind: CursorKind::MaskedColor,
+            width: 2,
+            height: 2,
+            pitch: 8,
+            x_hot: 1,
+            y_hot: 0,
+            data: (0..16).collect(),
+        }
+    }
+
+    #[test]
+    fn requests() {
+        for (since, min_level) in [(None, LogLevel::Trace), (Some(7), LogLevel::Warn)] {
+            let cmd = round_trip(&RequestCommand::RecentLogs { since, min_level });
+            assert!(
+                matches!(
+                    cmd,
+                    ServerCommand::Request(RequestCommand::RecentLogs { since: s, min_level: l })
+                        if s == since && l == min_level
+                ),
+                "{cmd:?}"
+            );
+        }
+
+        for id in [None, Some(2)] {
+            let cmd = round_trip(&RequestCommand::SubscribeCursor { id });
+            assert!(
+                matches!(
+                    cmd,
+                    ServerCommand::Request(RequestCommand::SubscribeCursor { id: i }) if i == id
+                ),
+                "{cmd:?}"
+            );
+        }
+
+        let cmd = round_trip(&RequestCommand::SubscribeLogs {
+            min_level: LogLevel::Info,
+        });
+        assert!(matches!(
+            cmd,
+            ServerCommand::Request(RequestCommand::SubscribeLogs {
+                min_level: LogLevel::Info
+            })
+        ));
+
+        assert!(matches!(
+            round_trip(&RequestCommand::Limits),
+            ServerCommand::Request(RequestCommand::Limits)
+        ));
+        assert!(matches!(
+            round_trip(&RequestCommand::DefaultModes),
+            ServerCommand::Request(RequestCommand::DefaultModes)
+        ));
+        assert!(matches!(
+            round_trip(&RequestCommand::ListRenderAdapters),
+            ServerCommand::Request(RequestCommand::ListRenderAdapters)
+        ));
+        assert!(matches!(
+            round_trip(&RequestCommand::CrashReport),
+            ServerCommand::Request(RequestCommand::CrashReport)
+        ));
+        assert!(matches!(
+            round_trip(&RequestCommand::FrameStats),
+            ServerCommand::Request(RequestCommand::FrameStats)
+        ));
+    }
+
+    #[test]
+    fn limits_and_errors() {
+        let limits = Limits { max_monitors: 4 };
+        assert!(matches!(
+            reply(&ReplyCommand::Limits(limits)),
+            ReplyCommand::Limits(l) if l == limits
+        ));
+
+        for error in [
+            DriverError::TooManyMonitors {
+                max: 4,
+                requested: 5,
+            },
+            DriverError::IdTooHigh { max: 4, id: 4 },
+            DriverError::DuplicateId(1),
+            DriverError::DuplicateMode(1, 1920, 1080),
+            DriverError::DuplicateRefreshRate(1, 1920, 1080, 60),
+            DriverError::InvalidMode(1, TimingError::OddHeight(1920, 1081)),
+            DriverError::InvalidDefaultMode(TimingError::Zero(1920, 1080, 0)),
+        ] {
+            assert!(matches!(
+                reply(&ReplyCommand::Error(error.clone())),
+                ReplyCommand::Error(e) if e == error
+            ));
+        }
+    }
+
+    #[test]
+    fn default_modes() {
+        let defaults = DefaultModes {
+            max_width: 3840,
+            max_height: 2160,
+            refresh_rates: vec![144, 60],
+        };
+        assert!(matches!(
+            reply(&ReplyCommand::DefaultModes(defaults.clone())),
+            ReplyCommand::DefaultModes(d) if d == defaults
+        ));
+    }
+
+    #[test]
+    fn render_adapters() {
+        let adapters = vec![
+            render_adapter::RenderAdapter {
+                luid: render_adapter::luid(0x1234, 0),
+                description: "Integrated GPU".to_owned(),
+            },
+            // high parts can be negative
+            render_adapter::RenderAdapter {
+                luid: render_adapter::luid(u32::MAX, -1),
+                description: "Discrete GPU".to_owned(),
+            },
+        ];
+
+        assert!(matches!(
+            reply(&ReplyCommand::RenderAdapters(adapters.clone())),
+            ReplyCommand::RenderAdapters(a) if a == adapters
+        ));
+    }
+
+    #[test]
+    fn logs() {
+        let entries = vec![log_entry(0, LogLevel::Error), log_entry(1, LogLevel::Trace)];
+        assert!(matches!(
+            reply(&ReplyCommand::RecentLogs(entries.clone())),
+            ReplyCommand::RecentLogs(e) if e == entries
+        ));
+
+        // entries of older drivers don't have fields
+        let entry: LogEntry = serde_json::from_str(
+            r#"{"seq":1,"timestamp":2,"level":"Info","target":"driver","message":"a"}"#,
+        )
+        .unwrap();
+        assert!(entry.fields.is_empty());
+
+        let entry = log_entry(2, LogLevel::Debug);
+        let log = EventCommand::Log {
+            level: entry.level,
+            target: entry.target.clone(),
+            message: entry.message.clone(),
+            timestamp: entry.timestamp,
+            fields: entry.fields.clone(),
+        };
+        assert!(matches!(
+            event(&log),
+            EventCommand::Log { level, target, message, timestamp, fields }
+                if level == entry.level
+                    && target == entry.target
+                    && message == entry.message
+                    && timestamp == entry.timestamp
+                    && fields == entry.fields
+        ));
+    }
+
+    #[test]
+    fn crash_report() {
+        let report = CrashReport {
+            timestamp: 1_700_000_000_000,
+            version: "0.3.0".to_owned(),
+            git_sha: "0123abc".to_owned(),
+            thread: None,
+            message: "index out of bounds".to_owned(),
+            location: Some("src/ipc.rs:42:5".to_owned()),
+            backtrace: vec!["0x7ff612340000".to_owned(), "ipc::notify".to_owned()],
+            monitors: Some(vec![Monitor {
+                id: 0,
+                name: Some("main".to_owned()),
+                enabled: true,
+                modes: vec![],
+            }]),
+            logs: vec![log_entry(0, LogLevel::Error)],
+        };
+
+        for report in [Some(report), None] {
+            assert!(matches!(
+                reply(&ReplyCommand::CrashReport(report.clone())),
+                ReplyCommand::CrashReport(r) if r == report
+            ));
+        }
+    }
+
+    #[test]
+    fn frame_stats() {
+        let stats = vec![
+            FrameStats {
+                id: 1,
+                frames: 600,
+                fps: 59.94,
+                timeouts: 2,
+                pending_waits: 598,
+                restarts: 1,
+                last_frame: Some(1_700_000_000_000),
+            },
+            FrameStats {
+                id: 0,
+                frames: 0,
+                fps: 0.0,
+                timeouts: 0,
+                pending_waits: 0,
+                restarts: 0,
+                last_frame: None,
+            },
+        ];
+
+        // the order is kept, and the rates are exact
+        assert!(matches!(
+            reply(&ReplyCommand::FrameStats(stats.clone())),
+            ReplyCommand::FrameStats(s) if s == stats
+        ));
+    }
+
+    #[test]
+    fn cursor() {
+        for cursor in [
+            Cursor {
+                id: 2,
+                visible: true,
+                x: 10,
+                y: -2,
+                shape: Some(cursor_shape()),
+            },
+            Cursor {
+                id: 2,
+                visible: false,
+                x: -32,
+                y: 1080,
+                shape: None,
+            },
+        ] {
+            assert!(matches!(
+                event(&EventCommand::Cursor(cursor.clone())),
+                EventCommand::Cursor(c) if c == cursor
+            ));
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{timing::error::TimingError, Dimen, Mode, Monitor, RefreshRate};
+
+/// Common 16:9 resolutions the default modes are picked from, smallest first
+pub const COMMON_RESOLUTIONS: &[(Dimen, Dimen)] = &[
+    (1280, 720),
+    (1600, 900),
+    (1920, 1080),
+    (2560, 1440),
+    (3200, 1800),
+    (3840, 2160),
+    (5120, 2880),
+    (7680, 4320),
+];
+
+/// Refresh rate used if the catalogue does not list any
+pub const DEFAULT_REFRESH_RATE: RefreshRate = 60;
+
+/// Catalogue of the modes a monitor gets if it was configured without any
+///
+/// All [`COMMON_RESOLUTIONS`] up to the maximum size are used, each with the
+/// same refresh rates. The largest resolution comes first, making it the
+/// preferred mode of the monitor.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct DefaultModes {
+    pub max_width: Dimen,
+    pub max_height: Dimen,
+    pub refresh_rates: Vec<RefreshRate>,
+}
+
+impl Default for DefaultModes {
+    fn default() -> Self {
+        Self {
+            max_width: 1920,
+            max_height: 1080,
+            refresh_rates: vec![DEFAULT_REFRESH_RATE],
+        }
+    }
+}
+
+impl DefaultModes {
+    /// The modes of the catalogue, largest first
+    ///
+    /// If the maximum is smaller than every common resolution, the smallest
+    /// one is used anyways, so a monitor never ends up without modes.
+    #[must_use]
+    pub fn modes(&self) -> Vec<Mode> {
+        let refresh_rates = if self.refresh_rates.is_empty() {
+            vec![DEFAULT_REFRESH_RATE]
+        } else {
+            let mut refresh_rates = self.refresh_rates.clone();
+            refresh_rates.sort_unstable_by(|a, b| b.cmp(a));
+            refresh_rates.dedup();
+            refresh_rates
+        };
+
+        let mut resolutions: Vec<_> = COMMON_RESOLUTIONS
+            .iter()
+            .copied()
+            .filter(|&(width, height)| width <= self.max_width && height <= self.max_height)
+            .rev()
+            .collect();
+
+        if resolutions.is_empty() {
+            resolutions.push(COMMON_RESOLUTIONS[0]);
+        }
+
+        resolutions
+            .into_iter()
+            .map(|(width, height)| Mode {
+                width,
+                height,
+                refresh_rates: refresh_rates.clone(),
+                interlaced: false,
+                blanking: None,
+            })
+            .collect()
+    }
+
+    /// Check that a timing can be built for every mode of the catalogue
+    pub fn validate(&self) -> Result<(), TimingError> {
+        self.modes().iter().try_for_each(Mode::validate)
+    }
+
+    /// Give every monitor without modes the modes of the catalogue
+    pub fn fill(&self, monitors: &mut [Monitor]) {
+        for monitor in monitors.iter_mut().filter(|m| m.modes.is_empty()) {
+            monitor.modes = self.modes();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sizes(modes: &[Mode]) -> Vec<(Dimen, Dimen)> {
+        modes.iter().map(|m| (m.width, m.height)).collect()
+    }
+
+    #[test]
+    fn default_catalogue() {
+        let modes = DefaultModes::default().modes();
+
+        assert_eq!(sizes(&modes), [(1920, 1080), (1600, 900), (1280, 720)]);
+        assert!(modes.iter().all(|m| m.refresh_rates == [60]));
+        assert!(modes.iter().all(|m| m.validate().is_ok()));
+    }
+
+    #[test]
+    fn max_size() {
+        let defaults = DefaultModes {
+            max_width: 3840,
+            max_height: 1440,
+            refresh_rates: vec![60, 120, 60],
+        };
+        let modes = defaults.modes();
+
+        assert_eq!(
+            sizes(&modes),
+            [(2560, 1440), (1920, 1080), (1600, 900), (1280, 720)]
+        );
+        assert!(modes.iter().all(|m| m.refresh_rates == [120, 60]));
+
+        // too small for anything, still gets the smallest resolution
+        let defaults = DefaultModes {
+            max_width: 640,
+            max_height: 480,
+            refresh_rates: Vec::new(),
+        };
+        let modes = defaults.modes();
+
+        assert_eq!(sizes(&modes), [(1280, 720)]);
+        assert_eq!(modes[0].refresh_rates, [DEFAULT_REFRESH_RATE]);
+    }
+
+    #[test]
+    fn fill() {
+        let mut monitors = vec![
+            Monitor {
+                id: 0,
+                name: None,
+                enabled: true,
+                modes: Vec::new(),
+            },
+            Monitor {
+                id: 1,
+                name: None,
+                enabled: true,
+                modes: vec![Mode {
+                    width: 800,
+                    height: 600,
+                    refresh_rates: vec![60],
+                    interlaced: false,
+                    blanking: None,
+                }],
+            },
+        ];
+
+        let defaults = DefaultModes::default();
+        defaults.fill(&mut monitors);
+
+        assert_eq!(monitors[0].modes, defaults.modes());
+        assert_eq!(sizes(&monitors[1].modes), [(800, 600)]);
+    }
+
+    #[test]
+    fn deserialize_partial() {
+        let defaults: DefaultModes = serde_json::from_str(r#"{"max_width":3840}"#).unwrap();
+
+        assert_eq!(
+            defaults,
+            DefaultModes {
+                max_width: 3840,
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -3,7 +3,7 @@ use std::collections::HashSet;
 use tokio::{sync::watch, task};
 use tokio_stream::{Stream, StreamExt};
 
-use crate::*;
+use crate::{defaults::DefaultModes, *};
 
 /// Abstraction layer over [Client].
 ///
@@ -13,11 +13,15 @@ use crate::*;
 /// [DriverClient::persist]. To synchronize this object with the driver, you
 /// must call [DriverClient::refresh_state]. The state will not be updated
 /// automatically.
+///
+/// Monitors without modes get the modes of the [DefaultModes] catalogue of
+/// the driver, as it was when connecting.
 #[derive(Debug)]
 pub struct DriverClient {
     client: Client,
     state_rx: watch::Receiver<Vec<Monitor>>,
     state: Vec<Monitor>,
+    default_modes: DefaultModes,
 }
 
 impl DriverClient {
@@ -35,6 +39,10 @@ impl DriverClient {
         let client = Client::connect_to(name).await?;
 
         let current_state = client.request_state().await?;
+        let default_modes = client
+            .request_default_modes()
+            .await
+            .map_err(error::InitError::RequestDefaultModes)?;
 
         let (state_tx, state_rx) = watch::channel(current_state.clone());
 
@@ -55,6 +63,7 @@ impl DriverClient {
             client,
             state_rx,
             state: current_state,
+            default_modes,
         })
     }
 
@@ -119,6 +128,32 @@ impl DriverClient {
         self.client.receive_events()
     }
 
+    /// Subscribe to the driver log.
+    ///
+    /// The returned stream receives every entry the driver logs from now on
+    /// which is at least as severe as `min_level`, as [EventCommand::Log].
+    /// Other events are left out.
+    pub async fn subscribe_logs(
+        &self,
+        min_level: LogLevel,
+    ) -> Result<impl Stream<Item = Result<EventCommand, error::ReceiveError>>, error::SendError>
+    {
+        self.client.subscribe_logs(min_level).await
+    }
+
+    /// Subscribe to the hardware cursor of monitor `id`, or of all monitors
+    /// if `None`.
+    ///
+    /// The returned stream receives the current cursor, and then every move
+    /// or change of it, as [EventCommand::Cursor]. Other events are left out.
+    pub async fn subscribe_cursor(
+        &self,
+        id: Option<Id>,
+    ) -> Result<impl Stream<Item = Result<EventCommand, error::ReceiveError>>, error::SendError>
+    {
+        self.client.subscribe_cursor(id).await
+    }
+
     /// Get the current monitor state stored inside this client.
     ///
     /// Note: Client state might be stale. To synchronize with the driver,
@@ -135,6 +170,7 @@ impl DriverClient {
         mons_have_duplicates(monitors)?;
 
         self.state = monitors.to_owned();
+        self.default_modes.fill(&mut self.state);
         Ok(())
     }
 
@@ -161,8 +197,131 @@ impl DriverClient {
     ///
     /// State changes of the client are not automatically sent to the driver.
     /// You must manually call this method to send changes to the driver.
-    pub async fn notify(&mut self) -> Result<(), error::SendError> {
-        self.client.notify(&self.state).await
+    ///
+    /// The state is [validated](config::validate) and checked against the
+    /// limits of the driver first, since the driver would reject it without
+    /// this method ever noticing, see [DriverClient::request_limits].
+    pub async fn notify(&mut self) -> Result<(), error::NotifyError> {
+        self.default_modes.fill(&mut self.state);
+        config::validate(&self.state)?;
+
+        let limits = self.client.request_limits().await?;
+        config::check_limits(&self.state, limits)?;
+
+        self.client.notify(&self.state).await?;
+        Ok(())
+    }
+
+    /// Get the catalogue of modes monitors without modes get.
+    ///
+    /// This is the catalogue of the driver when connecting, or the one set
+    /// with [DriverClient::set_default_modes] since.
+    pub fn default_modes(&self) -> &DefaultModes {
+        &self.default_modes
+    }
+
+    /// Set the catalogue of modes monitors without modes get, both for this
+    /// client and the driver.
+    ///
+    /// Monitors which already got their modes from the previous catalogue
+    /// are not changed. The catalogue is [validated](DefaultModes::validate)
+    /// first, since the driver would reject it without this method ever
+    /// noticing.
+    pub async fn set_default_modes(
+        &mut self,
+        defaults: DefaultModes,
+    ) -> Result<(), error::SetDefaultModesError> {
+        defaults
+            .validate()
+            .map_err(DriverError::InvalidDefaultMode)?;
+
+        self.client.set_default_modes(&defaults).await?;
+        self.default_modes = defaults;
+        Ok(())
+    }
+
+    /// Request the EDID the monitor with the given ID was plugged in with.
+    ///
+    /// Returns `None` if the driver does not know the monitor, or it is not
+    /// plugged in.
+    pub async fn request_edid(&self, id: Id) -> Result<Option<Vec<u8>>, error::RequestError> {
+        self.client.request_edid(id).await
+    }
+
+    /// Request the limits the driver was configured with.
+    ///
+    /// The driver rejects monitor states with more enabled monitors than
+    /// [Limits::max_monitors], or with enabled monitors whose ids aren't below
+    /// it. [DriverClient::notify] checks this before sending.
+    pub async fn request_limits(&self) -> Result<Limits, error::RequestError> {
+        self.client.request_limits().await
+    }
+
+    /// Select the gpu which renders the monitors, see
+    /// [DriverClient::request_render_adapters].
+    ///
+    /// The driver keeps the os's choice if nothing matches. `None` stops
+    /// selecting a gpu, the current one stays in use until the driver restarts.
+    pub async fn set_render_adapter(
+        &self,
+        preference: Option<&render_adapter::Preference>,
+    ) -> Result<(), error::SendError> {
+        self.client.set_render_adapter(preference).await?;
+        Ok(())
+    }
+
+    /// Change the level of the driver log.
+    ///
+    /// With a `module_filter`, like `virtual_display_driver::ipc`, the level
+    /// only applies to that module and its submodules, every other module
+    /// keeps the current level. The level is reset when the driver restarts.
+    pub async fn set_log_level(
+        &self,
+        level: LogLevel,
+        module_filter: Option<&str>,
+    ) -> Result<(), error::SendError> {
+        self.client.set_log_level(level, module_filter).await?;
+        Ok(())
+    }
+
+    /// Start or stop exporting the frames of the monitor with the specified
+    /// ID into shared memory, see the `frame-export` crate.
+    ///
+    /// The export continues across mode changes and while the monitor is
+    /// off, until it's stopped or the driver restarts.
+    pub async fn set_frame_export(&self, id: Id, enabled: bool) -> Result<(), error::SendError> {
+        self.client.set_frame_export(id, enabled).await?;
+        Ok(())
+    }
+
+    /// Request the gpus which can render the monitors.
+    pub async fn request_render_adapters(
+        &self,
+    ) -> Result<Vec<render_adapter::RenderAdapter>, error::RequestError> {
+        self.client.request_render_adapters().await
+    }
+
+    /// Request the log entries the driver keeps in memory, oldest first.
+    ///
+    /// Only entries after the one with sequence number `since` (all if
+    /// `None`) which are at least as severe as `min_level` are returned.
+    pub async fn request_recent_logs(
+        &self,
+        since: Option<u64>,
+        min_level: LogLevel,
+    ) -> Result<Vec<LogEntry>, error::RequestError> {
+        self.client.request_recent_logs(since, min_level).await
+    }
+
+    /// Request the report the driver wrote when it last panicked, `None` if
+    /// it didn't panic yet.
+    pub async fn request_crash_report(&self) -> Result<Option<CrashReport>, error::RequestError> {
+        self.client.request_crash_report().await
+    }
+
+    /// Request the frame statistics of the enabled monitors.
+    pub async fn request_frame_stats(&self) -> Result<Vec<FrameStats>, error::RequestError> {
+        self.client.request_frame_stats().await
     }
 
     /// Find the monitor with the given ID.
@@ -253,8 +412,9 @@ impl DriverClient {
 
     /// Write client state to the registry for current user.
     ///
-    /// Next time the driver is started, it will load this state from the
-    /// registry. This might be after a reboot or a driver restart.
+    /// The user session service applies this state when the user logs on.
+    /// The driver itself starts with the machine-wide
+    /// [`Config`](crate::config::Config) of its device registry key.
     pub fn persist(&self) -> Result<(), error::PersistError> {
         Client::persist(&self.state)
     }
@@ -340,7 +500,8 @@ impl DriverClient {
     ///
     /// Returns an error if a monitor with this ID already exists, or if the
     /// monitor is invalid. A monitor is invalid if it has duplicate modes, or
-    /// if any of its modes has duplicate refresh rates.
+    /// if any of its modes has duplicate refresh rates. A monitor without
+    /// modes gets the modes of [DriverClient::default_modes].
     ///
     /// Note: This does not affect the driver. Manually call
     /// [DriverClient::notify] to send these changes to the driver.
@@ -354,6 +515,7 @@ impl DriverClient {
         mon_has_duplicates(&monitor)?;
 
         self.state.push(monitor);
+        self.default_modes.fill(&mut self.state);
 
         Ok(())
     }
@@ -428,11 +590,11 @@ impl DriverClient {
             Err(_) => unreachable!(),
         }?;
 
-        if mon
-            .modes
-            .iter()
-            .any(|_mode| _mode.height == mode.height && _mode.width == mode.width)
-        {
+        if mon.modes.iter().any(|_mode| {
+            _mode.height == mode.height
+                && _mode.width == mode.width
+                && _mode.interlaced == mode.interlaced
+        }) {
             return Err(error::AddModeError::DupMode(mode.width, mode.height, id));
         }
 
@@ -552,6 +714,7 @@ impl DriverClient {
             client: self.client.clone(),
             state_rx: self.state_rx.clone(),
             state: self.state.clone(),
+            default_modes: self.default_modes.clone(),
         }
     }
 }
@@ -573,9 +736,9 @@ fn mons_have_duplicates(monitors: &[Monitor]) -> Result<(), error::DuplicateErro
 fn mon_has_duplicates(monitor: &Monitor) -> Result<(), error::DuplicateError> {
     let mut mode_iter = monitor.modes.iter();
     while let Some(mode) = mode_iter.next() {
-        let duplicate_mode = mode_iter
-            .clone()
-            .any(|m| mode.height == m.height && mode.width == m.width);
+        let duplicate_mode = mode_iter.clone().any(|m| {
+            mode.height == m.height && mode.width == m.width && mode.interlaced == m.interlaced
+        });
         if duplicate_mode {
             return Err(error::DuplicateError::Mode(
                 mode.width,
@@ -652,6 +815,26 @@ pub mod error {
         DupRefreshRate(u32, u32, u32, Id),
     }
 
+    /// Error returned from [DriverClient::notify].
+    #[derive(Debug, Error)]
+    pub enum NotifyError {
+        #[error("Failed to request limits: {0}")]
+        Limits(#[from] RequestError),
+        #[error(transparent)]
+        Rejected(#[from] DriverError),
+        #[error(transparent)]
+        Send(#[from] SendError),
+    }
+
+    /// Error returned from [DriverClient::set_default_modes].
+    #[derive(Debug, Error)]
+    pub enum SetDefaultModesError {
+        #[error(transparent)]
+        Rejected(#[from] DriverError),
+        #[error(transparent)]
+        Send(#[from] SendError),
+    }
+
     /// Error returned from [DriverClient::new] and [DriverClient::new_with].
     #[derive(Debug, Error)]
     pub enum InitError {
@@ -659,5 +842,7 @@ pub mod error {
         Connect(#[from] ConnectionError),
         #[error("Failed to request state: {0}")]
         RequestState(#[from] RequestError),
+        #[error("Failed to request default modes: {0}")]
+        RequestDefaultModes(RequestError),
     }
 }
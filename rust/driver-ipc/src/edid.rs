@@ -0,0 +1,1090 @@
+//! Builder and decoder for the EDIDs the driver hands to Windows.
+//!
+//! [`EdidBuilder`] generates the EDID of a monitor from its modes.
+//!
+//! Decodes the EDID 1.4 base block and the CTA-861 and `DisplayID` 2.0
+//! extension blocks into a structured [Edid], which can be printed in a human
+//! readable form with its [Display](fmt::Display) impl or serialized.
+
+mod builder;
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub use builder::{get_serial, EdidBuilder};
+
+/// Size of a single EDID block.
+pub const BLOCK_LEN: usize = 128;
+
+const HEADER: [u8; 8] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+const DESCRIPTOR_LEN: usize = 18;
+
+// extension block tags
+const CTA_EXTENSION: u8 = 0x02;
+const DISPLAYID_EXTENSION: u8 = 0x70;
+
+// cta-861 data block tags
+const CTA_VIDEO_BLOCK: u8 = 0x02;
+const CTA_EXTENDED_BLOCK: u8 = 0x07;
+const CTA_COLORIMETRY_BLOCK: u8 = 0x05;
+const CTA_HDR_STATIC_METADATA_BLOCK: u8 = 0x06;
+
+// displayid timing data block tags, both use 20 byte descriptors
+const DISPLAYID_TYPE_I_BLOCK: u8 = 0x03;
+const DISPLAYID_TYPE_VII_BLOCK: u8 = 0x22;
+const DISPLAYID_TIMING_LEN: usize = 20;
+
+/// Error returned from [`Edid::parse`].
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum EdidError {
+    #[error("EDID size {0} is not a non-zero multiple of {BLOCK_LEN}")]
+    InvalidSize(usize),
+    #[error("EDID header is invalid")]
+    InvalidHeader,
+    #[error("Block {0} has an invalid checksum")]
+    InvalidChecksum(usize),
+}
+
+/// A decoded EDID.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Edid {
+    /// 3 letter PNP manufacturer id
+    pub manufacturer: String,
+    pub product_code: u16,
+    pub serial: u32,
+    /// Week of manufacture, if specified
+    pub week: Option<u8>,
+    /// Year of manufacture, or the model year if `week` is `None`
+    pub year: u16,
+    pub version: u8,
+    pub revision: u8,
+    pub digital: bool,
+    /// Horizontal and vertical screen size in cm, if specified
+    pub screen_size_cm: Option<(u8, u8)>,
+    pub standard_timings: Vec<StandardTiming>,
+    pub descriptors: Vec<Descriptor>,
+    pub extensions: Vec<Extension>,
+}
+
+/// A standard timing from the base block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct StandardTiming {
+    pub width: u32,
+    pub height: u32,
+    pub refresh_rate: u32,
+}
+
+/// An 18 byte descriptor from the base block.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum Descriptor {
+    DetailedTiming(DetailedTiming),
+    MonitorName(String),
+    SerialNumber(String),
+    Text(String),
+    RangeLimits(RangeLimits),
+    Dummy,
+    /// Any other display descriptor, with its tag
+    Other(u8),
+}
+
+/// A detailed timing, from a base block or CTA-861 descriptor, or a `DisplayID`
+/// timing descriptor.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DetailedTiming {
+    pub pixel_clock_khz: u32,
+    pub h_active: u32,
+    pub h_blank: u32,
+    pub h_front_porch: u32,
+    pub h_sync: u32,
+    pub v_active: u32,
+    pub v_blank: u32,
+    pub v_front_porch: u32,
+    pub v_sync: u32,
+    pub interlaced: bool,
+    pub h_sync_positive: bool,
+    pub v_sync_positive: bool,
+    pub preferred: bool,
+}
+
+/// Display range limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RangeLimits {
+    pub min_v_rate_hz: u32,
+    pub max_v_rate_hz: u32,
+    pub min_h_rate_khz: u32,
+    pub max_h_rate_khz: u32,
+    pub max_pixel_clock_mhz: u32,
+}
+
+/// An extension block.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum Extension {
+    Cta(CtaExtension),
+    DisplayId(DisplayIdExtension),
+    /// Any other extension, with its tag
+    Other(u8),
+}
+
+/// A CTA-861 extension block.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CtaExtension {
+    pub revision: u8,
+    pub data_blocks: Vec<CtaDataBlock>,
+    pub detailed_timings: Vec<DetailedTiming>,
+}
+
+/// A data block from the data block collection of a CTA-861 extension.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum CtaDataBlock {
+    /// Video identification codes
+    Video(Vec<u8>),
+    /// Raw colorimetry flags
+    Colorimetry(u16),
+    HdrStaticMetadata(HdrStaticMetadata),
+    /// Any other data block, with its tag and extended tag
+    Other(u8, Option<u8>),
+}
+
+/// HDR static metadata, luminance values are raw code values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct HdrStaticMetadata {
+    /// Raw supported eotf flags
+    pub eotfs: u8,
+    /// Raw supported static metadata descriptor flags
+    pub metadata_types: u8,
+    pub max_luminance: Option<u8>,
+    pub max_frame_avg_luminance: Option<u8>,
+    pub min_luminance: Option<u8>,
+}
+
+/// A `DisplayID` extension block.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DisplayIdExtension {
+    pub version: u8,
+    pub timings: Vec<DetailedTiming>,
+    /// Tags of data blocks which were not decoded
+    pub other_blocks: Vec<u8>,
+}
+
+impl Edid {
+    /// Decode an EDID, including all of its extension blocks.
+    pub fn parse(data: &[u8]) -> Result<Self, EdidError> {
+        if data.is_empty() || data.len() % BLOCK_LEN != 0 {
+            return Err(EdidError::InvalidSize(data.len()));
+        }
+
+        if data[..HEADER.len()] != HEADER {
+            return Err(EdidError::InvalidHeader);
+        }
+
+        if let Some(i) = data
+            .chunks_exact(BLOCK_LEN)
+            .position(|block| !checksum_is_valid(block))
+        {
+            return Err(EdidError::InvalidChecksum(i));
+        }
+
+        let (base, blocks) = data.split_at(BLOCK_LEN);
+
+        let manufacturer = u16::from_be_bytes([base[8], base[9]]);
+        let manufacturer = [10, 5, 0]
+            .iter()
+            .map(|shift| char::from(b'@' + ((manufacturer >> shift) & 0x1F) as u8))
+            .collect();
+
+        let standard_timings = base[38..54]
+            .chunks_exact(2)
+            .filter_map(standard_timing)
+            .collect();
+
+        let descriptors = base[54..126]
+            .chunks_exact(DESCRIPTOR_LEN)
+            .enumerate()
+            .map(|(i, d)| descriptor(d, i == 0))
+            .collect();
+
+        let extensions = blocks.chunks_exact(BLOCK_LEN).map(extension).collect();
+
+        Ok(Self {
+            manufacturer,
+            product_code: u16::from_le_bytes([base[10], base[11]]),
+            serial: u32::from_le_bytes([base[12], base[13], base[14], base[15]]),
+            week: match base[16] {
+                0 | 0xFF => None,
+                week => Some(week),
+            },
+            year: 1990 + u16::from(base[17]),
+            version: base[18],
+            revision: base[19],
+            digital: base[20] & 0x80 != 0,
+            screen_size_cm: match (base[21], base[22]) {
+                (0, _) | (_, 0) => None,
+                size => Some(size),
+            },
+            standard_timings,
+            descriptors,
+            extensions,
+        })
+    }
+
+    /// The monitor name from the base block, if any.
+    #[must_use]
+    pub fn name(&self) -> Option<&str> {
+        self.descriptors.iter().find_map(|d| match d {
+            Descriptor::MonitorName(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// All detailed timings of the base and extension blocks, in order.
+    pub fn detailed_timings(&self) -> impl Iterator<Item = &DetailedTiming> {
+        let base = self.descriptors.iter().filter_map(|d| match d {
+            Descriptor::DetailedTiming(timing) => Some(timing),
+            _ => None,
+        });
+
+        let extensions = self.extensions.iter().flat_map(|ext| match ext {
+            Extension::Cta(cta) => cta.detailed_timings.iter(),
+            Extension::DisplayId(displayid) => displayid.timings.iter(),
+            Extension::Other(_) => [].iter(),
+        });
+
+        base.chain(extensions)
+    }
+}
+
+impl TryFrom<&[u8]> for Edid {
+    type Error = EdidError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        Self::parse(data)
+    }
+}
+
+impl DetailedTiming {
+    #[must_use]
+    pub fn h_total(&self) -> u32 {
+        self.h_active + self.h_blank
+    }
+
+    #[must_use]
+    pub fn v_total(&self) -> u32 {
+        self.v_active + self.v_blank
+    }
+
+    /// Vertical refresh rate in hz (field rate for interlaced timings)
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn refresh_rate(&self) -> f64 {
+        // the sizes of an interlaced timing are per field, and every other field
+        // has an extra half line of blanking
+        let (lines, fields) = if self.interlaced {
+            (2 * u64::from(self.v_total()) + 1, 2.0)
+        } else {
+            (u64::from(self.v_total()), 1.0)
+        };
+
+        let total = u64::from(self.h_total()) * lines;
+        if total == 0 {
+            return 0.0;
+        }
+
+        f64::from(self.pixel_clock_khz) * 1000.0 * fields / total as f64
+    }
+
+    /// Horizontal frequency in khz
+    #[must_use]
+    pub fn h_freq_khz(&self) -> f64 {
+        if self.h_total() == 0 {
+            return 0.0;
+        }
+
+        f64::from(self.pixel_clock_khz) / f64::from(self.h_total())
+    }
+
+    /// Decode an 18 byte detailed timing descriptor. Returns `None` if `d` is
+    /// a display descriptor
+    fn from_descriptor(d: &[u8], preferred: bool) -> Option<Self> {
+        let pixel_clock = u16::from_le_bytes([d[0], d[1]]);
+        if pixel_clock == 0 {
+            return None;
+        }
+
+        let d = d.iter().copied().map(u32::from).collect::<Vec<_>>();
+
+        // digital separate sync, polarities are undefined for other sync types
+        let separate_sync = d[17] & 0x18 == 0x18;
+
+        Some(Self {
+            pixel_clock_khz: u32::from(pixel_clock) * 10,
+            h_active: d[2] | (d[4] >> 4) << 8,
+            h_blank: d[3] | (d[4] & 0xF) << 8,
+            h_front_porch: d[8] | (d[11] >> 6) << 8,
+            h_sync: d[9] | (d[11] >> 4 & 0x3) << 8,
+            v_active: d[5] | (d[7] >> 4) << 8,
+            v_blank: d[6] | (d[7] & 0xF) << 8,
+            v_front_porch: d[10] >> 4 | (d[11] >> 2 & 0x3) << 4,
+            v_sync: d[10] & 0xF | (d[11] & 0x3) << 4,
+            interlaced: d[17] & 0x80 != 0,
+            h_sync_positive: separate_sync && d[17] & 0x02 != 0,
+            v_sync_positive: separate_sync && d[17] & 0x04 != 0,
+            preferred,
+        })
+    }
+
+    /// Decode a 20 byte `DisplayID` type I/VII timing descriptor
+    fn from_displayid(d: &[u8]) -> Self {
+        // every field is stored as value - 1
+        let field = |i: usize| u32::from(u16::from_le_bytes([d[i], d[i + 1]]));
+
+        Self {
+            pixel_clock_khz: u32::from_le_bytes([d[0], d[1], d[2], 0]) + 1,
+            h_active: field(4) + 1,
+            h_blank: field(6) + 1,
+            h_front_porch: (field(8) & 0x7FFF) + 1,
+            h_sync: field(10) + 1,
+            v_active: field(12) + 1,
+            v_blank: field(14) + 1,
+            v_front_porch: (field(16) & 0x7FFF) + 1,
+            v_sync: field(18) + 1,
+            interlaced: d[3] & 0x10 != 0,
+            h_sync_positive: field(8) & 0x8000 != 0,
+            v_sync_positive: field(16) & 0x8000 != 0,
+            preferred: d[3] & 0x80 != 0,
+        }
+    }
+}
+
+impl HdrStaticMetadata {
+    /// Max luminance in cd/m²
+    #[must_use]
+    pub fn max_luminance_nits(&self) -> Option<f64> {
+        self.max_luminance
+            .map(|cv| 50.0 * 2f64.powf(f64::from(cv) / 32.0))
+    }
+
+    /// Max frame-average luminance in cd/m²
+    #[must_use]
+    pub fn max_frame_avg_luminance_nits(&self) -> Option<f64> {
+        self.max_frame_avg_luminance
+            .map(|cv| 50.0 * 2f64.powf(f64::from(cv) / 32.0))
+    }
+
+    /// Min luminance in cd/m², which is relative to the max luminance
+    #[must_use]
+    pub fn min_luminance_nits(&self) -> Option<f64> {
+        let max = self.max_luminance_nits()?;
+        self.min_luminance
+            .map(|cv| max * (f64::from(cv) / 255.0).powi(2) / 100.0)
+    }
+}
+
+fn checksum_is_valid(block: &[u8]) -> bool {
+    block.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) == 0
+}
+
+fn standard_timing(d: &[u8]) -> Option<StandardTiming> {
+    // unused slots are 0x0101, 0x0000 is invalid but commonly seen
+    if d == [0x01, 0x01] || d[0] == 0 {
+        return None;
+    }
+
+    let width = (u32::from(d[0]) + 31) * 8;
+    let height = match d[1] >> 6 {
+        0 => width * 10 / 16,
+        1 => width * 3 / 4,
+        2 => width * 4 / 5,
+        _ => width * 9 / 16,
+    };
+
+    Some(StandardTiming {
+        width,
+        height,
+        refresh_rate: u32::from(d[1] & 0x3F) + 60,
+    })
+}
+
+fn descriptor(d: &[u8], first: bool) -> Descriptor {
+    // the first detailed timing of an edid 1.4 base block is always the preferred timing
+    if let Some(timing) = DetailedTiming::from_descriptor(d, first) {
+        return Descriptor::DetailedTiming(timing);
+    }
+
+    match d[3] {
+        0xFC => Descriptor::MonitorName(descriptor_text(d)),
+        0xFF => Descriptor::SerialNumber(descriptor_text(d)),
+        0xFE => Descriptor::Text(descriptor_text(d)),
+        0xFD => Descriptor::RangeLimits(range_limits(d)),
+        0x10 => Descriptor::Dummy,
+        tag => Descriptor::Other(tag),
+    }
+}
+
+fn descriptor_text(d: &[u8]) -> String {
+    // text is terminated with a line feed and padded with spaces
+    d[5..]
+        .iter()
+        .take_while(|&&c| c != 0x0A)
+        .map(|&c| char::from(c))
+        .collect::<String>()
+        .trim_end()
+        .to_owned()
+}
+
+fn range_limits(d: &[u8]) -> RangeLimits {
+    // edid 1.4 offsets: 0b10 = max + 255, 0b11 = min + max + 255
+    let decode = |flags: u8, min: u8, max: u8| {
+        let max_offset = if flags & 0b10 != 0 { 255 } else { 0 };
+        let min_offset = if flags == 0b11 { 255 } else { 0 };
+
+        (u32::from(min) + min_offset, u32::from(max) + max_offset)
+    };
+
+    let (min_v_rate_hz, max_v_rate_hz) = decode(d[4] & 0b11, d[5], d[6]);
+    let (min_h_rate_khz, max_h_rate_khz) = decode(d[4] >> 2 & 0b11, d[7], d[8]);
+
+    RangeLimits {
+        min_v_rate_hz,
+        max_v_rate_hz,
+        min_h_rate_khz,
+        max_h_rate_khz,
+        max_pixel_clock_mhz: u32::from(d[9]) * 10,
+    }
+}
+
+fn extension(block: &[u8]) -> Extension {
+    match block[0] {
+        CTA_EXTENSION => Extension::Cta(cta_extension(block)),
+        DISPLAYID_EXTENSION => Extension::DisplayId(displayid_extension(block)),
+        tag => Extension::Other(tag),
+    }
+}
+
+fn cta_extension(block: &[u8]) -> CtaExtension {
+    // 0 = no dtds and no data blocks, otherwise the dtds start at this offset
+    let dtd_offset = match usize::from(block[2]) {
+        0 => BLOCK_LEN - 1,
+        offset => offset.clamp(4, BLOCK_LEN - 1),
+    };
+
+    let mut data_blocks = Vec::new();
+
+    let mut i = 4;
+    while i < dtd_offset {
+        let tag = block[i] >> 5;
+        let len = usize::from(block[i] & 0x1F);
+
+        let Some(payload) = block.get(i + 1..=i + len).filter(|_| i + len < dtd_offset) else {
+            break;
+        };
+
+        data_blocks.push(cta_data_block(tag, payload));
+
+        i += len + 1;
+    }
+
+    let detailed_timings = block[dtd_offset..BLOCK_LEN - 1]
+        .chunks_exact(DESCRIPTOR_LEN)
+        .map_while(|d| DetailedTiming::from_descriptor(d, false))
+        .collect();
+
+    CtaExtension {
+        revision: block[1],
+        data_blocks,
+        detailed_timings,
+    }
+}
+
+fn cta_data_block(tag: u8, payload: &[u8]) -> CtaDataBlock {
+    match (tag, payload) {
+        (CTA_VIDEO_BLOCK, vics) => {
+            let vics = vics
+                .iter()
+                .map(|&vic| match vic {
+                    // vics 1-64 with the native bit set
+                    129..=192 => vic & 0x7F,
+                    _ => vic,
+                })
+                .collect();
+
+            CtaDataBlock::Video(vics)
+        }
+
+        (CTA_EXTENDED_BLOCK, [CTA_COLORIMETRY_BLOCK, flags @ ..]) if !flags.is_empty() => {
+            let low = flags[0];
+            let high = flags.get(1).copied().unwrap_or_default();
+
+            CtaDataBlock::Colorimetry(u16::from_le_bytes([low, high]))
+        }
+
+        (CTA_EXTENDED_BLOCK, [CTA_HDR_STATIC_METADATA_BLOCK, eotfs, metadata_types, rest @ ..]) => {
+            CtaDataBlock::HdrStaticMetadata(HdrStaticMetadata {
+                eotfs: *eotfs,
+                metadata_types: *metadata_types,
+                max_luminance: rest.first().copied(),
+                max_frame_avg_luminance: rest.get(1).copied(),
+                min_luminance: rest.get(2).copied(),
+            })
+        }
+
+        (CTA_EXTENDED_BLOCK, payload) => CtaDataBlock::Other(tag, payload.first().copied()),
+
+        (tag, _) => CtaDataBlock::Other(tag, None),
+    }
+}
+
+fn displayid_extension(block: &[u8]) -> DisplayIdExtension {
+    let version = block[1];
+    // the section payload, without the section header and checksum
+    let payload_len = usize::from(block[2]).min(BLOCK_LEN - 7);
+    let payload = &block[5..5 + payload_len];
+
+    let mut timings = Vec::new();
+    let mut other_blocks = Vec::new();
+
+    let mut i = 0;
+    while i + 3 <= payload.len() {
+        let tag = payload[i];
+        let len = usize::from(payload[i + 2]);
+
+        // padding after the last data block
+        if tag == 0 {
+            break;
+        }
+
+        let Some(data) = payload.get(i + 3..i + 3 + len) else {
+            break;
+        };
+
+        match tag {
+            DISPLAYID_TYPE_I_BLOCK | DISPLAYID_TYPE_VII_BLOCK => timings.extend(
+                data.chunks_exact(DISPLAYID_TIMING_LEN)
+                    .map(DetailedTiming::from_displayid),
+            ),
+
+            tag => other_blocks.push(tag),
+        }
+
+        i += 3 + len;
+    }
+
+    DisplayIdExtension {
+        version,
+        timings,
+        other_blocks,
+    }
+}
+
+impl fmt::Display for Edid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "EDID {}.{}", self.version, self.revision)?;
+        writeln!(
+            f,
+            "  Manufacturer: {}, product {:#06X}, serial {}",
+            self.manufacturer, self.product_code, self.serial
+        )?;
+
+        match self.week {
+            Some(week) => writeln!(f, "  Made in week {week} of {}", self.year)?,
+            None => writeln!(f, "  Model year {}", self.year)?,
+        }
+
+        let input = if self.digital { "Digital" } else { "Analog" };
+        match self.screen_size_cm {
+            Some((width, height)) => writeln!(f, "  {input} display, {width}x{height} cm")?,
+            None => writeln!(f, "  {input} display")?,
+        }
+
+        if !self.standard_timings.is_empty() {
+            writeln!(f, "  Standard timings:")?;
+            for timing in &self.standard_timings {
+                writeln!(f, "    {timing}")?;
+            }
+        }
+
+        writeln!(f, "  Descriptors:")?;
+        for descriptor in &self.descriptors {
+            writeln!(f, "    {descriptor}")?;
+        }
+
+        for (i, extension) in self.extensions.iter().enumerate() {
+            write!(f, "Block {}: {extension}", i + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for StandardTiming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}@{}", self.width, self.height, self.refresh_rate)
+    }
+}
+
+impl fmt::Display for DetailedTiming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sync = |positive| if positive { '+' } else { '-' };
+        // vertical values are per field, but interlaced modes are named by their frame height
+        let height = if self.interlaced {
+            self.v_active * 2
+        } else {
+            self.v_active
+        };
+
+        write!(
+            f,
+            "{}x{}{}@{:.3} {:.3} MHz (h: {} {} {} {}{}, v: {} {} {} {}{}, {:.3} kHz)",
+            self.h_active,
+            height,
+            if self.interlaced { "i" } else { "" },
+            self.refresh_rate(),
+            f64::from(self.pixel_clock_khz) / 1000.0,
+            self.h_front_porch,
+            self.h_sync,
+            self.h_blank - self.h_front_porch.min(self.h_blank) - self.h_sync.min(self.h_blank),
+            self.h_total(),
+            sync(self.h_sync_positive),
+            self.v_front_porch,
+            self.v_sync,
+            self.v_blank - self.v_front_porch.min(self.v_blank) - self.v_sync.min(self.v_blank),
+            self.v_total(),
+            sync(self.v_sync_positive),
+            self.h_freq_khz(),
+        )?;
+
+        if self.preferred {
+            write!(f, " [preferred]")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for RangeLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{} Hz, {}-{} kHz, max {} MHz",
+            self.min_v_rate_hz,
+            self.max_v_rate_hz,
+            self.min_h_rate_khz,
+            self.max_h_rate_khz,
+            self.max_pixel_clock_mhz
+        )
+    }
+}
+
+impl fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DetailedTiming(timing) => write!(f, "Detailed timing: {timing}"),
+            Self::MonitorName(name) => write!(f, "Monitor name: {name}"),
+            Self::SerialNumber(serial) => write!(f, "Serial number: {serial}"),
+            Self::Text(text) => write!(f, "Text: {text}"),
+            Self::RangeLimits(limits) => write!(f, "Range limits: {limits}"),
+            Self::Dummy => write!(f, "Dummy"),
+            Self::Other(tag) => write!(f, "Unknown descriptor {tag:#04X}"),
+        }
+    }
+}
+
+impl fmt::Display for Extension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cta(cta) => {
+                writeln!(f, "CTA-861 extension, revision {}", cta.revision)?;
+
+                for block in &cta.data_blocks {
+                    writeln!(f, "  {block}")?;
+                }
+
+                for timing in &cta.detailed_timings {
+                    writeln!(f, "  Detailed timing: {timing}")?;
+                }
+            }
+
+            Self::DisplayId(displayid) => {
+                writeln!(
+                    f,
+                    "DisplayID {}.{} extension",
+                    displayid.version >> 4,
+                    displayid.version & 0xF
+                )?;
+
+                for timing in &displayid.timings {
+                    writeln!(f, "  Detailed timing: {timing}")?;
+                }
+
+                for tag in &displayid.other_blocks {
+                    writeln!(f, "  Unknown data block {tag:#04X}")?;
+                }
+            }
+
+            Self::Other(tag) => writeln!(f, "Unknown extension {tag:#04X}")?,
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for CtaDataBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Video(vics) => {
+                write!(f, "Video data block, VICs:")?;
+                for vic in vics {
+                    write!(f, " {vic}")?;
+                }
+
+                Ok(())
+            }
+
+            Self::Colorimetry(flags) => write!(f, "Colorimetry data block: {flags:#06X}"),
+
+            Self::HdrStaticMetadata(hdr) => {
+                write!(
+                    f,
+                    "HDR static metadata data block: eotfs {:#04X}",
+                    hdr.eotfs
+                )?;
+
+                if let Some(max) = hdr.max_luminance_nits() {
+                    write!(f, ", max {max:.1} cd/m²")?;
+                }
+
+                if let Some(avg) = hdr.max_frame_avg_luminance_nits() {
+                    write!(f, ", max frame avg {avg:.1} cd/m²")?;
+                }
+
+                if let Some(min) = hdr.min_luminance_nits() {
+                    write!(f, ", min {min:.3} cd/m²")?;
+                }
+
+                Ok(())
+            }
+
+            Self::Other(_, Some(extended_tag)) => {
+                write!(f, "Unknown extended data block {extended_tag:#04X}")
+            }
+
+            Self::Other(tag, None) => write!(f, "Unknown data block {tag:#04X}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 1920x1080@60 cta-861 timing
+    const DTD_1080P60: [u8; DESCRIPTOR_LEN] = [
+        0x02, 0x3A, 0x80, 0x18, 0x71, 0x38, 0x2D, 0x40, 0x58, 0x2C, 0x45, 0x00, 0xC4, 0x8E, 0x21,
+        0x00, 0x00, 0x1E,
+    ];
+
+    fn timing_1080p60(preferred: bool) -> DetailedTiming {
+        DetailedTiming {
+            pixel_clock_khz: 148_500,
+            h_active: 1920,
+            h_blank: 280,
+            h_front_porch: 88,
+            h_sync: 44,
+            v_active: 1080,
+            v_blank: 45,
+            v_front_porch: 4,
+            v_sync: 5,
+            interlaced: false,
+            h_sync_positive: true,
+            v_sync_positive: true,
+            preferred,
+        }
+    }
+
+    fn text_descriptor(tag: u8, text: &str) -> [u8; DESCRIPTOR_LEN] {
+        let mut d = [0x20; DESCRIPTOR_LEN];
+        d[..5].copy_from_slice(&[0, 0, 0, tag, 0]);
+        d[5..5 + text.len()].copy_from_slice(text.as_bytes());
+        d[5 + text.len()] = 0x0A;
+        d
+    }
+
+    fn checksum(block: &mut [u8]) {
+        let sum = block[..BLOCK_LEN - 1]
+            .iter()
+            .fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        block[BLOCK_LEN - 1] = sum.wrapping_neg();
+    }
+
+    fn base_block(extensions: u8) -> Vec<u8> {
+        let mut block = vec![0; BLOCK_LEN];
+
+        block[..8].copy_from_slice(&HEADER);
+        // "CHY"
+        block[8..10].copy_from_slice(&[0x0D, 0x19]);
+        block[10..12].copy_from_slice(&0x1234u16.to_le_bytes());
+        block[12..16].copy_from_slice(&7u32.to_le_bytes());
+        block[16..18].copy_from_slice(&[12, 33]);
+        block[18..20].copy_from_slice(&[1, 4]);
+        block[20] = 0xA5;
+        block[21..23].copy_from_slice(&[50, 31]);
+
+        block[38..54].fill(0x01);
+        // 1920x1080@60, 1280x1024@75, 1280x800@60
+        block[38..44].copy_from_slice(&[0xD1, 0xC0, 0x81, 0x8F, 0x81, 0x00]);
+
+        let mut range_limits = [0x20; DESCRIPTOR_LEN];
+        // max v + 255, min and max h + 255
+        range_limits[..11].copy_from_slice(&[0, 0, 0, 0xFD, 0b1110, 23, 5, 1, 10, 100, 0x01]);
+        range_limits[11] = 0x0A;
+
+        block[54..72].copy_from_slice(&DTD_1080P60);
+        block[72..90].copy_from_slice(&text_descriptor(0xFC, "Test"));
+        block[90..108].copy_from_slice(&range_limits);
+        block[111] = 0x10;
+
+        block[126] = extensions;
+        checksum(&mut block);
+
+        block
+    }
+
+    #[test]
+    fn invalid_edids() {
+        assert_eq!(Edid::parse(&[]), Err(EdidError::InvalidSize(0)));
+        assert_eq!(
+            Edid::parse(&base_block(0)[..127]),
+            Err(EdidError::InvalidSize(127))
+        );
+
+        let mut edid = base_block(0);
+        edid[0] = 0xFF;
+        assert_eq!(Edid::parse(&edid), Err(EdidError::InvalidHeader));
+
+        let mut edid = base_block(1);
+        edid.extend([0x03; BLOCK_LEN]);
+        assert_eq!(Edid::parse(&edid), Err(EdidError::InvalidChecksum(1)));
+
+        edid[20] = 0;
+        assert_eq!(Edid::parse(&edid), Err(EdidError::InvalidChecksum(0)));
+    }
+
+    #[test]
+    fn base_block_fields() {
+        let edid = Edid::parse(&base_block(0)).unwrap();
+
+        assert_eq!(edid.manufacturer, "CHY");
+        assert_eq!(edid.product_code, 0x1234);
+        assert_eq!(edid.serial, 7);
+        assert_eq!(edid.week, Some(12));
+        assert_eq!(edid.year, 2023);
+        assert_eq!((edid.version, edid.revision), (1, 4));
+        assert!(edid.digital);
+        assert_eq!(edid.screen_size_cm, Some((50, 31)));
+        assert_eq!(edid.name(), Some("Test"));
+        assert!(edid.extensions.is_empty());
+    }
+
+    #[test]
+    fn standard_timings() {
+        let edid = Edid::parse(&base_block(0)).unwrap();
+
+        let timings = [(1920, 1080, 60), (1280, 1024, 75), (1280, 800, 60)].map(
+            |(width, height, refresh_rate)| StandardTiming {
+                width,
+                height,
+                refresh_rate,
+            },
+        );
+
+        assert_eq!(edid.standard_timings, timings);
+    }
+
+    #[test]
+    fn descriptors() {
+        let edid = Edid::parse(&base_block(0)).unwrap();
+
+        assert_eq!(
+            edid.descriptors,
+            [
+                Descriptor::DetailedTiming(timing_1080p60(true)),
+                Descriptor::MonitorName("Test".to_owned()),
+                Descriptor::RangeLimits(RangeLimits {
+                    min_v_rate_hz: 23,
+                    max_v_rate_hz: 260,
+                    min_h_rate_khz: 256,
+                    max_h_rate_khz: 265,
+                    max_pixel_clock_mhz: 1000,
+                }),
+                Descriptor::Dummy,
+            ]
+        );
+    }
+
+    #[test]
+    fn detailed_timing_rates() {
+        let timing = timing_1080p60(false);
+
+        assert!((timing.refresh_rate() - 60.0).abs() < 0.001);
+        assert!((timing.h_freq_khz() - 67.5).abs() < 0.001);
+
+        // 1920x1080i@60, 1125 lines per frame
+        let interlaced = DetailedTiming {
+            pixel_clock_khz: 74_250,
+            v_active: 540,
+            v_blank: 22,
+            v_front_porch: 2,
+            interlaced: true,
+            ..timing
+        };
+
+        assert!((interlaced.refresh_rate() - 60.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn cta_extension() {
+        let mut edid = base_block(1);
+
+        let mut block = vec![0; BLOCK_LEN];
+        let data_blocks = [
+            // video: 1080p60 (native), 4k120
+            0x42, 0x90, 0x76, // colorimetry: bt.2020 rgb
+            0xE3, 0x05, 0x80, 0x00, // hdr static metadata
+            0xE6, 0x06, 0x05, 0x01, 0x8A, 0x60, 0x12, // vendor specific
+            0x61, 0x00,
+        ];
+
+        let dtd_offset = u8::try_from(4 + data_blocks.len()).unwrap();
+        block[..4].copy_from_slice(&[0x02, 0x03, dtd_offset, 0x00]);
+        block[4..4 + data_blocks.len()].copy_from_slice(&data_blocks);
+        let dtd = 4 + data_blocks.len();
+        block[dtd..dtd + DESCRIPTOR_LEN].copy_from_slice(&DTD_1080P60);
+        checksum(&mut block);
+
+        edid.extend(block);
+
+        let edid = Edid::parse(&edid).unwrap();
+
+        let hdr = HdrStaticMetadata {
+            eotfs: 0x05,
+            metadata_types: 0x01,
+            max_luminance: Some(0x8A),
+            max_frame_avg_luminance: Some(0x60),
+            min_luminance: Some(0x12),
+        };
+
+        assert_eq!(
+            edid.extensions,
+            [Extension::Cta(CtaExtension {
+                revision: 3,
+                data_blocks: vec![
+                    CtaDataBlock::Video(vec![16, 118]),
+                    CtaDataBlock::Colorimetry(0x0080),
+                    CtaDataBlock::HdrStaticMetadata(hdr),
+                    CtaDataBlock::Other(0x03, None),
+                ],
+                detailed_timings: vec![timing_1080p60(false)],
+            })]
+        );
+
+        assert!((hdr.max_luminance_nits().unwrap() - 993.6).abs() < 1.0);
+        assert!((hdr.max_frame_avg_luminance_nits().unwrap() - 400.0).abs() < 0.1);
+        assert!((hdr.min_luminance_nits().unwrap() - 0.05).abs() < 0.01);
+
+        assert_eq!(edid.detailed_timings().count(), 2);
+    }
+
+    #[test]
+    fn displayid_extension() {
+        let mut edid = base_block(1);
+
+        let timing = DetailedTiming {
+            pixel_clock_khz: 1_283_840,
+            h_active: 3840,
+            h_blank: 80,
+            h_front_porch: 8,
+            h_sync: 32,
+            v_active: 2160,
+            v_blank: 62,
+            v_front_porch: 48,
+            v_sync: 8,
+            interlaced: false,
+            h_sync_positive: true,
+            v_sync_positive: false,
+            preferred: true,
+        };
+
+        let mut descriptor = vec![];
+        descriptor.extend(&(timing.pixel_clock_khz - 1).to_le_bytes()[..3]);
+        descriptor.push(0x80);
+        for (value, flag) in [
+            (timing.h_active, 0),
+            (timing.h_blank, 0),
+            (timing.h_front_porch, 0x8000),
+            (timing.h_sync, 0),
+            (timing.v_active, 0),
+            (timing.v_blank, 0),
+            (timing.v_front_porch, 0),
+            (timing.v_sync, 0),
+        ] {
+            descriptor.extend(((u16::try_from(value).unwrap() - 1) | flag).to_le_bytes());
+        }
+
+        let mut block = vec![0; BLOCK_LEN];
+        block[..5].copy_from_slice(&[0x70, 0x20, 3 + 20 + 3, 0x00, 0x00]);
+        block[5..8].copy_from_slice(&[DISPLAYID_TYPE_VII_BLOCK, 0x00, 20]);
+        block[8..28].copy_from_slice(&descriptor);
+        // unknown data block without payload
+        block[28..31].copy_from_slice(&[0x7E, 0x00, 0x00]);
+        checksum(&mut block);
+
+        edid.extend(block);
+
+        let edid = Edid::parse(&edid).unwrap();
+
+        assert_eq!(
+            edid.extensions,
+            [Extension::DisplayId(DisplayIdExtension {
+                version: 0x20,
+                timings: vec![timing],
+                other_blocks: vec![0x7E],
+            })]
+        );
+    }
+
+    #[test]
+    fn unknown_extension() {
+        let mut edid = base_block(1);
+
+        let mut block = vec![0; BLOCK_LEN];
+        block[0] = 0xF0;
+        checksum(&mut block);
+        edid.extend(block);
+
+        let edid = Edid::parse(&edid).unwrap();
+
+        assert_eq!(edid.extensions, [Extension::Other(0xF0)]);
+    }
+
+    #[test]
+    fn display() {
+        let edid = Edid::parse(&base_block(0)).unwrap();
+        let text = edid.to_string();
+
+        assert!(text.starts_with("EDID 1.4\n"));
+        assert!(text.contains("Manufacturer: CHY, product 0x1234, serial 7"));
+        assert!(text.contains("1920x1080@60.000 148.500 MHz"));
+        assert!(text.contains("Monitor name: Test"));
+        assert!(text.contains("Range limits: 23-260 Hz, 256-265 kHz, max 1000 MHz"));
+    }
+
+    #[test]
+    fn serde_roundtrip() {
+        let edid = Edid::parse(&base_block(0)).unwrap();
+
+        let json = serde_json::to_string(&edid).unwrap();
+        assert_eq!(serde_json::from_str::<Edid>(&json).unwrap(), edid);
+    }
+}
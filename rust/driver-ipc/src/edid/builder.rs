@@ -0,0 +1,1255 @@
+use std::{array::TryFromSliceError, ops::Deref};
+
+use bytemuck::{Pod, Zeroable};
+
+use super::{
+    RangeLimits, BLOCK_LEN, CTA_COLORIMETRY_BLOCK, CTA_EXTENDED_BLOCK, CTA_EXTENSION,
+    CTA_HDR_STATIC_METADATA_BLOCK, CTA_VIDEO_BLOCK, DESCRIPTOR_LEN, DISPLAYID_EXTENSION,
+    DISPLAYID_TIMING_LEN, DISPLAYID_TYPE_VII_BLOCK, HEADER,
+};
+use crate::{
+    timing::{is_aspect, Timing},
+    Dimen, Mode, Monitor, RefreshRate,
+};
+
+// "CHY" in compressed ascii
+const MANUFACTURER_ID: [u8; 2] = [0x0D, 0x19];
+// monitor name used when the monitor has not been given one
+const DEFAULT_NAME: &str = "VirtuDisplay+";
+// 500mm x 310mm
+const SCREEN_SIZE_CM: [u8; 2] = [0x32, 0x1F];
+// red, green, blue and white point chromaticity coordinates (sRGB)
+const CHROMATICITY: [u8; 10] = [0xEE, 0x95, 0xA3, 0x54, 0x4C, 0x99, 0x26, 0x0F, 0x50, 0x54];
+
+// a video data block can hold at most 31 vics
+const CTA_MAX_VICS: usize = 31;
+
+// the section payload of a displayid extension can hold at most 5 type vii timings
+const DISPLAYID_TIMINGS_PER_BLOCK: usize = 5;
+
+// cta-861 video identification codes for common modes
+const VICS: &[(Dimen, Dimen, RefreshRate, u8)] = &[
+    (640, 480, 60, 1),
+    (1280, 720, 60, 4),
+    (1920, 1080, 60, 16),
+    (1280, 720, 50, 19),
+    (1920, 1080, 50, 31),
+    (1920, 1080, 24, 32),
+    (1920, 1080, 25, 33),
+    (1920, 1080, 30, 34),
+    (1920, 1080, 120, 63),
+    (1920, 1080, 100, 64),
+    (3840, 2160, 24, 93),
+    (3840, 2160, 25, 94),
+    (3840, 2160, 30, 95),
+    (3840, 2160, 50, 96),
+    (3840, 2160, 60, 97),
+    (3840, 2160, 100, 117),
+    (3840, 2160, 120, 118),
+    (7680, 4320, 24, 194),
+    (7680, 4320, 25, 195),
+    (7680, 4320, 30, 196),
+    (7680, 4320, 48, 197),
+    (7680, 4320, 50, 198),
+    (7680, 4320, 60, 199),
+    (7680, 4320, 100, 200),
+    (7680, 4320, 120, 201),
+];
+
+// these are only used when no mode produced a usable timing
+// 23-240hz vertical, 15-255khz horizontal, 150mhz pixel clock
+const DEFAULT_RANGE_LIMITS: RangeLimits = RangeLimits {
+    min_v_rate_hz: 23,
+    max_v_rate_hz: 240,
+    min_h_rate_khz: 15,
+    max_h_rate_khz: 255,
+    max_pixel_clock_mhz: 150,
+};
+
+#[repr(C)]
+struct AlignedEdid<const N: usize> {
+    data: [u8; N],
+    // required to make this type aligned to Header
+    _align: [Header; 0],
+}
+
+impl<const N: usize> AlignedEdid<N> {
+    fn new(data: &[u8]) -> Result<Self, TryFromSliceError> {
+        let data: [u8; N] = data.try_into()?;
+        Ok(Self { data, _align: [] })
+    }
+}
+
+impl<const N: usize> Deref for AlignedEdid<N> {
+    type Target = Header;
+
+    fn deref(&self) -> &Self::Target {
+        let header = &self.data[..HEADER_SIZE];
+        bytemuck::from_bytes(header)
+    }
+}
+
+const HEADER_SIZE: usize = std::mem::size_of::<Header>();
+
+/// Start of the base block, up to the version
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct Header {
+    magic: [u8; 8],
+    manufacturer_id: [u8; 2],
+    product_code: u16,
+    serial_number: u32,
+    manufacture_week: u8,
+    manufacture_year: u8,
+    version: u8,
+    revision: u8,
+}
+
+impl Header {
+    fn new(serial: u32) -> Self {
+        Self {
+            magic: HEADER,
+            manufacturer_id: MANUFACTURER_ID,
+            product_code: 0,
+            serial_number: serial,
+            // week 0xFF means the year is the model year
+            manufacture_week: 0xFF,
+            // 1990 + 33 = 2023
+            manufacture_year: 0x21,
+            // edid 1.4
+            version: 0x01,
+            revision: 0x04,
+        }
+    }
+}
+
+/// The serial number of an EDID, without decoding the rest of it
+///
+/// The driver uses the monitor id as the serial, see [`EdidBuilder`]
+pub fn get_serial(edid: &[u8]) -> Result<u32, TryFromSliceError> {
+    // the serial is in the base block, any extension blocks follow it
+    let base = edid.get(..BLOCK_LEN).unwrap_or(edid);
+    let edid = AlignedEdid::<BLOCK_LEN>::new(base)?;
+    Ok(edid.serial_number)
+}
+
+fn gen_checksum(data: &mut [u8]) {
+    // important, this is the bare minimum length
+    assert!(data.len() >= 128);
+
+    // slice to the entire data minus the last checksum byte
+    let edid_data = &data[..=126];
+
+    // do checksum calculation
+    let sum: u32 = edid_data.iter().copied().map(u32::from).sum();
+    // this wont ever truncate
+    #[allow(clippy::cast_possible_truncation)]
+    let checksum = (256 - (sum % 256)) as u8;
+
+    // update last byte with new checksum
+    data[127] = checksum;
+}
+
+/// Builds the EDID a virtual monitor reports to the OS
+///
+/// The serial number is used to identify the monitor later on, see [`get_serial`]
+pub struct EdidBuilder<'a> {
+    serial: u32,
+    name: Option<&'a str>,
+    modes: &'a [Mode],
+}
+
+impl<'a> EdidBuilder<'a> {
+    #[must_use]
+    pub fn new(serial: u32) -> Self {
+        Self {
+            serial,
+            name: None,
+            modes: &[],
+        }
+    }
+
+    /// Builder for the edid of `monitor`, which uses its id as the serial
+    #[must_use]
+    pub fn from_monitor(monitor: &'a Monitor) -> Self {
+        let builder = Self::new(monitor.id).modes(&monitor.modes);
+
+        match monitor.name.as_deref() {
+            Some(name) => builder.name(name),
+            None => builder,
+        }
+    }
+
+    /// Monitor name descriptor. Only the first 13 ascii characters are used
+    #[must_use]
+    pub fn name(mut self, name: &'a str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Modes to describe. The first mode's first refresh rate becomes the preferred timing
+    #[must_use]
+    pub fn modes(mut self, modes: &'a [Mode]) -> Self {
+        self.modes = modes;
+        self
+    }
+
+    /// Build the base block, followed by a CTA-861 extension and as many `DisplayID` extensions
+    /// as are needed to describe every mode
+    #[must_use]
+    pub fn build(&self) -> Vec<u8> {
+        // calculate timings for every mode, in order
+        let mut timings = Vec::<(Option<ModeKey>, Timing)>::new();
+        for (mode, refresh_rate) in self.flattened_modes() {
+            let Ok(timing) = Timing::from_mode(mode, refresh_rate) else {
+                continue;
+            };
+
+            if timings.iter().any(|&(_, t)| t == timing) {
+                continue;
+            }
+
+            // standard timings and vics can only describe generated progressive timings
+            let key = (!mode.interlaced && mode.blanking.is_none()).then_some((
+                mode.width,
+                mode.height,
+                refresh_rate,
+            ));
+
+            timings.push((key, timing));
+        }
+
+        let preferred = timings.first().map(|&(_, timing)| timing);
+
+        // modes are taken out of here as soon as something describes them
+        let mut remaining = timings.clone();
+
+        // the first 2 modes which fit into a dtd get one
+        let dtds = take_described(&mut remaining, 2, |_, timing| dtd(timing));
+        // then as much as possible goes into the standard timings
+        let standard_timings = take_described(&mut remaining, 8, |mode, _| {
+            mode.and_then(|(width, height, refresh_rate)| {
+                standard_timing(width, height, refresh_rate)
+            })
+        });
+        // cta-861 covers common modes by vic, and has room for a few more dtds
+        let vics = take_described(&mut remaining, CTA_MAX_VICS, |mode, _| mode.and_then(vic));
+        let cta_dtds = take_described(&mut remaining, cta_dtd_capacity(&vics), |_, timing| {
+            dtd(timing)
+        });
+        // displayid type vii timings can describe everything left over
+        let displayid_timings = remaining
+            .iter()
+            .map(|&(_, timing)| displayid_timing(&timing, Some(timing) == preferred))
+            .collect::<Vec<_>>();
+
+        let range_limits = RangeLimits::from_timings(timings.iter().map(|&(_, timing)| timing))
+            .unwrap_or(DEFAULT_RANGE_LIMITS);
+
+        let mut edid = self
+            .base_block(&dtds, &standard_timings, range_limits)
+            .to_vec();
+        edid.extend(cta_block(&vics, &cta_dtds));
+        for timings in displayid_timings.chunks(DISPLAYID_TIMINGS_PER_BLOCK) {
+            edid.extend(displayid_block(timings));
+        }
+
+        // number of extension blocks
+        #[allow(clippy::cast_possible_truncation)]
+        {
+            edid[126] = (edid.len() / BLOCK_LEN - 1).min(255) as u8;
+        }
+        gen_checksum(&mut edid[..BLOCK_LEN]);
+
+        edid
+    }
+
+    fn base_block(
+        &self,
+        dtds: &[[u8; DESCRIPTOR_LEN]],
+        standard_timings: &[[u8; 2]],
+        range_limits: RangeLimits,
+    ) -> [u8; BLOCK_LEN] {
+        let mut edid = [0; BLOCK_LEN];
+
+        edid[..HEADER_SIZE].copy_from_slice(bytemuck::bytes_of(&Header::new(self.serial)));
+
+        // digital input
+        edid[20] = 0x80;
+        edid[21..23].copy_from_slice(&SCREEN_SIZE_CM);
+        // gamma 2.2
+        edid[23] = 0x78;
+        // srgb default color space, preferred timing is native, continuous frequency
+        edid[24] = 0x07;
+        edid[25..35].copy_from_slice(&CHROMATICITY);
+        // established timings (35..38) are left empty, everything is in the standard timings or dtds
+
+        for (i, slot) in edid[38..54].chunks_exact_mut(2).enumerate() {
+            // 0x01 0x01 marks an unused slot
+            slot.copy_from_slice(standard_timings.get(i).unwrap_or(&[0x01, 0x01]));
+        }
+
+        let mut descriptors = dtds.to_vec();
+        // pad remaining dtd slots so range limits and name always land in the last 2 slots
+        descriptors.resize(2, dummy_descriptor());
+        descriptors.push(range_limits.descriptor());
+        descriptors.push(name_descriptor(self.name.unwrap_or(DEFAULT_NAME)));
+
+        for (descriptor, slot) in descriptors
+            .iter()
+            .zip(edid[54..126].chunks_exact_mut(DESCRIPTOR_LEN))
+        {
+            slot.copy_from_slice(descriptor);
+        }
+
+        edid
+    }
+
+    fn flattened_modes(&self) -> impl Iterator<Item = (&Mode, RefreshRate)> + '_ {
+        self.modes
+            .iter()
+            .flat_map(|m| m.refresh_rates.iter().map(move |&rr| (m, rr)))
+    }
+}
+
+type ModeKey = (Dimen, Dimen, RefreshRate);
+
+/// Move up to `limit` modes which `describe` returns something for out of `remaining`
+fn take_described<T>(
+    remaining: &mut Vec<(Option<ModeKey>, Timing)>,
+    limit: usize,
+    mut describe: impl FnMut(Option<ModeKey>, &Timing) -> Option<T>,
+) -> Vec<T> {
+    let mut described = Vec::new();
+
+    remaining.retain(|(mode, timing)| {
+        if described.len() >= limit {
+            return true;
+        }
+
+        match describe(*mode, timing) {
+            Some(d) => {
+                described.push(d);
+                false
+            }
+
+            None => true,
+        }
+    });
+
+    described
+}
+
+/// Encode a timing as a detailed timing descriptor
+///
+/// Returns `None` if the timing is too large to be described by one
+/// (pixel clock > 655.35mhz or active/blanking sizes > 4095)
+#[allow(clippy::cast_possible_truncation)]
+fn dtd(timing: &Timing) -> Option<[u8; DESCRIPTOR_LEN]> {
+    // in 10khz units
+    let pixel_clock = u16::try_from(timing.pixel_clock_khz / 10).ok()?;
+
+    let fits = [
+        timing.h_active,
+        timing.h_blank,
+        timing.v_active,
+        timing.v_blank,
+    ]
+    .iter()
+    .all(|&v| v <= 0xFFF)
+        && timing.h_front_porch <= 0x3FF
+        && timing.h_sync <= 0x3FF
+        && timing.v_front_porch <= 0x3F
+        && timing.v_sync <= 0x3F;
+
+    if !fits {
+        return None;
+    }
+
+    let mut d = [0; DESCRIPTOR_LEN];
+
+    d[..2].copy_from_slice(&pixel_clock.to_le_bytes());
+
+    d[2] = timing.h_active as u8;
+    d[3] = timing.h_blank as u8;
+    d[4] = ((timing.h_active >> 8) << 4 | (timing.h_blank >> 8)) as u8;
+
+    d[5] = timing.v_active as u8;
+    d[6] = timing.v_blank as u8;
+    d[7] = ((timing.v_active >> 8) << 4 | (timing.v_blank >> 8)) as u8;
+
+    d[8] = timing.h_front_porch as u8;
+    d[9] = timing.h_sync as u8;
+    d[10] = ((timing.v_front_porch & 0xF) << 4 | (timing.v_sync & 0xF)) as u8;
+    d[11] = ((timing.h_front_porch >> 8) << 6
+        | (timing.h_sync >> 8) << 4
+        | (timing.v_front_porch >> 4) << 2
+        | (timing.v_sync >> 4)) as u8;
+
+    // image size (12..15) and borders (15..17) are left undefined
+
+    // digital separate sync
+    d[17] = u8::from(timing.interlaced) << 7
+        | 0x18
+        | u8::from(timing.v_sync_positive) << 2
+        | u8::from(timing.h_sync_positive) << 1;
+
+    Some(d)
+}
+
+/// Encode a timing as a `DisplayID` 2.0 type VII timing descriptor
+#[allow(clippy::cast_possible_truncation)]
+fn displayid_timing(timing: &Timing, preferred: bool) -> [u8; DISPLAYID_TIMING_LEN] {
+    // every field is stored as value - 1
+    let field = |value: u32, max: u32| value.saturating_sub(1).min(max) as u16;
+
+    let mut d = [0; DISPLAYID_TIMING_LEN];
+
+    // 24 bit, in 1khz units
+    let pixel_clock = timing.pixel_clock_khz.saturating_sub(1).min(0xFF_FFFF);
+    d[..3].copy_from_slice(&pixel_clock.to_le_bytes()[..3]);
+
+    // interlaced fields are half the height of the frame
+    let height = timing.v_active << u8::from(timing.interlaced);
+
+    // no stereo
+    d[3] = u8::from(preferred) << 7
+        | u8::from(timing.interlaced) << 4
+        | displayid_aspect_ratio(timing.h_active, height);
+
+    let h_front_porch =
+        field(timing.h_front_porch, 0x7FFF) | u16::from(timing.h_sync_positive) << 15;
+    let v_front_porch =
+        field(timing.v_front_porch, 0x7FFF) | u16::from(timing.v_sync_positive) << 15;
+
+    let fields = [
+        field(timing.h_active, 0xFFFF),
+        field(timing.h_blank, 0xFFFF),
+        h_front_porch,
+        field(timing.h_sync, 0xFFFF),
+        field(timing.v_active, 0xFFFF),
+        field(timing.v_blank, 0xFFFF),
+        v_front_porch,
+        field(timing.v_sync, 0xFFFF),
+    ];
+
+    for (field, bytes) in fields.iter().zip(d[4..].chunks_exact_mut(2)) {
+        bytes.copy_from_slice(&field.to_le_bytes());
+    }
+
+    d
+}
+
+/// `DisplayID` aspect ratio code
+fn displayid_aspect_ratio(width: Dimen, height: Dimen) -> u8 {
+    [
+        (1, 1),
+        (5, 4),
+        (4, 3),
+        (15, 9),
+        (16, 9),
+        (16, 10),
+        (64, 27),
+        (256, 135),
+    ]
+    .iter()
+    .position(|&(w, h)| is_aspect(width, height, w, h))
+    // 8 = undefined, calculate it from the active size
+    .map_or(8, |i| {
+        #[allow(clippy::cast_possible_truncation)]
+        let i = i as u8;
+        i
+    })
+}
+
+/// Encode a standard timing
+///
+/// Returns `None` if this mode can't be described by one
+fn standard_timing(width: Dimen, height: Dimen, refresh_rate: RefreshRate) -> Option<[u8; 2]> {
+    if !(256..=2288).contains(&width) || width % 8 != 0 || !(60..=123).contains(&refresh_rate) {
+        return None;
+    }
+
+    let aspect = [(16, 10), (4, 3), (5, 4), (16, 9)]
+        .iter()
+        .position(|&(w, h)| width * h / w == height && width * h % w == 0)?;
+
+    #[allow(clippy::cast_possible_truncation)]
+    Some([
+        (width / 8 - 31) as u8,
+        (aspect as u8) << 6 | (refresh_rate - 60) as u8,
+    ])
+}
+
+impl RangeLimits {
+    fn from_timings(timings: impl Iterator<Item = Timing> + Clone) -> Option<Self> {
+        let v_rates = timings.clone().map(|t| {
+            let (num, den) = t.v_freq();
+            // round to nearest, timings were calculated from whole refresh rates
+            let rate = (num + den / 2) / den;
+            u32::try_from(rate).unwrap_or(u32::MAX)
+        });
+
+        let h_rates = timings.clone().map(|t| t.h_freq_hz());
+
+        let min_h_rate_khz = h_rates.clone().min()? / 1000;
+        let max_h_rate_khz = h_rates.max()?.div_ceil(1000);
+
+        Some(Self {
+            min_v_rate_hz: v_rates.clone().min()?,
+            max_v_rate_hz: v_rates.max()?,
+            min_h_rate_khz: u32::try_from(min_h_rate_khz).unwrap_or(u32::MAX),
+            max_h_rate_khz: u32::try_from(max_h_rate_khz).unwrap_or(u32::MAX),
+            max_pixel_clock_mhz: timings.map(|t| t.pixel_clock_khz.div_ceil(1000)).max()?,
+        })
+    }
+
+    /// Encode as a display range limits descriptor, using the edid 1.4 rate offsets
+    /// for rates above 255
+    #[allow(clippy::cast_possible_truncation)]
+    fn descriptor(&self) -> [u8; DESCRIPTOR_LEN] {
+        // returns (offset flags, min, max)
+        let encode = |min: u32, max: u32| {
+            let max = max.clamp(1, 510);
+            let min = min.clamp(1, max);
+
+            match (min > 255, max > 255) {
+                (true, _) => (0b11, (min - 255) as u8, (max - 255) as u8),
+                (false, true) => (0b10, min as u8, (max - 255) as u8),
+                (false, false) => (0b00, min as u8, max as u8),
+            }
+        };
+
+        let (v_flags, min_v, max_v) = encode(self.min_v_rate_hz, self.max_v_rate_hz);
+        let (h_flags, min_h, max_h) = encode(self.min_h_rate_khz, self.max_h_rate_khz);
+
+        // in 10mhz units
+        let max_pixel_clock = self.max_pixel_clock_mhz.div_ceil(10).clamp(1, 255) as u8;
+
+        let mut d = [0x20; DESCRIPTOR_LEN];
+        d[..5].copy_from_slice(&[0x00, 0x00, 0x00, 0xFD, h_flags << 2 | v_flags]);
+        d[5] = min_v;
+        d[6] = max_v;
+        d[7] = min_h;
+        d[8] = max_h;
+        d[9] = max_pixel_clock;
+        // range limits only, no timing formula
+        d[10] = 0x01;
+        d[11] = 0x0A;
+
+        d
+    }
+}
+
+fn name_descriptor(name: &str) -> [u8; DESCRIPTOR_LEN] {
+    let mut d = [0x20; DESCRIPTOR_LEN];
+    d[..5].copy_from_slice(&[0x00, 0x00, 0x00, 0xFC, 0x00]);
+
+    let name = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_graphic() || c == ' ' {
+                c as u8
+            } else {
+                b'?'
+            }
+        })
+        .take(13)
+        .collect::<Vec<_>>();
+
+    d[5..5 + name.len()].copy_from_slice(&name);
+
+    // name is terminated with a line feed if it's shorter than 13 characters
+    if name.len() < 13 {
+        d[5 + name.len()] = 0x0A;
+    }
+
+    d
+}
+
+fn dummy_descriptor() -> [u8; DESCRIPTOR_LEN] {
+    let mut d = [0; DESCRIPTOR_LEN];
+    d[3] = 0x10;
+    d
+}
+
+fn vic(mode: ModeKey) -> Option<u8> {
+    VICS.iter()
+        .find(|&&(width, height, refresh_rate, _)| (width, height, refresh_rate) == mode)
+        .map(|&(.., vic)| vic)
+}
+
+/// Data block collection of the CTA-861 extension
+#[allow(clippy::cast_possible_truncation)]
+fn cta_data_blocks(vics: &[u8]) -> Vec<u8> {
+    let mut blocks = Vec::new();
+
+    if !vics.is_empty() {
+        blocks.push(CTA_VIDEO_BLOCK << 5 | vics.len() as u8);
+        blocks.extend(vics);
+    }
+
+    // bt.2020 rgb
+    blocks.extend([
+        CTA_EXTENDED_BLOCK << 5 | 3,
+        CTA_COLORIMETRY_BLOCK,
+        0x80,
+        0x00,
+    ]);
+
+    // traditional sdr + smpte st 2084 eotfs, static metadata type 1,
+    // ~1000 nits max, ~400 nits max frame average, ~0.05 nits min luminance
+    blocks.extend([
+        CTA_EXTENDED_BLOCK << 5 | 6,
+        CTA_HDR_STATIC_METADATA_BLOCK,
+        0x05,
+        0x01,
+        0x8A,
+        0x60,
+        0x12,
+    ]);
+
+    blocks
+}
+
+/// How many dtds fit into the CTA-861 extension after its data blocks
+fn cta_dtd_capacity(vics: &[u8]) -> usize {
+    // minus header and checksum
+    (BLOCK_LEN - 5 - cta_data_blocks(vics).len()) / DESCRIPTOR_LEN
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn cta_block(vics: &[u8], dtds: &[[u8; DESCRIPTOR_LEN]]) -> [u8; BLOCK_LEN] {
+    let mut block = [0; BLOCK_LEN];
+
+    let data_blocks = cta_data_blocks(vics);
+    let dtd_offset = 4 + data_blocks.len();
+
+    // tag, revision 3
+    block[0] = CTA_EXTENSION;
+    block[1] = 0x03;
+    block[2] = dtd_offset as u8;
+    // no underscan, basic audio or ycbcr support, no native dtds
+    block[3] = 0x00;
+    block[4..dtd_offset].copy_from_slice(&data_blocks);
+
+    for (dtd, slot) in dtds
+        .iter()
+        .zip(block[dtd_offset..BLOCK_LEN - 1].chunks_exact_mut(DESCRIPTOR_LEN))
+    {
+        slot.copy_from_slice(dtd);
+    }
+
+    gen_checksum(&mut block);
+
+    block
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn displayid_block(timings: &[[u8; DISPLAYID_TIMING_LEN]]) -> [u8; BLOCK_LEN] {
+    let mut block = [0; BLOCK_LEN];
+
+    let timings_len = timings.len() * DISPLAYID_TIMING_LEN;
+    // type vii block header + timings
+    let payload_len = 3 + timings_len;
+
+    block[0] = DISPLAYID_EXTENSION;
+    // section header: version 2.0, payload bytes, no product type (it's an extension), no extensions
+    block[1..5].copy_from_slice(&[0x20, payload_len as u8, 0x00, 0x00]);
+    // type vii timing block header: tag, revision 0 (20 byte descriptors), payload bytes
+    block[5..8].copy_from_slice(&[DISPLAYID_TYPE_VII_BLOCK, 0x00, timings_len as u8]);
+
+    for (timing, slot) in timings
+        .iter()
+        .zip(block[8..].chunks_exact_mut(DISPLAYID_TIMING_LEN))
+    {
+        slot.copy_from_slice(timing);
+    }
+
+    // section checksum covers the section header and payload
+    let section_end = 5 + payload_len;
+    let sum: u32 = block[1..section_end].iter().copied().map(u32::from).sum();
+    block[section_end] = (256 - (sum % 256)) as u8;
+
+    gen_checksum(&mut block);
+
+    block
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{edid::DetailedTiming, Blanking};
+
+    fn mode(width: Dimen, height: Dimen, refresh_rates: &[RefreshRate]) -> Mode {
+        Mode {
+            width,
+            height,
+            refresh_rates: refresh_rates.to_vec(),
+            interlaced: false,
+            blanking: None,
+        }
+    }
+
+    fn descriptors(edid: &[u8]) -> Vec<&[u8]> {
+        edid[54..126].chunks_exact(DESCRIPTOR_LEN).collect()
+    }
+
+    /// `timing` as the decoder returns it
+    fn detailed(timing: Timing) -> DetailedTiming {
+        DetailedTiming {
+            pixel_clock_khz: timing.pixel_clock_khz,
+            h_active: timing.h_active,
+            h_blank: timing.h_blank,
+            h_front_porch: timing.h_front_porch,
+            h_sync: timing.h_sync,
+            v_active: timing.v_active,
+            v_blank: timing.v_blank,
+            v_front_porch: timing.v_front_porch,
+            v_sync: timing.v_sync,
+            interlaced: timing.interlaced,
+            h_sync_positive: timing.h_sync_positive,
+            v_sync_positive: timing.v_sync_positive,
+            preferred: false,
+        }
+    }
+
+    fn dtd_timing(d: &[u8]) -> DetailedTiming {
+        DetailedTiming::from_descriptor(d, false).expect("not a detailed timing descriptor")
+    }
+
+    fn assert_checksum(edid: &[u8]) {
+        assert_eq!(edid.len() % BLOCK_LEN, 0);
+
+        for (i, block) in edid.chunks_exact(BLOCK_LEN).enumerate() {
+            let sum = block.iter().copied().map(u32::from).sum::<u32>();
+            assert_eq!(sum % 256, 0, "invalid checksum in block {i}");
+        }
+    }
+
+    fn blocks(edid: &[u8]) -> Vec<&[u8]> {
+        edid.chunks_exact(BLOCK_LEN).collect()
+    }
+
+    /// Returns the data block collection and dtds of a cta block
+    fn decode_cta(block: &[u8]) -> (Vec<&[u8]>, Vec<&[u8]>) {
+        assert_eq!(block[..2], [0x02, 0x03]);
+
+        let dtd_offset = usize::from(block[2]);
+
+        let mut data_blocks = Vec::new();
+        let mut i = 4;
+        while i < dtd_offset {
+            let len = usize::from(block[i] & 0x1F);
+            data_blocks.push(&block[i..=i + len]);
+            i += len + 1;
+        }
+        assert_eq!(i, dtd_offset);
+
+        let dtds = block[dtd_offset..127]
+            .chunks_exact(DESCRIPTOR_LEN)
+            .take_while(|d| d[..2] != [0, 0])
+            .collect();
+
+        (data_blocks, dtds)
+    }
+
+    /// Returns the timings of a displayid block
+    fn timings_of_displayid(block: &[u8]) -> Vec<DetailedTiming> {
+        assert_eq!(block[..2], [DISPLAYID_EXTENSION, 0x20]);
+
+        let payload_len = usize::from(block[2]);
+        let section_sum = block[1..=5 + payload_len]
+            .iter()
+            .copied()
+            .map(u32::from)
+            .sum::<u32>();
+        assert_eq!(section_sum % 256, 0, "invalid section checksum");
+
+        assert_eq!(block[5], DISPLAYID_TYPE_VII_BLOCK);
+        let timings_len = usize::from(block[7]);
+        assert_eq!(timings_len + 3, payload_len);
+
+        block[8..8 + timings_len]
+            .chunks_exact(DISPLAYID_TIMING_LEN)
+            .map(DetailedTiming::from_displayid)
+            .collect()
+    }
+
+    #[test]
+    fn header() {
+        let edid = EdidBuilder::new(7).build();
+
+        // base block + cta extension
+        assert_eq!(edid.len(), 2 * BLOCK_LEN);
+        assert_eq!(edid[..8], [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00]);
+        assert_eq!(edid[8..10], MANUFACTURER_ID);
+        assert_eq!(edid[18..20], [1, 4]);
+        assert_eq!(edid[126], 1);
+        assert_checksum(&edid);
+    }
+
+    #[test]
+    fn serial_roundtrip() {
+        for serial in [0, 1, 15, 0xDEAD_BEEF, u32::MAX] {
+            let edid = EdidBuilder::new(serial)
+                .modes(&[mode(1920, 1080, &[60])])
+                .build();
+            assert_eq!(get_serial(&edid).unwrap(), serial);
+        }
+    }
+
+    #[test]
+    fn get_serial_wrong_size() {
+        assert!(get_serial(&[]).is_err());
+        assert!(get_serial(&[0; 127]).is_err());
+    }
+
+    #[test]
+    fn get_serial_with_extensions() {
+        let modes = [mode(7680, 4320, &[60, 120]), mode(3840, 2160, &[144])];
+        let edid = EdidBuilder::new(42).modes(&modes).build();
+
+        assert!(edid.len() > 2 * BLOCK_LEN);
+        assert_eq!(get_serial(&edid).unwrap(), 42);
+    }
+
+    #[test]
+    fn checksum_is_valid() {
+        let modes = [
+            mode(1920, 1080, &[60, 144]),
+            mode(2560, 1440, &[60, 120, 165]),
+            mode(1280, 1024, &[75]),
+            mode(7680, 4320, &[60]),
+        ];
+
+        for serial in 0..32 {
+            let edid = EdidBuilder::new(serial)
+                .name("checksum")
+                .modes(&modes)
+                .build();
+            assert_checksum(&edid);
+        }
+    }
+
+    #[test]
+    fn dtd_roundtrip() {
+        for (w, h, rr) in [
+            (1920, 1080, 60),
+            (2560, 1440, 100),
+            (3840, 2160, 60),
+            (800, 600, 75),
+        ] {
+            let timing = Timing::new(w, h, rr).unwrap();
+            let d = dtd(&timing).unwrap();
+            assert_eq!(dtd_timing(&d), detailed(timing), "{w}x{h}@{rr}");
+        }
+    }
+
+    #[test]
+    fn dtd_too_large() {
+        // 3840x2160@144 needs more than 655.35mhz
+        let timing = Timing::new(3840, 2160, 144).unwrap();
+        assert!(dtd(&timing).is_none());
+
+        // 8k doesn't fit the 12 bit active fields
+        let timing = Timing::new(7680, 4320, 30).unwrap();
+        assert!(dtd(&timing).is_none());
+
+        // the reduced blanking v2 front porch grows past 6 bits at high refresh rates
+        let timing = Timing::new(2560, 1440, 144).unwrap();
+        assert!(dtd(&timing).is_none());
+    }
+
+    #[test]
+    fn preferred_timing_is_first_mode() {
+        let modes = [mode(2560, 1440, &[75, 60]), mode(1920, 1080, &[60])];
+        let edid = EdidBuilder::new(0).modes(&modes).build();
+        let descriptors = descriptors(&edid);
+
+        let first = dtd_timing(descriptors[0]);
+        assert_eq!(first, detailed(Timing::new(2560, 1440, 75).unwrap()));
+
+        let second = dtd_timing(descriptors[1]);
+        assert_eq!(second, detailed(Timing::new(2560, 1440, 60).unwrap()));
+    }
+
+    #[test]
+    fn modes_without_dtd_are_skipped() {
+        let modes = [mode(7680, 4320, &[60]), mode(1920, 1080, &[60])];
+        let edid = EdidBuilder::new(0).modes(&modes).build();
+        let descriptors = descriptors(&edid);
+
+        assert_eq!(
+            dtd_timing(descriptors[0]),
+            detailed(Timing::new(1920, 1080, 60).unwrap())
+        );
+        assert_eq!(descriptors[1], dummy_descriptor());
+    }
+
+    #[test]
+    fn no_modes() {
+        let edid = EdidBuilder::new(0).build();
+        let descriptors = descriptors(&edid);
+
+        assert_eq!(descriptors[0], dummy_descriptor());
+        assert_eq!(descriptors[1], dummy_descriptor());
+        assert_eq!(descriptors[2], DEFAULT_RANGE_LIMITS.descriptor());
+        assert!(edid[38..54].iter().all(|&b| b == 0x01));
+        assert_checksum(&edid);
+    }
+
+    #[test]
+    fn standard_timings() {
+        assert_eq!(standard_timing(1920, 1080, 60), Some([0xD1, 0xC0]));
+        assert_eq!(standard_timing(1280, 1024, 75), Some([0x81, 0x8F]));
+        assert_eq!(standard_timing(1024, 768, 60), Some([0x61, 0x40]));
+        assert_eq!(standard_timing(1680, 1050, 60), Some([0xB3, 0x00]));
+
+        // not representable
+        assert_eq!(standard_timing(1920, 1080, 59), None);
+        assert_eq!(standard_timing(1920, 1080, 144), None);
+        assert_eq!(standard_timing(2560, 1440, 60), None);
+        assert_eq!(standard_timing(1366, 768, 60), None);
+        assert_eq!(standard_timing(1920, 1000, 60), None);
+    }
+
+    #[test]
+    fn standard_timings_in_edid() {
+        let modes = [
+            mode(1920, 1080, &[60, 75]),
+            mode(1280, 1024, &[60]),
+            mode(1024, 768, &[60]),
+            mode(2560, 1440, &[60]),
+        ];
+        let edid = EdidBuilder::new(0).modes(&modes).build();
+
+        // 1920x1080@60 and 1920x1080@75 are already described by dtds
+        let expected = [[0x81, 0x80], [0x61, 0x40]];
+        let slots = edid[38..54].chunks_exact(2).collect::<Vec<_>>();
+
+        assert_eq!(slots[0], expected[0]);
+        assert_eq!(slots[1], expected[1]);
+        assert!(slots[2..].iter().all(|s| *s == [0x01, 0x01]));
+    }
+
+    #[test]
+    fn standard_timings_limited_to_eight() {
+        let modes = (0..12)
+            .map(|i| mode(1920, 1080, &[60 + i]))
+            .collect::<Vec<_>>();
+        let edid = EdidBuilder::new(0).modes(&modes).build();
+
+        // first 2 are dtds, the next 8 are standard timings
+        let slots = edid[38..54].chunks_exact(2).collect::<Vec<_>>();
+        for (i, slot) in slots.iter().enumerate() {
+            #[allow(clippy::cast_possible_truncation)]
+            let rr = 62 + i as u8 - 60;
+            assert_eq!(*slot, [0xD1, 0xC0 | rr]);
+        }
+    }
+
+    #[test]
+    fn range_limits() {
+        let modes = [mode(1920, 1080, &[30, 60]), mode(2560, 1440, &[144])];
+        let edid = EdidBuilder::new(0).modes(&modes).build();
+        let d = descriptors(&edid)[2];
+
+        let slowest = Timing::new(1920, 1080, 30).unwrap();
+        let fastest = Timing::new(2560, 1440, 144).unwrap();
+
+        assert_eq!(d[..4], [0x00, 0x00, 0x00, 0xFD]);
+        assert_eq!(d[4], 0);
+        assert_eq!(d[5], 30);
+        assert_eq!(d[6], 144);
+        assert_eq!(u64::from(d[7]), slowest.h_freq_hz() / 1000);
+        assert_eq!(u64::from(d[8]), fastest.h_freq_hz().div_ceil(1000));
+        assert_eq!(
+            u32::from(d[9]),
+            fastest.pixel_clock_khz.div_ceil(1000).div_ceil(10)
+        );
+        assert_eq!(d[10], 0x01);
+        assert_eq!(d[11], 0x0A);
+    }
+
+    #[test]
+    fn range_limits_offsets() {
+        let limits = RangeLimits {
+            min_v_rate_hz: 60,
+            max_v_rate_hz: 360,
+            min_h_rate_khz: 300,
+            max_h_rate_khz: 600,
+            max_pixel_clock_mhz: 3000,
+        };
+
+        let d = limits.descriptor();
+        // max v offset, min+max h offset
+        assert_eq!(d[4], 0b1110);
+        assert_eq!(d[5], 60);
+        assert_eq!(d[6], 105);
+        assert_eq!(d[7], 45);
+        // clamped to 510
+        assert_eq!(d[8], 255);
+        // clamped to 2550mhz
+        assert_eq!(d[9], 255);
+    }
+
+    #[test]
+    fn monitor_name() {
+        let edid = EdidBuilder::new(0).name("Test").build();
+        let d = descriptors(&edid)[3];
+        assert_eq!(d[..5], [0x00, 0x00, 0x00, 0xFC, 0x00]);
+        assert_eq!(&d[5..], b"Test\n        ");
+
+        let edid = EdidBuilder::new(0).name("A very long monitor name").build();
+        assert_eq!(&descriptors(&edid)[3][5..], b"A very long m");
+
+        let edid = EdidBuilder::new(0).name("Mönitor").build();
+        assert_eq!(&descriptors(&edid)[3][5..], b"M?nitor\n     ");
+
+        let edid = EdidBuilder::new(0).build();
+        assert_eq!(&descriptors(&edid)[3][5..], b"VirtuDisplay+");
+    }
+    #[test]
+    fn extension_count() {
+        let large = (0..11)
+            .map(|i| mode(7680, 4320, &[70 + i]))
+            .collect::<Vec<_>>();
+
+        for (modes, blocks) in [
+            (vec![], 2),
+            (vec![mode(1920, 1080, &[60])], 2),
+            (vec![mode(3840, 2160, &[144])], 3),
+            (large.clone()[..5].to_vec(), 3),
+            (large.clone()[..6].to_vec(), 4),
+            (large, 5),
+        ] {
+            let edid = EdidBuilder::new(0).modes(&modes).build();
+
+            assert_eq!(edid.len(), blocks * BLOCK_LEN);
+            assert_eq!(usize::from(edid[126]), blocks - 1);
+            assert_checksum(&edid);
+        }
+    }
+
+    #[test]
+    fn cta_colorimetry_and_hdr() {
+        let edid = EdidBuilder::new(0).build();
+        let (data_blocks, dtds) = decode_cta(blocks(&edid)[1]);
+
+        assert!(dtds.is_empty());
+        assert_eq!(
+            data_blocks,
+            [
+                &[0xE3, CTA_COLORIMETRY_BLOCK, 0x80, 0x00][..],
+                &[
+                    0xE6,
+                    CTA_HDR_STATIC_METADATA_BLOCK,
+                    0x05,
+                    0x01,
+                    0x8A,
+                    0x60,
+                    0x12
+                ][..],
+            ]
+        );
+    }
+
+    #[test]
+    fn cta_vics() {
+        let modes = [
+            mode(2560, 1440, &[60, 75]),
+            mode(3840, 2160, &[60, 120]),
+            mode(1920, 1080, &[60, 50]),
+            mode(7680, 4320, &[60]),
+        ];
+        let edid = EdidBuilder::new(0).modes(&modes).build();
+        let (data_blocks, dtds) = decode_cta(blocks(&edid)[1]);
+
+        // 1920x1080@60 is a standard timing, everything else with a vic is described by it
+        assert_eq!(data_blocks[0], [CTA_VIDEO_BLOCK << 5 | 4, 97, 118, 31, 199]);
+        assert!(dtds.is_empty());
+
+        // everything was described, no displayid block needed
+        assert_eq!(edid.len(), 2 * BLOCK_LEN);
+    }
+
+    #[test]
+    fn cta_dtds() {
+        let modes = [mode(2560, 1440, &[60, 75, 90, 100, 110])];
+        let edid = EdidBuilder::new(0).modes(&modes).build();
+        let (_, dtds) = decode_cta(blocks(&edid)[1]);
+
+        let expected = [90, 100, 110].map(|rr| Timing::new(2560, 1440, rr).unwrap());
+
+        assert_eq!(
+            dtds.iter().map(|d| dtd_timing(d)).collect::<Vec<_>>(),
+            expected.map(detailed)
+        );
+    }
+
+    #[test]
+    fn cta_dtds_overflow_into_displayid() {
+        let modes = [mode(2560, 1440, &(61..=80).collect::<Vec<_>>())];
+        let edid = EdidBuilder::new(0).modes(&modes).build();
+        let blocks = blocks(&edid);
+
+        let (_, dtds) = decode_cta(blocks[1]);
+        assert_eq!(dtds.len(), cta_dtd_capacity(&[]));
+
+        let described_by_dtds = 2 + dtds.len();
+        let displayid = blocks[2..]
+            .iter()
+            .flat_map(|block| timings_of_displayid(block))
+            .collect::<Vec<_>>();
+
+        assert_eq!(described_by_dtds + displayid.len(), 20);
+        assert_eq!(
+            displayid[0],
+            detailed(
+                Timing::new(2560, 1440, 61 + u32::try_from(described_by_dtds).unwrap()).unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn displayid_timings() {
+        let modes = [mode(1920, 1080, &[60]), mode(3840, 2160, &[144, 240])];
+        let edid = EdidBuilder::new(0).modes(&modes).build();
+        let blocks = blocks(&edid);
+
+        assert_eq!(blocks.len(), 3);
+
+        let timings = timings_of_displayid(blocks[2]);
+        assert_eq!(
+            timings,
+            [
+                detailed(Timing::new(3840, 2160, 144).unwrap()),
+                detailed(Timing::new(3840, 2160, 240).unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn displayid_preferred_timing() {
+        // the preferred mode doesn't fit into a dtd
+        let modes = [mode(7680, 4320, &[60]), mode(5120, 2880, &[60])];
+        let edid = EdidBuilder::new(0).modes(&modes).build();
+        let blocks = blocks(&edid);
+
+        // 8k@60 has a vic
+        let (data_blocks, _) = decode_cta(blocks[1]);
+        assert_eq!(data_blocks[0], [CTA_VIDEO_BLOCK << 5 | 1, 199]);
+
+        let modes = [mode(5120, 2880, &[60]), mode(7680, 4320, &[60])];
+        let edid = EdidBuilder::new(0).modes(&modes).build();
+        let blocks = self::blocks(&edid);
+
+        let timings = timings_of_displayid(blocks[2]);
+        assert_eq!(timings.len(), 1);
+        assert!(timings[0].preferred);
+    }
+
+    #[test]
+    fn displayid_aspect_ratios() {
+        assert_eq!(displayid_aspect_ratio(1024, 1024), 0);
+        assert_eq!(displayid_aspect_ratio(1280, 1024), 1);
+        assert_eq!(displayid_aspect_ratio(1600, 1200), 2);
+        assert_eq!(displayid_aspect_ratio(1280, 768), 3);
+        assert_eq!(displayid_aspect_ratio(7680, 4320), 4);
+        assert_eq!(displayid_aspect_ratio(2560, 1600), 5);
+        assert_eq!(displayid_aspect_ratio(5120, 2160), 6);
+        assert_eq!(displayid_aspect_ratio(4096, 2160), 7);
+        assert_eq!(displayid_aspect_ratio(1000, 100), 8);
+    }
+
+    #[test]
+    fn interlaced_dtd() {
+        let modes = [Mode {
+            interlaced: true,
+            ..mode(1920, 1080, &[50])
+        }];
+        let edid = EdidBuilder::new(0).modes(&modes).build();
+        let d = descriptors(&edid)[0];
+
+        assert_eq!(d[17] & 0x80, 0x80);
+        assert_eq!(
+            dtd_timing(d),
+            detailed(Timing::new_interlaced(1920, 1080, 50).unwrap())
+        );
+
+        // interlaced modes don't have a standard timing or vic
+        assert!(edid[38..54].iter().all(|&b| b == 0x01));
+        let (data_blocks, _) = decode_cta(blocks(&edid)[1]);
+        assert!(data_blocks.iter().all(|b| b[0] >> 5 != CTA_VIDEO_BLOCK));
+    }
+
+    #[test]
+    fn interlaced_displayid() {
+        // too large for a dtd
+        let modes = [Mode {
+            interlaced: true,
+            ..mode(7680, 4320, &[60])
+        }];
+        let edid = EdidBuilder::new(0).modes(&modes).build();
+        let timing = timings_of_displayid(blocks(&edid)[2])[0];
+        let d = &blocks(&edid)[2][8..8 + DISPLAYID_TIMING_LEN];
+
+        assert_eq!(
+            timing,
+            DetailedTiming {
+                preferred: true,
+                ..detailed(Timing::new_interlaced(7680, 4320, 60).unwrap())
+            }
+        );
+        // 16:9 aspect ratio of the whole frame
+        assert_eq!(d[3] & 0x0F, 4);
+    }
+
+    #[test]
+    fn custom_blanking() {
+        let blanking = Blanking {
+            h_front_porch: 16,
+            h_sync: 32,
+            h_back_porch: 48,
+            v_front_porch: 3,
+            v_sync: 5,
+            v_back_porch: 12,
+            h_sync_positive: false,
+            v_sync_positive: true,
+        };
+        let modes = [Mode {
+            blanking: Some(blanking),
+            ..mode(1920, 1080, &[60])
+        }];
+        let edid = EdidBuilder::new(0).modes(&modes).build();
+
+        assert_eq!(
+            dtd_timing(descriptors(&edid)[0]),
+            detailed(Timing::custom(1920, 1080, 60, false, &blanking).unwrap())
+        );
+
+        // custom timings aren't the ones a standard timing or vic stands for
+        assert!(edid[38..54].iter().all(|&b| b == 0x01));
+        let (data_blocks, _) = decode_cta(blocks(&edid)[1]);
+        assert!(data_blocks.iter().all(|b| b[0] >> 5 != CTA_VIDEO_BLOCK));
+    }
+
+    #[test]
+    fn invalid_modes_are_skipped() {
+        let modes = [
+            Mode {
+                interlaced: true,
+                ..mode(1920, 1081, &[60])
+            },
+            mode(1920, 1080, &[0, 60]),
+        ];
+        let edid = EdidBuilder::new(0).modes(&modes).build();
+
+        assert_eq!(
+            dtd_timing(descriptors(&edid)[0]),
+            detailed(Timing::new(1920, 1080, 60).unwrap())
+        );
+        assert_eq!(descriptors(&edid)[1], dummy_descriptor());
+    }
+}
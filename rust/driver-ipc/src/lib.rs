@@ -1,13 +1,24 @@
+#[cfg(windows)]
 mod client;
+pub mod config;
 mod core;
+pub mod defaults;
+#[cfg(windows)]
 mod driver_client;
+pub mod edid;
+pub mod reconcile;
+pub mod render_adapter;
+#[cfg(windows)]
 pub mod sync;
+pub mod timing;
 
+#[cfg(windows)]
 pub use client::Client;
 pub use core::*;
+#[cfg(windows)]
 pub use driver_client::DriverClient;
 
-#[cfg(test)]
+#[cfg(all(test, windows))]
 mod mock;
 
 pub static DEFAULT_PIPE_NAME: &str = "virtualdisplaydriver";
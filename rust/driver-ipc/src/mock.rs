@@ -14,6 +14,7 @@ use self::client::EOF;
 pub struct MockServer {
     server: Arc<named_pipe::NamedPipeServer>,
     state: Vec<Monitor>,
+    default_modes: defaults::DefaultModes,
     command_rx: broadcast::Receiver<ServerCommand>,
     command_tx: broadcast::Sender<ServerCommand>,
     notify_closed: Arc<Notify>,
@@ -76,6 +77,7 @@ impl MockServer {
         Self {
             server,
             state: vec![],
+            default_modes: defaults::DefaultModes::default(),
             command_rx,
             command_tx,
             notify_closed,
@@ -123,6 +125,57 @@ impl MockServer {
                     .expect("Failed to write reply");
                 false
             }
+            ServerCommand::Request(RequestCommand::GetEdid(id)) => {
+                // the mock doesn't generate real edids, the id is enough to check the reply
+                let edid = self
+                    .state
+                    .iter()
+                    .any(|m| m.id == id)
+                    .then(|| id.to_le_bytes().to_vec());
+
+                let reply = ReplyCommand::Edid(id, edid);
+                let mut reply = serde_json::to_vec(&reply).unwrap();
+                reply.push(EOF);
+
+                server
+                    .write_all(&reply)
+                    .await
+                    .expect("Failed to write reply");
+                false
+            }
+            ServerCommand::Request(RequestCommand::Limits) => {
+                let reply = ReplyCommand::Limits(Limits {
+                    max_monitors: config::DEFAULT_MAX_MONITORS,
+                });
+                let mut reply = serde_json::to_vec(&reply).unwrap();
+                reply.push(EOF);
+
+                server
+                    .write_all(&reply)
+                    .await
+                    .expect("Failed to write reply");
+                false
+            }
+            ServerCommand::Request(RequestCommand::DefaultModes) => {
+                let reply = ReplyCommand::DefaultModes(self.default_modes.clone());
+                let mut reply = serde_json::to_vec(&reply).unwrap();
+                reply.push(EOF);
+
+                server
+                    .write_all(&reply)
+                    .await
+                    .expect("Failed to write reply");
+                false
+            }
+            // the mock has no gpus, log, cursor or frames, the replies are tested in core.rs
+            ServerCommand::Request(
+                RequestCommand::ListRenderAdapters
+                | RequestCommand::RecentLogs { .. }
+                | RequestCommand::SubscribeLogs { .. }
+                | RequestCommand::SubscribeCursor { .. }
+                | RequestCommand::CrashReport
+                | RequestCommand::FrameStats,
+            ) => false,
             ServerCommand::Driver(DriverCommand::Notify(monitors)) => {
                 self.state = monitors;
                 true
@@ -135,6 +188,15 @@ impl MockServer {
                 self.state.clear();
                 true
             }
+            ServerCommand::Driver(DriverCommand::SetDefaultModes(defaults)) => {
+                self.default_modes = defaults;
+                false
+            }
+            ServerCommand::Driver(
+                DriverCommand::SetRenderAdapter(_)
+                | DriverCommand::SetLogLevel { .. }
+                | DriverCommand::SetFrameExport { .. },
+            ) => false,
         };
 
         if changed {
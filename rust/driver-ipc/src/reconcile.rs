@@ -0,0 +1,372 @@
+//! Planning of the steps the driver takes to go from its current monitors to
+//! the ones a client asked for.
+//!
+//! This only decides what has to happen, executing the plan is up to the
+//! driver. That keeps it free of any os calls, so it can be tested anywhere.
+
+use crate::{timing::Timing, Id, Monitor};
+
+/// A monitor as the driver currently knows it
+#[derive(Debug, Clone, Copy)]
+pub struct Current<'a> {
+    pub monitor: &'a Monitor,
+    /// Whether the monitor arrived and is visible to the os
+    pub arrived: bool,
+    /// Timings of the monitor description (edid) the monitor arrived with
+    pub description: &'a [Timing],
+}
+
+/// A single step of a [`plan`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// Remove the monitor from the os.
+    Depart(Id),
+    /// Replace the target modes of the arrived monitor with its new modes,
+    /// without it departing. If the framework can't do that, depart and
+    /// arrive it instead.
+    UpdateModes(Id),
+    /// Only data the os doesn't see changed, like the name.
+    Rename(Id),
+    /// Create the monitor with its new data and plug it into the os.
+    Arrive(Id),
+}
+
+/// Plan the steps from the `current` monitors to the `desired` ones.
+///
+/// All departures come first, freeing up their connectors, then the in place
+/// updates and renames, and the arrivals last. Within each group the actions
+/// are in the order of the monitors. Monitors which are missing from
+/// `desired` depart if needed, storing the `desired` monitors after executing
+/// the plan is up to the caller.
+#[must_use]
+pub fn plan(current: &[Current<'_>], desired: &[Monitor]) -> Vec<Action> {
+    let mut departs = Vec::new();
+    let mut updates = Vec::new();
+    let mut arrives = Vec::new();
+
+    for cur in current {
+        let id = cur.monitor.id;
+        if cur.arrived && !desired.iter().any(|m| m.id == id) {
+            departs.push(Action::Depart(id));
+        }
+    }
+
+    for monitor in desired {
+        let id = monitor.id;
+
+        let Some(cur) = current.iter().find(|c| c.monitor.id == id) else {
+            if monitor.enabled {
+                arrives.push(Action::Arrive(id));
+            }
+
+            continue;
+        };
+
+        let modes_changed = cur.monitor.modes != monitor.modes;
+        let renamed = cur.monitor.name != monitor.name;
+
+        if cur.arrived && !monitor.enabled {
+            departs.push(Action::Depart(id));
+        } else if cur.arrived && modes_changed {
+            if can_update_in_place(cur.description, monitor) {
+                updates.push(Action::UpdateModes(id));
+            } else {
+                departs.push(Action::Depart(id));
+                arrives.push(Action::Arrive(id));
+            }
+        } else if !cur.arrived && monitor.enabled {
+            // just enabled, or it was disconnected
+            arrives.push(Action::Arrive(id));
+        } else if renamed {
+            updates.push(Action::Rename(id));
+        }
+    }
+
+    departs.extend(updates);
+    departs.extend(arrives);
+    departs
+}
+
+/// Windows only offers modes which are both in the monitor description and
+/// the target modes, so new modes can only be applied in place if all of them
+/// were in the description the monitor arrived with.
+fn can_update_in_place(description: &[Timing], monitor: &Monitor) -> bool {
+    let mut timings = monitor.modes.iter().flat_map(crate::Mode::timings).peekable();
+
+    timings.peek().is_some() && timings.all(|timing| description.contains(&timing))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Mode;
+
+    fn mode(width: u32, height: u32, refresh_rates: &[u32]) -> Mode {
+        Mode {
+            width,
+            height,
+            refresh_rates: refresh_rates.to_vec(),
+            interlaced: false,
+            blanking: None,
+        }
+    }
+
+    fn monitor(id: Id, enabled: bool, modes: Vec<Mode>) -> Monitor {
+        Monitor {
+            id,
+            name: None,
+            enabled,
+            modes,
+        }
+    }
+
+    /// What the driver keeps for a monitor
+    struct State {
+        monitor: Monitor,
+        arrived: bool,
+        description: Vec<Timing>,
+    }
+
+    impl State {
+        fn arrived(monitor: Monitor) -> Self {
+            let description = monitor.modes.iter().flat_map(Mode::timings).collect();
+
+            Self {
+                monitor,
+                arrived: true,
+                description,
+            }
+        }
+
+        fn departed(monitor: Monitor) -> Self {
+            Self {
+                monitor,
+                arrived: false,
+                description: Vec::new(),
+            }
+        }
+    }
+
+    fn plan_from(current: &[State], desired: &[Monitor]) -> Vec<Action> {
+        let current = current
+            .iter()
+            .map(|s| Current {
+                monitor: &s.monitor,
+                arrived: s.arrived,
+                description: &s.description,
+            })
+            .collect::<Vec<_>>();
+
+        plan(&current, desired)
+    }
+
+    fn hd() -> Vec<Mode> {
+        vec![mode(1920, 1080, &[60, 120]), mode(1280, 720, &[60])]
+    }
+
+    #[test]
+    fn nothing_changed() {
+        let current = [
+            State::arrived(monitor(0, true, hd())),
+            State::departed(monitor(1, false, hd())),
+        ];
+        let desired = [monitor(0, true, hd()), monitor(1, false, hd())];
+
+        assert_eq!(plan_from(&current, &desired), []);
+        assert_eq!(plan_from(&[], &[]), []);
+    }
+
+    #[test]
+    fn new_monitors() {
+        let desired = [monitor(0, true, hd()), monitor(1, false, hd())];
+
+        assert_eq!(plan_from(&[], &desired), [Action::Arrive(0)]);
+    }
+
+    #[test]
+    fn removed_monitors() {
+        let current = [
+            State::arrived(monitor(0, true, hd())),
+            State::departed(monitor(1, false, hd())),
+            State::arrived(monitor(2, true, hd())),
+        ];
+
+        assert_eq!(
+            plan_from(&current, &[]),
+            [Action::Depart(0), Action::Depart(2)]
+        );
+        assert_eq!(
+            plan_from(&current, &[monitor(0, true, hd())]),
+            [Action::Depart(2)]
+        );
+    }
+
+    #[test]
+    fn enable_disable() {
+        let current = [State::arrived(monitor(0, true, hd()))];
+        assert_eq!(
+            plan_from(&current, &[monitor(0, false, hd())]),
+            [Action::Depart(0)]
+        );
+
+        let current = [State::departed(monitor(0, false, hd()))];
+        assert_eq!(
+            plan_from(&current, &[monitor(0, true, hd())]),
+            [Action::Arrive(0)]
+        );
+    }
+
+    #[test]
+    fn disconnected_monitor_arrives_again() {
+        // enabled, but the os object is gone
+        let current = [State::departed(monitor(0, true, hd()))];
+
+        assert_eq!(
+            plan_from(&current, &[monitor(0, true, hd())]),
+            [Action::Arrive(0)]
+        );
+    }
+
+    #[test]
+    fn removed_modes_update_in_place() {
+        let current = [State::arrived(monitor(0, true, hd()))];
+
+        let desired = [monitor(0, true, vec![mode(1920, 1080, &[120])])];
+        assert_eq!(plan_from(&current, &desired), [Action::UpdateModes(0)]);
+
+        // modes of the description can come back in place too
+        let mut state = State::arrived(monitor(0, true, hd()));
+        state.monitor.modes = vec![mode(1280, 720, &[60])];
+        assert_eq!(
+            plan_from(&[state], &[monitor(0, true, hd())]),
+            [Action::UpdateModes(0)]
+        );
+    }
+
+    #[test]
+    fn new_modes_depart_and_arrive() {
+        let current = [State::arrived(monitor(0, true, hd()))];
+
+        let mut modes = hd();
+        modes.push(mode(2560, 1440, &[60]));
+        assert_eq!(
+            plan_from(&current, &[monitor(0, true, modes)]),
+            [Action::Depart(0), Action::Arrive(0)]
+        );
+
+        // a new refresh rate of an existing resolution is a new mode as well
+        let desired = [monitor(0, true, vec![mode(1920, 1080, &[60, 144])])];
+        assert_eq!(
+            plan_from(&current, &desired),
+            [Action::Depart(0), Action::Arrive(0)]
+        );
+    }
+
+    #[test]
+    fn modes_without_timings_depart_and_arrive() {
+        let current = [State::arrived(monitor(0, true, hd()))];
+
+        // nothing to put into the target modes
+        for modes in [Vec::new(), vec![mode(0, 0, &[60])]] {
+            assert_eq!(
+                plan_from(&current, &[monitor(0, true, modes)]),
+                [Action::Depart(0), Action::Arrive(0)]
+            );
+        }
+    }
+
+    #[test]
+    fn modes_of_disabled_monitors() {
+        let current = [State::departed(monitor(0, false, hd()))];
+
+        let desired = [monitor(0, false, vec![mode(2560, 1440, &[60])])];
+        assert_eq!(plan_from(&current, &desired), []);
+
+        // disabled with new modes at once only departs, it arrives when enabled again
+        let current = [State::arrived(monitor(0, true, hd()))];
+        assert_eq!(plan_from(&current, &desired), [Action::Depart(0)]);
+    }
+
+    #[test]
+    fn rename() {
+        let current = [
+            State::arrived(monitor(0, true, hd())),
+            State::departed(monitor(1, false, hd())),
+        ];
+
+        let mut desired = [monitor(0, true, hd()), monitor(1, false, hd())];
+        desired[0].name = Some("foo".to_owned());
+        desired[1].name = Some("bar".to_owned());
+        assert_eq!(
+            plan_from(&current, &desired),
+            [Action::Rename(0), Action::Rename(1)]
+        );
+
+        // the arrival already has the new name
+        let mut desired = monitor(0, true, vec![mode(2560, 1440, &[60])]);
+        desired.name = Some("foo".to_owned());
+        assert_eq!(
+            plan_from(&current[..1], &[desired]),
+            [Action::Depart(0), Action::Arrive(0)]
+        );
+
+        // so does an in place update
+        let mut desired = monitor(0, true, vec![mode(1280, 720, &[60])]);
+        desired.name = Some("foo".to_owned());
+        assert_eq!(
+            plan_from(&current[..1], &[desired]),
+            [Action::UpdateModes(0)]
+        );
+    }
+
+    #[test]
+    fn order() {
+        let current = [
+            State::arrived(monitor(0, true, hd())),
+            State::arrived(monitor(1, true, hd())),
+            State::arrived(monitor(2, true, hd())),
+            State::departed(monitor(3, false, hd())),
+            State::arrived(monitor(4, true, hd())),
+        ];
+
+        let mut renamed = monitor(4, true, hd());
+        renamed.name = Some("foo".to_owned());
+
+        let desired = [
+            monitor(5, true, hd()),
+            renamed,
+            monitor(3, true, hd()),
+            // new mode
+            monitor(2, true, vec![mode(640, 480, &[60])]),
+            // removed mode
+            monitor(1, true, vec![mode(1280, 720, &[60])]),
+        ];
+
+        assert_eq!(
+            plan_from(&current, &desired),
+            [
+                // monitor 0 was removed
+                Action::Depart(0),
+                Action::Depart(2),
+                Action::Rename(4),
+                Action::UpdateModes(1),
+                Action::Arrive(5),
+                Action::Arrive(3),
+                Action::Arrive(2),
+            ]
+        );
+    }
+
+    #[test]
+    fn interlaced_is_a_different_mode() {
+        let current = [State::arrived(monitor(0, true, hd()))];
+
+        let mut interlaced = mode(1920, 1080, &[60]);
+        interlaced.interlaced = true;
+
+        assert_eq!(
+            plan_from(&current, &[monitor(0, true, vec![interlaced])]),
+            [Action::Depart(0), Action::Arrive(0)]
+        );
+    }
+}
@@ -0,0 +1,100 @@
+//! Selection of the gpu which renders the virtual monitors.
+
+use serde::{Deserialize, Serialize};
+
+/// A gpu which can render the virtual monitors
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RenderAdapter {
+    /// Locally unique id of the adapter, see [`luid`]
+    pub luid: u64,
+    pub description: String,
+}
+
+/// Which gpu should render the virtual monitors
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub enum Preference {
+    /// The adapter with exactly this luid
+    Luid(u64),
+    /// The first adapter whose description contains this, ignoring case
+    Description(String),
+}
+
+impl Preference {
+    /// Find the preferred adapter, `None` if no adapter matches
+    #[must_use]
+    pub fn find<'a>(&self, adapters: &'a [RenderAdapter]) -> Option<&'a RenderAdapter> {
+        match self {
+            Self::Luid(luid) => adapters.iter().find(|a| a.luid == *luid),
+
+            Self::Description(description) => {
+                let description = description.to_lowercase();
+                adapters
+                    .iter()
+                    .find(|a| a.description.to_lowercase().contains(&description))
+            }
+        }
+    }
+}
+
+/// Combine the parts of a windows `LUID` into one number
+#[must_use]
+#[allow(clippy::cast_sign_loss)]
+pub fn luid(low_part: u32, high_part: i32) -> u64 {
+    (u64::from(high_part as u32) << 32) | u64::from(low_part)
+}
+
+/// Split a number made by [`luid`] into the low and high part of a windows
+/// `LUID`
+#[must_use]
+#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+pub fn luid_parts(luid: u64) -> (u32, i32) {
+    (luid as u32, (luid >> 32) as u32 as i32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn adapters() -> Vec<RenderAdapter> {
+        vec![
+            RenderAdapter {
+                luid: luid(0x1234, 0),
+                description: "Intel(R) UHD Graphics".to_owned(),
+            },
+            RenderAdapter {
+                luid: luid(0x5678, 1),
+                description: "NVIDIA GeForce RTX 4060 Laptop GPU".to_owned(),
+            },
+        ]
+    }
+
+    #[test]
+    fn find() {
+        let adapters = adapters();
+
+        let found = Preference::Luid(luid(0x5678, 1)).find(&adapters);
+        assert_eq!(found, Some(&adapters[1]));
+
+        let found = Preference::Description("nvidia".to_owned()).find(&adapters);
+        assert_eq!(found, Some(&adapters[1]));
+
+        // first match wins
+        let found = Preference::Description("g".to_owned()).find(&adapters);
+        assert_eq!(found, Some(&adapters[0]));
+
+        assert_eq!(Preference::Luid(0).find(&adapters), None);
+        assert_eq!(
+            Preference::Description("amd".to_owned()).find(&adapters),
+            None
+        );
+    }
+
+    #[test]
+    fn luid_round_trip() {
+        for parts in [(0, 0), (0x1234, 1), (u32::MAX, -1), (7, i32::MIN)] {
+            assert_eq!(luid_parts(luid(parts.0, parts.1)), parts);
+        }
+
+        assert_eq!(luid(0x5678, 1), 0x1_0000_5678);
+    }
+}
@@ -4,7 +4,13 @@ use tokio::sync::{mpsc, oneshot};
 use tokio_stream::StreamExt;
 
 use super::RUNTIME;
-use crate::{client::error, Client as AsyncClient, EventCommand, Id, Monitor};
+use crate::{
+    client::error,
+    defaults::DefaultModes,
+    render_adapter::{Preference, RenderAdapter},
+    Client as AsyncClient, CrashReport, EventCommand, FrameStats, Id, Limits, LogEntry, LogLevel,
+    Monitor,
+};
 
 /// Client for interacting with the Virtual Display Driver.
 ///
@@ -37,6 +43,12 @@ impl Client {
         Ok(Self(client))
     }
 
+    /// Find the pipe names of all running driver instances, which can be
+    /// passed to [Client::connect_to].
+    pub fn discover() -> Result<Vec<String>, error::DiscoverError> {
+        AsyncClient::discover()
+    }
+
     /// Send new state to the driver.
     pub fn notify(&self, monitors: &[Monitor]) -> Result<(), error::SendError> {
         RUNTIME.block_on(self.0.notify(monitors))
@@ -52,6 +64,49 @@ impl Client {
         RUNTIME.block_on(self.0.remove_all())
     }
 
+    /// Set the catalogue of modes the driver gives monitors without modes.
+    ///
+    /// Monitors the driver already knows are not changed. The driver rejects
+    /// catalogues with modes no timing can be built for, check it with
+    /// [DefaultModes::validate] first.
+    pub fn set_default_modes(&self, defaults: &DefaultModes) -> Result<(), error::SendError> {
+        RUNTIME.block_on(self.0.set_default_modes(defaults))
+    }
+
+    /// Select the gpu which renders the monitors, see
+    /// [Client::request_render_adapters].
+    ///
+    /// The driver keeps the os's choice if nothing matches. `None` stops
+    /// selecting a gpu, the current one stays in use until the driver restarts.
+    pub fn set_render_adapter(
+        &self,
+        preference: Option<&Preference>,
+    ) -> Result<(), error::SendError> {
+        RUNTIME.block_on(self.0.set_render_adapter(preference))
+    }
+
+    /// Change the level of the driver log.
+    ///
+    /// With a `module_filter`, like `virtual_display_driver::ipc`, the level
+    /// only applies to that module and its submodules, every other module
+    /// keeps the current level. The level is reset when the driver restarts.
+    pub fn set_log_level(
+        &self,
+        level: LogLevel,
+        module_filter: Option<&str>,
+    ) -> Result<(), error::SendError> {
+        RUNTIME.block_on(self.0.set_log_level(level, module_filter))
+    }
+
+    /// Start or stop exporting the frames of the monitor with the specified
+    /// ID into shared memory, see the `frame-export` crate.
+    ///
+    /// The export continues across mode changes and while the monitor is
+    /// off, until it's stopped or the driver restarts.
+    pub fn set_frame_export(&self, id: Id, enabled: bool) -> Result<(), error::SendError> {
+        RUNTIME.block_on(self.0.set_frame_export(id, enabled))
+    }
+
     /// Block and receive the next driver event.
     ///
     /// Only new events after calling this method will be received.
@@ -84,6 +139,41 @@ impl Client {
         EventsSubscription::start_subscriber(cb, stream)
     }
 
+    /// Subscribe to the driver log.
+    ///
+    /// `cb` receives every entry the driver logs from now on which is at least
+    /// as severe as `min_level`, as [EventCommand::Log]. Other events are left
+    /// out. Returns an object that can be used to cancel the subscription.
+    ///
+    /// Note: The callback should return as soon as possible, see
+    /// [Client::add_event_receiver].
+    pub fn subscribe_logs(
+        &self,
+        min_level: LogLevel,
+        cb: impl FnMut(Result<EventCommand, error::ReceiveError>) + Send + panic::UnwindSafe + 'static,
+    ) -> Result<EventsSubscription, error::SendError> {
+        let stream = RUNTIME.block_on(self.0.subscribe_logs(min_level))?;
+        Ok(EventsSubscription::start_subscriber(cb, Box::pin(stream)))
+    }
+
+    /// Subscribe to the hardware cursor of monitor `id`, or of all monitors
+    /// if `None`.
+    ///
+    /// `cb` receives the current cursor, and then every move or change of
+    /// it, as [EventCommand::Cursor]. Other events are left out. Returns an
+    /// object that can be used to cancel the subscription.
+    ///
+    /// Note: The callback should return as soon as possible, see
+    /// [Client::add_event_receiver].
+    pub fn subscribe_cursor(
+        &self,
+        id: Option<Id>,
+        cb: impl FnMut(Result<EventCommand, error::ReceiveError>) + Send + panic::UnwindSafe + 'static,
+    ) -> Result<EventsSubscription, error::SendError> {
+        let stream = RUNTIME.block_on(self.0.subscribe_cursor(id))?;
+        Ok(EventsSubscription::start_subscriber(cb, Box::pin(stream)))
+    }
+
     /// Request the current state of the driver.
     ///
     /// Returns [IpcError::Timeout] if the driver does not respond within 5
@@ -92,10 +182,80 @@ impl Client {
         RUNTIME.block_on(self.0.request_state())
     }
 
+    /// Request the EDID the monitor with the specified ID was plugged in
+    /// with.
+    ///
+    /// Returns `None` if the driver does not know the monitor, or it is not
+    /// plugged in.
+    ///
+    /// Returns [IpcError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub fn request_edid(&self, id: Id) -> Result<Option<Vec<u8>>, error::RequestError> {
+        RUNTIME.block_on(self.0.request_edid(id))
+    }
+
+    /// Request the limits the driver was configured with.
+    ///
+    /// Returns [IpcError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub fn request_limits(&self) -> Result<Limits, error::RequestError> {
+        RUNTIME.block_on(self.0.request_limits())
+    }
+
+    /// Request the catalogue of modes the driver gives monitors without
+    /// modes, see [Client::set_default_modes].
+    ///
+    /// Returns [IpcError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub fn request_default_modes(&self) -> Result<DefaultModes, error::RequestError> {
+        RUNTIME.block_on(self.0.request_default_modes())
+    }
+
+    /// Request the gpus which can render the monitors.
+    ///
+    /// Returns [IpcError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub fn request_render_adapters(&self) -> Result<Vec<RenderAdapter>, error::RequestError> {
+        RUNTIME.block_on(self.0.request_render_adapters())
+    }
+
+    /// Request the log entries the driver keeps in memory, oldest first.
+    ///
+    /// Only entries after the one with sequence number `since` (all if
+    /// `None`) which are at least as severe as `min_level` are returned.
+    ///
+    /// Returns [IpcError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub fn request_recent_logs(
+        &self,
+        since: Option<u64>,
+        min_level: LogLevel,
+    ) -> Result<Vec<LogEntry>, error::RequestError> {
+        RUNTIME.block_on(self.0.request_recent_logs(since, min_level))
+    }
+
+    /// Request the report the driver wrote when it last panicked, `None` if
+    /// it didn't panic yet.
+    ///
+    /// Returns [IpcError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub fn request_crash_report(&self) -> Result<Option<CrashReport>, error::RequestError> {
+        RUNTIME.block_on(self.0.request_crash_report())
+    }
+
+    /// Request the frame statistics of the enabled monitors.
+    ///
+    /// Returns [IpcError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub fn request_frame_stats(&self) -> Result<Vec<FrameStats>, error::RequestError> {
+        RUNTIME.block_on(self.0.request_frame_stats())
+    }
+
     /// Write `monitors` to the registry for current user.
     ///
-    /// Next time the driver is started, it will load this state from the
-    /// registry. This might be after a reboot or a driver restart.
+    /// The user session service applies this state when the user logs on.
+    /// The driver itself starts with the machine-wide
+    /// [`Config`](crate::config::Config) of its device registry key.
     pub fn persist(monitors: &[Monitor]) -> Result<(), error::PersistError> {
         AsyncClient::persist(monitors)
     }
@@ -1,6 +1,10 @@
 use super::{client::EventsSubscription, RUNTIME};
 use crate::{
-    driver_client::error, DriverClient as AsyncDriverClient, EventCommand, Id, Mode, Monitor,
+    defaults::DefaultModes,
+    driver_client::error,
+    render_adapter::{Preference, RenderAdapter},
+    CrashReport, DriverClient as AsyncDriverClient, EventCommand, FrameStats, Id, Limits, LogEntry,
+    LogLevel, Mode, Monitor,
 };
 
 /// Abstraction layer over [Client].
@@ -88,6 +92,47 @@ impl DriverClient {
         EventsSubscription::start_subscriber(cb, stream)
     }
 
+    /// Subscribe to the driver log.
+    ///
+    /// `cb` receives every entry the driver logs from now on which is at least
+    /// as severe as `min_level`, as [EventCommand::Log]. Other events are left
+    /// out. Returns an object that can be used to cancel the subscription.
+    ///
+    /// Note: The callback should return as soon as possible, see
+    /// [DriverClient::add_event_receiver].
+    pub fn subscribe_logs(
+        &self,
+        min_level: LogLevel,
+        cb: impl FnMut(Result<EventCommand, error::ReceiveError>)
+            + Send
+            + std::panic::UnwindSafe
+            + 'static,
+    ) -> Result<EventsSubscription, error::SendError> {
+        let stream = RUNTIME.block_on(self.0.subscribe_logs(min_level))?;
+        Ok(EventsSubscription::start_subscriber(cb, Box::pin(stream)))
+    }
+
+    /// Subscribe to the hardware cursor of monitor `id`, or of all monitors
+    /// if `None`.
+    ///
+    /// `cb` receives the current cursor, and then every move or change of
+    /// it, as [EventCommand::Cursor]. Other events are left out. Returns an
+    /// object that can be used to cancel the subscription.
+    ///
+    /// Note: The callback should return as soon as possible, see
+    /// [DriverClient::add_event_receiver].
+    pub fn subscribe_cursor(
+        &self,
+        id: Option<Id>,
+        cb: impl FnMut(Result<EventCommand, error::ReceiveError>)
+            + Send
+            + std::panic::UnwindSafe
+            + 'static,
+    ) -> Result<EventsSubscription, error::SendError> {
+        let stream = RUNTIME.block_on(self.0.subscribe_cursor(id))?;
+        Ok(EventsSubscription::start_subscriber(cb, Box::pin(stream)))
+    }
+
     /// Get the current monitor state stored inside this client.
     ///
     /// Note: Client state might be stale. To synchronize with the driver,
@@ -121,10 +166,113 @@ impl DriverClient {
     ///
     /// State changes of the client are not automatically sent to the driver.
     /// You must manually call this method to send changes to the driver.
-    pub fn notify(&mut self) -> Result<(), error::SendError> {
+    ///
+    /// The state is validated and checked against the limits of the driver
+    /// first, see [DriverClient::request_limits].
+    pub fn notify(&mut self) -> Result<(), error::NotifyError> {
         RUNTIME.block_on(self.0.notify())
     }
 
+    /// Get the catalogue of modes monitors without modes get.
+    ///
+    /// This is the catalogue of the driver when connecting, or the one set
+    /// with [DriverClient::set_default_modes] since.
+    pub fn default_modes(&self) -> &DefaultModes {
+        self.0.default_modes()
+    }
+
+    /// Set the catalogue of modes monitors without modes get, both for this
+    /// client and the driver.
+    ///
+    /// Monitors which already got their modes from the previous catalogue
+    /// are not changed. The catalogue is [validated](DefaultModes::validate)
+    /// first.
+    pub fn set_default_modes(
+        &mut self,
+        defaults: DefaultModes,
+    ) -> Result<(), error::SetDefaultModesError> {
+        RUNTIME.block_on(self.0.set_default_modes(defaults))
+    }
+
+    /// Request the EDID the monitor with the given ID was plugged in with.
+    ///
+    /// Returns `None` if the driver does not know the monitor, or it is not
+    /// plugged in.
+    pub fn request_edid(&self, id: Id) -> Result<Option<Vec<u8>>, error::RequestError> {
+        RUNTIME.block_on(self.0.request_edid(id))
+    }
+
+    /// Request the limits the driver was configured with.
+    ///
+    /// The driver rejects monitor states with more enabled monitors than
+    /// [Limits::max_monitors], or with enabled monitors whose ids aren't below
+    /// it. [DriverClient::notify] checks this before sending.
+    pub fn request_limits(&self) -> Result<Limits, error::RequestError> {
+        RUNTIME.block_on(self.0.request_limits())
+    }
+
+    /// Select the gpu which renders the monitors, see
+    /// [DriverClient::request_render_adapters].
+    ///
+    /// The driver keeps the os's choice if nothing matches. `None` stops
+    /// selecting a gpu, the current one stays in use until the driver restarts.
+    pub fn set_render_adapter(
+        &self,
+        preference: Option<&Preference>,
+    ) -> Result<(), error::SendError> {
+        RUNTIME.block_on(self.0.set_render_adapter(preference))
+    }
+
+    /// Change the level of the driver log.
+    ///
+    /// With a `module_filter`, like `virtual_display_driver::ipc`, the level
+    /// only applies to that module and its submodules, every other module
+    /// keeps the current level. The level is reset when the driver restarts.
+    pub fn set_log_level(
+        &self,
+        level: LogLevel,
+        module_filter: Option<&str>,
+    ) -> Result<(), error::SendError> {
+        RUNTIME.block_on(self.0.set_log_level(level, module_filter))
+    }
+
+    /// Start or stop exporting the frames of the monitor with the specified
+    /// ID into shared memory, see the `frame-export` crate.
+    ///
+    /// The export continues across mode changes and while the monitor is
+    /// off, until it's stopped or the driver restarts.
+    pub fn set_frame_export(&self, id: Id, enabled: bool) -> Result<(), error::SendError> {
+        RUNTIME.block_on(self.0.set_frame_export(id, enabled))
+    }
+
+    /// Request the gpus which can render the monitors.
+    pub fn request_render_adapters(&self) -> Result<Vec<RenderAdapter>, error::RequestError> {
+        RUNTIME.block_on(self.0.request_render_adapters())
+    }
+
+    /// Request the log entries the driver keeps in memory, oldest first.
+    ///
+    /// Only entries after the one with sequence number `since` (all if
+    /// `None`) which are at least as severe as `min_level` are returned.
+    pub fn request_recent_logs(
+        &self,
+        since: Option<u64>,
+        min_level: LogLevel,
+    ) -> Result<Vec<LogEntry>, error::RequestError> {
+        RUNTIME.block_on(self.0.request_recent_logs(since, min_level))
+    }
+
+    /// Request the report the driver wrote when it last panicked, `None` if
+    /// it didn't panic yet.
+    pub fn request_crash_report(&self) -> Result<Option<CrashReport>, error::RequestError> {
+        RUNTIME.block_on(self.0.request_crash_report())
+    }
+
+    /// Request the frame statistics of the enabled monitors.
+    pub fn request_frame_stats(&self) -> Result<Vec<FrameStats>, error::RequestError> {
+        RUNTIME.block_on(self.0.request_frame_stats())
+    }
+
     /// Find the monitor with the given ID.
     ///
     /// Note: Client state might be stale. To synchronize with the driver,
@@ -202,8 +350,9 @@ impl DriverClient {
 
     /// Write client state to the registry for current user.
     ///
-    /// Next time the driver is started, it will load this state from the
-    /// registry. This might be after a reboot or a driver restart.
+    /// The user session service applies this state when the user logs on.
+    /// The driver itself starts with the machine-wide
+    /// [`Config`](crate::config::Config) of its device registry key.
     pub fn persist(&self) -> Result<(), error::PersistError> {
         self.0.persist()
     }
@@ -260,7 +409,8 @@ impl DriverClient {
     ///
     /// Returns an error if a monitor with this ID already exists, or if the
     /// monitor is invalid. A monitor is invalid if it has duplicate modes, or
-    /// if any of its modes has duplicate refresh rates.
+    /// if any of its modes has duplicate refresh rates. A monitor without
+    /// modes gets the modes of [DriverClient::default_modes].
     ///
     /// Note: This does not affect the driver. Manually call
     /// [DriverClient::notify] to send these changes to the driver.
@@ -0,0 +1,914 @@
+//! Video timing generation.
+//!
+//! Implements VESA DMT lookups for standard modes and the VESA CVT formulas
+//! (standard blanking, reduced blanking v1 and v2) for everything else. The
+//! driver uses [`Timing::from_mode`] for every mode, so the timings it reports
+//! to Windows and the ones in the EDID always agree.
+
+use crate::{Blanking, Dimen, Mode, RefreshRate};
+
+use self::error::TimingError;
+
+/// Full timing of a single mode.
+///
+/// Horizontal values are in pixels, vertical values in lines. Like in an EDID
+/// detailed timing descriptor, the vertical values of interlaced timings are
+/// per field.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Timing {
+    pub pixel_clock_khz: u32,
+    pub h_active: u32,
+    pub h_blank: u32,
+    pub h_front_porch: u32,
+    pub h_sync: u32,
+    pub v_active: u32,
+    pub v_blank: u32,
+    pub v_front_porch: u32,
+    pub v_sync: u32,
+    pub h_sync_positive: bool,
+    pub v_sync_positive: bool,
+    pub interlaced: bool,
+}
+
+// horizontal sizes of cvt timings are a multiple of this
+const CELL_GRAN: u32 = 8;
+
+// VESA DMT 1.0 r13 timings of common modes
+#[rustfmt::skip]
+const DMT: &[(RefreshRate, Timing)] = &[
+    (60, timing(25_175, [640, 16, 96, 48], [480, 10, 2, 33], false, false)),
+    (75, timing(31_500, [640, 16, 64, 120], [480, 1, 3, 16], false, false)),
+    (60, timing(40_000, [800, 40, 128, 88], [600, 1, 4, 23], true, true)),
+    (75, timing(49_500, [800, 16, 80, 160], [600, 1, 3, 21], true, true)),
+    (60, timing(65_000, [1024, 24, 136, 160], [768, 3, 6, 29], false, false)),
+    (75, timing(78_750, [1024, 16, 96, 176], [768, 1, 3, 28], true, true)),
+    (60, timing(74_250, [1280, 110, 40, 220], [720, 5, 5, 20], true, true)),
+    (60, timing(71_000, [1280, 48, 32, 80], [800, 3, 6, 14], true, false)),
+    (60, timing(108_000, [1280, 96, 112, 312], [960, 1, 3, 36], true, true)),
+    (60, timing(108_000, [1280, 48, 112, 248], [1024, 1, 3, 38], true, true)),
+    (75, timing(135_000, [1280, 16, 144, 248], [1024, 1, 3, 38], true, true)),
+    (60, timing(85_500, [1366, 70, 143, 213], [768, 3, 3, 24], true, true)),
+    (60, timing(88_750, [1440, 48, 32, 80], [900, 3, 6, 17], true, false)),
+    (60, timing(108_000, [1600, 24, 80, 96], [900, 1, 3, 96], true, true)),
+    (60, timing(162_000, [1600, 64, 192, 304], [1200, 1, 3, 46], true, true)),
+    (60, timing(119_000, [1680, 48, 32, 80], [1050, 3, 6, 21], true, false)),
+    (60, timing(148_500, [1920, 88, 44, 148], [1080, 4, 5, 36], true, true)),
+    (60, timing(154_000, [1920, 48, 32, 80], [1200, 3, 6, 26], true, false)),
+    (60, timing(268_500, [2560, 48, 32, 80], [1600, 3, 6, 37], true, false)),
+];
+
+// CTA-861 interlaced broadcast timings, by field rate
+#[rustfmt::skip]
+const CTA_INTERLACED: &[(RefreshRate, Timing)] = &[
+    (60, Timing { interlaced: true, ..timing(74_250, [1920, 88, 44, 148], [540, 2, 5, 15], true, true) }),
+    (50, Timing { interlaced: true, ..timing(74_250, [1920, 528, 44, 148], [540, 2, 5, 15], true, true) }),
+];
+
+/// Build a timing from `[active, front porch, sync, back porch]`
+const fn timing(
+    pixel_clock_khz: u32,
+    h: [u32; 4],
+    v: [u32; 4],
+    h_sync_positive: bool,
+    v_sync_positive: bool,
+) -> Timing {
+    Timing {
+        pixel_clock_khz,
+        h_active: h[0],
+        h_blank: h[1] + h[2] + h[3],
+        h_front_porch: h[1],
+        h_sync: h[2],
+        v_active: v[0],
+        v_blank: v[1] + v[2] + v[3],
+        v_front_porch: v[1],
+        v_sync: v[2],
+        h_sync_positive,
+        v_sync_positive,
+        interlaced: false,
+    }
+}
+
+impl Timing {
+    /// The timing the driver uses for one refresh rate of a mode.
+    ///
+    /// Uses the custom blanking of the mode if it has one, otherwise the timing
+    /// is generated by [`Timing::new`] or [`Timing::new_interlaced`].
+    pub fn from_mode(mode: &Mode, refresh_rate: RefreshRate) -> Result<Self, TimingError> {
+        let Mode {
+            width,
+            height,
+            interlaced,
+            ..
+        } = *mode;
+
+        if width == 0 || height == 0 || refresh_rate == 0 {
+            return Err(TimingError::Zero(width, height, refresh_rate));
+        }
+
+        if interlaced && height % 2 != 0 {
+            return Err(TimingError::OddHeight(width, height));
+        }
+
+        match (&mode.blanking, interlaced) {
+            (Some(blanking), _) => Self::custom(width, height, refresh_rate, interlaced, blanking),
+            (None, false) => Self::new(width, height, refresh_rate)
+                .ok_or(TimingError::Unsupported(width, height, refresh_rate)),
+            (None, true) => Self::new_interlaced(width, height, refresh_rate)
+                .ok_or(TimingError::Unsupported(width, height, refresh_rate)),
+        }
+    }
+
+    /// The generated timing of a progressive mode.
+    ///
+    /// Standard modes use their DMT timing. 60hz modes use CVT reduced blanking
+    /// v1, which is what real displays advertise for them. Every other refresh
+    /// rate uses CVT reduced blanking v2, which hits the refresh rate almost
+    /// exactly. Its pixel clock is rounded down to the 10khz steps an EDID
+    /// detailed timing descriptor can hold.
+    ///
+    /// Returns `None` if no sensible timing exists for this mode.
+    #[must_use]
+    pub fn new(width: Dimen, height: Dimen, refresh_rate: RefreshRate) -> Option<Self> {
+        Self::dmt(width, height, refresh_rate).or_else(|| {
+            if refresh_rate == 60 {
+                Self::cvt_rb(width, height, refresh_rate)
+            } else {
+                Self::cvt_rb2(width, height, refresh_rate).map(|timing| Self {
+                    pixel_clock_khz: timing.pixel_clock_khz / 10 * 10,
+                    ..timing
+                })
+            }
+        })
+    }
+
+    /// The generated timing of an interlaced mode, `height` is the height of the
+    /// whole frame and `refresh_rate` the field rate.
+    ///
+    /// Broadcast modes use their CTA-861 timing, everything else uses CVT.
+    ///
+    /// Returns `None` if no sensible timing exists for this mode.
+    #[must_use]
+    pub fn new_interlaced(width: Dimen, height: Dimen, refresh_rate: RefreshRate) -> Option<Self> {
+        CTA_INTERLACED
+            .iter()
+            .find(|(rr, t)| (t.h_active, t.v_active * 2, *rr) == (width, height, refresh_rate))
+            .map(|&(_, timing)| timing)
+            .or_else(|| Self::cvt(width, height, refresh_rate, true))
+    }
+
+    /// Build a timing from custom blanking. The pixel clock is calculated from
+    /// the refresh rate and rounded to 10khz, the precision of an EDID.
+    pub fn custom(
+        width: Dimen,
+        height: Dimen,
+        refresh_rate: RefreshRate,
+        interlaced: bool,
+        blanking: &Blanking,
+    ) -> Result<Self, TimingError> {
+        if blanking.h_sync == 0 || blanking.v_sync == 0 {
+            return Err(TimingError::ZeroSync(width, height));
+        }
+
+        let sum = |values: &[u32]| {
+            values
+                .iter()
+                .try_fold(0, |sum: u32, &v| sum.checked_add(v))
+                .ok_or(TimingError::BlankingTooLarge(width, height))
+        };
+
+        let h_blank = sum(&[
+            blanking.h_front_porch,
+            blanking.h_sync,
+            blanking.h_back_porch,
+        ])?;
+        let v_blank = sum(&[
+            blanking.v_front_porch,
+            blanking.v_sync,
+            blanking.v_back_porch,
+        ])?;
+        let v_active = if interlaced { height / 2 } else { height };
+
+        let h_total = u128::from(sum(&[width, h_blank])?);
+        let v_total = u128::from(sum(&[v_active, v_blank])?);
+
+        let clock_hz = if interlaced {
+            // every field is half a line longer than its v_total
+            u128::from(refresh_rate) * h_total * (2 * v_total + 1) / 2
+        } else {
+            u128::from(refresh_rate) * h_total * v_total
+        };
+        let pixel_clock_khz = (clock_hz + 5_000) / 10_000 * 10;
+
+        Ok(Self {
+            pixel_clock_khz: u32::try_from(pixel_clock_khz)
+                .map_err(|_| TimingError::ClockTooHigh(width, height, refresh_rate))?,
+            h_active: width,
+            h_blank,
+            h_front_porch: blanking.h_front_porch,
+            h_sync: blanking.h_sync,
+            v_active,
+            v_blank,
+            v_front_porch: blanking.v_front_porch,
+            v_sync: blanking.v_sync,
+            h_sync_positive: blanking.h_sync_positive,
+            v_sync_positive: blanking.v_sync_positive,
+            interlaced,
+        })
+    }
+
+    /// Look up the VESA DMT timing of a mode.
+    ///
+    /// Returns `None` if this is not a DMT mode.
+    #[must_use]
+    pub fn dmt(width: Dimen, height: Dimen, refresh_rate: RefreshRate) -> Option<Self> {
+        DMT.iter()
+            .find(|(rr, t)| (t.h_active, t.v_active, *rr) == (width, height, refresh_rate))
+            .map(|&(_, timing)| timing)
+    }
+
+    /// Calculate a VESA CVT timing with standard (CRT) blanking. For interlaced
+    /// timings `refresh_rate` is the field rate.
+    ///
+    /// Returns `None` if no sensible timing exists for this mode.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    #[must_use]
+    pub fn cvt(
+        width: Dimen,
+        height: Dimen,
+        refresh_rate: RefreshRate,
+        interlaced: bool,
+    ) -> Option<Self> {
+        // minimum time of vertical sync + back porch in us
+        const MIN_VSYNC_BP_US: f64 = 550.0;
+        const V_FRONT_PORCH: u32 = 3;
+        const MIN_V_BACK_PORCH: u32 = 6;
+        // blanking formula gradient and offset (C' and M')
+        const C_PRIME: f64 = 30.0;
+        const M_PRIME: f64 = 300.0;
+        // horizontal sync width in % of the line
+        const H_SYNC_PERCENT: f64 = 8.0;
+        // pixel clock is a multiple of 0.25mhz
+        const CLOCK_STEP_KHZ: f64 = 250.0;
+
+        if width == 0 || height == 0 || refresh_rate == 0 {
+            return None;
+        }
+
+        // the spec rounds the width down to the character cell, keep it
+        // exact so the timing always matches the mode
+        let h_active = width;
+        let v_sync = v_sync_width(h_active, height);
+
+        // lines per field, and the extra half line of interlaced fields
+        let (v_lines, interlace) = if interlaced {
+            (height / 2, 0.5)
+        } else {
+            (height, 0.0)
+        };
+
+        // estimated line period in us
+        let h_period = (1_000_000.0 / f64::from(refresh_rate) - MIN_VSYNC_BP_US)
+            / (f64::from(v_lines) + f64::from(V_FRONT_PORCH) + interlace);
+        if h_period <= 0.0 {
+            return None;
+        }
+
+        // positive, these casts won't ever lose the sign, but they saturate
+        // for absurd sizes, so the sums are checked
+
+        let v_sync_bp = ((MIN_VSYNC_BP_US / h_period) as u32).checked_add(1)?;
+        let v_sync_bp = v_sync_bp.max(v_sync + MIN_V_BACK_PORCH);
+
+        let duty_cycle = (C_PRIME - M_PRIME * h_period / 1000.0).max(20.0);
+        let h_blank = (f64::from(h_active) * duty_cycle
+            / (100.0 - duty_cycle)
+            / f64::from(2 * CELL_GRAN)) as u32
+            * 2
+            * CELL_GRAN;
+
+        let h_total = h_active.checked_add(h_blank)?;
+        let v_blank = v_sync_bp.checked_add(V_FRONT_PORCH)?;
+        // the totals have to fit too
+        v_lines.checked_add(v_blank)?;
+
+        let pixel_clock_khz =
+            ((f64::from(h_total) / h_period * 1000.0 / CLOCK_STEP_KHZ) as u32).checked_mul(250)?;
+
+        let h_sync =
+            (H_SYNC_PERCENT / 100.0 * f64::from(h_total) / f64::from(CELL_GRAN)) as u32 * CELL_GRAN;
+
+        Some(Self {
+            pixel_clock_khz,
+            h_active,
+            h_blank,
+            // sync is centered in the blanking period
+            h_front_porch: h_blank / 2 - h_sync,
+            h_sync,
+            v_active: v_lines,
+            v_blank,
+            v_front_porch: V_FRONT_PORCH,
+            v_sync,
+            h_sync_positive: false,
+            v_sync_positive: true,
+            interlaced,
+        })
+    }
+
+    /// Calculate a VESA CVT reduced blanking (v1) timing.
+    ///
+    /// Returns `None` if no sensible timing exists for this mode.
+    #[must_use]
+    pub fn cvt_rb(width: Dimen, height: Dimen, refresh_rate: RefreshRate) -> Option<Self> {
+        const H_BLANK: u32 = 160;
+        const H_SYNC: u32 = 32;
+        const H_FRONT_PORCH: u32 = 48;
+        const V_FRONT_PORCH: u32 = 3;
+        const MIN_V_BACK_PORCH: u32 = 6;
+        // pixel clock is a multiple of 0.25mhz
+        const CLOCK_STEP_HZ: u128 = 250_000;
+
+        if width == 0 || height == 0 || refresh_rate == 0 {
+            return None;
+        }
+
+        // exact width, like in `cvt`
+        let h_active = width;
+        let v_sync = v_sync_width(h_active, height);
+
+        let v_blank =
+            rb_vbi_lines(height, refresh_rate)?.max(V_FRONT_PORCH + v_sync + MIN_V_BACK_PORCH);
+
+        let h_total = u128::from(h_active.checked_add(H_BLANK)?);
+        let v_total = u128::from(height.checked_add(v_blank)?);
+
+        let pixel_clock_hz =
+            u128::from(refresh_rate) * h_total * v_total / CLOCK_STEP_HZ * CLOCK_STEP_HZ;
+
+        Some(Self {
+            pixel_clock_khz: u32::try_from(pixel_clock_hz / 1000).ok()?,
+            h_active,
+            h_blank: H_BLANK,
+            h_front_porch: H_FRONT_PORCH,
+            h_sync: H_SYNC,
+            v_active: height,
+            v_blank,
+            v_front_porch: V_FRONT_PORCH,
+            v_sync,
+            h_sync_positive: true,
+            v_sync_positive: false,
+            interlaced: false,
+        })
+    }
+
+    /// Calculate a VESA CVT reduced blanking v2 timing.
+    ///
+    /// Returns `None` if no sensible timing exists for this mode.
+    #[must_use]
+    pub fn cvt_rb2(width: Dimen, height: Dimen, refresh_rate: RefreshRate) -> Option<Self> {
+        const H_BLANK: u32 = 80;
+        const H_SYNC: u32 = 32;
+        const H_FRONT_PORCH: u32 = 8;
+        const V_SYNC: u32 = 8;
+        const MIN_V_FRONT_PORCH: u32 = 1;
+        const V_BACK_PORCH: u32 = 6;
+
+        if width == 0 || height == 0 || refresh_rate == 0 {
+            return None;
+        }
+
+        let v_blank =
+            rb_vbi_lines(height, refresh_rate)?.max(MIN_V_FRONT_PORCH + V_SYNC + V_BACK_PORCH);
+
+        let h_total = u128::from(width.checked_add(H_BLANK)?);
+        let v_total = u128::from(height.checked_add(v_blank)?);
+
+        // pixel clock is a multiple of 1khz
+        let pixel_clock_khz = u128::from(refresh_rate) * h_total * v_total / 1000;
+
+        Some(Self {
+            pixel_clock_khz: u32::try_from(pixel_clock_khz).ok()?,
+            h_active: width,
+            h_blank: H_BLANK,
+            h_front_porch: H_FRONT_PORCH,
+            h_sync: H_SYNC,
+            v_active: height,
+            v_blank,
+            // v2 has a fixed back porch, the front porch takes the rest
+            v_front_porch: v_blank - V_SYNC - V_BACK_PORCH,
+            v_sync: V_SYNC,
+            h_sync_positive: true,
+            v_sync_positive: false,
+            interlaced: false,
+        })
+    }
+
+    #[must_use]
+    pub fn h_total(&self) -> u32 {
+        self.h_active + self.h_blank
+    }
+
+    #[must_use]
+    pub fn v_total(&self) -> u32 {
+        self.v_active + self.v_blank
+    }
+
+    #[must_use]
+    pub fn h_back_porch(&self) -> u32 {
+        self.h_blank - self.h_front_porch - self.h_sync
+    }
+
+    #[must_use]
+    pub fn v_back_porch(&self) -> u32 {
+        self.v_blank - self.v_front_porch - self.v_sync
+    }
+
+    #[must_use]
+    pub fn pixel_clock_hz(&self) -> u64 {
+        u64::from(self.pixel_clock_khz) * 1000
+    }
+
+    /// Horizontal frequency in hz
+    #[must_use]
+    pub fn h_freq_hz(&self) -> u64 {
+        self.pixel_clock_hz() / u64::from(self.h_total())
+    }
+
+    /// Exact vertical frequency in hz, as `(numerator, denominator)`
+    ///
+    /// This is the field rate for interlaced timings.
+    #[must_use]
+    pub fn v_freq(&self) -> (u64, u64) {
+        let h_total = u64::from(self.h_total());
+        let v_total = u64::from(self.v_total());
+
+        if self.interlaced {
+            // every field is half a line longer than its v_total
+            (self.pixel_clock_hz() * 2, h_total * (2 * v_total + 1))
+        } else {
+            (self.pixel_clock_hz(), h_total * v_total)
+        }
+    }
+}
+
+impl Mode {
+    /// Check that a timing can be built for every refresh rate of this mode.
+    pub fn validate(&self) -> Result<(), TimingError> {
+        for &refresh_rate in &self.refresh_rates {
+            Timing::from_mode(self, refresh_rate)?;
+        }
+
+        Ok(())
+    }
+
+    /// The timings of every refresh rate of this mode, skipping the ones no
+    /// timing can be built for.
+    pub fn timings(&self) -> impl Iterator<Item = Timing> + '_ {
+        self.refresh_rates
+            .iter()
+            .filter_map(|&refresh_rate| Timing::from_mode(self, refresh_rate).ok())
+    }
+}
+
+/// Lines in the vertical blanking interval of a reduced blanking timing, before
+/// applying the minimum porch and sync sizes
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn rb_vbi_lines(height: Dimen, refresh_rate: RefreshRate) -> Option<u32> {
+    // minimum vertical blanking time in us
+    const MIN_V_BLANK_US: f64 = 460.0;
+
+    // estimated line period in us
+    let h_period = (1_000_000.0 / f64::from(refresh_rate) - MIN_V_BLANK_US) / f64::from(height);
+    if h_period <= 0.0 {
+        return None;
+    }
+
+    // positive, this won't ever lose the sign, but it saturates for absurd sizes
+    ((MIN_V_BLANK_US / h_period) as u32).checked_add(1)
+}
+
+/// Whether `width`x`height` has the aspect ratio `w`:`h`
+pub(crate) fn is_aspect(width: Dimen, height: Dimen, w: u32, h: u32) -> bool {
+    // allow for widths rounded to the character cell, like 1366x768
+    (u64::from(height) * u64::from(w) / u64::from(h)).abs_diff(u64::from(width)) < 8
+}
+
+/// CVT vertical sync width, which encodes the aspect ratio
+fn v_sync_width(width: Dimen, height: Dimen) -> u32 {
+    let is_aspect = |w, h| is_aspect(width, height, w, h);
+
+    if is_aspect(4, 3) {
+        4
+    } else if is_aspect(16, 9) {
+        5
+    } else if is_aspect(16, 10) {
+        6
+    } else if is_aspect(5, 4) || is_aspect(15, 9) {
+        7
+    } else {
+        10
+    }
+}
+
+pub mod error {
+    use serde::{Deserialize, Serialize};
+    use thiserror::Error;
+
+    use crate::{Dimen, RefreshRate};
+
+    /// Error returned from [`Timing::from_mode`](super::Timing::from_mode).
+    #[derive(Debug, Clone, Deserialize, Serialize, Error, PartialEq, Eq)]
+    pub enum TimingError {
+        #[error("Mode {0}x{1}@{2} must not have a size or refresh rate of 0")]
+        Zero(Dimen, Dimen, RefreshRate),
+        #[error("Interlaced mode {0}x{1} must have an even height")]
+        OddHeight(Dimen, Dimen),
+        #[error("Sync pulses of mode {0}x{1} must not be 0")]
+        ZeroSync(Dimen, Dimen),
+        #[error("Blanking of mode {0}x{1} is too large")]
+        BlankingTooLarge(Dimen, Dimen),
+        #[error("Pixel clock of mode {0}x{1}@{2} is too high")]
+        ClockTooHigh(Dimen, Dimen, RefreshRate),
+        #[error("No timing can be generated for mode {0}x{1}@{2}")]
+        Unsupported(Dimen, Dimen, RefreshRate),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // VESA CVT 1.2 timings
+    #[test]
+    fn cvt() {
+        let timings = [
+            (
+                (1024, 768, 60),
+                timing(63_500, [1024, 48, 104, 152], [768, 3, 4, 23], false, true),
+            ),
+            (
+                (1280, 720, 60),
+                timing(74_500, [1280, 64, 128, 192], [720, 3, 5, 20], false, true),
+            ),
+            (
+                (1920, 1080, 60),
+                timing(
+                    173_000,
+                    [1920, 128, 200, 328],
+                    [1080, 3, 5, 32],
+                    false,
+                    true,
+                ),
+            ),
+            (
+                (1920, 1200, 60),
+                timing(
+                    193_250,
+                    [1920, 136, 200, 336],
+                    [1200, 3, 6, 36],
+                    false,
+                    true,
+                ),
+            ),
+        ];
+
+        for ((width, height, refresh_rate), expected) in timings {
+            assert_eq!(
+                Timing::cvt(width, height, refresh_rate, false),
+                Some(expected),
+                "{width}x{height}@{refresh_rate}"
+            );
+        }
+    }
+
+    // VESA CVT 1.2 reduced blanking timings, and the DMT timings derived from them
+    #[test]
+    fn cvt_rb() {
+        let timings = [
+            (
+                (1280, 800, 60),
+                timing(71_000, [1280, 48, 32, 80], [800, 3, 6, 14], true, false),
+            ),
+            (
+                (1400, 1050, 60),
+                timing(101_000, [1400, 48, 32, 80], [1050, 3, 4, 23], true, false),
+            ),
+            (
+                (1920, 1080, 60),
+                timing(138_500, [1920, 48, 32, 80], [1080, 3, 5, 23], true, false),
+            ),
+            (
+                (2560, 1440, 60),
+                timing(241_500, [2560, 48, 32, 80], [1440, 3, 5, 33], true, false),
+            ),
+            (
+                (3840, 2160, 60),
+                timing(533_250, [3840, 48, 32, 80], [2160, 3, 5, 54], true, false),
+            ),
+        ];
+
+        for ((width, height, refresh_rate), expected) in timings {
+            assert_eq!(
+                Timing::cvt_rb(width, height, refresh_rate),
+                Some(expected),
+                "{width}x{height}@{refresh_rate}"
+            );
+        }
+
+        for (width, height) in [
+            (1280, 800),
+            (1440, 900),
+            (1680, 1050),
+            (1920, 1200),
+            (2560, 1600),
+        ] {
+            assert_eq!(
+                Timing::cvt_rb(width, height, 60),
+                Timing::dmt(width, height, 60),
+                "{width}x{height}"
+            );
+        }
+    }
+
+    // VESA CVT 1.2 reduced blanking v2 timings
+    #[test]
+    fn cvt_rb2() {
+        let timings = [
+            (
+                (1920, 1080, 60),
+                timing(133_320, [1920, 8, 32, 40], [1080, 17, 8, 6], true, false),
+            ),
+            (
+                (3840, 2160, 60),
+                timing(522_614, [3840, 8, 32, 40], [2160, 48, 8, 6], true, false),
+            ),
+        ];
+
+        for ((width, height, refresh_rate), expected) in timings {
+            assert_eq!(
+                Timing::cvt_rb2(width, height, refresh_rate),
+                Some(expected),
+                "{width}x{height}@{refresh_rate}"
+            );
+        }
+    }
+
+    #[test]
+    fn cvt_rb2_exact_refresh_rate() {
+        for (width, height, refresh_rate) in
+            [(1920, 1080, 144), (2560, 1440, 165), (3840, 2160, 120)]
+        {
+            let timing = Timing::cvt_rb2(width, height, refresh_rate).unwrap();
+            let (num, den) = timing.v_freq();
+
+            // only off by the 1khz clock step
+            let error = num.abs_diff(u64::from(refresh_rate) * den);
+            assert!(error < 1000, "{width}x{height}@{refresh_rate}");
+        }
+    }
+
+    #[test]
+    fn invalid_modes() {
+        for (width, height, refresh_rate) in [(0, 1080, 60), (1920, 0, 60), (1920, 1080, 0)] {
+            assert_eq!(Timing::cvt(width, height, refresh_rate, false), None);
+            assert_eq!(Timing::cvt_rb(width, height, refresh_rate), None);
+            assert_eq!(Timing::cvt_rb2(width, height, refresh_rate), None);
+            assert_eq!(Timing::new(width, height, refresh_rate), None);
+        }
+
+        // the vertical blanking interval alone is longer than a frame
+        assert_eq!(Timing::cvt(1920, 1080, 2000, false), None);
+        assert_eq!(Timing::cvt_rb(1920, 1080, 3000), None);
+        assert_eq!(Timing::cvt_rb2(1920, 1080, 3000), None);
+
+        // the totals or the pixel clock don't fit
+        for (width, height) in [(u32::MAX, 1080), (1920, u32::MAX), (u32::MAX, u32::MAX)] {
+            for refresh_rate in [1, 60, 144] {
+                for interlaced in [false, true] {
+                    assert_eq!(Timing::cvt(width, height, refresh_rate, interlaced), None);
+                }
+                assert_eq!(Timing::cvt_rb(width, height, refresh_rate), None);
+                assert_eq!(Timing::cvt_rb2(width, height, refresh_rate), None);
+            }
+        }
+    }
+
+    #[test]
+    fn exact_width() {
+        for width in [1366, 1921, 2555] {
+            let cvt = Timing::cvt(width, 1080, 60, false).unwrap();
+            let cvt_rb = Timing::cvt_rb(width, 1080, 60).unwrap();
+            let cvt_rb2 = Timing::cvt_rb2(width, 1080, 60).unwrap();
+
+            for timing in [cvt, cvt_rb, cvt_rb2] {
+                assert_eq!(timing.h_active, width, "{width}x1080 {timing:?}");
+            }
+
+            // still centered in the blanking period
+            assert_eq!(cvt.h_front_porch + cvt.h_sync, cvt.h_blank / 2);
+        }
+
+        for interlaced in [false, true] {
+            let mode = mode(1921, 1080, interlaced, None);
+            assert_eq!(mode.validate(), Ok(()));
+
+            let timing = Timing::from_mode(&mode, 60).unwrap();
+            assert_eq!(timing.h_active, 1921);
+        }
+    }
+
+    #[test]
+    fn dmt() {
+        let timing = Timing::dmt(1920, 1080, 60).unwrap();
+
+        assert_eq!(timing.pixel_clock_khz, 148_500);
+        assert_eq!((timing.h_total(), timing.v_total()), (2200, 1125));
+        assert_eq!((timing.h_back_porch(), timing.v_back_porch()), (148, 36));
+        assert_eq!(timing.v_freq(), (148_500_000, 2_475_000));
+
+        assert_eq!(Timing::dmt(1920, 1080, 59), None);
+        assert_eq!(Timing::dmt(1920, 1081, 60), None);
+    }
+
+    #[test]
+    fn dmt_refresh_rates() {
+        for &(refresh_rate, timing) in DMT {
+            let (num, den) = timing.v_freq();
+            let rate =
+                f64::from(u32::try_from(num).unwrap()) / f64::from(u32::try_from(den).unwrap());
+
+            // dmt rates are nominal, e.g. 800x600@60 is 60.32hz
+            assert!(
+                (rate - f64::from(refresh_rate)).abs() < 0.5,
+                "{}x{}@{refresh_rate} is {rate}",
+                timing.h_active,
+                timing.v_active
+            );
+        }
+    }
+
+    #[test]
+    fn new_prefers_dmt() {
+        assert_eq!(Timing::new(1920, 1080, 60), Timing::dmt(1920, 1080, 60));
+        assert_eq!(Timing::new(1024, 768, 75), Timing::dmt(1024, 768, 75));
+        assert_eq!(Timing::new(2560, 1440, 60), Timing::cvt_rb(2560, 1440, 60));
+
+        let rb2 = Timing::cvt_rb2(1920, 1080, 144).unwrap();
+        assert_eq!(rb2.pixel_clock_khz, 333_216);
+        assert_eq!(
+            Timing::new(1920, 1080, 144),
+            Some(Timing {
+                pixel_clock_khz: 333_210,
+                ..rb2
+            })
+        );
+
+        let rb2 = Timing::cvt_rb2(2560, 1440, 144).unwrap();
+        assert_eq!(
+            Timing::new(2560, 1440, 144),
+            Some(Timing {
+                pixel_clock_khz: rb2.pixel_clock_khz / 10 * 10,
+                ..rb2
+            })
+        );
+    }
+
+    fn blanking(h: [u32; 3], v: [u32; 3], h_pos: bool, v_pos: bool) -> Blanking {
+        Blanking {
+            h_front_porch: h[0],
+            h_sync: h[1],
+            h_back_porch: h[2],
+            v_front_porch: v[0],
+            v_sync: v[1],
+            v_back_porch: v[2],
+            h_sync_positive: h_pos,
+            v_sync_positive: v_pos,
+        }
+    }
+
+    fn mode(width: Dimen, height: Dimen, interlaced: bool, blanking: Option<Blanking>) -> Mode {
+        Mode {
+            width,
+            height,
+            refresh_rates: vec![60],
+            interlaced,
+            blanking,
+        }
+    }
+
+    #[test]
+    fn cta_interlaced() {
+        for refresh_rate in [50, 60] {
+            let timing = Timing::new_interlaced(1920, 1080, refresh_rate).unwrap();
+
+            assert!(timing.interlaced);
+            assert_eq!(timing.pixel_clock_khz, 74_250);
+            assert_eq!((timing.v_active, timing.v_total()), (540, 562));
+
+            // exactly the field rate
+            let (num, den) = timing.v_freq();
+            assert_eq!(num, u64::from(refresh_rate) * den);
+        }
+
+        assert_eq!(
+            Timing::new_interlaced(1920, 1080, 50).unwrap().h_total(),
+            2640
+        );
+        assert_eq!(
+            Timing::new_interlaced(1920, 1080, 60).unwrap().h_total(),
+            2200
+        );
+    }
+
+    #[test]
+    fn cvt_interlaced() {
+        let timing = Timing::new_interlaced(1280, 1024, 60).unwrap();
+        assert_eq!(Timing::cvt(1280, 1024, 60, true), Some(timing));
+
+        assert!(timing.interlaced);
+        assert_eq!(timing.v_active, 512);
+        assert_eq!(timing.v_sync, 7);
+
+        let (num, den) = timing.v_freq();
+        let rate = f64::from(u32::try_from(num).unwrap()) / f64::from(u32::try_from(den).unwrap());
+        assert!((rate - 60.0).abs() < 0.5, "{rate}");
+    }
+
+    #[test]
+    fn custom() {
+        let dmt = blanking([88, 44, 148], [4, 5, 36], true, true);
+        assert_eq!(
+            Timing::custom(1920, 1080, 60, false, &dmt),
+            Ok(Timing::dmt(1920, 1080, 60).unwrap())
+        );
+
+        let cta = blanking([528, 44, 148], [2, 5, 15], true, true);
+        assert_eq!(
+            Timing::custom(1920, 1080, 50, true, &cta),
+            Ok(Timing::new_interlaced(1920, 1080, 50).unwrap())
+        );
+
+        // clock is rounded to 10khz
+        let timing = Timing::custom(1000, 1000, 61, false, &dmt).unwrap();
+        assert_eq!(timing.pixel_clock_khz, 81_590);
+        assert_eq!((timing.h_total(), timing.v_total()), (1280, 1045));
+    }
+
+    #[test]
+    fn from_mode() {
+        assert_eq!(
+            Timing::from_mode(&mode(2560, 1440, false, None), 60),
+            Ok(Timing::new(2560, 1440, 60).unwrap())
+        );
+        assert_eq!(
+            Timing::from_mode(&mode(1920, 1080, true, None), 50),
+            Ok(Timing::new_interlaced(1920, 1080, 50).unwrap())
+        );
+
+        let custom = blanking([8, 32, 40], [3, 5, 10], false, true);
+        assert_eq!(
+            Timing::from_mode(&mode(1920, 1080, false, Some(custom)), 60),
+            Timing::custom(1920, 1080, 60, false, &custom)
+        );
+    }
+
+    #[test]
+    fn validate() {
+        let valid = blanking([8, 32, 40], [3, 5, 10], true, false);
+        assert_eq!(mode(1920, 1080, false, Some(valid)).validate(), Ok(()));
+        assert_eq!(mode(1920, 1080, true, Some(valid)).validate(), Ok(()));
+
+        assert_eq!(
+            mode(0, 1080, false, None).validate(),
+            Err(TimingError::Zero(0, 1080, 60))
+        );
+        assert_eq!(
+            mode(1920, 1081, true, None).validate(),
+            Err(TimingError::OddHeight(1920, 1081))
+        );
+
+        let no_sync = blanking([8, 0, 40], [3, 5, 10], true, false);
+        assert_eq!(
+            mode(1920, 1080, false, Some(no_sync)).validate(),
+            Err(TimingError::ZeroSync(1920, 1080))
+        );
+
+        let overflow = blanking([u32::MAX, 32, 40], [3, 5, 10], true, false);
+        assert_eq!(
+            mode(1920, 1080, false, Some(overflow)).validate(),
+            Err(TimingError::BlankingTooLarge(1920, 1080))
+        );
+
+        let huge = blanking([1 << 30, 32, 40], [1 << 30, 5, 10], true, false);
+        assert_eq!(
+            mode(1920, 1080, false, Some(huge)).validate(),
+            Err(TimingError::ClockTooHigh(1920, 1080, 60))
+        );
+
+        for (width, height) in [(u32::MAX, 1080), (1920, u32::MAX)] {
+            assert_eq!(
+                mode(width, height, false, None).validate(),
+                Err(TimingError::Unsupported(width, height, 60))
+            );
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use crate::ring::Record;
+
+/// Keeps the first records until a sink which couldn't be opened early on (like the event log
+/// during boot) is available, so startup diagnostics aren't lost
+#[derive(Debug)]
+pub struct EarlyBuffer {
+    capacity: usize,
+    records: Vec<Record>,
+    dropped: usize,
+}
+
+impl EarlyBuffer {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: Vec::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Keep `record`, unless the buffer is full
+    pub fn push(&mut self, record: Record) {
+        if self.records.len() < self.capacity {
+            self.records.push(record);
+        } else {
+            self.dropped += 1;
+        }
+    }
+
+    /// The kept records, oldest first
+    #[must_use]
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// Amount of records which didn't fit anymore
+    #[must_use]
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use log::Level;
+
+    use super::*;
+
+    fn record(seq: u64) -> Record {
+        Record::new(
+            seq,
+            &log::Record::builder()
+                .level(Level::Info)
+                .args(format_args!("{seq}"))
+                .build(),
+        )
+    }
+
+    #[test]
+    fn keeps_first() {
+        let mut early = EarlyBuffer::new(2);
+
+        for seq in 0..5 {
+            early.push(record(seq));
+        }
+
+        let seqs = early.records().iter().map(|r| r.seq).collect::<Vec<_>>();
+        assert_eq!(seqs, [0, 1]);
+        assert_eq!(early.dropped(), 3);
+    }
+}
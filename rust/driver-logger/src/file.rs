@@ -0,0 +1,307 @@
+use std::{
+    fmt::Write as _,
+    fs::{self, File, OpenOptions},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    format::{json_line, text_message, Format},
+    time::{format_timestamp, unix_millis},
+};
+
+/// Where log files are written and how many are kept
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileConfig {
+    pub dir: PathBuf,
+    /// The current file is `{name}.log`, older ones are `{name}.1.log`, `{name}.2.log` and so on
+    pub name: String,
+    /// Size in bytes after which the current file is rotated
+    pub max_size: u64,
+    /// Amount of files kept, including the current one
+    pub max_files: usize,
+    pub format: Format,
+}
+
+impl FileConfig {
+    pub const DEFAULT_MAX_SIZE: u64 = 1024 * 1024;
+    pub const DEFAULT_MAX_FILES: usize = 5;
+
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>, name: impl Into<String>) -> Self {
+        Self {
+            dir: dir.into(),
+            name: name.into(),
+            max_size: Self::DEFAULT_MAX_SIZE,
+            max_files: Self::DEFAULT_MAX_FILES,
+            format: Format::Text,
+        }
+    }
+
+    /// Path of the file `index` rotations old, 0 is the current one
+    #[must_use]
+    pub fn path(&self, index: usize) -> PathBuf {
+        if index == 0 {
+            self.dir.join(format!("{}.log", self.name))
+        } else {
+            self.dir.join(format!("{}.{index}.log", self.name))
+        }
+    }
+}
+
+/// `%ProgramData%\VirtualDisplayDriver\logs`, where logs go if nothing else is configured
+#[must_use]
+pub fn default_log_dir() -> PathBuf {
+    let program_data = std::env::var_os("ProgramData")
+        .map_or_else(|| PathBuf::from(r"C:\ProgramData"), PathBuf::from);
+
+    program_data.join("VirtualDisplayDriver").join("logs")
+}
+
+/// A file which is moved aside once it's full, keeping a limited amount of older files
+#[derive(Debug)]
+pub struct RotatingFile {
+    config: FileConfig,
+    // `None` only if reopening it after a rotation failed, retried on the next write
+    file: Option<File>,
+    size: u64,
+}
+
+impl RotatingFile {
+    /// Open the current file, appending to it if it already exists
+    pub fn open(config: FileConfig) -> io::Result<Self> {
+        fs::create_dir_all(&config.dir)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(config.path(0))?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            config,
+            file: Some(file),
+            size,
+        })
+    }
+
+    #[must_use]
+    pub fn config(&self) -> &FileConfig {
+        &self.config
+    }
+
+    /// Write `data` to the current file, rotating first if it doesn't fit anymore
+    ///
+    /// Data larger than the maximum size still gets written, to a file of its own.
+    pub fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        let len = data.len() as u64;
+
+        if self.file.is_none() || (self.size > 0 && self.size + len > self.config.max_size) {
+            self.rotate()?;
+        }
+
+        if let Some(file) = self.file.as_mut() {
+            file.write_all(data)?;
+            self.size += len;
+        }
+
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        // closed first, an open file can't be moved
+        self.file = None;
+
+        // the oldest file is overwritten by the one before it
+        for index in (1..self.config.max_files).rev() {
+            rename_if_exists(&self.config.path(index - 1), &self.config.path(index))?;
+        }
+
+        self.file = Some(
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(self.config.path(0))?,
+        );
+        self.size = 0;
+
+        Ok(())
+    }
+}
+
+fn rename_if_exists(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// A record as a line of a log file, multiline text messages continue on indented lines
+pub(crate) fn format_record(time: SystemTime, record: &log::Record, format: Format) -> String {
+    if format == Format::Json {
+        return json_line(time, record) + "\n";
+    }
+
+    let target = if record.target().is_empty() {
+        record.module_path().unwrap_or_default()
+    } else {
+        record.target()
+    };
+
+    let level = format!("[{}]", record.level());
+
+    let mut line = format!(
+        "{} {level:<7} [{target}",
+        format_timestamp(unix_millis(time))
+    );
+
+    if let Some(number) = record.line() {
+        _ = write!(line, ":{number}");
+    }
+
+    line.push(']');
+
+    let message = text_message(record);
+    let mut lines = message.lines();
+
+    _ = writeln!(line, " {}", lines.next().unwrap_or_default());
+    for continued in lines {
+        _ = writeln!(line, "    {continued}");
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use log::Level;
+
+    use super::*;
+
+    // a config in an empty directory of its own
+    fn config(test: &str, max_size: u64, max_files: usize) -> FileConfig {
+        let dir = std::env::temp_dir().join(format!("driver-logger-{test}-{}", std::process::id()));
+        _ = fs::remove_dir_all(&dir);
+
+        FileConfig {
+            dir,
+            name: "test".to_owned(),
+            max_size,
+            max_files,
+            format: Format::Text,
+        }
+    }
+
+    fn read(config: &FileConfig, index: usize) -> Option<String> {
+        fs::read_to_string(config.path(index)).ok()
+    }
+
+    #[test]
+    fn rotate() {
+        let config = config("rotate", 8, 3);
+        let mut file = RotatingFile::open(config.clone()).unwrap();
+
+        file.write(b"aaaa").unwrap();
+        file.write(b"bbbb").unwrap();
+        assert_eq!(read(&config, 0).as_deref(), Some("aaaabbbb"));
+        assert_eq!(read(&config, 1), None);
+
+        file.write(b"cc").unwrap();
+        assert_eq!(read(&config, 0).as_deref(), Some("cc"));
+        assert_eq!(read(&config, 1).as_deref(), Some("aaaabbbb"));
+
+        // larger than a whole file, gets one of its own
+        file.write(b"dddddddddd").unwrap();
+        assert_eq!(read(&config, 0).as_deref(), Some("dddddddddd"));
+        assert_eq!(read(&config, 1).as_deref(), Some("cc"));
+        assert_eq!(read(&config, 2).as_deref(), Some("aaaabbbb"));
+
+        fs::remove_dir_all(&config.dir).unwrap();
+    }
+
+    #[test]
+    fn max_files() {
+        let config = config("max_files", 1, 3);
+        let mut file = RotatingFile::open(config.clone()).unwrap();
+
+        for data in ["a", "b", "c", "d", "e"] {
+            file.write(data.as_bytes()).unwrap();
+        }
+
+        assert_eq!(read(&config, 0).as_deref(), Some("e"));
+        assert_eq!(read(&config, 1).as_deref(), Some("d"));
+        assert_eq!(read(&config, 2).as_deref(), Some("c"));
+        assert_eq!(read(&config, 3), None);
+
+        // a single file is truncated instead
+        let config = FileConfig {
+            max_files: 1,
+            ..config
+        };
+        let mut file = RotatingFile::open(config.clone()).unwrap();
+
+        file.write(b"f").unwrap();
+        assert_eq!(read(&config, 0).as_deref(), Some("f"));
+        assert_eq!(read(&config, 1).as_deref(), Some("d"));
+
+        fs::remove_dir_all(&config.dir).unwrap();
+    }
+
+    #[test]
+    fn append() {
+        let config = config("append", 8, 2);
+
+        RotatingFile::open(config.clone())
+            .unwrap()
+            .write(b"aaaa")
+            .unwrap();
+
+        // the existing content counts towards the size
+        let mut file = RotatingFile::open(config.clone()).unwrap();
+        file.write(b"bbbb").unwrap();
+        assert_eq!(read(&config, 0).as_deref(), Some("aaaabbbb"));
+
+        file.write(b"c").unwrap();
+        assert_eq!(read(&config, 0).as_deref(), Some("c"));
+        assert_eq!(read(&config, 1).as_deref(), Some("aaaabbbb"));
+
+        fs::remove_dir_all(&config.dir).unwrap();
+    }
+
+    #[test]
+    fn format() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+
+        let line = format_record(
+            time,
+            &log::Record::builder()
+                .level(Level::Warn)
+                .target("driver")
+                .line(Some(12))
+                .args(format_args!("first\nsecond"))
+                .build(),
+            Format::Text,
+        );
+
+        assert_eq!(
+            line,
+            "2023-11-14 22:13:20.123 [WARN]  [driver:12] first\n    second\n"
+        );
+
+        let line = format_record(
+            time,
+            &log::Record::builder()
+                .level(Level::Warn)
+                .args(format_args!("first\nsecond"))
+                .build(),
+            Format::Json,
+        );
+
+        assert_eq!(line.lines().count(), 1);
+        assert!(line.ends_with('\n'));
+    }
+}
@@ -0,0 +1,114 @@
+use log::{LevelFilter, Metadata};
+
+/// Which records get logged, can be changed at runtime with [`set_filter`](crate::set_filter)
+///
+/// Like an `env_logger` directive such as `info,virtual_display_driver::ipc=trace`, a module can have
+/// its own level while every other module keeps logging at the default one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    /// Level of every module without a level of its own
+    pub level: LevelFilter,
+    /// Level of this module and its submodules, e.g. `virtual_display_driver::ipc`
+    pub module: Option<(String, LevelFilter)>,
+}
+
+impl Filter {
+    #[must_use]
+    pub const fn new(level: LevelFilter) -> Self {
+        Self {
+            level,
+            module: None,
+        }
+    }
+
+    #[must_use]
+    pub fn enabled(&self, metadata: &Metadata) -> bool {
+        let level = match &self.module {
+            Some((module, level)) if in_module(metadata.target(), module) => *level,
+            _ => self.level,
+        };
+
+        metadata.level() <= level
+    }
+
+    /// The most verbose level any record can pass with
+    #[must_use]
+    pub fn max_level(&self) -> LevelFilter {
+        match &self.module {
+            Some((_, level)) => self.level.max(*level),
+            None => self.level,
+        }
+    }
+}
+
+fn in_module(target: &str, module: &str) -> bool {
+    target
+        .strip_prefix(module)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+#[cfg(test)]
+mod test {
+    use log::Level;
+
+    use super::*;
+
+    fn enabled(filter: &Filter, level: Level, target: &str) -> bool {
+        let metadata = Metadata::builder().level(level).target(target).build();
+        filter.enabled(&metadata)
+    }
+
+    #[test]
+    fn level() {
+        let filter = Filter::new(LevelFilter::Info);
+
+        assert!(enabled(&filter, Level::Error, "driver"));
+        assert!(enabled(&filter, Level::Info, "driver"));
+        assert!(!enabled(&filter, Level::Debug, "driver"));
+
+        assert!(!enabled(
+            &Filter::new(LevelFilter::Off),
+            Level::Error,
+            "driver"
+        ));
+    }
+
+    #[test]
+    fn module() {
+        let filter = Filter {
+            level: LevelFilter::Info,
+            module: Some(("driver::ipc".to_owned(), LevelFilter::Trace)),
+        };
+
+        assert!(enabled(&filter, Level::Trace, "driver::ipc"));
+        assert!(enabled(&filter, Level::Trace, "driver::ipc::pipe"));
+        assert!(!enabled(&filter, Level::Debug, "driver::ipcx"));
+        assert!(!enabled(&filter, Level::Debug, "driver"));
+        assert!(!enabled(&filter, Level::Debug, "driver::state"));
+        assert_eq!(filter.max_level(), LevelFilter::Trace);
+
+        // a quieter module doesn't lower the level of the others
+        let filter = Filter {
+            level: LevelFilter::Info,
+            module: Some(("driver::ipc".to_owned(), LevelFilter::Error)),
+        };
+
+        assert!(!enabled(&filter, Level::Warn, "driver::ipc"));
+        assert!(enabled(&filter, Level::Info, "driver::state"));
+        assert_eq!(filter.max_level(), LevelFilter::Info);
+    }
+
+    #[test]
+    fn module_keeps_other_errors() {
+        let filter = Filter {
+            level: LevelFilter::Warn,
+            module: Some(("driver::ipc".to_owned(), LevelFilter::Trace)),
+        };
+
+        for target in ["driver", "driver::state", "driver::ipcx", "wdf_umdf"] {
+            assert!(enabled(&filter, Level::Error, target), "{target}");
+            assert!(enabled(&filter, Level::Warn, target), "{target}");
+            assert!(!enabled(&filter, Level::Info, target), "{target}");
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::{fmt::Write as _, time::SystemTime};
+
+use log::kv::{self, VisitSource};
+use serde_json::{json, Map, Value};
+
+use crate::time::unix_millis;
+
+/// Key-values of a record, sorted by key
+pub type Fields = Map<String, Value>;
+
+/// How the sinks of the logger write records
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Format {
+    /// Human readable lines, key-values are appended as `key=value`
+    #[default]
+    Text,
+    /// One json object per record, see [`json_line`]
+    Json,
+}
+
+/// The key-values of `record`, numbers and bools stay what they are, everything else becomes a
+/// string
+#[must_use]
+pub fn fields(record: &log::Record) -> Fields {
+    struct Visitor(Fields);
+
+    impl<'kvs> VisitSource<'kvs> for Visitor {
+        fn visit_pair(
+            &mut self,
+            key: kv::Key<'kvs>,
+            value: kv::Value<'kvs>,
+        ) -> Result<(), kv::Error> {
+            self.0.insert(key.to_string(), field_value(&value));
+            Ok(())
+        }
+    }
+
+    let mut visitor = Visitor(Fields::new());
+    // the visitor never fails
+    _ = record.key_values().visit(&mut visitor);
+
+    visitor.0
+}
+
+fn field_value(value: &kv::Value) -> Value {
+    if let Some(value) = value.to_u64() {
+        value.into()
+    } else if let Some(value) = value.to_i64() {
+        value.into()
+    } else if let Some(value) = value.to_f64() {
+        value.into()
+    } else if let Some(value) = value.to_bool() {
+        value.into()
+    } else {
+        value.to_string().into()
+    }
+}
+
+/// [`Fields`] as the key-values of a [`log::Record`]
+pub(crate) struct FieldsSource<'a>(pub &'a Fields);
+
+impl kv::Source for FieldsSource<'_> {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), kv::Error> {
+        for (key, value) in self.0 {
+            let value = match value {
+                Value::Bool(value) => kv::Value::from(*value),
+                Value::Number(number) => {
+                    if let Some(value) = number.as_u64() {
+                        kv::Value::from(value)
+                    } else if let Some(value) = number.as_i64() {
+                        kv::Value::from(value)
+                    } else {
+                        kv::Value::from(number.as_f64().unwrap_or_default())
+                    }
+                }
+                Value::String(value) => kv::Value::from(value.as_str()),
+                value => kv::Value::from_display(value),
+            };
+
+            visitor.visit_pair(kv::Key::from_str(key), value)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The message of `record` followed by its key-values as `key=value`
+#[must_use]
+pub fn text_message(record: &log::Record) -> String {
+    let mut message = record.args().to_string();
+
+    for (key, value) in fields(record) {
+        // strings without quotes
+        if let Value::String(value) = value {
+            _ = write!(message, " {key}={value}");
+        } else {
+            _ = write!(message, " {key}={value}");
+        }
+    }
+
+    message
+}
+
+/// `record` as a single line json object, without a line break
+///
+/// ```json
+/// {"timestamp":1700000000123,"level":"INFO","target":"virtual_display_driver::ipc","line":12,"message":"Client connected","fields":{"client_id":1}}
+/// ```
+///
+/// `timestamp` is in milliseconds since the unix epoch, `line` is left out if unknown.
+#[must_use]
+pub fn json_line(time: SystemTime, record: &log::Record) -> String {
+    let mut line = json!({
+        "timestamp": unix_millis(time),
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "message": record.args().to_string(),
+        "fields": fields(record),
+    });
+
+    if let Some(number) = record.line() {
+        line["line"] = number.into();
+    }
+
+    line.to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use log::Level;
+
+    use super::*;
+
+    fn with_record(cb: impl FnOnce(&log::Record)) {
+        let kvs: [(&str, kv::Value); 4] = [
+            ("monitor_id", 3u32.into()),
+            ("offset", (-2i64).into()),
+            ("connected", true.into()),
+            ("name", "a b".into()),
+        ];
+
+        cb(&log::Record::builder()
+            .level(Level::Info)
+            .target("driver")
+            .line(Some(12))
+            .args(format_args!("hello"))
+            .key_values(&kvs)
+            .build());
+    }
+
+    #[test]
+    fn text() {
+        with_record(|record| {
+            assert_eq!(
+                text_message(record),
+                "hello connected=true monitor_id=3 name=a b offset=-2"
+            );
+        });
+    }
+
+    #[test]
+    fn json() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+
+        with_record(|record| {
+            let line = json_line(time, record);
+            assert!(!line.contains('\n'));
+
+            let line = serde_json::from_str::<Value>(&line).unwrap();
+            assert_eq!(
+                line,
+                json!({
+                    "timestamp": 1_700_000_000_123u64,
+                    "level": "INFO",
+                    "target": "driver",
+                    "line": 12,
+                    "message": "hello",
+                    "fields": {
+                        "monitor_id": 3,
+                        "offset": -2,
+                        "connected": true,
+                        "name": "a b",
+                    },
+                })
+            );
+        });
+    }
+}
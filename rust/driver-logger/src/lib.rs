@@ -1,20 +1,148 @@
 #![allow(clippy::missing_errors_doc)]
 
+mod early;
+mod file;
+mod filter;
+mod format;
+mod ring;
+mod time;
 mod win_debug;
 mod win_logger;
 
-use std::error::Error;
+use std::{
+    error::Error,
+    io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock, PoisonError, RwLock,
+    },
+    time::{Duration, Instant, SystemTime},
+};
 
-use log::{Level, Log};
+use log::{Level, LevelFilter, Log};
+use tokio::sync::broadcast;
 
+use crate::early::EarlyBuffer;
+use crate::file::format_record;
+pub use crate::file::{default_log_dir, FileConfig, RotatingFile};
+pub use crate::filter::Filter;
+pub use crate::format::{fields, json_line, text_message, Fields, Format};
+pub use crate::ring::Record;
+use crate::ring::RingBuffer;
+pub use crate::time::{format_timestamp, unix_millis};
 use crate::win_debug::WinDebugLogger;
 use crate::win_logger::WinLogger;
 
+// active filter of the logger, starts out with the level it was created with
+static FILTER: RwLock<Filter> = RwLock::new(Filter::new(LevelFilter::Info));
+
+/// Replace the filter of the logger
+pub fn set_filter(filter: Filter) {
+    let level = filter.max_level();
+
+    // a panic while holding the lock can't leave the filter half written
+    *FILTER.write().unwrap_or_else(PoisonError::into_inner) = filter;
+
+    log::set_max_level(level);
+}
+
+/// The active filter of the logger
+#[must_use]
+pub fn filter() -> Filter {
+    FILTER
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+// file the records are written to, if any
+static FILE: Mutex<Option<RotatingFile>> = Mutex::new(None);
+
+// event log, once it could be registered
+static EVENT_LOG: OnceLock<WinLogger> = OnceLock::new();
+
+// records logged before the event log was available, see `DriverLogger::early_buffer`
+//
+// always locked before `FILE`, so a sink which is opened meanwhile neither misses a record nor gets
+// it twice
+static EARLY: Mutex<Option<EarlyBuffer>> = Mutex::new(None);
+
+// how often registering the event log is retried while it isn't available
+const EVENT_LOG_RETRY: Duration = Duration::from_secs(1);
+
+/// Write records to rotating files from now on, or stop writing them with `None`
+///
+/// Can be called at any time, e.g. once the configured directory is known. Records which are
+/// still buffered because the event log isn't available yet are written first.
+pub fn set_log_file(config: Option<FileConfig>) -> io::Result<()> {
+    let mut file = config.map(RotatingFile::open).transpose()?;
+
+    let early = EARLY.lock().unwrap_or_else(PoisonError::into_inner);
+
+    if let (Some(file), Some(early)) = (file.as_mut(), early.as_ref()) {
+        for record in early.records() {
+            let line =
+                record.with_log_record(|r| format_record(record.time, r, file.config().format));
+            _ = file.write(line.as_bytes());
+        }
+    }
+
+    *FILE.lock().unwrap_or_else(PoisonError::into_inner) = file;
+
+    Ok(())
+}
+
+/// The config of the file records are written to, if any
+#[must_use]
+pub fn log_file() -> Option<FileConfig> {
+    FILE.lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+        .map(|file| file.config().clone())
+}
+
+// recent records, if the logger keeps them
+static RING: OnceLock<RingBuffer> = OnceLock::new();
+
+// records for subscribers, if the logger sends them
+static SUBSCRIBERS: OnceLock<broadcast::Sender<Record>> = OnceLock::new();
+
+// sequence number of the next record
+static NEXT_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// The kept records after `since` (all if `None`) which are at least as severe as `level`, see
+/// [`DriverLogger::ring_buffer`]
+#[must_use]
+pub fn recent_records(since: Option<u64>, level: LevelFilter) -> Vec<Record> {
+    RING.get()
+        .map(|ring| ring.records(since, level))
+        .unwrap_or_default()
+}
+
+/// Receive the records logged from now on, `None` if the logger doesn't send them, see
+/// [`DriverLogger::subscribers`]
+///
+/// A receiver which falls behind by more than the capacity misses the oldest records.
+#[must_use]
+pub fn subscribe() -> Option<broadcast::Receiver<Record>> {
+    SUBSCRIBERS.get().map(broadcast::Sender::subscribe)
+}
+
 // A logger which logs to multiple logger implementations
 pub struct DriverLogger {
     pub level: Level,
+    format: Format,
     win_debug: Option<WinDebugLogger>,
-    win_logger: Option<WinLogger>,
+    // name of the event log source, while it couldn't be registered yet
+    event_log: Option<PendingEventLog>,
+    early_capacity: usize,
+    ring: Option<&'static RingBuffer>,
+    subscribers: Option<&'static broadcast::Sender<Record>>,
+}
+
+struct PendingEventLog {
+    name: String,
+    next_attempt: Mutex<Instant>,
 }
 
 impl DriverLogger {
@@ -22,37 +150,176 @@ impl DriverLogger {
     pub fn new(level: Level) -> Self {
         Self {
             level,
-            win_logger: None,
+            format: Format::Text,
             win_debug: None,
+            event_log: None,
+            early_capacity: 0,
+            ring: None,
+            subscribers: None,
         }
     }
 
     pub fn debug(&mut self) -> &mut Self {
-        self.win_debug = Some(WinDebugLogger { level: self.level });
+        // records are already filtered before they get here
+        self.win_debug = Some(WinDebugLogger {
+            level: Level::Trace,
+        });
         self
     }
 
-    pub fn name(&mut self, name: &str) -> Result<&mut Self, Box<dyn Error>> {
-        self.win_logger = Some(WinLogger::new(name)?);
+    /// Report records to the event log as source `name`
+    ///
+    /// The event log isn't available early during boot. Registering is retried while logging
+    /// then, and the records up to then are reported once it worked, see [`Self::early_buffer`].
+    pub fn name(&mut self, name: &str) -> &mut Self {
+        match WinLogger::new(name) {
+            Ok(logger) => {
+                _ = EVENT_LOG.set(logger);
+                self.event_log = None;
+            }
+
+            Err(_) => {
+                self.event_log = Some(PendingEventLog {
+                    name: name.to_owned(),
+                    next_attempt: Mutex::new(Instant::now() + EVENT_LOG_RETRY),
+                });
+            }
+        }
+
+        self
+    }
+
+    /// Keep the first `capacity` records until the event log is available, to report them then
+    ///
+    /// Files opened meanwhile get them as well, see [`set_log_file`]. Nothing is kept if the event
+    /// log is available right away.
+    pub fn early_buffer(&mut self, capacity: usize) -> &mut Self {
+        self.early_capacity = capacity;
+        self
+    }
+
+    /// Format the records of the debug output and the event log with `format`, files have their
+    /// own, see [`FileConfig::format`]
+    pub fn format(&mut self, format: Format) -> &mut Self {
+        self.format = format;
+        self
+    }
+
+    /// Also write records to rotating files, see [`set_log_file`]
+    pub fn file(&mut self, config: FileConfig) -> Result<&mut Self, Box<dyn Error>> {
+        set_log_file(Some(config))?;
         Ok(self)
     }
 
+    /// Keep the most recent `capacity` records in memory, see [`recent_records`]
+    pub fn ring_buffer(&mut self, capacity: usize) -> &mut Self {
+        self.ring = Some(RING.get_or_init(|| RingBuffer::new(capacity)));
+        self
+    }
+
+    /// Send records to [`subscribe`]rs, keeping up to `capacity` records per subscriber which
+    /// were not received yet
+    pub fn subscribers(&mut self, capacity: usize) -> &mut Self {
+        self.subscribers = Some(SUBSCRIBERS.get_or_init(|| broadcast::channel(capacity).0));
+        self
+    }
+
     pub fn init(self) -> Result<(), Box<dyn Error>> {
         let level = self.level;
 
+        if self.event_log.is_some() && self.early_capacity > 0 {
+            *EARLY.lock().unwrap_or_else(PoisonError::into_inner) =
+                Some(EarlyBuffer::new(self.early_capacity));
+        }
+
         match log::set_boxed_logger(Box::new(self)) {
             Ok(()) => {
-                log::set_max_level(level.to_level_filter());
+                set_filter(Filter::new(level.to_level_filter()));
                 Ok(())
             }
             Err(e) => Err(e.into()),
         }
     }
+
+    /// Register the event log if it's pending and the last attempt was long enough ago
+    fn retry_event_log(&self) {
+        let Some(pending) = self.event_log.as_ref() else {
+            return;
+        };
+
+        if EVENT_LOG.get().is_some() {
+            return;
+        }
+
+        {
+            let mut next_attempt = pending
+                .next_attempt
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+
+            let now = Instant::now();
+            if now < *next_attempt {
+                return;
+            }
+
+            *next_attempt = now + EVENT_LOG_RETRY;
+        }
+
+        let Ok(logger) = WinLogger::new(&pending.name) else {
+            return;
+        };
+
+        let mut early = EARLY.lock().unwrap_or_else(PoisonError::into_inner);
+
+        // set while holding the buffer, so records logged meanwhile are reported exactly once
+        let logger = EVENT_LOG.get_or_init(|| logger);
+
+        if let Some(early) = early.take() {
+            for record in early.records() {
+                record.with_log_record(|r| report_early(logger, record.time, r, self.format));
+            }
+
+            if early.dropped() > 0 {
+                logger.report(
+                    Level::Warn,
+                    &format!(
+                        "{} records were dropped before the event log was available",
+                        early.dropped()
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Report a record of the early buffer, which carries the time it was logged at since the event
+/// log's own time is when it was reported
+fn report_early(logger: &WinLogger, time: SystemTime, record: &log::Record, format: Format) {
+    let message = match format {
+        Format::Text => format!(
+            "[{}] {}",
+            format_timestamp(unix_millis(time)),
+            text_message(record)
+        ),
+        Format::Json => json_line(time, record),
+    };
+
+    logger.report(record.level(), &message);
+}
+
+fn report(logger: &WinLogger, time: SystemTime, record: &log::Record, format: Format) {
+    match format {
+        Format::Text => logger.log(record),
+        Format::Json => logger.report(record.level(), &json_line(time, record)),
+    }
 }
 
 impl Log for DriverLogger {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        metadata.level() <= self.level
+        FILTER
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .enabled(metadata)
     }
 
     fn log(&self, record: &log::Record) {
@@ -60,13 +327,60 @@ impl Log for DriverLogger {
             return;
         }
 
+        let time = SystemTime::now();
+
         if let Some(debug) = self.win_debug.as_ref() {
-            debug.log(record);
+            match self.format {
+                Format::Text => debug.log(record),
+                Format::Json => debug.write(&json_line(time, record)),
+            }
         }
 
-        if let Some(logger) = self.win_logger.as_ref() {
-            logger.log(record);
+        let event_log = EVENT_LOG.get();
+
+        if let Some(logger) = event_log {
+            report(logger, time, record, self.format);
         }
+
+        let subscribers = self
+            .subscribers
+            .filter(|subscribers| subscribers.receiver_count() > 0);
+
+        {
+            let mut early = EARLY.lock().unwrap_or_else(PoisonError::into_inner);
+
+            if let Some(file) = FILE.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+                let line = format_record(time, record, file.config().format);
+                // nowhere to report a failed write to
+                _ = file.write(line.as_bytes());
+            }
+
+            if event_log.is_none() && early.is_none() {
+                // the event log became available since it was checked above
+                if let Some(logger) = EVENT_LOG.get() {
+                    report(logger, time, record, self.format);
+                }
+            }
+
+            if self.ring.is_some() || subscribers.is_some() || early.is_some() {
+                let record = Record::new(NEXT_SEQ.fetch_add(1, Ordering::Relaxed), record);
+
+                if let Some(subscribers) = subscribers {
+                    // fails only if the last subscriber just left
+                    _ = subscribers.send(record.clone());
+                }
+
+                if let Some(early) = early.as_mut() {
+                    early.push(record.clone());
+                }
+
+                if let Some(ring) = self.ring {
+                    ring.push(record);
+                }
+            }
+        }
+
+        self.retry_event_log();
     }
 
     fn flush(&self) {
@@ -74,7 +388,7 @@ impl Log for DriverLogger {
             debug.flush();
         }
 
-        if let Some(logger) = self.win_logger.as_ref() {
+        if let Some(logger) = EVENT_LOG.get() {
             logger.flush();
         }
     }
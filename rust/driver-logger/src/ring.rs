@@ -0,0 +1,199 @@
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, PoisonError},
+    time::SystemTime,
+};
+
+use log::{Level, LevelFilter};
+
+use crate::format::{fields, Fields, FieldsSource};
+
+/// A logged record, as kept in memory and sent to subscribers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// Increases by one with every record, so readers can continue where they left off
+    pub seq: u64,
+    pub time: SystemTime,
+    pub level: Level,
+    pub target: String,
+    /// Source line the record was logged from, if known
+    pub line: Option<u32>,
+    pub message: String,
+    pub fields: Fields,
+}
+
+impl Record {
+    pub(crate) fn new(seq: u64, record: &log::Record) -> Self {
+        Self {
+            seq,
+            time: SystemTime::now(),
+            level: record.level(),
+            target: record.target().to_owned(),
+            line: record.line(),
+            message: record.args().to_string(),
+            fields: fields(record),
+        }
+    }
+
+    /// Call `cb` with this record as a [`log::Record`], e.g. to hand it to a sink later
+    pub fn with_log_record<R>(&self, cb: impl FnOnce(&log::Record) -> R) -> R {
+        let fields = FieldsSource(&self.fields);
+
+        cb(&log::Record::builder()
+            .level(self.level)
+            .target(&self.target)
+            .line(self.line)
+            .args(format_args!("{}", self.message))
+            .key_values(&fields)
+            .build())
+    }
+}
+
+/// Keeps the most recent records, dropping the oldest once it is full
+#[derive(Debug)]
+pub struct RingBuffer {
+    capacity: usize,
+    records: Mutex<VecDeque<Record>>,
+}
+
+impl RingBuffer {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: Mutex::default(),
+        }
+    }
+
+    pub fn push(&self, record: Record) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut records = self.records.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+
+        records.push_back(record);
+    }
+
+    /// The records after `since` (all if `None`) which are at least as severe as `level`, oldest first
+    #[must_use]
+    pub fn records(&self, since: Option<u64>, level: LevelFilter) -> Vec<Record> {
+        let records = self.records.lock().unwrap_or_else(PoisonError::into_inner);
+
+        records
+            .iter()
+            .filter(|r| since.map_or(true, |since| r.seq > since) && r.level <= level)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn push(ring: &RingBuffer, seq: u64, level: Level, message: &str) {
+        ring.push(Record::new(
+            seq,
+            &log::Record::builder()
+                .level(level)
+                .target("driver")
+                .args(format_args!("{message}"))
+                .build(),
+        ));
+    }
+
+    fn messages(records: &[Record]) -> Vec<(u64, &str)> {
+        records
+            .iter()
+            .map(|r| (r.seq, r.message.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_most_recent() {
+        let ring = RingBuffer::new(2);
+
+        push(&ring, 0, Level::Info, "a");
+        push(&ring, 1, Level::Info, "b");
+        push(&ring, 2, Level::Info, "c");
+
+        let records = ring.records(None, LevelFilter::Trace);
+        assert_eq!(messages(&records), [(1, "b"), (2, "c")]);
+        assert_eq!(records[0].target, "driver");
+
+        assert!(RingBuffer::new(0)
+            .records(None, LevelFilter::Trace)
+            .is_empty());
+    }
+
+    #[test]
+    fn filter() {
+        let ring = RingBuffer::new(10);
+
+        push(&ring, 0, Level::Error, "a");
+        push(&ring, 1, Level::Debug, "b");
+        push(&ring, 2, Level::Warn, "c");
+
+        let records = ring.records(None, LevelFilter::Warn);
+        assert_eq!(messages(&records), [(0, "a"), (2, "c")]);
+
+        let records = ring.records(Some(0), LevelFilter::Trace);
+        assert_eq!(messages(&records), [(1, "b"), (2, "c")]);
+
+        let records = ring.records(Some(0), LevelFilter::Warn);
+        assert_eq!(messages(&records), [(2, "c")]);
+
+        assert!(ring.records(Some(2), LevelFilter::Trace).is_empty());
+        assert!(ring.records(None, LevelFilter::Off).is_empty());
+    }
+
+    #[test]
+    fn since_dropped() {
+        let ring = RingBuffer::new(2);
+
+        for (seq, message) in [(0, "a"), (1, "b"), (2, "c"), (3, "d")] {
+            push(&ring, seq, Level::Info, message);
+        }
+
+        // a reader which fell behind gets everything still kept
+        let records = ring.records(Some(0), LevelFilter::Trace);
+        assert_eq!(messages(&records), [(2, "c"), (3, "d")]);
+
+        let records = ring.records(Some(2), LevelFilter::Trace);
+        assert_eq!(messages(&records), [(3, "d")]);
+    }
+
+    #[test]
+    fn log_record() {
+        let kvs: [(&str, log::kv::Value); 3] = [
+            ("monitor_id", 3u32.into()),
+            ("ratio", 1.5.into()),
+            ("name", "a".into()),
+        ];
+
+        let record = Record::new(
+            0,
+            &log::Record::builder()
+                .level(Level::Warn)
+                .target("driver")
+                .line(Some(7))
+                .args(format_args!("hello"))
+                .key_values(&kvs)
+                .build(),
+        );
+
+        let again = record.with_log_record(|r| Record::new(0, r));
+        assert_eq!(
+            Record {
+                time: record.time,
+                ..again
+            },
+            record
+        );
+    }
+}
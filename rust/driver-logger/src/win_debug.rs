@@ -5,6 +5,8 @@ use std::fmt::Write;
 use log::{Level, Log, SetLoggerError};
 use windows::{core::PCWSTR, Win32::System::Diagnostics::Debug::OutputDebugStringW};
 
+use crate::format::text_message;
+
 #[derive(Debug)]
 pub struct WinDebugLogger {
     pub level: Level,
@@ -27,6 +29,24 @@ impl Log for WinDebugLogger {
     fn flush(&self) {}
 }
 
+impl WinDebugLogger {
+    /// Write already formatted `text`, line by line
+    pub fn write(&self, text: &str) {
+        for line in text.lines() {
+            output(line);
+        }
+    }
+}
+
+fn output(line: &str) {
+    let line = format!("{line}\0");
+    let line = line.encode_utf16().collect::<Vec<u16>>();
+
+    unsafe {
+        OutputDebugStringW(PCWSTR(line.as_ptr()));
+    }
+}
+
 fn log(record: &log::Record) -> Option<()> {
     let target = if record.target().is_empty() {
         record.module_path().unwrap_or_default()
@@ -46,14 +66,8 @@ fn log(record: &log::Record) -> Option<()> {
 
     write!(&mut base, "]").ok()?;
 
-    for line in record.args().to_string().lines() {
-        let full = format!("{base} {line}\0");
-        let full = full.encode_utf16().collect::<Vec<u16>>();
-
-        // Write the output
-        unsafe {
-            OutputDebugStringW(PCWSTR(full.as_ptr()));
-        }
+    for line in text_message(record).lines() {
+        output(&format!("{base} {line}"));
     }
 
     Some(())
@@ -17,6 +17,8 @@ use windows_sys::Win32::{
 };
 use winreg::{enums::HKEY_LOCAL_MACHINE, RegKey};
 
+use crate::format::text_message;
+
 // Generated from MC.
 const MSG_ERROR: u32 = 0xC000_0001;
 const MSG_WARNING: u32 = 0x8000_0002;
@@ -130,33 +132,39 @@ impl log::Log for WinLogger {
 
     fn log(&self, record: &Record) {
         if self.filter.matches(record) {
-            let level = record.level();
-            let (wtype, dweventid) = match level {
-                Level::Error => (EVENTLOG_ERROR_TYPE, MSG_ERROR),
-                Level::Warn => (EVENTLOG_WARNING_TYPE, MSG_WARNING),
-                Level::Info => (EVENTLOG_INFORMATION_TYPE, MSG_INFO),
-                Level::Debug => (EVENTLOG_INFORMATION_TYPE, MSG_DEBUG),
-                Level::Trace => (EVENTLOG_INFORMATION_TYPE, MSG_TRACE),
-            };
-
-            let msg = U16CString::from_str_truncate(format!("{}", record.args()));
-            let msg_ptr = msg.as_ptr();
-
-            unsafe {
-                ReportEventW(
-                    self.handle,
-                    wtype,     // type
-                    0,         // category
-                    dweventid, // event id == resource msg id
-                    std::ptr::null_mut(),
-                    1,
-                    0,
-                    &msg_ptr,
-                    std::ptr::null_mut(),
-                )
-            };
+            self.report(record.level(), &text_message(record));
         }
     }
 
     fn flush(&self) {}
 }
+
+impl WinLogger {
+    /// Report already formatted `msg` as an event of `level`
+    pub fn report(&self, level: Level, msg: &str) {
+        let (wtype, dweventid) = match level {
+            Level::Error => (EVENTLOG_ERROR_TYPE, MSG_ERROR),
+            Level::Warn => (EVENTLOG_WARNING_TYPE, MSG_WARNING),
+            Level::Info => (EVENTLOG_INFORMATION_TYPE, MSG_INFO),
+            Level::Debug => (EVENTLOG_INFORMATION_TYPE, MSG_DEBUG),
+            Level::Trace => (EVENTLOG_INFORMATION_TYPE, MSG_TRACE),
+        };
+
+        let msg = U16CString::from_str_truncate(msg);
+        let msg_ptr = msg.as_ptr();
+
+        unsafe {
+            ReportEventW(
+                self.handle,
+                wtype,     // type
+                0,         // category
+                dweventid, // event id == resource msg id
+                std::ptr::null_mut(),
+                1,
+                0,
+                &msg_ptr,
+                std::ptr::null_mut(),
+            )
+        };
+    }
+}
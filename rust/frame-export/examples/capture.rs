@@ -0,0 +1,366 @@
+//! Capture frames of a monitor into a png image or a y4m video
+//!
+//! The driver only exports the frames of monitors it was told to, e.g. with
+//! `virtual-display-driver-cli export <id>`. The hardware cursor is drawn into the frames, unless
+//! `--no-cursor` is given. Without the driver, `--synthetic` captures a test pattern instead.
+
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use clap::Parser;
+use frame_export::{
+    draw_cursor, error::ReadError, ring_size, synthetic::SyntheticProducer, Consumer, CursorReader,
+    Format, FrameInfo, HeapMemory, Memory,
+};
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// Id of the monitor.
+    id: u32,
+    /// File to write: `.png` for an image of the next frame, `.y4m` for a video.
+    output: PathBuf,
+    /// Number of frames of a video.
+    #[arg(short, long, default_value_t = 60)]
+    frames: u32,
+    /// Frame rate of a video.
+    #[arg(long, default_value_t = 60)]
+    fps: u32,
+    /// Name of the driver instance to use, if there is more than one.
+    #[arg(short, long)]
+    instance: Option<String>,
+    /// Capture a test pattern instead of the frames of the driver.
+    #[arg(long)]
+    synthetic: bool,
+    /// Leave out the hardware cursor.
+    #[arg(long)]
+    no_cursor: bool,
+}
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    if args.synthetic {
+        synthetic(&args)
+    } else {
+        driver(&args)
+    }
+}
+
+#[cfg(windows)]
+fn driver(args: &Args) -> Result<()> {
+    use driver_ipc::config::pipe_name;
+    use frame_export::{cursor_mapping_name, mapping_name, Mapping};
+
+    let pipe_name = pipe_name(args.instance.as_deref());
+
+    let name = mapping_name(&pipe_name, args.id);
+    let mapping = Mapping::open(&name)
+        .map_err(|e| format!("Failed to open {name}, is the monitor exported? {e}"))?;
+
+    // monitors without a hardware cursor have none
+    let cursor = if args.no_cursor {
+        None
+    } else {
+        Mapping::open(&cursor_mapping_name(&pipe_name, args.id))
+            .ok()
+            .and_then(|mapping| CursorReader::open(mapping).ok())
+    };
+
+    capture(&Consumer::open(mapping)?, cursor.as_ref(), args)
+}
+
+#[cfg(not(windows))]
+fn driver(_: &Args) -> Result<()> {
+    Err("The driver only runs on windows, use --synthetic".into())
+}
+
+fn synthetic(args: &Args) -> Result<()> {
+    const SLOTS: u32 = 3;
+    let (width, height) = (640, 360);
+
+    let slot_size = FrameInfo::new(Format::Bgra8, width, height).size();
+    let size = ring_size(SLOTS, slot_size).ok_or("Frames too large")?;
+    let memory = Arc::new(HeapMemory::new(size));
+
+    let mut producer = SyntheticProducer::new(memory.clone(), SLOTS, width, height)?;
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let producing = thread::spawn({
+        let stop = stop.clone();
+        let interval = Duration::from_secs(1) / args.fps.max(1);
+
+        move || {
+            while !stop.load(Ordering::Relaxed) {
+                producer.produce().expect("test pattern fits the slots");
+                thread::sleep(interval);
+            }
+        }
+    });
+
+    let result = capture(
+        &Consumer::open(memory)?,
+        None::<&CursorReader<HeapMemory>>,
+        args,
+    );
+
+    stop.store(true, Ordering::Relaxed);
+    producing.join().expect("producer panicked");
+
+    result
+}
+
+fn capture<M: Memory, C: Memory>(
+    consumer: &Consumer<M>,
+    cursor: Option<&CursorReader<C>>,
+    args: &Args,
+) -> Result<()> {
+    let extension = args.output.extension().and_then(|e| e.to_str());
+    let count = match extension {
+        Some("png") => 1,
+        Some("y4m") => args.frames,
+        _ => return Err("The output has to be a .png or .y4m file".into()),
+    };
+
+    let mut buf = Vec::new();
+    let mut shape = Vec::new();
+    let mut video = None;
+    let mut next = consumer.latest() + 1;
+    let mut written = 0;
+    let mut dropped = 0;
+
+    while written < count {
+        if consumer.latest() < next {
+            if consumer.closed() {
+                return Err("The export stopped".into());
+            }
+
+            thread::sleep(Duration::from_millis(1));
+            continue;
+        }
+
+        let frame = match consumer.read(next, &mut buf) {
+            Ok(frame) => frame,
+            // fell behind, continue with the latest frame
+            Err(ReadError::Overwritten(_)) => {
+                let latest = consumer.latest();
+                dropped += latest - next;
+                next = latest;
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        if let Some(cursor) = cursor {
+            draw_cursor(&frame.info, &mut buf, &cursor.read(&mut shape)?, &shape);
+        }
+
+        if count == 1 {
+            write_png(
+                BufWriter::new(File::create(&args.output)?),
+                &frame.info,
+                &buf,
+            )?;
+        } else {
+            let video = match &mut video {
+                Some(video) => video,
+                None => {
+                    let file = BufWriter::new(File::create(&args.output)?);
+                    video.insert(Y4mWriter::new(
+                        file,
+                        frame.info.width,
+                        frame.info.height,
+                        args.fps,
+                    )?)
+                }
+            };
+
+            video.write(&frame.info, &buf)?;
+        }
+
+        written += 1;
+        next += 1;
+    }
+
+    if let Some(video) = video {
+        video.finish()?;
+    }
+
+    println!(
+        "Captured {written} frames into {}, dropped {dropped}",
+        args.output.display()
+    );
+
+    Ok(())
+}
+
+/// Red, green and blue of every pixel of row `y` of a frame
+fn rgb_row<'a>(info: &FrameInfo, data: &'a [u8], y: u32) -> impl Iterator<Item = [u8; 3]> + 'a {
+    let start = y as usize * info.stride as usize;
+    let row = &data[start..start + info.row_size()];
+
+    match info.format {
+        Format::Bgra8 => row
+            .chunks_exact(4)
+            .map(|pixel| [pixel[2], pixel[1], pixel[0]]),
+    }
+}
+
+/// Encode a frame as png, without its alpha
+fn write_png(writer: impl Write, info: &FrameInfo, data: &[u8]) -> Result<()> {
+    let rgb = (0..info.height)
+        .flat_map(|y| rgb_row(info, data, y))
+        .flatten()
+        .collect::<Vec<_>>();
+
+    let mut encoder = png::Encoder::new(writer, info.width, info.height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&rgb)?;
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Writes frames into a y4m video, in 4:4:4 YCbCr of BT.709 with limited range
+struct Y4mWriter<W: Write> {
+    writer: W,
+    width: u32,
+    height: u32,
+    planes: Vec<u8>,
+}
+
+impl<W: Write> Y4mWriter<W> {
+    /// Write the header of a video of `width` * `height` with `fps` frames per second
+    fn new(mut writer: W, width: u32, height: u32, fps: u32) -> io::Result<Self> {
+        writeln!(
+            writer,
+            "YUV4MPEG2 W{width} H{height} F{fps}:1 Ip A1:1 C444 XCOLORRANGE=LIMITED"
+        )?;
+
+        Ok(Self {
+            writer,
+            width,
+            height,
+            planes: Vec::new(),
+        })
+    }
+
+    /// Write a frame, which has to have the size of the video
+    fn write(&mut self, info: &FrameInfo, data: &[u8]) -> io::Result<()> {
+        if (info.width, info.height) != (self.width, self.height) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "frame is {}x{}, but the video {}x{}",
+                    info.width, info.height, self.width, self.height
+                ),
+            ));
+        }
+
+        let pixels = self.width as usize * self.height as usize;
+        self.planes.clear();
+        self.planes.resize(pixels * 3, 0);
+        let (y_plane, chroma) = self.planes.split_at_mut(pixels);
+        let (u_plane, v_plane) = chroma.split_at_mut(pixels);
+
+        let rgb = (0..self.height).flat_map(|y| rgb_row(info, data, y));
+        for (i, [r, g, b]) in rgb.enumerate() {
+            let (r, g, b) = (i32::from(r), i32::from(g), i32::from(b));
+
+            // results are within 16..=240, so they fit
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            {
+                y_plane[i] = (((47 * r + 157 * g + 16 * b + 128) >> 8) + 16) as u8;
+                u_plane[i] = (((-26 * r - 86 * g + 112 * b + 128) >> 8) + 128) as u8;
+                v_plane[i] = (((112 * r - 102 * g - 10 * b + 128) >> 8) + 128) as u8;
+            }
+        }
+
+        self.writer.write_all(b"FRAME\n")?;
+        self.writer.write_all(&self.planes)
+    }
+
+    /// Flush and return the writer
+    fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 2x2 bgra frame with padded rows: red, green / blue, white
+    fn frame() -> (FrameInfo, Vec<u8>) {
+        let mut info = FrameInfo::new(Format::Bgra8, 2, 2);
+        info.stride = 12;
+
+        let data = [
+            [0, 0, 255, 255, 0, 255, 0, 255, 9, 9, 9, 9],
+            [255, 0, 0, 255, 255, 255, 255, 255, 9, 9, 9, 9],
+        ]
+        .concat();
+
+        (info, data)
+    }
+
+    #[test]
+    fn encode_png() {
+        let (info, data) = frame();
+
+        let mut out = Vec::new();
+        write_png(&mut out, &info, &data).unwrap();
+
+        let mut reader = png::Decoder::new(&out[..]).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let decoded = reader.next_frame(&mut pixels).unwrap();
+
+        assert_eq!((decoded.width, decoded.height), (2, 2));
+        assert_eq!(decoded.color_type, png::ColorType::Rgb);
+        assert_eq!(
+            pixels[..decoded.buffer_size()],
+            [255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255]
+        );
+    }
+
+    #[test]
+    fn encode_y4m() {
+        let (info, data) = frame();
+
+        let mut writer = Y4mWriter::new(Vec::new(), 2, 2, 60).unwrap();
+        writer.write(&info, &data).unwrap();
+        writer.write(&info, &data).unwrap();
+        assert!(writer
+            .write(&FrameInfo::new(Format::Bgra8, 1, 2), &data)
+            .is_err());
+        let out = writer.finish().unwrap();
+
+        let header = b"YUV4MPEG2 W2 H2 F60:1 Ip A1:1 C444 XCOLORRANGE=LIMITED\n";
+        assert_eq!(&out[..header.len()], header);
+
+        let frame = &out[header.len()..header.len() + 6 + 12];
+        assert_eq!(&frame[..6], b"FRAME\n");
+        // y, u and v of red, green, blue and white
+        assert_eq!(
+            &frame[6..],
+            [63, 172, 32, 235, 102, 42, 240, 128, 240, 26, 118, 128]
+        );
+        assert_eq!(out.len(), header.len() + 2 * (6 + 12));
+    }
+}
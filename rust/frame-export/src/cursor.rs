@@ -0,0 +1,565 @@
+use std::{
+    mem::size_of,
+    ptr,
+    sync::atomic::{fence, AtomicI32, AtomicU32, AtomicU64, Ordering},
+    thread,
+};
+
+use crate::{
+    error::{CreateError, OpenError, ReadError, WriteError},
+    Format, FrameInfo, Memory, VERSION,
+};
+
+/// `magic` of the [`CursorHeader`], "VDDCURSR"
+pub const CURSOR_MAGIC: u64 = u64::from_le_bytes(*b"VDDCURSR");
+
+pub const CURSOR_HEADER_SIZE: usize = 128;
+
+// reads retried while the writer is busy, before giving up
+const READ_ATTEMPTS: u32 = 1000;
+
+/// Start of the shared memory of a cursor, followed by the data of its shape
+///
+/// `magic`, `version` and `capacity` are written once when it's created.
+#[repr(C)]
+#[derive(Debug)]
+pub struct CursorHeader {
+    /// [`CURSOR_MAGIC`], written last, so a cursor without it isn't ready yet
+    pub magic: AtomicU64,
+    /// [`VERSION`]
+    pub version: AtomicU32,
+    /// Bytes of shape data there is room for
+    pub capacity: AtomicU32,
+    /// Odd while the cursor is written, increases with every update
+    pub sequence: AtomicU64,
+    /// 1 once the producer stopped
+    pub closed: AtomicU32,
+    /// 1 if the cursor is shown
+    pub visible: AtomicU32,
+    /// Position of the top left corner of the shape, relative to the monitor
+    pub x: AtomicI32,
+    pub y: AtomicI32,
+    /// Id of the shape, 0 before the first one
+    pub shape_id: AtomicU32,
+    /// [`CursorKind`] of the shape
+    pub kind: AtomicU32,
+    pub width: AtomicU32,
+    pub height: AtomicU32,
+    /// Bytes from the start of one row of the shape to the next
+    pub pitch: AtomicU32,
+    /// Position of the hot spot, relative to the top left corner of the shape
+    pub x_hot: AtomicU32,
+    pub y_hot: AtomicU32,
+    /// Bytes of shape data, `pitch * height`
+    pub size: AtomicU32,
+    _reserved: [AtomicU32; 14],
+}
+
+const _: () = assert!(size_of::<CursorHeader>() == CURSOR_HEADER_SIZE);
+
+/// How the pixels of a cursor shape are drawn, they are 8 bits each of blue, green, red and alpha
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CursorKind {
+    /// Blended with the screen by their alpha
+    Alpha = 1,
+    /// Pixels with an alpha of 0xff replace the screen, the color of ones with an alpha of 0 is
+    /// xored with it
+    MaskedColor = 2,
+}
+
+impl CursorKind {
+    #[must_use]
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            1 => Some(Self::Alpha),
+            2 => Some(Self::MaskedColor),
+            _ => None,
+        }
+    }
+}
+
+/// Image of a cursor, without its data
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CursorShape {
+    /// Changes with every new shape, never 0
+    pub id: u32,
+    pub kind: CursorKind,
+    pub width: u32,
+    pub height: u32,
+    /// Bytes from the start of one row to the next
+    pub pitch: u32,
+    /// Position of the hot spot, relative to the top left corner
+    pub x_hot: u32,
+    pub y_hot: u32,
+}
+
+impl CursorShape {
+    /// Bytes of data of the shape
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        u64::from(self.pitch) * u64::from(self.height)
+    }
+}
+
+/// A cursor read from shared memory
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub visible: bool,
+    /// Position of the top left corner of the shape, relative to the monitor
+    pub x: i32,
+    pub y: i32,
+    /// `None` before the first shape
+    pub shape: Option<CursorShape>,
+}
+
+/// Size of the shared memory of a cursor, for shapes of up to `capacity` bytes
+#[must_use]
+pub fn cursor_size(capacity: u32) -> usize {
+    CURSOR_HEADER_SIZE + capacity as usize
+}
+
+/// Check that memory is aligned and has room for the header and `capacity` bytes
+fn check_cursor_memory(memory: &impl Memory, capacity: u32) -> Result<(), (usize, usize)> {
+    let needed = cursor_size(capacity);
+
+    if memory.size() < needed {
+        return Err((needed, memory.size()));
+    }
+
+    Ok(())
+}
+
+// the memory is checked to be aligned before the header is accessed
+#[allow(clippy::cast_ptr_alignment)]
+fn cursor_header(memory: &impl Memory) -> &CursorHeader {
+    // SAFETY: the caller checked that the header fits, and that the memory is aligned
+    unsafe { &*memory.as_ptr().cast::<CursorHeader>() }
+}
+
+/// Writes the cursor of a monitor into shared memory
+#[derive(Debug)]
+pub struct CursorWriter<M: Memory> {
+    memory: M,
+    capacity: u32,
+}
+
+impl<M: Memory> CursorWriter<M> {
+    /// Create a cursor in `memory`, with room for shapes of up to `capacity` bytes
+    ///
+    /// The cursor is hidden and has no shape until it's written.
+    pub fn create(memory: M, capacity: u32) -> Result<Self, CreateError> {
+        if memory.as_ptr().align_offset(8) != 0 {
+            return Err(CreateError::Unaligned);
+        }
+
+        check_cursor_memory(&memory, capacity)
+            .map_err(|(needed, available)| CreateError::TooSmall { needed, available })?;
+
+        let header = cursor_header(&memory);
+
+        // consumers opening it meanwhile see that it isn't ready
+        header.magic.store(0, Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        header.version.store(VERSION, Ordering::Relaxed);
+        header.capacity.store(capacity, Ordering::Relaxed);
+        // continue the sequence, so consumers which still have it open see the change
+        let sequence = header.sequence.load(Ordering::Relaxed).next_multiple_of(2) + 2;
+        header.sequence.store(sequence, Ordering::Relaxed);
+        header.visible.store(0, Ordering::Relaxed);
+        header.shape_id.store(0, Ordering::Relaxed);
+        header.size.store(0, Ordering::Relaxed);
+        header.closed.store(0, Ordering::Relaxed);
+
+        header.magic.store(CURSOR_MAGIC, Ordering::Release);
+
+        Ok(Self { memory, capacity })
+    }
+
+    /// Write the position of the cursor, and its shape if it changed
+    ///
+    /// Without a shape, the last one is kept.
+    pub fn write(
+        &mut self,
+        visible: bool,
+        x: i32,
+        y: i32,
+        shape: Option<(&CursorShape, &[u8])>,
+    ) -> Result<(), WriteError> {
+        if let Some((shape, data)) = shape {
+            let size = shape.size();
+            if size > u64::from(self.capacity) {
+                return Err(WriteError::ShapeTooLarge {
+                    size,
+                    capacity: self.capacity,
+                });
+            }
+
+            if data.len() as u64 != size {
+                return Err(WriteError::ShapeData {
+                    size,
+                    len: data.len(),
+                });
+            }
+        }
+
+        let header = cursor_header(&self.memory);
+
+        let sequence = header.sequence.load(Ordering::Relaxed);
+        header.sequence.store(sequence + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        header.visible.store(u32::from(visible), Ordering::Relaxed);
+        header.x.store(x, Ordering::Relaxed);
+        header.y.store(y, Ordering::Relaxed);
+
+        if let Some((shape, data)) = shape {
+            header.shape_id.store(shape.id, Ordering::Relaxed);
+            header.kind.store(shape.kind as u32, Ordering::Relaxed);
+            header.width.store(shape.width, Ordering::Relaxed);
+            header.height.store(shape.height, Ordering::Relaxed);
+            header.pitch.store(shape.pitch, Ordering::Relaxed);
+            header.x_hot.store(shape.x_hot, Ordering::Relaxed);
+            header.y_hot.store(shape.y_hot, Ordering::Relaxed);
+            // checked above that it fits into the capacity, which is a u32
+            #[allow(clippy::cast_possible_truncation)]
+            header.size.store(data.len() as u32, Ordering::Relaxed);
+
+            // SAFETY: the memory has room for `capacity` bytes after the header, see above
+            let dest = unsafe { self.memory.as_ptr().add(CURSOR_HEADER_SIZE) };
+            // SAFETY: see above
+            unsafe { ptr::copy_nonoverlapping(data.as_ptr(), dest, data.len()) };
+        }
+
+        header.sequence.store(sequence + 2, Ordering::Release);
+
+        Ok(())
+    }
+}
+
+impl<M: Memory> Drop for CursorWriter<M> {
+    fn drop(&mut self) {
+        cursor_header(&self.memory)
+            .closed
+            .store(1, Ordering::Release);
+    }
+}
+
+/// Reads the cursor of a monitor from shared memory
+#[derive(Debug)]
+pub struct CursorReader<M: Memory> {
+    memory: M,
+    capacity: u32,
+}
+
+impl<M: Memory> CursorReader<M> {
+    /// Open the cursor in `memory`
+    pub fn open(memory: M) -> Result<Self, OpenError> {
+        if memory.as_ptr().align_offset(8) != 0 {
+            return Err(OpenError::Unaligned);
+        }
+
+        check_cursor_memory(&memory, 0)
+            .map_err(|(needed, available)| OpenError::TooSmall { needed, available })?;
+
+        let header = cursor_header(&memory);
+
+        if header.magic.load(Ordering::Acquire) != CURSOR_MAGIC {
+            return Err(OpenError::NotReady);
+        }
+
+        let version = header.version.load(Ordering::Relaxed);
+        if version != VERSION {
+            return Err(OpenError::Version(version));
+        }
+
+        let capacity = header.capacity.load(Ordering::Relaxed);
+        check_cursor_memory(&memory, capacity)
+            .map_err(|(needed, available)| OpenError::TooSmall { needed, available })?;
+
+        Ok(Self { memory, capacity })
+    }
+
+    /// Increases with every update, to check whether the cursor changed without reading it
+    #[must_use]
+    pub fn sequence(&self) -> u64 {
+        cursor_header(&self.memory).sequence.load(Ordering::Acquire)
+    }
+
+    /// Whether the producer stopped, the cursor has to be opened again to get updates
+    #[must_use]
+    pub fn closed(&self) -> bool {
+        cursor_header(&self.memory).closed.load(Ordering::Acquire) != 0
+    }
+
+    /// Read the cursor and copy the data of its shape into `buf`, replacing its content
+    pub fn read(&self, buf: &mut Vec<u8>) -> Result<Cursor, ReadError> {
+        for _ in 0..READ_ATTEMPTS {
+            if let Some(cursor) = self.try_read(buf)? {
+                return Ok(cursor);
+            }
+
+            thread::yield_now();
+        }
+
+        Err(ReadError::Busy)
+    }
+
+    /// Read the cursor, `None` if it was written meanwhile
+    fn try_read(&self, buf: &mut Vec<u8>) -> Result<Option<Cursor>, ReadError> {
+        let header = cursor_header(&self.memory);
+
+        let sequence = header.sequence.load(Ordering::Acquire);
+        if sequence % 2 == 1 {
+            return Ok(None);
+        }
+
+        let visible = header.visible.load(Ordering::Relaxed) != 0;
+        let x = header.x.load(Ordering::Relaxed);
+        let y = header.y.load(Ordering::Relaxed);
+        let shape_id = header.shape_id.load(Ordering::Relaxed);
+        let kind = header.kind.load(Ordering::Relaxed);
+        let width = header.width.load(Ordering::Relaxed);
+        let height = header.height.load(Ordering::Relaxed);
+        let pitch = header.pitch.load(Ordering::Relaxed);
+        let x_hot = header.x_hot.load(Ordering::Relaxed);
+        let y_hot = header.y_hot.load(Ordering::Relaxed);
+
+        // it might be written meanwhile, so don't trust the size
+        let size = header.size.load(Ordering::Relaxed).min(self.capacity) as usize;
+
+        buf.clear();
+        buf.reserve(size);
+
+        // SAFETY: the memory has room for `capacity` bytes after the header, and `buf` for `size`
+        let src = unsafe { self.memory.as_ptr().add(CURSOR_HEADER_SIZE) };
+        // SAFETY: see above
+        unsafe { ptr::copy_nonoverlapping(src, buf.as_mut_ptr(), size) };
+        // SAFETY: the bytes were copied above
+        unsafe { buf.set_len(size) };
+
+        fence(Ordering::Acquire);
+        if header.sequence.load(Ordering::Relaxed) != sequence {
+            return Ok(None);
+        }
+
+        let shape = if shape_id == 0 {
+            None
+        } else {
+            Some(CursorShape {
+                id: shape_id,
+                kind: CursorKind::from_raw(kind).ok_or(ReadError::CursorKind(kind))?,
+                width,
+                height,
+                pitch,
+                x_hot,
+                y_hot,
+            })
+        };
+
+        Ok(Some(Cursor {
+            visible,
+            x,
+            y,
+            shape,
+        }))
+    }
+}
+
+/// Draw `cursor` with the data `shape_data` of its shape into a frame read from a ring, if it's
+/// visible
+///
+/// # Panics
+///
+/// If `data` is smaller than the size of `info`, or `shape_data` than the size of the shape.
+pub fn draw_cursor(info: &FrameInfo, data: &mut [u8], cursor: &Cursor, shape_data: &[u8]) {
+    let Some(shape) = cursor.shape.filter(|_| cursor.visible) else {
+        return;
+    };
+
+    for row in 0..shape.height {
+        let Ok(y) = u32::try_from(i64::from(cursor.y) + i64::from(row)) else {
+            continue;
+        };
+        if y >= info.height {
+            break;
+        }
+
+        for column in 0..shape.width {
+            let Ok(x) = u32::try_from(i64::from(cursor.x) + i64::from(column)) else {
+                continue;
+            };
+            if x >= info.width {
+                break;
+            }
+
+            let src = (row * shape.pitch + column * 4) as usize;
+            let src = &shape_data[src..src + 4];
+
+            let dst = match info.format {
+                Format::Bgra8 => (y * info.stride + x * 4) as usize,
+            };
+            let dst = &mut data[dst..dst + 3];
+
+            match shape.kind {
+                CursorKind::Alpha => {
+                    let alpha = u32::from(src[3]);
+                    for (dst, &src) in dst.iter_mut().zip(src) {
+                        let blended =
+                            (u32::from(src) * alpha + u32::from(*dst) * (255 - alpha) + 127) / 255;
+                        // at most 255
+                        #[allow(clippy::cast_possible_truncation)]
+                        {
+                            *dst = blended as u8;
+                        }
+                    }
+                }
+                CursorKind::MaskedColor if src[3] == 0 => {
+                    for (dst, &src) in dst.iter_mut().zip(src) {
+                        *dst ^= src;
+                    }
+                }
+                CursorKind::MaskedColor => dst.copy_from_slice(&src[..3]),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::HeapMemory;
+
+    fn shape(id: u32) -> CursorShape {
+        CursorShape {
+            id,
+            kind: CursorKind::Alpha,
+            width: 2,
+            height: 2,
+            pitch: 8,
+            x_hot: 1,
+            y_hot: 0,
+        }
+    }
+
+    #[test]
+    fn write_read() {
+        let memory = Arc::new(HeapMemory::new(cursor_size(64)));
+        let mut writer = CursorWriter::create(memory.clone(), 64).unwrap();
+        let reader = CursorReader::open(memory).unwrap();
+
+        let mut buf = Vec::new();
+        let cursor = reader.read(&mut buf).unwrap();
+        assert_eq!((cursor.visible, cursor.shape), (false, None));
+        assert!(buf.is_empty());
+
+        let sequence = reader.sequence();
+        writer
+            .write(true, 10, -2, Some((&shape(1), &[7; 16])))
+            .unwrap();
+        assert!(reader.sequence() > sequence);
+
+        let cursor = reader.read(&mut buf).unwrap();
+        assert_eq!(
+            cursor,
+            Cursor {
+                visible: true,
+                x: 10,
+                y: -2,
+                shape: Some(shape(1)),
+            }
+        );
+        assert_eq!(buf, [7; 16]);
+
+        // moving keeps the shape
+        writer.write(false, 11, 3, None).unwrap();
+        let cursor = reader.read(&mut buf).unwrap();
+        assert_eq!((cursor.visible, cursor.x, cursor.y), (false, 11, 3));
+        assert_eq!(cursor.shape, Some(shape(1)));
+        assert_eq!(buf, [7; 16]);
+
+        drop(writer);
+        assert!(reader.closed());
+    }
+
+    #[test]
+    fn draw() {
+        // 3x2 gray frame with padded rows
+        let mut info = FrameInfo::new(Format::Bgra8, 3, 2);
+        info.stride = 16;
+        let mut data = vec![0x80; 32];
+
+        // white and half transparent black over the left edge
+        let mut cursor = Cursor {
+            visible: true,
+            x: -1,
+            y: 1,
+            shape: Some(shape(1)),
+        };
+        let pixels = [[9, 9, 9, 0xff], [0, 0, 0, 0x80], [0, 0, 0, 0], [1, 2, 3, 0]].concat();
+        draw_cursor(&info, &mut data, &cursor, &pixels);
+
+        assert_eq!(data[..16], [0x80; 16]);
+        assert_eq!(data[16..20], [0x40, 0x40, 0x40, 0x80]);
+        assert_eq!(data[20..32], [0x80; 12]);
+
+        // xor where the alpha is 0, replace where it's 0xff
+        let mut data = vec![0x80; 32];
+        cursor.x = 1;
+        cursor.y = 0;
+        cursor.shape = Some(CursorShape {
+            kind: CursorKind::MaskedColor,
+            ..shape(2)
+        });
+        draw_cursor(&info, &mut data, &cursor, &pixels);
+
+        assert_eq!(data[..4], [0x80; 4]);
+        assert_eq!(data[4..12], [9, 9, 9, 0x80, 0, 0, 0, 0x80]);
+        assert_eq!(data[16..20], [0x80; 4]);
+        assert_eq!(
+            data[20..28],
+            [0x80, 0x80, 0x80, 0x80, 0x81, 0x82, 0x83, 0x80]
+        );
+
+        let mut hidden = data.clone();
+        cursor.visible = false;
+        draw_cursor(&info, &mut hidden, &cursor, &pixels);
+        assert_eq!(hidden, data);
+    }
+
+    #[test]
+    fn limits() {
+        let memory = Arc::new(HeapMemory::new(cursor_size(8)));
+
+        assert_eq!(
+            CursorReader::open(memory.clone()).unwrap_err(),
+            OpenError::NotReady
+        );
+        assert!(matches!(
+            CursorWriter::create(memory.clone(), 64),
+            Err(CreateError::TooSmall { .. })
+        ));
+
+        let mut writer = CursorWriter::create(memory, 8).unwrap();
+        assert_eq!(
+            writer.write(true, 0, 0, Some((&shape(1), &[0; 16]))),
+            Err(WriteError::ShapeTooLarge {
+                size: 16,
+                capacity: 8
+            })
+        );
+
+        let small = CursorShape {
+            height: 1,
+            ..shape(2)
+        };
+        assert_eq!(
+            writer.write(true, 0, 0, Some((&small, &[0; 4]))),
+            Err(WriteError::ShapeData { size: 8, len: 4 })
+        );
+        assert!(writer.write(true, 0, 0, Some((&small, &[0; 8]))).is_ok());
+    }
+}
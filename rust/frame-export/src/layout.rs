@@ -0,0 +1,117 @@
+use std::{
+    mem::size_of,
+    sync::atomic::{AtomicU32, AtomicU64},
+};
+
+/// `magic` of the [`Header`], "VDDFRAME"
+pub const MAGIC: u64 = u64::from_le_bytes(*b"VDDFRAME");
+
+/// `version` of the [`Header`], changes with incompatible layouts
+pub const VERSION: u32 = 1;
+
+pub const HEADER_SIZE: usize = 64;
+pub const SLOT_HEADER_SIZE: usize = 64;
+
+/// Alignment of the frame data of every slot
+pub const SLOT_ALIGN: usize = 64;
+
+/// Start of the shared memory
+///
+/// Every value is written once when the ring is created, except `latest` and `closed`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct Header {
+    /// [`MAGIC`], written last, so a ring without it isn't ready yet
+    pub magic: AtomicU64,
+    /// [`VERSION`]
+    pub version: AtomicU32,
+    /// Number of slots
+    pub slot_count: AtomicU32,
+    /// Bytes of frame data every slot has room for
+    pub slot_size: AtomicU64,
+    /// Number of the last frame written, 0 before the first
+    pub latest: AtomicU64,
+    /// 1 once the producer stopped
+    pub closed: AtomicU32,
+    _reserved: [AtomicU32; 7],
+}
+
+/// Frame a slot holds
+#[repr(C)]
+#[derive(Debug)]
+pub struct SlotHeader {
+    /// `2f - 1` while frame `f` is written, `2f` once it's complete, 0 before the first frame
+    pub sequence: AtomicU64,
+    /// Number of the frame, counting from 1
+    pub frame: AtomicU64,
+    /// [`Format`] of the data
+    pub format: AtomicU32,
+    pub width: AtomicU32,
+    pub height: AtomicU32,
+    /// Bytes from the start of one row to the next
+    pub stride: AtomicU32,
+    /// Bytes of data, `stride * height`
+    pub size: AtomicU64,
+    /// `QueryPerformanceCounter` time the os presents the frame at, 0 if unknown
+    pub present_qpc: AtomicU64,
+    /// Microseconds since the unix epoch when the frame was written
+    pub timestamp: AtomicU64,
+    _reserved: AtomicU64,
+}
+
+const _: () = assert!(size_of::<Header>() == HEADER_SIZE);
+const _: () = assert!(size_of::<SlotHeader>() == SLOT_HEADER_SIZE);
+
+/// Pixel format of a frame
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// 8 bits each of blue, green, red and an undefined alpha, in this order
+    Bgra8 = 1,
+}
+
+impl Format {
+    #[must_use]
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            1 => Some(Self::Bgra8),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn bytes_per_pixel(self) -> u32 {
+        match self {
+            Self::Bgra8 => 4,
+        }
+    }
+}
+
+/// Offset of the frame data of the first slot, for `slot_count` slots
+#[must_use]
+pub fn data_offset(slot_count: u32) -> usize {
+    (HEADER_SIZE + SLOT_HEADER_SIZE * slot_count as usize).next_multiple_of(SLOT_ALIGN)
+}
+
+/// Size of the shared memory of a ring, `None` if it doesn't fit into the address space
+#[must_use]
+pub fn ring_size(slot_count: u32, slot_size: u64) -> Option<usize> {
+    let slot_size = usize::try_from(slot_size).ok()?;
+
+    slot_size
+        .checked_mul(slot_count as usize)?
+        .checked_add(data_offset(slot_count))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn offsets() {
+        assert_eq!(data_offset(0), 64);
+        assert_eq!(data_offset(3), 256);
+        assert_eq!(ring_size(3, 128), Some(256 + 384));
+        assert_eq!(ring_size(u32::MAX, u64::MAX), None);
+    }
+}
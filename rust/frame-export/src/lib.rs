@@ -0,0 +1,73 @@
+//! Frames of a virtual monitor, exported by the driver into shared memory
+//!
+//! Exporting is opt-in per monitor, see `DriverCommand::SetFrameExport` of `driver-ipc`. The driver
+//! then copies every frame the os renders to the monitor into a ring of slots in the shared memory
+//! named [`mapping_name`], which consumers open with [`Mapping::open`] and read with a
+//! [`Consumer`].
+//!
+//! # Layout
+//!
+//! All values are little endian and naturally aligned.
+//!
+//! | Offset                          | Size                     | Content                 |
+//! |---------------------------------|--------------------------|-------------------------|
+//! | 0                               | [`HEADER_SIZE`]          | [`Header`]              |
+//! | [`HEADER_SIZE`]                 | [`SLOT_HEADER_SIZE`] * n | n [`SlotHeader`]s       |
+//! | [`data_offset`]`(n)`            | `slot_size` * n          | frame data of the slots |
+//!
+//! `slot_size` is a multiple of [`SLOT_ALIGN`]. The data of frame `f` is in slot `f % n`, its rows
+//! are `stride` bytes apart.
+//!
+//! # Protocol
+//!
+//! Frames are numbered from 1. The producer writes frame `f` by
+//!
+//! 1. setting the `sequence` of its slot to `2f - 1`,
+//! 2. writing the data and the other fields of the slot,
+//! 3. setting `sequence` to `2f`,
+//! 4. setting `latest` of the header to `f`.
+//!
+//! Consumers poll `latest` and read a frame `f` by checking that `sequence` is `2f`, copying the
+//! data and fields, and checking that `sequence` is still `2f`. Otherwise the producer overwrote
+//! the slot meanwhile, because the consumer fell more than `n - 1` frames behind.
+//!
+//! Once the producer stops, it sets `closed` of the header. Consumers have to open the mapping
+//! again to get new frames, it might be exported with another size.
+//!
+//! # Cursor
+//!
+//! The os doesn't draw the hardware cursor into the frames. While they are exported, the driver
+//! writes the cursor into the shared memory named [`cursor_mapping_name`], which consumers read
+//! with a [`CursorReader`] to draw it themselves. It starts with a [`CursorHeader`] of
+//! [`CURSOR_HEADER_SIZE`] bytes, followed by `capacity` bytes for the data of the shape.
+//!
+//! The producer writes the cursor by making `sequence` odd, writing the fields, and the shape data
+//! if the shape changed, and making `sequence` even again. Consumers copy the fields and the shape
+//! data between two reads of the same even `sequence`.
+
+mod cursor;
+mod layout;
+mod ring;
+pub mod synthetic;
+#[cfg(windows)]
+mod windows;
+
+pub use crate::cursor::*;
+pub use crate::layout::*;
+pub use crate::ring::*;
+#[cfg(windows)]
+pub use crate::windows::Mapping;
+
+/// Name of the shared memory the driver with pipe `pipe_name` exports the frames of monitor `id`
+/// into
+#[must_use]
+pub fn mapping_name(pipe_name: &str, id: u32) -> String {
+    format!(r"Global\{pipe_name}-frames-{id}")
+}
+
+/// Name of the shared memory the driver with pipe `pipe_name` writes the hardware cursor of
+/// monitor `id` into, while its frames are exported
+#[must_use]
+pub fn cursor_mapping_name(pipe_name: &str, id: u32) -> String {
+    format!(r"Global\{pipe_name}-cursor-{id}")
+}
@@ -0,0 +1,644 @@
+use std::{
+    marker::PhantomData,
+    ptr,
+    sync::{
+        atomic::{fence, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::layout::{
+    data_offset, ring_size, Format, Header, SlotHeader, HEADER_SIZE, MAGIC, SLOT_ALIGN,
+    SLOT_HEADER_SIZE, VERSION,
+};
+
+use self::error::{CreateError, OpenError, ReadError, WriteError};
+
+/// Memory a ring lives in
+///
+/// # Safety
+///
+/// [`Memory::as_ptr`] has to point to [`Memory::size`] bytes aligned to 8, which stay valid as
+/// long as `self`. Other threads and processes may only access them through rings.
+pub unsafe trait Memory {
+    fn as_ptr(&self) -> *mut u8;
+    fn size(&self) -> usize;
+}
+
+unsafe impl<M: Memory> Memory for Arc<M> {
+    fn as_ptr(&self) -> *mut u8 {
+        M::as_ptr(self)
+    }
+
+    fn size(&self) -> usize {
+        M::size(self)
+    }
+}
+
+/// Memory of this process, e.g. to test a consumer with a [`synthetic`](crate::synthetic)
+/// producer
+#[derive(Debug)]
+pub struct HeapMemory(Box<[AtomicU64]>);
+
+impl HeapMemory {
+    /// Zeroed memory of at least `size` bytes
+    #[must_use]
+    pub fn new(size: usize) -> Self {
+        Self((0..size.div_ceil(8)).map(|_| AtomicU64::new(0)).collect())
+    }
+}
+
+// the atomics allow writing through a shared reference
+unsafe impl Memory for HeapMemory {
+    fn as_ptr(&self) -> *mut u8 {
+        self.0.as_ptr().cast_mut().cast()
+    }
+
+    fn size(&self) -> usize {
+        self.0.len() * 8
+    }
+}
+
+/// Format, size and timing of a frame
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FrameInfo {
+    pub format: Format,
+    pub width: u32,
+    pub height: u32,
+    /// Bytes from the start of one row to the next
+    pub stride: u32,
+    /// `QueryPerformanceCounter` time the os presents the frame at, 0 if unknown
+    pub present_qpc: u64,
+    /// Microseconds since the unix epoch when the frame was written
+    pub timestamp: u64,
+}
+
+impl FrameInfo {
+    /// A frame with rows without padding, written now
+    #[must_use]
+    pub fn new(format: Format, width: u32, height: u32) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| {
+                u64::try_from(time.as_micros()).unwrap_or(u64::MAX)
+            });
+
+        Self {
+            format,
+            width,
+            height,
+            stride: width * format.bytes_per_pixel(),
+            present_qpc: 0,
+            timestamp,
+        }
+    }
+
+    /// Bytes of data of the frame
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        u64::from(self.stride) * u64::from(self.height)
+    }
+
+    /// Bytes of the pixels of a row, without the padding up to the stride
+    #[must_use]
+    pub fn row_size(&self) -> usize {
+        self.width as usize * self.format.bytes_per_pixel() as usize
+    }
+}
+
+/// A frame read from a ring
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// Number of the frame, counting from 1
+    pub number: u64,
+    pub info: FrameInfo,
+}
+
+/// View of the ring in some memory, with the geometry it was opened with
+#[derive(Debug)]
+struct Ring<M> {
+    memory: M,
+    slot_count: u32,
+    slot_size: usize,
+}
+
+impl<M: Memory> Ring<M> {
+    // the memory was checked to be aligned when the ring was opened
+    #[allow(clippy::cast_ptr_alignment)]
+    fn header(&self) -> &Header {
+        // SAFETY: the memory was checked to hold the ring when it was opened
+        unsafe { &*self.memory.as_ptr().cast::<Header>() }
+    }
+
+    /// Header and data of the slot of `frame`
+    #[allow(clippy::cast_ptr_alignment)]
+    fn slot(&self, frame: u64) -> (&SlotHeader, *mut u8) {
+        #[allow(clippy::cast_possible_truncation)]
+        let index = (frame % u64::from(self.slot_count)) as usize;
+
+        let base = self.memory.as_ptr();
+
+        // SAFETY: both are within the memory, which was checked to hold the ring
+        let header = unsafe { base.add(HEADER_SIZE + index * SLOT_HEADER_SIZE) };
+        let data = unsafe { base.add(data_offset(self.slot_count) + index * self.slot_size) };
+
+        // SAFETY: slot headers are only accessed through atomics
+        (unsafe { &*header.cast::<SlotHeader>() }, data)
+    }
+}
+
+/// Check that a ring of `slot_count` slots of `slot_size` fits into `memory`
+fn check_memory(
+    memory: &impl Memory,
+    slot_count: u32,
+    slot_size: u64,
+) -> Result<usize, (usize, usize)> {
+    let needed = ring_size(slot_count, slot_size).unwrap_or(usize::MAX);
+
+    if memory.size() < needed {
+        return Err((needed, memory.size()));
+    }
+
+    usize::try_from(slot_size).map_err(|_| (needed, memory.size()))
+}
+
+/// Writes frames into a ring
+#[derive(Debug)]
+pub struct Producer<M: Memory> {
+    ring: Ring<M>,
+    // number of the next frame
+    next: u64,
+}
+
+impl<M: Memory> Producer<M> {
+    /// Create a ring of `slot_count` slots in `memory`, with room for `slot_size` bytes of data
+    /// each
+    ///
+    /// `slot_size` is rounded up to a multiple of [`SLOT_ALIGN`]. If `memory` already holds a ring
+    /// of this size, it's continued, so consumers which still have it open get the new frames.
+    pub fn create(memory: M, slot_count: u32, slot_size: u64) -> Result<Self, CreateError> {
+        if slot_count == 0 {
+            return Err(CreateError::NoSlots);
+        }
+
+        if memory.as_ptr().align_offset(8) != 0 {
+            return Err(CreateError::Unaligned);
+        }
+
+        let slot_size = slot_size.next_multiple_of(SLOT_ALIGN as u64);
+        let ring = Ring {
+            slot_size: check_memory(&memory, slot_count, slot_size)
+                .map_err(|(needed, available)| CreateError::TooSmall { needed, available })?,
+            memory,
+            slot_count,
+        };
+
+        let header = ring.header();
+
+        let existing = header.magic.load(Ordering::Acquire) == MAGIC
+            && header.version.load(Ordering::Relaxed) == VERSION
+            && header.slot_count.load(Ordering::Relaxed) == slot_count
+            && header.slot_size.load(Ordering::Relaxed) == slot_size;
+
+        let next = if existing {
+            header.latest.load(Ordering::Relaxed) + 1
+        } else {
+            // consumers opening it meanwhile see that it isn't ready
+            header.magic.store(0, Ordering::Relaxed);
+            fence(Ordering::Release);
+
+            header.version.store(VERSION, Ordering::Relaxed);
+            header.slot_count.store(slot_count, Ordering::Relaxed);
+            header.slot_size.store(slot_size, Ordering::Relaxed);
+            header.latest.store(0, Ordering::Relaxed);
+
+            for index in 0..slot_count {
+                ring.slot(u64::from(index))
+                    .0
+                    .sequence
+                    .store(0, Ordering::Relaxed);
+            }
+
+            1
+        };
+
+        header.closed.store(0, Ordering::Relaxed);
+        header.magic.store(MAGIC, Ordering::Release);
+
+        Ok(Self { ring, next })
+    }
+
+    /// Number of the next frame
+    #[must_use]
+    pub fn next_frame(&self) -> u64 {
+        self.next
+    }
+
+    /// Write the next frame, returning its number
+    ///
+    /// `rows` gets the data of the slot to copy the rows of the frame into.
+    pub fn write(
+        &mut self,
+        info: &FrameInfo,
+        rows: impl FnOnce(&mut Rows),
+    ) -> Result<u64, WriteError> {
+        let size = info.size();
+        if size > self.ring.slot_size as u64 {
+            return Err(WriteError::TooLarge {
+                size,
+                slot_size: self.ring.slot_size as u64,
+            });
+        }
+
+        if (info.stride as usize) < info.row_size() {
+            return Err(WriteError::Stride {
+                stride: info.stride,
+                width: info.width,
+            });
+        }
+
+        let frame = self.next;
+        let (slot, data) = self.ring.slot(frame);
+
+        slot.sequence.store(2 * frame - 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        slot.frame.store(frame, Ordering::Relaxed);
+        slot.format.store(info.format as u32, Ordering::Relaxed);
+        slot.width.store(info.width, Ordering::Relaxed);
+        slot.height.store(info.height, Ordering::Relaxed);
+        slot.stride.store(info.stride, Ordering::Relaxed);
+        slot.size.store(size, Ordering::Relaxed);
+        slot.present_qpc.store(info.present_qpc, Ordering::Relaxed);
+        slot.timestamp.store(info.timestamp, Ordering::Relaxed);
+
+        rows(&mut Rows {
+            data,
+            stride: info.stride as usize,
+            height: info.height,
+            _slot: PhantomData,
+        });
+
+        slot.sequence.store(2 * frame, Ordering::Release);
+        self.ring.header().latest.store(frame, Ordering::Release);
+
+        self.next += 1;
+
+        Ok(frame)
+    }
+}
+
+impl<M: Memory> Drop for Producer<M> {
+    fn drop(&mut self) {
+        self.ring.header().closed.store(1, Ordering::Release);
+    }
+}
+
+/// Data of the slot a frame is written into
+pub struct Rows<'a> {
+    data: *mut u8,
+    stride: usize,
+    height: u32,
+    _slot: PhantomData<&'a mut [u8]>,
+}
+
+impl Rows<'_> {
+    /// Copy `row` to the start of row `y`
+    ///
+    /// # Panics
+    ///
+    /// If `y` isn't below the height of the frame, or `row` is longer than the stride.
+    pub fn write(&mut self, y: u32, row: &[u8]) {
+        assert!(y < self.height, "row {y} of {} rows", self.height);
+        assert!(row.len() <= self.stride, "row of {} bytes", row.len());
+
+        // SAFETY: the slot has room for `height` rows of `stride` bytes
+        let start = unsafe { self.data.add(y as usize * self.stride) };
+        // SAFETY: see above
+        unsafe { ptr::copy_nonoverlapping(row.as_ptr(), start, row.len()) };
+    }
+}
+
+/// Reads frames from a ring
+#[derive(Debug)]
+pub struct Consumer<M: Memory> {
+    ring: Ring<M>,
+}
+
+impl<M: Memory> Consumer<M> {
+    /// Open the ring in `memory`
+    pub fn open(memory: M) -> Result<Self, OpenError> {
+        if memory.as_ptr().align_offset(8) != 0 {
+            return Err(OpenError::Unaligned);
+        }
+
+        if memory.size() < HEADER_SIZE {
+            return Err(OpenError::TooSmall {
+                needed: HEADER_SIZE,
+                available: memory.size(),
+            });
+        }
+
+        // SAFETY: checked above that the header fits, and that the memory is aligned
+        #[allow(clippy::cast_ptr_alignment)]
+        let header = unsafe { &*memory.as_ptr().cast::<Header>() };
+
+        if header.magic.load(Ordering::Acquire) != MAGIC {
+            return Err(OpenError::NotReady);
+        }
+
+        let version = header.version.load(Ordering::Relaxed);
+        if version != VERSION {
+            return Err(OpenError::Version(version));
+        }
+
+        let slot_count = header.slot_count.load(Ordering::Relaxed);
+        let slot_size = header.slot_size.load(Ordering::Relaxed);
+
+        if slot_count == 0 {
+            return Err(OpenError::NotReady);
+        }
+
+        let ring = Ring {
+            slot_size: check_memory(&memory, slot_count, slot_size)
+                .map_err(|(needed, available)| OpenError::TooSmall { needed, available })?,
+            memory,
+            slot_count,
+        };
+
+        Ok(Self { ring })
+    }
+
+    /// Number of the last frame written, 0 before the first
+    #[must_use]
+    pub fn latest(&self) -> u64 {
+        self.ring.header().latest.load(Ordering::Acquire)
+    }
+
+    /// Whether the producer stopped, the ring has to be opened again to get new frames
+    #[must_use]
+    pub fn closed(&self) -> bool {
+        self.ring.header().closed.load(Ordering::Acquire) != 0
+    }
+
+    /// Number of slots, frames older than `latest() - slot_count() + 1` are overwritten
+    #[must_use]
+    pub fn slot_count(&self) -> u32 {
+        self.ring.slot_count
+    }
+
+    /// Copy the data of frame `number` into `buf`, replacing its content
+    pub fn read(&self, number: u64, buf: &mut Vec<u8>) -> Result<Frame, ReadError> {
+        if number == 0 {
+            return Err(ReadError::NotWritten(number));
+        }
+
+        let (slot, data) = self.ring.slot(number);
+
+        let sequence = slot.sequence.load(Ordering::Acquire);
+        if sequence < 2 * number {
+            return Err(ReadError::NotWritten(number));
+        }
+        if sequence > 2 * number {
+            return Err(ReadError::Overwritten(number));
+        }
+
+        let format = slot.format.load(Ordering::Relaxed);
+        let width = slot.width.load(Ordering::Relaxed);
+        let height = slot.height.load(Ordering::Relaxed);
+        let stride = slot.stride.load(Ordering::Relaxed);
+        let size = slot.size.load(Ordering::Relaxed);
+        let present_qpc = slot.present_qpc.load(Ordering::Relaxed);
+        let timestamp = slot.timestamp.load(Ordering::Relaxed);
+
+        // the slot might be overwritten meanwhile, so don't trust the size
+        let size = usize::try_from(size)
+            .ok()
+            .filter(|&size| size <= self.ring.slot_size)
+            .ok_or(ReadError::Overwritten(number))?;
+
+        buf.clear();
+        buf.reserve(size);
+
+        // SAFETY: the slot has room for `slot_size` bytes, and `buf` for `size`
+        unsafe { ptr::copy_nonoverlapping(data, buf.as_mut_ptr(), size) };
+        // SAFETY: the bytes were copied above
+        unsafe { buf.set_len(size) };
+
+        fence(Ordering::Acquire);
+        if slot.sequence.load(Ordering::Relaxed) != sequence {
+            return Err(ReadError::Overwritten(number));
+        }
+
+        let format = Format::from_raw(format).ok_or(ReadError::Format(format))?;
+
+        Ok(Frame {
+            number,
+            info: FrameInfo {
+                format,
+                width,
+                height,
+                stride,
+                present_qpc,
+                timestamp,
+            },
+        })
+    }
+
+    /// Copy the data of the last frame into `buf`, `None` before the first frame
+    pub fn read_latest(&self, buf: &mut Vec<u8>) -> Result<Option<Frame>, ReadError> {
+        loop {
+            let latest = self.latest();
+            if latest == 0 {
+                return Ok(None);
+            }
+
+            match self.read(latest, buf) {
+                Ok(frame) => return Ok(Some(frame)),
+
+                // the producer was faster, try the next latest
+                Err(ReadError::Overwritten(_)) => continue,
+
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+pub mod error {
+    #[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+    pub enum CreateError {
+        #[error("A ring needs at least one slot")]
+        NoSlots,
+        #[error("The memory isn't aligned to 8 bytes")]
+        Unaligned,
+        #[error("The ring needs {needed} bytes, but the memory only has {available}")]
+        TooSmall { needed: usize, available: usize },
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+    pub enum OpenError {
+        #[error("The ring isn't ready yet")]
+        NotReady,
+        #[error("The ring has layout version {0}, but only version {supported} is supported", supported = crate::VERSION)]
+        Version(u32),
+        #[error("The memory isn't aligned to 8 bytes")]
+        Unaligned,
+        #[error("The ring needs {needed} bytes, but the memory only has {available}")]
+        TooSmall { needed: usize, available: usize },
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+    pub enum WriteError {
+        #[error("The frame has {size} bytes, but the slots only have room for {slot_size}")]
+        TooLarge { size: u64, slot_size: u64 },
+        #[error("The stride {stride} is too small for a width of {width}")]
+        Stride { stride: u32, width: u32 },
+        #[error("The cursor shape has {size} bytes, but there is only room for {capacity}")]
+        ShapeTooLarge { size: u64, capacity: u32 },
+        #[error("The cursor shape has {size} bytes, but {len} were given")]
+        ShapeData { size: u64, len: usize },
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+    pub enum ReadError {
+        #[error("Frame {0} wasn't written yet")]
+        NotWritten(u64),
+        #[error("Frame {0} was overwritten")]
+        Overwritten(u64),
+        #[error("Unknown frame format {0}")]
+        Format(u32),
+        #[error("Unknown cursor kind {0}")]
+        CursorKind(u32),
+        #[error("The cursor is being written for too long")]
+        Busy,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn info(width: u32, height: u32) -> FrameInfo {
+        FrameInfo::new(Format::Bgra8, width, height)
+    }
+
+    fn fill(value: u8) -> impl FnOnce(&mut Rows) {
+        move |rows| {
+            for y in 0..rows.height {
+                rows.write(y, &vec![value; rows.stride]);
+            }
+        }
+    }
+
+    fn ring(slot_count: u32, slot_size: u64) -> Arc<HeapMemory> {
+        Arc::new(HeapMemory::new(
+            ring_size(slot_count, slot_size.next_multiple_of(SLOT_ALIGN as u64)).unwrap(),
+        ))
+    }
+
+    #[test]
+    fn write_read() {
+        let memory = ring(3, 4 * 4 * 2);
+        let mut producer = Producer::create(memory.clone(), 3, 4 * 4 * 2).unwrap();
+        let consumer = Consumer::open(memory).unwrap();
+
+        let mut buf = Vec::new();
+        assert_eq!(consumer.read_latest(&mut buf), Ok(None));
+        assert_eq!(consumer.read(1, &mut buf), Err(ReadError::NotWritten(1)));
+
+        let info = info(4, 2);
+        assert_eq!(producer.write(&info, fill(7)), Ok(1));
+        assert_eq!(consumer.latest(), 1);
+
+        let frame = consumer.read(1, &mut buf).unwrap();
+        assert_eq!(frame, Frame { number: 1, info });
+        assert_eq!(buf, vec![7; 32]);
+
+        assert_eq!(producer.write(&info, fill(8)), Ok(2));
+        let frame = consumer.read_latest(&mut buf).unwrap().unwrap();
+        assert_eq!(frame.number, 2);
+        assert_eq!(buf, vec![8; 32]);
+    }
+
+    #[test]
+    fn overwritten() {
+        let memory = ring(2, 64);
+        let mut producer = Producer::create(memory.clone(), 2, 64).unwrap();
+        let consumer = Consumer::open(memory).unwrap();
+
+        for value in 1..=3 {
+            producer.write(&info(2, 2), fill(value)).unwrap();
+        }
+
+        let mut buf = Vec::new();
+        assert_eq!(consumer.read(1, &mut buf), Err(ReadError::Overwritten(1)));
+        assert!(consumer.read(2, &mut buf).is_ok());
+        assert_eq!(consumer.read(3, &mut buf).unwrap().number, 3);
+        assert_eq!(consumer.read(4, &mut buf), Err(ReadError::NotWritten(4)));
+    }
+
+    #[test]
+    fn limits() {
+        let memory = ring(1, 64);
+
+        assert_eq!(
+            Producer::create(memory.clone(), 0, 64).unwrap_err(),
+            CreateError::NoSlots
+        );
+        assert!(matches!(
+            Producer::create(memory.clone(), 2, 64),
+            Err(CreateError::TooSmall { .. })
+        ));
+        assert_eq!(
+            Consumer::open(memory.clone()).unwrap_err(),
+            OpenError::NotReady
+        );
+
+        let mut producer = Producer::create(memory, 1, 64).unwrap();
+        assert_eq!(
+            producer.write(&info(8, 8), fill(0)),
+            Err(WriteError::TooLarge {
+                size: 256,
+                slot_size: 64
+            })
+        );
+
+        let mut narrow = info(4, 1);
+        narrow.stride = 8;
+        assert_eq!(
+            producer.write(&narrow, fill(0)),
+            Err(WriteError::Stride {
+                stride: 8,
+                width: 4
+            })
+        );
+    }
+
+    #[test]
+    fn reopen() {
+        let memory = ring(2, 64);
+
+        let mut producer = Producer::create(memory.clone(), 2, 64).unwrap();
+        producer.write(&info(2, 2), fill(1)).unwrap();
+        drop(producer);
+
+        let consumer = Consumer::open(memory.clone()).unwrap();
+        assert!(consumer.closed());
+
+        // the same size continues the frames
+        let mut producer = Producer::create(memory.clone(), 2, 64).unwrap();
+        assert!(!consumer.closed());
+        assert_eq!(producer.write(&info(2, 2), fill(2)), Ok(2));
+        drop(producer);
+
+        // another one starts over
+        let mut producer = Producer::create(memory.clone(), 1, 128).unwrap();
+        assert_eq!(producer.write(&info(2, 2), fill(3)), Ok(1));
+
+        let consumer = Consumer::open(memory).unwrap();
+        assert_eq!(consumer.slot_count(), 1);
+        assert_eq!(consumer.latest(), 1);
+    }
+}
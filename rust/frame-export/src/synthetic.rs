@@ -0,0 +1,111 @@
+//! Producer of generated frames, to test consumers without the driver
+
+use crate::{
+    error::{CreateError, WriteError},
+    Format, FrameInfo, Memory, Producer,
+};
+
+// colors of the bars, as bgra
+const BARS: [[u8; 4]; 8] = [
+    [0xff, 0xff, 0xff, 0xff],
+    [0x00, 0xff, 0xff, 0xff],
+    [0xff, 0xff, 0x00, 0xff],
+    [0x00, 0xff, 0x00, 0xff],
+    [0xff, 0x00, 0xff, 0xff],
+    [0x00, 0x00, 0xff, 0xff],
+    [0xff, 0x00, 0x00, 0xff],
+    [0x00, 0x00, 0x00, 0xff],
+];
+
+/// Row `y` of frame `number` of the test pattern, in [`Format::Bgra8`]
+///
+/// The pattern is eight vertical color bars, which move one pixel to the right every frame. The
+/// bottom eighth counts the frames in binary, so a consumer can check which frame it got.
+#[must_use]
+pub fn pattern_row(width: u32, height: u32, y: u32, number: u64) -> Vec<u8> {
+    let bar_width = u64::from(width.div_ceil(8).max(1));
+
+    (0..width)
+        .flat_map(|x| {
+            if y >= height - height / 8 {
+                // one bit per eighth of the width, most significant first
+                let bit = 7 - u64::from(x) * 8 / u64::from(width);
+                if number >> bit & 1 == 1 {
+                    BARS[0]
+                } else {
+                    BARS[7]
+                }
+            } else {
+                let bar = (u64::from(x) + number) / bar_width % 8;
+                #[allow(clippy::cast_possible_truncation)]
+                BARS[bar as usize]
+            }
+        })
+        .collect()
+}
+
+/// Writes frames of the test pattern into a ring
+#[derive(Debug)]
+pub struct SyntheticProducer<M: Memory> {
+    producer: Producer<M>,
+    width: u32,
+    height: u32,
+}
+
+impl<M: Memory> SyntheticProducer<M> {
+    /// Create a ring in `memory` for frames of `width` * `height`, see [`Producer::create`]
+    pub fn new(memory: M, slot_count: u32, width: u32, height: u32) -> Result<Self, CreateError> {
+        let slot_size = FrameInfo::new(Format::Bgra8, width, height).size();
+
+        Ok(Self {
+            producer: Producer::create(memory, slot_count, slot_size)?,
+            width,
+            height,
+        })
+    }
+
+    /// Write the next frame, returning its number
+    pub fn produce(&mut self) -> Result<u64, WriteError> {
+        let number = self.producer.next_frame();
+        let info = FrameInfo::new(Format::Bgra8, self.width, self.height);
+
+        self.producer.write(&info, |rows| {
+            for y in 0..self.height {
+                rows.write(y, &pattern_row(self.width, self.height, y, number));
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{ring_size, Consumer, HeapMemory};
+
+    #[test]
+    fn produce() {
+        let memory = Arc::new(HeapMemory::new(ring_size(2, 16 * 8 * 4).unwrap()));
+        let mut producer = SyntheticProducer::new(memory.clone(), 2, 16, 8).unwrap();
+        let consumer = Consumer::open(memory).unwrap();
+
+        assert_eq!(producer.produce(), Ok(1));
+        assert_eq!(producer.produce(), Ok(2));
+
+        let mut buf = Vec::new();
+        let frame = consumer.read_latest(&mut buf).unwrap().unwrap();
+        assert_eq!(frame.number, 2);
+        assert_eq!((frame.info.width, frame.info.height), (16, 8));
+
+        let rows = (0..8)
+            .flat_map(|y| pattern_row(16, 8, y, 2))
+            .collect::<Vec<_>>();
+        assert_eq!(buf, rows);
+
+        // frame 2 in binary, bit 1 is the 7th eighth
+        let last = &buf[7 * 16 * 4..];
+        assert_eq!(last[12 * 4..13 * 4], BARS[0]);
+        assert_eq!(last[14 * 4..15 * 4], BARS[7]);
+    }
+}
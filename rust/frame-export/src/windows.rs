@@ -0,0 +1,153 @@
+use std::{mem::size_of, ptr};
+
+use windows::{
+    core::{Error, Result, HSTRING, PCWSTR},
+    Win32::{
+        Foundation::{
+            CloseHandle, GetLastError, LocalFree, ERROR_ALREADY_EXISTS, HANDLE, HLOCAL,
+            INVALID_HANDLE_VALUE,
+        },
+        Security::{
+            Authorization::{
+                ConvertStringSecurityDescriptorToSecurityDescriptorW, SDDL_REVISION_1,
+            },
+            PSECURITY_DESCRIPTOR, SECURITY_ATTRIBUTES,
+        },
+        System::Memory::{
+            CreateFileMappingW, MapViewOfFile, OpenFileMappingW, UnmapViewOfFile, VirtualQuery,
+            FILE_MAP, FILE_MAP_ALL_ACCESS, FILE_MAP_READ, MEMORY_BASIC_INFORMATION,
+            MEMORY_MAPPED_VIEW_ADDRESS, PAGE_READWRITE,
+        },
+    },
+};
+
+use crate::Memory;
+
+// the creator (the driver) may do anything, the system, administrators and interactive users may
+// only read
+const SDDL: &str = "D:P(A;;GA;;;OW)(A;;GR;;;SY)(A;;GR;;;BA)(A;;GR;;;IU)";
+
+/// Named shared memory, backed by the page file
+#[derive(Debug)]
+pub struct Mapping {
+    handle: HANDLE,
+    view: MEMORY_MAPPED_VIEW_ADDRESS,
+    size: usize,
+}
+
+// the view is only accessed through rings, which use atomics
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    /// Create the shared memory `name` of at least `size` bytes
+    ///
+    /// Only the creator may write it, administrators and interactive users may open it to read it.
+    /// Fails if it exists already, e.g. while consumers still have a previous one open, or if
+    /// another process created it to pose as the driver.
+    pub fn create(name: &str, size: usize) -> Result<Self> {
+        let sddl = HSTRING::from(SDDL);
+        let mut sd = PSECURITY_DESCRIPTOR::default();
+
+        unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                PCWSTR(sddl.as_ptr()),
+                SDDL_REVISION_1,
+                &mut sd,
+                None,
+            )?;
+        }
+
+        let sa = SECURITY_ATTRIBUTES {
+            #[allow(clippy::cast_possible_truncation)]
+            nLength: size_of::<SECURITY_ATTRIBUTES>() as u32,
+            lpSecurityDescriptor: sd.0,
+            bInheritHandle: false.into(),
+        };
+
+        let name = HSTRING::from(name);
+        let size_64 = size as u64;
+
+        #[allow(clippy::cast_possible_truncation)]
+        let handle = unsafe {
+            CreateFileMappingW(
+                INVALID_HANDLE_VALUE,
+                Some(ptr::from_ref(&sa)),
+                PAGE_READWRITE,
+                (size_64 >> 32) as u32,
+                size_64 as u32,
+                PCWSTR(name.as_ptr()),
+            )
+        };
+        // an existing mapping is opened instead, which is only told by the last error
+        let existed = handle.is_ok() && unsafe { GetLastError() } == ERROR_ALREADY_EXISTS;
+
+        // the mapping keeps a copy of the security descriptor
+        _ = unsafe { LocalFree(HLOCAL(sd.0)) };
+
+        let handle = handle?;
+        if existed {
+            _ = unsafe { CloseHandle(handle) };
+            return Err(Error::from(ERROR_ALREADY_EXISTS.to_hresult()));
+        }
+
+        unsafe { Self::map(handle, FILE_MAP_ALL_ACCESS) }
+    }
+
+    /// Open the existing shared memory `name` to read it
+    pub fn open(name: &str) -> Result<Self> {
+        let name = HSTRING::from(name);
+
+        let handle = unsafe { OpenFileMappingW(FILE_MAP_READ.0, false, PCWSTR(name.as_ptr()))? };
+
+        unsafe { Self::map(handle, FILE_MAP_READ) }
+    }
+
+    /// Map all of `handle`, which is closed on errors
+    ///
+    /// # Safety
+    ///
+    /// `handle` has to be a file mapping owned by the caller.
+    unsafe fn map(handle: HANDLE, access: FILE_MAP) -> Result<Self> {
+        let view = unsafe { MapViewOfFile(handle, access, 0, 0, 0) };
+        if view.Value.is_null() {
+            let error = Error::from_win32();
+            _ = unsafe { CloseHandle(handle) };
+            return Err(error);
+        }
+
+        // the view has the size of the mapping, rounded up to pages
+        let mut info = MEMORY_BASIC_INFORMATION::default();
+        unsafe {
+            VirtualQuery(
+                Some(view.Value),
+                &mut info,
+                size_of::<MEMORY_BASIC_INFORMATION>(),
+            );
+        }
+
+        Ok(Self {
+            handle,
+            view,
+            size: info.RegionSize,
+        })
+    }
+}
+
+// views are page aligned and stay mapped until drop
+unsafe impl Memory for Mapping {
+    fn as_ptr(&self) -> *mut u8 {
+        self.view.Value.cast()
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        _ = unsafe { UnmapViewOfFile(self.view) };
+        _ = unsafe { CloseHandle(self.handle) };
+    }
+}
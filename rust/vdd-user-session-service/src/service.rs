@@ -4,6 +4,8 @@ use driver_ipc::{
     sync::{Client, DriverClient},
     Monitor,
 };
+use driver_logger::{default_log_dir, DriverLogger, FileConfig};
+use log::{error, info, Level};
 use windows::Win32::{
     Foundation::{CloseHandle, HANDLE},
     Security::{ImpersonateLoggedOnUser, SE_TCB_NAME},
@@ -34,8 +36,22 @@ pub fn start_service() -> windows_service::Result<()> {
 
 #[allow(clippy::needless_pass_by_value)]
 fn service_main(arguments: Vec<OsString>) {
-    if let Err(_e) = run_service(&arguments) {
-        // error handling
+    init_log();
+
+    if let Err(e) = run_service(&arguments) {
+        error!("Service failed: {e}");
+    }
+}
+
+fn init_log() {
+    let mut logger = DriverLogger::new(Level::Info);
+
+    // the service has no console, so the files are the only place its log goes
+    if logger
+        .file(FileConfig::new(default_log_dir(), SERVICE_NAME))
+        .is_ok()
+    {
+        _ = logger.init();
     }
 }
 
@@ -76,11 +92,19 @@ fn run_service(_arguments: &[OsString]) -> windows_service::Result<()> {
                     }
 
                     SessionChangeReason::SessionLogoff => {
-                        let Ok(client) = Client::connect() else {
-                            return ServiceControlHandlerResult::Other(0x3);
+                        info!("Session {} logged off", param.notification.session_id);
+
+                        let client = match Client::connect() {
+                            Ok(client) => client,
+                            Err(e) => {
+                                error!("Failed to connect to driver: {e}");
+                                return ServiceControlHandlerResult::Other(0x3);
+                            }
                         };
 
-                        _ = client.remove_all();
+                        if let Err(e) = client.remove_all() {
+                            error!("Failed to remove monitors: {e}");
+                        }
                     }
 
                     _ => (),
@@ -141,6 +165,8 @@ fn get_current_session() -> Result<u32, ()> {
 }
 
 fn notify(session_id: u32) -> Result<(), ServiceControlHandlerResult> {
+    info!("Restoring monitors of session {session_id}");
+
     impersonate_user(session_id, || {
         let hklm = RegKey::predef(HKEY_CURRENT_USER);
         let key = r"SOFTWARE\VirtualDisplayDriver";
@@ -154,15 +180,22 @@ fn notify(session_id: u32) -> Result<(), ServiceControlHandlerResult> {
             .map(|data| serde_json::from_str::<Vec<Monitor>>(&data).unwrap_or_default())
             .unwrap_or_default();
 
-        let Ok(mut client) = DriverClient::new() else {
-            return Err(ServiceControlHandlerResult::NoError);
+        let mut client = match DriverClient::new() {
+            Ok(client) => client,
+            Err(e) => {
+                error!("Failed to connect to driver: {e}");
+                return Err(ServiceControlHandlerResult::NoError);
+            }
         };
 
-        if client.set_monitors(&monitors).is_err() {
+        if let Err(e) = client.set_monitors(&monitors) {
+            error!("Failed to set monitors: {e}");
             return Err(ServiceControlHandlerResult::NoError);
         }
 
-        _ = client.notify();
+        if let Err(e) = client.notify() {
+            error!("Failed to notify driver: {e}");
+        }
 
         Ok(())
     })
@@ -173,12 +206,14 @@ fn impersonate_user(
     cb: impl FnOnce() -> Result<(), ServiceControlHandlerResult>,
 ) -> Result<(), ServiceControlHandlerResult> {
     let mut token = HANDLE::default();
-    if unsafe { WTSQueryUserToken(session_id, &mut token).is_err() } {
+    if let Err(e) = unsafe { WTSQueryUserToken(session_id, &mut token) } {
+        error!("Failed to get user token of session {session_id}: {e}");
         return Err(ServiceControlHandlerResult::NoError);
     }
 
     // impersonate user for current user reg call
-    if unsafe { ImpersonateLoggedOnUser(token).is_err() } {
+    if let Err(e) = unsafe { ImpersonateLoggedOnUser(token) } {
+        error!("Failed to impersonate user of session {session_id}: {e}");
         return Err(ServiceControlHandlerResult::NoError);
     }
 
@@ -1,5 +1,13 @@
 mod mode;
 
+use std::{
+    io::Write as _,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
 use clap::Parser;
 use eyre::{bail, eyre, Context as _};
 use joinery::JoinableIterator;
@@ -7,7 +15,14 @@ use lazy_format::lazy_format;
 use owo_colors::OwoColorize;
 use serde::{Deserialize, Serialize};
 
-use driver_ipc::{sync::DriverClient, Id, Monitor};
+use driver_ipc::{
+    config::pipe_name,
+    edid::Edid,
+    sync::{Client, DriverClient},
+    Cursor, EventCommand, FrameStats, Id, LogFields, LogLevel, Monitor, DEFAULT_PIPE_NAME,
+};
+use driver_logger::{format_timestamp, DriverLogger, FileConfig};
+use frame_export::{cursor_mapping_name, mapping_name};
 
 #[derive(Debug, Parser)]
 struct Args {
@@ -21,8 +36,18 @@ struct Args {
 #[derive(Debug, Parser)]
 struct GlobalOptions {
     /// Format output as JSON.
-    #[clap(short, long)]
+    #[clap(short, long, global = true)]
     json: bool,
+
+    /// Name of the driver instance to use, if there is more than one. See
+    /// the `instances` command.
+    #[clap(short, long, global = true)]
+    instance: Option<String>,
+
+    /// Write a log of the commands run and the errors they hit to rotating
+    /// files in this directory.
+    #[clap(long, global = true)]
+    log_dir: Option<PathBuf>,
 }
 
 #[derive(Debug, Parser)]
@@ -45,12 +70,36 @@ enum Command {
     RemoveAll,
     /// Persist changes to current user
     Persist,
+    /// Show the EDID a virtual monitor was plugged in with.
+    Edid(EdidCommand),
+    /// List the running driver instances.
+    Instances,
+    /// Change the level of the driver log until the driver restarts.
+    LogLevel(LogLevelCommand),
+    /// Show the recent driver log, e.g. to attach it to a bug report.
+    Logs(LogsCommand),
+    /// Show the report the driver wrote when it last crashed, e.g. to attach
+    /// it to a bug report.
+    CrashReport,
+    /// Show how many frames the virtual monitors are rendered with.
+    Stats(StatsCommand),
+    /// Copy the frames of a virtual monitor into shared memory, e.g. for the
+    /// capture example of the frame-export crate.
+    Export(ExportCommand),
+    /// Show the hardware cursor of the virtual monitors as it moves and
+    /// changes.
+    Cursor(CursorCommand),
 }
 
 #[derive(Debug, Parser)]
 struct AddCommand {
-    /// One or more resolutions/refresh rates to add to the virtual monitor.
+    /// Resolutions/refresh rates to add to the virtual monitor. If none are
+    /// given, the monitor gets a default set of common 16:9 resolutions.
     /// Example values: `1920x1080`, `3840x2160@120`, `1280x720@60/120`.
+    /// Append `i` to the resolution for an interlaced mode (`1920x1080i@50`),
+    /// and custom blanking as front porch, sync and back porch horizontally
+    /// and vertically plus optional sync polarity
+    /// (`1920x1080@60:88,44,148,4,5,36,++`).
     mode: Vec<mode::Mode>,
 
     /// Manual ID to set for the monitor. Must not conflict with an
@@ -74,6 +123,10 @@ struct AddModeCommand {
 
     /// One or more resolutions/refresh rates to add to the virtual monitor.
     /// Example values: `1920x1080`, `3840x2160@120`, `1280x720@60/120`.
+    /// Append `i` to the resolution for an interlaced mode (`1920x1080i@50`),
+    /// and custom blanking as front porch, sync and back porch horizontally
+    /// and vertically plus optional sync polarity
+    /// (`1920x1080@60:88,44,148,4,5,36,++`).
     mode: Vec<mode::Mode>,
 }
 
@@ -85,7 +138,8 @@ struct RemoveModeCommand {
     /// A resolution and optional refresh rate to remove from the virtual
     /// monitor. Omitting the refresh rate will remove the resolution, including
     /// the refresh rate will keep the resolution but remove just the given
-    /// refresh rate. Example values: `1920x1080`, `3840x2160@120`.
+    /// refresh rate. Example values: `1920x1080`, `3840x2160@120`,
+    /// `1920x1080i@50`.
     mode: mode::Mode,
 }
 
@@ -107,38 +161,196 @@ struct RemoveCommand {
     id: Vec<String>,
 }
 
+#[derive(Debug, Parser)]
+struct EdidCommand {
+    /// The ID or name of the monitor to show the EDID of.
+    id: String,
+
+    /// Print the EDID bytes as hex instead of decoding them.
+    #[clap(long, conflicts_with = "json")]
+    raw: bool,
+}
+
+#[derive(Debug, Parser)]
+struct LogLevelCommand {
+    /// One of `off`, `error`, `warn`, `info`, `debug` or `trace`.
+    level: LogLevel,
+
+    /// Only change the level of this module and its submodules, e.g.
+    /// `virtual_display_driver::ipc`. Other modules keep their level.
+    #[clap(long)]
+    module: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+struct LogsCommand {
+    /// Only show entries after the one with this sequence number.
+    #[clap(long, conflicts_with = "follow")]
+    since: Option<u64>,
+
+    /// Keep showing new entries as the driver logs them, instead of the
+    /// recent ones. With `--json`, every entry is printed on its own line.
+    #[clap(short, long)]
+    follow: bool,
+
+    /// Only show entries at least as severe as this level.
+    #[clap(long, default_value = "trace")]
+    level: LogLevel,
+}
+
+#[derive(Debug, Parser)]
+struct StatsCommand {
+    /// Keep showing the statistics, refreshing them every `--interval`
+    /// seconds. With `--json`, every refresh is printed on its own line.
+    #[clap(short, long)]
+    watch: bool,
+
+    /// Seconds between refreshes with `--watch`.
+    #[clap(
+        long,
+        default_value = "1",
+        requires = "watch",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    interval: u64,
+}
+
+#[derive(Debug, Parser)]
+struct ExportCommand {
+    /// The ID or name of the monitor to export the frames of.
+    id: String,
+
+    /// Stop exporting the frames instead.
+    #[clap(long)]
+    stop: bool,
+}
+
+#[derive(Debug, Parser)]
+struct CursorCommand {
+    /// The ID or name of the monitor to show the cursor of, all if not
+    /// given. With `--json`, every update is printed on its own line.
+    id: Option<String>,
+}
+
 fn main() -> eyre::Result<()> {
     let Args { options, command } = Args::parse();
-    let mut client = DriverClient::new().context("Failed to connect to Virtual Display Driver; please ensure the driver is installed and working")?;
+
+    if let Some(dir) = &options.log_dir {
+        init_log(dir)?;
+        log::info!("Running {command:?}");
+    }
+
+    let result = run(&options, command);
+
+    if let Err(e) = &result {
+        log::error!("{e:#}");
+    }
+
+    result
+}
+
+fn init_log(dir: &Path) -> eyre::Result<()> {
+    let mut logger = DriverLogger::new(log::Level::Info);
+
+    logger
+        .file(FileConfig::new(dir, "virtual-display-driver-cli"))
+        .map_err(|e| eyre!("Failed to open log file in {}: {e}", dir.display()))?;
+
+    logger
+        .init()
+        .map_err(|e| eyre!("Failed to initialize log: {e}"))
+}
+
+fn run(options: &GlobalOptions, command: Command) -> eyre::Result<()> {
+    // doesn't need a connection, there might not be a default instance
+    if let Command::Instances = command {
+        return instances(options);
+    }
+
+    let pipe_name = pipe_name(options.instance.as_deref());
+    let mut client = DriverClient::new_with(&pipe_name).context("Failed to connect to Virtual Display Driver; please ensure the driver is installed and working")?;
 
     match command {
         Command::List => {
-            list(&mut client, &options)?;
+            list(&mut client, options)?;
         }
         Command::Add(command) => {
-            add(&mut client, &options, command)?;
+            add(&mut client, options, command)?;
         }
         Command::AddMode(command) => {
-            add_mode(&mut client, &options, command)?;
+            add_mode(&mut client, options, command)?;
         }
         Command::RemoveMode(command) => {
-            remove_mode(&mut client, &options, &command)?;
+            remove_mode(&mut client, options, &command)?;
         }
         Command::Enable(command) => {
-            enable(&mut client, &options, &command)?;
+            enable(&mut client, options, &command)?;
         }
         Command::Disable(command) => {
-            disable(&mut client, &options, &command)?;
+            disable(&mut client, options, &command)?;
         }
         Command::Remove(command) => {
-            remove(&mut client, &options, &command)?;
+            remove(&mut client, options, &command)?;
         }
         Command::RemoveAll => {
-            remove_all(&mut client, &options)?;
+            remove_all(&mut client, options)?;
         }
         Command::Persist => {
             persist(&mut client)?;
         }
+        Command::Edid(command) => {
+            edid(&client, options, &command)?;
+        }
+        Command::LogLevel(command) => {
+            log_level(&client, options, &command)?;
+        }
+        Command::Logs(command) => {
+            logs(&client, options, &command)?;
+        }
+        Command::CrashReport => {
+            crash_report(&client, options)?;
+        }
+        Command::Stats(command) => {
+            stats(&client, options, &command)?;
+        }
+        Command::Export(command) => {
+            export(&client, options, &command, &pipe_name)?;
+        }
+        Command::Cursor(command) => {
+            cursor(&client, options, &command)?;
+        }
+        Command::Instances => unreachable!(),
+    }
+
+    Ok(())
+}
+
+fn instances(opts: &GlobalOptions) -> eyre::Result<()> {
+    let pipe_names = Client::discover().context("Failed to find driver instances")?;
+
+    // the instance name is what `--instance` takes, `None` for the default instance
+    let instances = pipe_names
+        .iter()
+        .map(|pipe_name| {
+            pipe_name
+                .strip_prefix(DEFAULT_PIPE_NAME)
+                .and_then(|instance| instance.strip_prefix('-'))
+        })
+        .collect::<Vec<_>>();
+
+    if opts.json {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &instances)?;
+    } else if !instances.is_empty() {
+        println!("{}", "Driver instances".underline());
+        for instance in instances {
+            match instance {
+                Some(instance) => println!("{}", instance.green()),
+                None => println!("{}", "(default)".dimmed()),
+            }
+        }
+    } else {
+        println!("No driver instances found.");
     }
 
     Ok(())
@@ -156,46 +368,7 @@ fn list(client: &mut DriverClient, opts: &GlobalOptions) -> eyre::Result<()> {
         let mut stdout = std::io::stdout().lock();
         serde_json::to_writer_pretty(&mut stdout, &monitors)?;
     } else if !monitors.is_empty() {
-        println!("{}", "Virtual monitors".underline());
-        for (i, monitor) in monitors.iter().enumerate() {
-            if i > 0 {
-                println!();
-            }
-
-            let name_label = lazy_format!(match (&monitor.name) {
-                Some(name) => (" {}{name}{}", "[".dimmed(), "]".dimmed()),
-                None => "",
-            });
-            let disabled_label = lazy_format!(if monitor.enabled => ""
-            else =>
-                (" {}", "(disabled)".red())
-            );
-            println!(
-                "Monitor {}{name_label}{disabled_label}:",
-                monitor.id.green(),
-            );
-
-            if monitor.modes.is_empty() {
-                println!("{} {}", "-".dimmed(), "No modes".red());
-            } else {
-                for mode in &monitor.modes {
-                    let refresh_rate_labels = mode
-                        .refresh_rates
-                        .iter()
-                        .map(|rate| lazy_format!("{}", rate.blue()))
-                        .join_with("/");
-                    println!(
-                        "{} {}{}{}{}{}",
-                        "-".dimmed(),
-                        mode.width.green(),
-                        "x".dimmed(),
-                        mode.height.green(),
-                        "@".dimmed(),
-                        refresh_rate_labels,
-                    );
-                }
-            }
-        }
+        print_monitors(monitors);
     } else {
         println!("No virtual monitors found.");
     }
@@ -203,6 +376,56 @@ fn list(client: &mut DriverClient, opts: &GlobalOptions) -> eyre::Result<()> {
     Ok(())
 }
 
+fn print_monitors(monitors: &[Monitor]) {
+    println!("{}", "Virtual monitors".underline());
+    for (i, monitor) in monitors.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+
+        let name_label = lazy_format!(match (&monitor.name) {
+            Some(name) => (" {}{name}{}", "[".dimmed(), "]".dimmed()),
+            None => "",
+        });
+        let disabled_label = lazy_format!(if monitor.enabled => ""
+        else =>
+            (" {}", "(disabled)".red())
+        );
+        println!(
+            "Monitor {}{name_label}{disabled_label}:",
+            monitor.id.green(),
+        );
+
+        if monitor.modes.is_empty() {
+            println!("{} {}", "-".dimmed(), "No modes".red());
+        } else {
+            for mode in &monitor.modes {
+                let refresh_rate_labels = mode
+                    .refresh_rates
+                    .iter()
+                    .map(|rate| lazy_format!("{}", rate.blue()))
+                    .join_with("/");
+                let scan_label = lazy_format!(if mode.interlaced => ("{}", "i".green())
+                else => ""
+                );
+                let blanking_label = lazy_format!(if mode.blanking.is_some() =>
+                    (" {}", "(custom timing)".dimmed())
+                else => ""
+                );
+                println!(
+                    "{} {}{}{}{scan_label}{}{}{blanking_label}",
+                    "-".dimmed(),
+                    mode.width.green(),
+                    "x".dimmed(),
+                    mode.height.green(),
+                    "@".dimmed(),
+                    refresh_rate_labels,
+                );
+            }
+        }
+    }
+}
+
 fn add(client: &mut DriverClient, opts: &GlobalOptions, command: AddCommand) -> eyre::Result<()> {
     let modes = command
         .mode
@@ -394,6 +617,393 @@ fn remove_all(client: &mut DriverClient, opts: &GlobalOptions) -> eyre::Result<(
     Ok(())
 }
 
+fn edid(client: &DriverClient, opts: &GlobalOptions, command: &EdidCommand) -> eyre::Result<()> {
+    let not_found = || eyre!("Monitor `{}` not found", command.id);
+
+    let id = client.find_id(&command.id).ok_or_else(not_found)?;
+    let data = client
+        .request_edid(id)?
+        .ok_or_else(|| eyre!("Monitor `{}` is not plugged in", command.id))?;
+
+    if command.raw {
+        for line in data.chunks(16) {
+            let bytes = line.iter().map(|byte| lazy_format!("{byte:02x}"));
+            println!("{}", bytes.join_with(" "));
+        }
+
+        return Ok(());
+    }
+
+    let edid = Edid::parse(&data).context("Driver returned an invalid EDID")?;
+
+    if opts.json {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &edid)?;
+    } else {
+        print!("{edid}");
+    }
+
+    Ok(())
+}
+
+fn log_level(
+    client: &DriverClient,
+    opts: &GlobalOptions,
+    command: &LogLevelCommand,
+) -> eyre::Result<()> {
+    client.set_log_level(command.level, command.module.as_deref())?;
+
+    if opts.json {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &command.level)?;
+    } else if let Some(module) = &command.module {
+        println!(
+            "Set log level of {} to {}.",
+            module.green(),
+            command.level.green()
+        );
+    } else {
+        println!("Set log level to {}.", command.level.green());
+    }
+
+    Ok(())
+}
+
+fn logs(client: &DriverClient, opts: &GlobalOptions, command: &LogsCommand) -> eyre::Result<()> {
+    if command.follow {
+        return follow_logs(client, opts, command.level);
+    }
+
+    let entries = client.request_recent_logs(command.since, command.level)?;
+
+    if opts.json {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &entries)?;
+    } else if !entries.is_empty() {
+        for entry in &entries {
+            print_log_line(
+                entry.timestamp,
+                entry.level,
+                &entry.target,
+                &entry.message,
+                &entry.fields,
+            );
+        }
+    } else {
+        println!("No log entries.");
+    }
+
+    Ok(())
+}
+
+fn follow_logs(client: &DriverClient, opts: &GlobalOptions, level: LogLevel) -> eyre::Result<()> {
+    let (tx, rx) = mpsc::channel();
+
+    // the subscription ends when it's dropped
+    let _subscription = client.subscribe_logs(level, move |event| {
+        _ = tx.send(event);
+    })?;
+
+    for event in rx {
+        let EventCommand::Log {
+            level,
+            target,
+            message,
+            timestamp,
+            fields,
+        } = event?
+        else {
+            continue;
+        };
+
+        if opts.json {
+            let entry = serde_json::json!({
+                "timestamp": timestamp,
+                "level": level,
+                "target": target,
+                "message": message,
+                "fields": fields,
+            });
+
+            let mut stdout = std::io::stdout().lock();
+            serde_json::to_writer(&mut stdout, &entry)?;
+            writeln!(stdout)?;
+        } else {
+            print_log_line(timestamp, level, &target, &message, &fields);
+        }
+    }
+
+    Ok(())
+}
+
+fn crash_report(client: &DriverClient, opts: &GlobalOptions) -> eyre::Result<()> {
+    let report = client.request_crash_report()?;
+
+    if opts.json {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &report)?;
+        return Ok(());
+    }
+
+    let Some(report) = report else {
+        println!("The driver didn't crash yet.");
+        return Ok(());
+    };
+
+    println!(
+        "Driver v{} @ {} crashed at {}",
+        report.version,
+        report.git_sha,
+        format_timestamp(report.timestamp)
+    );
+    println!(
+        "Thread {} panicked at {}:",
+        report.thread.as_deref().unwrap_or("<unnamed>").green(),
+        report.location.as_deref().unwrap_or("<unknown>").green()
+    );
+    println!("{}", report.message.red());
+
+    println!();
+    println!("{}", "Stack backtrace".underline());
+    for (i, frame) in report.backtrace.iter().enumerate() {
+        println!("{i:>4}: {frame}");
+    }
+
+    println!();
+    match &report.monitors {
+        Some(monitors) if !monitors.is_empty() => print_monitors(monitors),
+        Some(_) => println!("No virtual monitors."),
+        None => println!("The monitors couldn't be retrieved."),
+    }
+
+    if !report.logs.is_empty() {
+        println!();
+        println!("{}", "Recent log".underline());
+        for entry in &report.logs {
+            print_log_line(
+                entry.timestamp,
+                entry.level,
+                &entry.target,
+                &entry.message,
+                &entry.fields,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn stats(client: &DriverClient, opts: &GlobalOptions, command: &StatsCommand) -> eyre::Result<()> {
+    if !command.watch {
+        let stats = client.request_frame_stats()?;
+
+        if opts.json {
+            let mut stdout = std::io::stdout().lock();
+            serde_json::to_writer_pretty(&mut stdout, &stats)?;
+        } else {
+            print_frame_stats(client, &stats);
+        }
+
+        return Ok(());
+    }
+
+    let interval = Duration::from_secs(command.interval);
+
+    loop {
+        let stats = client.request_frame_stats()?;
+
+        if opts.json {
+            let mut stdout = std::io::stdout().lock();
+            serde_json::to_writer(&mut stdout, &stats)?;
+            writeln!(stdout)?;
+        } else {
+            // clear the screen and move the cursor to the top left
+            print!("\x1b[2J\x1b[H");
+            print_frame_stats(client, &stats);
+        }
+
+        thread::sleep(interval);
+    }
+}
+
+fn export(
+    client: &DriverClient,
+    opts: &GlobalOptions,
+    command: &ExportCommand,
+    pipe_name: &str,
+) -> eyre::Result<()> {
+    let id = client
+        .find_id(&command.id)
+        .ok_or_else(|| eyre!("Monitor `{}` not found", command.id))?;
+
+    client.set_frame_export(id, !command.stop)?;
+
+    let outcome = ExportOutcome {
+        id,
+        enabled: !command.stop,
+        mapping: mapping_name(pipe_name, id),
+        cursor_mapping: cursor_mapping_name(pipe_name, id),
+    };
+
+    if opts.json {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &outcome)?;
+    } else if outcome.enabled {
+        println!(
+            "Exporting the frames of virtual monitor with ID {} into {}, and its cursor into {}.",
+            id.green(),
+            outcome.mapping.green(),
+            outcome.cursor_mapping.green()
+        );
+    } else {
+        println!(
+            "Stopped exporting the frames of virtual monitor with ID {}.",
+            id.green()
+        );
+    }
+
+    Ok(())
+}
+
+fn cursor(
+    client: &DriverClient,
+    opts: &GlobalOptions,
+    command: &CursorCommand,
+) -> eyre::Result<()> {
+    let id = command
+        .id
+        .as_deref()
+        .map(|id| {
+            client
+                .find_id(id)
+                .ok_or_else(|| eyre!("Monitor `{id}` not found"))
+        })
+        .transpose()?;
+
+    let (tx, rx) = mpsc::channel();
+
+    // the subscription ends when it's dropped
+    let _subscription = client.subscribe_cursor(id, move |event| {
+        _ = tx.send(event);
+    })?;
+
+    for event in rx {
+        let EventCommand::Cursor(cursor) = event? else {
+            continue;
+        };
+
+        if opts.json {
+            let mut stdout = std::io::stdout().lock();
+            serde_json::to_writer(&mut stdout, &cursor)?;
+            writeln!(stdout)?;
+        } else {
+            print_cursor(&cursor);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_cursor(cursor: &Cursor) {
+    let position = lazy_format!(if cursor.visible => ("at {}, {}", cursor.x.blue(), cursor.y.blue())
+        else =>
+            ("{}", "hidden".red())
+    );
+
+    let shape = lazy_format!(match (&cursor.shape) {
+        Some(shape) => (
+            ", new shape {} ({:?} {}x{}, hot spot {}, {})",
+            shape.id.green(),
+            shape.kind,
+            shape.width,
+            shape.height,
+            shape.x_hot,
+            shape.y_hot,
+        ),
+        None => "",
+    });
+
+    println!("Monitor {}: {position}{shape}", cursor.id.green());
+}
+
+fn print_frame_stats(client: &DriverClient, stats: &[FrameStats]) {
+    if stats.is_empty() {
+        println!("No virtual monitors are enabled.");
+        return;
+    }
+
+    println!("{}", "Frame statistics".underline());
+    for stats in stats {
+        let name = client
+            .find_monitor(stats.id)
+            .and_then(|monitor| monitor.name.as_deref());
+        let name_label = lazy_format!(match (name) {
+            Some(name) => (" {}{name}{}", "[".dimmed(), "]".dimmed()),
+            None => "",
+        });
+        let last_frame_label = lazy_format!(match (stats.last_frame) {
+            Some(last_frame) => ("last frame at {}", format_timestamp(last_frame)),
+            None => ("{}", "no frames yet".red()),
+        });
+        println!(
+            "Monitor {}{name_label}: {} fps, {} frames, {last_frame_label}",
+            stats.id.green(),
+            lazy_format!("{:.1}", stats.fps).blue(),
+            stats.frames.blue(),
+        );
+        println!(
+            "{} {} timeouts, {} pending waits, {} restarts",
+            "-".dimmed(),
+            stats.timeouts,
+            stats.pending_waits,
+            stats.restarts,
+        );
+    }
+}
+
+fn print_log_line(
+    timestamp: u64,
+    level: LogLevel,
+    target: &str,
+    message: &str,
+    fields: &LogFields,
+) {
+    // padded before coloring, the escape codes would count as width
+    let label = format!("{:<7}", format!("[{level}]"));
+    let label = match level {
+        LogLevel::Error => label.red().to_string(),
+        LogLevel::Warn => label.yellow().to_string(),
+        LogLevel::Info => label.green().to_string(),
+        _ => label.dimmed().to_string(),
+    };
+
+    let mut lines = message.lines();
+
+    let fields = fields
+        .iter()
+        .map(|(key, value)| {
+            // strings without quotes
+            if let serde_json::Value::String(value) = value {
+                format!(" {key}={value}")
+            } else {
+                format!(" {key}={value}")
+            }
+        })
+        .collect::<String>();
+
+    println!(
+        "{} {label} {} {}{}",
+        format_timestamp(timestamp).dimmed(),
+        lazy_format!("[{target}]").dimmed(),
+        lines.next().unwrap_or_default(),
+        fields.dimmed()
+    );
+
+    for line in lines {
+        println!("    {line}");
+    }
+}
+
 fn set_enabled(
     client: &mut DriverClient,
     query: &str,
@@ -420,3 +1030,13 @@ struct EnableDisableOutcome {
     monitor: driver_ipc::Monitor,
     toggled: bool,
 }
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+struct ExportOutcome {
+    id: Id,
+    enabled: bool,
+    // name of the shared memory the frames are copied into
+    mapping: String,
+    // name of the shared memory the hardware cursor is written into
+    cursor_mapping: String,
+}
@@ -6,8 +6,9 @@ use joinery::JoinableIterator as _;
 const DEFAULT_REFRESH_RATE: driver_ipc::RefreshRate = 60;
 
 /// Represent a mode as specified by the user as a CLI argument. Can be parsed
-/// from a string such as `1920x1080` or `3840x2160@60/120`, or converted
-/// from/to the type [`driver_ipc::Mode`].
+/// from a string such as `1920x1080`, `3840x2160@60/120` or `1920x1080i@50`,
+/// optionally followed by custom [`Blanking`] like `1920x1080@60:88,44,148,4,5,36`,
+/// or converted from/to the type [`driver_ipc::Mode`].
 ///
 /// This type is very similar to [`driver_ipc::Mode`], but with a few key
 /// differences:
@@ -17,7 +18,8 @@ const DEFAULT_REFRESH_RATE: driver_ipc::RefreshRate = 60;
 ///   if one isn't specified. See [`remove`] for a use-case where the empty list
 ///   of refresh rates is used.
 /// - It implements [`std::str::FromStr`], with a convenient user-facing error
-///   message using `eyre`.
+///   message using `eyre`. Modes which no timing can be built for are
+///   rejected.
 /// - It implements [`std::fmt::Display`] with a nice output format.
 /// - The list of resolutions is represented with a set, meaning that duplicate
 ///   resolutions will be ignored.
@@ -26,8 +28,20 @@ pub struct Mode {
     pub width: driver_ipc::Dimen,
     pub height: driver_ipc::Dimen,
     pub refresh_rates: BTreeSet<driver_ipc::RefreshRate>,
+    pub interlaced: bool,
+    pub blanking: Option<Blanking>,
 }
 
+/// Custom blanking of a mode as specified by the user, such as
+/// `88,44,148,4,5,36,++`: the horizontal front porch, sync width and back
+/// porch, the same 3 values vertically, and optionally the horizontal and
+/// vertical sync polarity (`+-` if omitted).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Blanking(pub driver_ipc::Blanking);
+
+/// Modes with the same key are merged together.
+type ModeKey = (driver_ipc::Dimen, driver_ipc::Dimen, bool);
+
 impl Mode {
     /// Add the default refresh rate if the list of refresh rates is empty.
     fn ensure_refresh_rate(&mut self) {
@@ -35,6 +49,10 @@ impl Mode {
             self.refresh_rates.insert(DEFAULT_REFRESH_RATE);
         }
     }
+
+    fn key(&self) -> ModeKey {
+        (self.width, self.height, self.interlaced)
+    }
 }
 
 impl From<driver_ipc::Mode> for Mode {
@@ -43,6 +61,8 @@ impl From<driver_ipc::Mode> for Mode {
             width: value.width,
             height: value.height,
             refresh_rates: value.refresh_rates.into_iter().collect(),
+            interlaced: value.interlaced,
+            blanking: value.blanking.map(Blanking),
         }
     }
 }
@@ -55,40 +75,70 @@ impl From<Mode> for driver_ipc::Mode {
             width: value.width,
             height: value.height,
             refresh_rates: value.refresh_rates.into_iter().collect(),
+            interlaced: value.interlaced,
+            blanking: value.blanking.map(|blanking| blanking.0),
         }
     }
 }
 
 impl std::fmt::Display for Mode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.refresh_rates.is_empty() {
-            write!(f, "{}x{}", self.width, self.height)?;
-        } else {
-            write!(
-                f,
-                "{}x{}@{}",
-                self.width,
-                self.height,
-                self.refresh_rates.iter().join_with("/"),
-            )?;
+        let scan = if self.interlaced { "i" } else { "" };
+        write!(f, "{}x{}{scan}", self.width, self.height)?;
+
+        if !self.refresh_rates.is_empty() {
+            write!(f, "@{}", self.refresh_rates.iter().join_with("/"))?;
+        }
+
+        if let Some(blanking) = &self.blanking {
+            write!(f, ":{blanking}")?;
         }
 
         Ok(())
     }
 }
 
+impl std::fmt::Display for Blanking {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let b = &self.0;
+        let polarity = |positive| if positive { '+' } else { '-' };
+
+        write!(
+            f,
+            "{},{},{},{},{},{},{}{}",
+            b.h_front_porch,
+            b.h_sync,
+            b.h_back_porch,
+            b.v_front_porch,
+            b.v_sync,
+            b.v_back_porch,
+            polarity(b.h_sync_positive),
+            polarity(b.v_sync_positive),
+        )
+    }
+}
+
 impl std::str::FromStr for Mode {
     type Err = eyre::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (resolution, refresh_rate_list) = match s.split_once('@') {
-            Some((resolution, refresh_rate_list)) => (resolution, Some(refresh_rate_list)),
+        let (mode, blanking) = match s.split_once(':') {
+            Some((mode, blanking)) => (mode, Some(blanking.parse()?)),
             None => (s, None),
         };
 
+        let (resolution, refresh_rate_list) = match mode.split_once('@') {
+            Some((resolution, refresh_rate_list)) => (resolution, Some(refresh_rate_list)),
+            None => (mode, None),
+        };
+
         let (width, height) = resolution.split_once('x').ok_or_else(|| {
             eyre::eyre!("invalid resolution in {s:?}, expected a string like \"1920x1080\"",)
         })?;
+        let (height, interlaced) = match height.strip_suffix('i') {
+            Some(height) => (height, true),
+            None => (height, false),
+        };
         let width = width
             .parse()
             .with_context(|| format!("invalid width in {s:?}, expected a number"))?;
@@ -108,32 +158,104 @@ impl std::str::FromStr for Mode {
             None => BTreeSet::new(),
         };
 
-        Ok(Self {
+        let mode = Self {
             width,
             height,
             refresh_rates,
-        })
+            interlaced,
+            blanking,
+        };
+
+        driver_ipc::Mode::from(mode.clone())
+            .validate()
+            .with_context(|| format!("invalid mode {s:?}"))?;
+
+        Ok(mode)
+    }
+}
+
+impl std::str::FromStr for Blanking {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            eyre::eyre!(
+                "invalid blanking {s:?}, expected a string like \"88,44,148,4,5,36\" or \"88,44,148,4,5,36,++\""
+            )
+        };
+
+        let mut parts = s.split(',');
+
+        let mut values = [0; 6];
+        for value in &mut values {
+            let part = parts.next().ok_or_else(invalid)?;
+            *value = part
+                .parse()
+                .with_context(|| format!("invalid blanking value {part:?}, expected a number"))?;
+        }
+
+        let polarity = |c| match c {
+            '+' => Ok(true),
+            '-' => Ok(false),
+            _ => Err(invalid()),
+        };
+
+        let (h_sync_positive, v_sync_positive) = match parts.next() {
+            Some(p) => {
+                let mut chars = p.chars();
+                match (chars.next(), chars.next(), chars.next()) {
+                    (Some(h), Some(v), None) => (polarity(h)?, polarity(v)?),
+                    _ => return Err(invalid()),
+                }
+            }
+            None => (true, false),
+        };
+
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+
+        let [h_front_porch, h_sync, h_back_porch, v_front_porch, v_sync, v_back_porch] = values;
+
+        Ok(Self(driver_ipc::Blanking {
+            h_front_porch,
+            h_sync,
+            h_back_porch,
+            v_front_porch,
+            v_sync,
+            v_back_porch,
+            h_sync_positive,
+            v_sync_positive,
+        }))
     }
 }
 
 /// Merge together a list of modes. Multiple modes with the same resolution
-/// will be merged into one, and the sets of refresh rates will be combined.
+/// and scan will be merged into one, and the sets of refresh rates will be
+/// combined. Custom blanking of a later mode replaces that of an earlier one.
 pub fn merge(modes: impl IntoIterator<Item = Mode>) -> Vec<Mode> {
     let mut resolutions =
-        HashMap::<(driver_ipc::Dimen, driver_ipc::Dimen), BTreeSet<driver_ipc::RefreshRate>>::new();
+        HashMap::<ModeKey, (BTreeSet<driver_ipc::RefreshRate>, Option<Blanking>)>::new();
 
     for mode in modes {
-        let refresh_rates = resolutions.entry((mode.width, mode.height)).or_default();
+        let (refresh_rates, blanking) = resolutions.entry(mode.key()).or_default();
         refresh_rates.extend(&mode.refresh_rates);
+        if mode.blanking.is_some() {
+            *blanking = mode.blanking;
+        }
     }
 
     resolutions
         .into_iter()
-        .map(|((width, height), refresh_rates)| Mode {
-            width,
-            height,
-            refresh_rates,
-        })
+        .map(
+            |((width, height, interlaced), (refresh_rates, blanking))| Mode {
+                width,
+                height,
+                refresh_rates,
+                interlaced,
+                blanking,
+            },
+        )
         .collect()
 }
 
@@ -149,23 +271,25 @@ pub fn remove(
     remove_mode: &Mode,
 ) -> eyre::Result<Vec<Mode>> {
     let mut resolutions =
-        HashMap::<(driver_ipc::Dimen, driver_ipc::Dimen), BTreeSet<driver_ipc::RefreshRate>>::new();
+        HashMap::<ModeKey, (BTreeSet<driver_ipc::RefreshRate>, Option<Blanking>)>::new();
 
     for mut mode in modes {
         mode.ensure_refresh_rate();
 
-        let refresh_rates = resolutions.entry((mode.width, mode.height)).or_default();
+        let (refresh_rates, blanking) = resolutions.entry(mode.key()).or_default();
         refresh_rates.extend(&mode.refresh_rates);
+        if mode.blanking.is_some() {
+            *blanking = mode.blanking;
+        }
     }
 
     if remove_mode.refresh_rates.is_empty() {
-        let removed = resolutions.remove(&(remove_mode.width, remove_mode.height));
+        let removed = resolutions.remove(&remove_mode.key());
         if removed.is_none() {
             eyre::bail!("mode {remove_mode} not found");
         }
     } else {
-        let Some(refresh_rates) = resolutions.get_mut(&(remove_mode.width, remove_mode.height))
-        else {
+        let Some((refresh_rates, _)) = resolutions.get_mut(&remove_mode.key()) else {
             eyre::bail!("mode {remove_mode} not found");
         };
         for refresh_rate in &remove_mode.refresh_rates {
@@ -178,12 +302,137 @@ pub fn remove(
 
     let modes = resolutions
         .into_iter()
-        .filter(|(_, refresh_rates)| !refresh_rates.is_empty())
-        .map(|((width, height), refresh_rates)| Mode {
-            width,
-            height,
-            refresh_rates,
-        })
+        .filter(|(_, (refresh_rates, _))| !refresh_rates.is_empty())
+        .map(
+            |((width, height, interlaced), (refresh_rates, blanking))| Mode {
+                width,
+                height,
+                refresh_rates,
+                interlaced,
+                blanking,
+            },
+        )
         .collect();
     Ok(modes)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(s: &str) -> Mode {
+        s.parse().unwrap_or_else(|err| panic!("{s:?}: {err:?}"))
+    }
+
+    #[test]
+    fn parse_mode() {
+        let mode = parse("1920x1080");
+        assert_eq!((mode.width, mode.height), (1920, 1080));
+        assert!(mode.refresh_rates.is_empty());
+        assert!(!mode.interlaced);
+        assert_eq!(mode.blanking, None);
+
+        let mode = parse("3840x2160@120/60/120");
+        assert_eq!((mode.width, mode.height), (3840, 2160));
+        assert_eq!(mode.refresh_rates, BTreeSet::from([60, 120]));
+
+        let mode = parse("1920x1080i@50");
+        assert_eq!((mode.width, mode.height), (1920, 1080));
+        assert_eq!(mode.refresh_rates, BTreeSet::from([50]));
+        assert!(mode.interlaced);
+
+        let mode = parse("1920x1080@60:88,44,148,4,5,36");
+        assert_eq!(mode.refresh_rates, BTreeSet::from([60]));
+        assert_eq!(
+            mode.blanking,
+            Some(Blanking(driver_ipc::Blanking {
+                h_front_porch: 88,
+                h_sync: 44,
+                h_back_porch: 148,
+                v_front_porch: 4,
+                v_sync: 5,
+                v_back_porch: 36,
+                h_sync_positive: true,
+                v_sync_positive: false,
+            }))
+        );
+    }
+
+    #[test]
+    fn parse_blanking() {
+        let polarity = |s: &str| {
+            let Blanking(blanking) = s.parse().unwrap();
+            (blanking.h_sync_positive, blanking.v_sync_positive)
+        };
+
+        assert_eq!(polarity("1,2,3,4,5,6"), (true, false));
+        assert_eq!(polarity("1,2,3,4,5,6,++"), (true, true));
+        assert_eq!(polarity("1,2,3,4,5,6,+-"), (true, false));
+        assert_eq!(polarity("1,2,3,4,5,6,-+"), (false, true));
+        assert_eq!(polarity("1,2,3,4,5,6,--"), (false, false));
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for s in [
+            "1920x1080",
+            "1920x1080@60",
+            "3840x2160@60/120/144",
+            "1920x1080i@50/60",
+            "1920x1080@60:88,44,148,4,5,36,+-",
+            "1920x1080i@50:528,44,148,2,5,15,++",
+            "800x600:40,128,88,1,4,23,-+",
+        ] {
+            let mode = parse(s);
+            assert_eq!(mode.to_string(), s);
+            assert_eq!(parse(&mode.to_string()).to_string(), s);
+        }
+
+        // the default polarity is written out, and refresh rates are sorted
+        assert_eq!(
+            parse("1920x1080@120/60:88,44,148,4,5,36").to_string(),
+            "1920x1080@60/120:88,44,148,4,5,36,+-"
+        );
+    }
+
+    #[test]
+    fn reject_invalid() {
+        for s in [
+            // malformed resolution or refresh rates
+            "",
+            "1920",
+            "1920x",
+            "x1080",
+            "1920x1080p",
+            "axb",
+            "1920x1080@",
+            "1920x1080@60/",
+            "1920x1080@sixty",
+            // wrong number of blanking values
+            "1920x1080:",
+            "1920x1080:88,44,148,4,5",
+            "1920x1080:88,44,148,4,5,36,+-,1",
+            "1920x1080:88,44,148,4,5,36,7",
+            "1920x1080:88,44,148,4,5,-36",
+            // bad polarity
+            "1920x1080:88,44,148,4,5,36,+",
+            "1920x1080:88,44,148,4,5,36,+++",
+            "1920x1080:88,44,148,4,5,36,+x",
+            "1920x1080:88,44,148,4,5,36,",
+            // no timing can be built
+            "0x1080",
+            "1920x0",
+            "1920x1080@0",
+            "1920x1080@60/0",
+            "1920x1081i",
+            "1920x1080:88,0,148,4,5,36",
+            "1920x1080:88,44,148,4,0,36",
+            "1920x1080:4294967295,44,148,4,5,36",
+            "1920x1080@3000",
+            "4294967295x1080",
+            "1920x4294967295",
+        ] {
+            assert!(s.parse::<Mode>().is_err(), "{s:?} was accepted");
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use std::{
     mem::{self, MaybeUninit},
     ptr::NonNull,
+    slice,
 };
 
+use driver_ipc::{edid, timing::Timing};
 use log::error;
 use wdf_umdf_sys::{
     DISPLAYCONFIG_VIDEO_SIGNAL_INFO__bindgen_ty_1,
@@ -12,14 +14,14 @@ use wdf_umdf_sys::{
     IDARG_IN_COMMITMODES, IDARG_IN_GETDEFAULTDESCRIPTIONMODES, IDARG_IN_PARSEMONITORDESCRIPTION,
     IDARG_IN_QUERYTARGETMODES, IDARG_IN_SETSWAPCHAIN, IDARG_OUT_GETDEFAULTDESCRIPTIONMODES,
     IDARG_OUT_PARSEMONITORDESCRIPTION, IDARG_OUT_QUERYTARGETMODES, IDDCX_ADAPTER__,
-    IDDCX_MONITOR_MODE, IDDCX_MONITOR_MODE_ORIGIN, IDDCX_MONITOR__, IDDCX_TARGET_MODE, NTSTATUS,
-    WDFDEVICE, WDF_POWER_DEVICE_STATE,
+    IDDCX_MONITOR_MODE, IDDCX_MONITOR_MODE_ORIGIN, IDDCX_MONITOR__, IDDCX_PATH_FLAGS,
+    IDDCX_TARGET_MODE, NTSTATUS, WDFDEVICE, WDF_POWER_DEVICE_STATE,
 };
 
 use crate::{
     context::{DeviceContext, MonitorContext},
-    edid::Edid,
-    ipc::{AdapterObject, FlattenModes, ADAPTER, MONITOR_MODES},
+    ipc::{AdapterObject, FlattenModes, ADAPTER},
+    state::{MonitorObject, STATE},
 };
 
 pub extern "C-unwind" fn adapter_init_finished(
@@ -62,26 +64,38 @@ pub extern "C-unwind" fn device_d0_entry(
     NTSTATUS::STATUS_SUCCESS
 }
 
-fn display_info(width: u32, height: u32, refresh_rate: u32) -> DISPLAYCONFIG_VIDEO_SIGNAL_INFO {
-    let clock_rate = refresh_rate * (height + 4) * (height + 4) + 1000;
+/// Video signal of a mode, described by its timing
+///
+/// Monitor modes use a `v_sync_freq_divider` of 0, target modes 1
+fn signal_info(timing: &Timing, v_sync_freq_divider: u32) -> DISPLAYCONFIG_VIDEO_SIGNAL_INFO {
+    let (v_num, v_den) = timing.v_freq();
+
+    // timings describe a single field of interlaced modes, windows wants the whole frame
+    let (v_active, v_total, scan_line_ordering) = if timing.interlaced {
+        (
+            timing.v_active * 2,
+            timing.v_total() * 2 + 1,
+            DISPLAYCONFIG_SCANLINE_ORDERING::DISPLAYCONFIG_SCANLINE_ORDERING_INTERLACED_UPPERFIELDFIRST,
+        )
+    } else {
+        (
+            timing.v_active,
+            timing.v_total(),
+            DISPLAYCONFIG_SCANLINE_ORDERING::DISPLAYCONFIG_SCANLINE_ORDERING_PROGRESSIVE,
+        )
+    };
 
     DISPLAYCONFIG_VIDEO_SIGNAL_INFO {
-        pixelRate: u64::from(clock_rate),
-        hSyncFreq: DISPLAYCONFIG_RATIONAL {
-            Numerator: clock_rate,
-            Denominator: height + 4,
-        },
-        vSyncFreq: DISPLAYCONFIG_RATIONAL {
-            Numerator: clock_rate,
-            Denominator: (height + 4) * (height + 4),
-        },
+        pixelRate: timing.pixel_clock_hz(),
+        hSyncFreq: rational(timing.pixel_clock_hz(), u64::from(timing.h_total())),
+        vSyncFreq: rational(v_num, v_den),
         activeSize: DISPLAYCONFIG_2DREGION {
-            cx: width,
-            cy: height,
+            cx: timing.h_active,
+            cy: v_active,
         },
         totalSize: DISPLAYCONFIG_2DREGION {
-            cx: width + 4,
-            cy: height + 4,
+            cx: timing.h_total(),
+            cy: v_total,
         },
         __bindgen_anon_1: DISPLAYCONFIG_VIDEO_SIGNAL_INFO__bindgen_ty_1 {
             AdditionalSignalInfo: unsafe {
@@ -90,13 +104,39 @@ fn display_info(width: u32, height: u32, refresh_rate: u32) -> DISPLAYCONFIG_VID
                     DISPLAYCONFIG_VIDEO_SIGNAL_INFO__bindgen_ty_1__bindgen_ty_1,
                 >(
                     DISPLAYCONFIG_VIDEO_SIGNAL_INFO__bindgen_ty_1__bindgen_ty_1::new_bitfield_1(
-                        255, 0, 0,
+                        255,
+                        v_sync_freq_divider,
+                        0,
                     ),
                 )
             },
         },
-        scanLineOrdering:
-            DISPLAYCONFIG_SCANLINE_ORDERING::DISPLAYCONFIG_SCANLINE_ORDERING_PROGRESSIVE,
+        scanLineOrdering: scan_line_ordering,
+    }
+}
+
+/// Reduce `numerator / denominator` until it fits into a `DISPLAYCONFIG_RATIONAL`
+fn rational(mut numerator: u64, mut denominator: u64) -> DISPLAYCONFIG_RATIONAL {
+    let gcd = {
+        let (mut a, mut b) = (numerator, denominator);
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a.max(1)
+    };
+
+    numerator /= gcd;
+    denominator /= gcd;
+
+    // lose some precision rather than overflowing
+    while numerator > u64::from(u32::MAX) || denominator > u64::from(u32::MAX) {
+        numerator >>= 1;
+        denominator >>= 1;
+    }
+
+    DISPLAYCONFIG_RATIONAL {
+        Numerator: u32::try_from(numerator).unwrap_or(u32::MAX),
+        Denominator: u32::try_from(denominator).unwrap_or(u32::MAX).max(1),
     }
 }
 
@@ -107,11 +147,6 @@ pub extern "C-unwind" fn parse_monitor_description(
     let in_args = unsafe { &*p_in_args };
     let out_args = unsafe { &mut *p_out_args };
 
-    let Ok(monitors) = MONITOR_MODES.lock() else {
-        error!("MONITOR_MODES mutex poisoned");
-        return NTSTATUS::STATUS_DRIVER_INTERNAL_ERROR;
-    };
-
     let edid = unsafe {
         std::slice::from_raw_parts(
             in_args.MonitorDescription.pData as *const u8,
@@ -119,7 +154,7 @@ pub extern "C-unwind" fn parse_monitor_description(
         )
     };
 
-    let monitor_index = Edid::get_serial(edid);
+    let monitor_index = edid::get_serial(edid);
     let Ok(monitor_index) = monitor_index else {
         error!(
             "We got an edid {} bytes long, but this is incorrect",
@@ -128,17 +163,22 @@ pub extern "C-unwind" fn parse_monitor_description(
         return NTSTATUS::STATUS_INVALID_VIEW_SIZE;
     };
 
-    let Some(monitor) = monitors.iter().find(|&m| m.data.id == monitor_index) else {
-        error!("Failed to find monitor id {monitor_index}");
-        return NTSTATUS::STATUS_DRIVER_INTERNAL_ERROR;
+    let modes = match STATE.modes_of_id(monitor_index) {
+        Ok(Some(modes)) => modes,
+
+        Ok(None) => {
+            error!("Failed to find monitor id {monitor_index}");
+            return NTSTATUS::STATUS_DRIVER_INTERNAL_ERROR;
+        }
+
+        Err(e) => {
+            error!("{e}");
+            return NTSTATUS::STATUS_DRIVER_INTERNAL_ERROR;
+        }
     };
 
-    let number_of_modes: u32 = monitor
-        .data
-        .modes
-        .iter()
-        .map(|m| u32::try_from(m.refresh_rates.len()).expect("Cannot use > u32::MAX refresh rates"))
-        .sum();
+    let number_of_modes =
+        u32::try_from(modes.flatten().count()).expect("Cannot use > u32::MAX refresh rates");
 
     out_args.MonitorModeBufferOutputCount = number_of_modes;
     if in_args.MonitorModeBufferInputCount < number_of_modes {
@@ -159,12 +199,12 @@ pub extern "C-unwind" fn parse_monitor_description(
         )
     };
 
-    for (mode, out_mode) in monitor.data.modes.flatten().zip(monitor_modes.iter_mut()) {
+    for (timing, out_mode) in modes.flatten().zip(monitor_modes.iter_mut()) {
         out_mode.write(IDDCX_MONITOR_MODE {
             #[allow(clippy::cast_possible_truncation)]
             Size: mem::size_of::<IDDCX_MONITOR_MODE>() as u32,
             Origin: IDDCX_MONITOR_MODE_ORIGIN::IDDCX_MONITOR_MODE_ORIGIN_MONITORDESCRIPTOR,
-            MonitorVideoSignalInfo: display_info(mode.width, mode.height, mode.refresh_rate),
+            MonitorVideoSignalInfo: signal_info(&timing, 0),
         });
     }
 
@@ -175,48 +215,83 @@ pub extern "C-unwind" fn parse_monitor_description(
 }
 
 pub extern "C-unwind" fn monitor_get_default_modes(
-    _monitor_object: *mut IDDCX_MONITOR__,
-    _p_in_args: *const IDARG_IN_GETDEFAULTDESCRIPTIONMODES,
-    _p_out_args: *mut IDARG_OUT_GETDEFAULTDESCRIPTIONMODES,
+    monitor_object: *mut IDDCX_MONITOR__,
+    p_in_args: *const IDARG_IN_GETDEFAULTDESCRIPTIONMODES,
+    p_out_args: *mut IDARG_OUT_GETDEFAULTDESCRIPTIONMODES,
 ) -> NTSTATUS {
-    NTSTATUS::STATUS_NOT_IMPLEMENTED
-}
+    let Some(monitor_ptr) = NonNull::new(monitor_object) else {
+        error!("Monitor ptr was null");
+        return NTSTATUS::STATUS_INVALID_ADDRESS;
+    };
+
+    let in_args = unsafe { &*p_in_args };
+    let out_args = unsafe { &mut *p_out_args };
+
+    // Only called for monitors without a description. Ours always have an edid, but in case windows
+    // asks anyways, report the modes of the monitor, or the default catalogue if it has none
+    let modes = match STATE.modes_of_object(MonitorObject(monitor_ptr)) {
+        Ok(modes) => modes.filter(|modes| !modes.is_empty()),
+        Err(e) => {
+            error!("{e}");
+            return NTSTATUS::STATUS_DRIVER_INTERNAL_ERROR;
+        }
+    };
+
+    let modes = match modes {
+        Some(modes) => modes,
+        None => match STATE.default_modes() {
+            Ok(defaults) => defaults.modes(),
+            Err(e) => {
+                error!("{e}");
+                return NTSTATUS::STATUS_DRIVER_INTERNAL_ERROR;
+            }
+        },
+    };
+
+    let number_of_modes =
+        u32::try_from(modes.flatten().count()).expect("Cannot use > u32::MAX modes");
+
+    out_args.DefaultMonitorModeBufferOutputCount = number_of_modes;
+    if in_args.DefaultMonitorModeBufferInputCount < number_of_modes {
+        // Return success if there was no buffer, since the caller was only asking for a count of modes
+        return if in_args.DefaultMonitorModeBufferInputCount > 0 {
+            NTSTATUS::STATUS_BUFFER_TOO_SMALL
+        } else {
+            NTSTATUS::STATUS_SUCCESS
+        };
+    }
 
-pub fn target_mode(width: u32, height: u32, refresh_rate: u32) -> IDDCX_TARGET_MODE {
-    let total_size = DISPLAYCONFIG_2DREGION {
-        cx: width,
-        cy: height,
+    let default_modes = unsafe {
+        std::slice::from_raw_parts_mut(
+            in_args
+                .pDefaultMonitorModes
+                .cast::<MaybeUninit<IDDCX_MONITOR_MODE>>(),
+            number_of_modes as usize,
+        )
     };
 
+    for (timing, out_mode) in modes.flatten().zip(default_modes.iter_mut()) {
+        out_mode.write(IDDCX_MONITOR_MODE {
+            #[allow(clippy::cast_possible_truncation)]
+            Size: mem::size_of::<IDDCX_MONITOR_MODE>() as u32,
+            Origin: IDDCX_MONITOR_MODE_ORIGIN::IDDCX_MONITOR_MODE_ORIGIN_DRIVER,
+            MonitorVideoSignalInfo: signal_info(&timing, 0),
+        });
+    }
+
+    // The first mode is the preferred one, same as in the edid
+    out_args.PreferredMonitorModeIdx = 0;
+
+    NTSTATUS::STATUS_SUCCESS
+}
+
+pub fn target_mode(timing: &Timing) -> IDDCX_TARGET_MODE {
     IDDCX_TARGET_MODE {
         #[allow(clippy::cast_possible_truncation)]
         Size: mem::size_of::<IDDCX_TARGET_MODE>() as u32,
 
         TargetVideoSignalInfo: DISPLAYCONFIG_TARGET_MODE {
-            targetVideoSignalInfo: DISPLAYCONFIG_VIDEO_SIGNAL_INFO {
-                pixelRate: u64::from(refresh_rate) * u64::from(width) * u64::from(height),
-                hSyncFreq: DISPLAYCONFIG_RATIONAL {
-                    Numerator: refresh_rate * height,
-                    Denominator: 1,
-                },
-                vSyncFreq: DISPLAYCONFIG_RATIONAL {
-                    Numerator: refresh_rate,
-                    Denominator: 1,
-                },
-                totalSize: total_size,
-                activeSize: total_size,
-                scanLineOrdering:
-                    DISPLAYCONFIG_SCANLINE_ORDERING::DISPLAYCONFIG_SCANLINE_ORDERING_PROGRESSIVE,
-                __bindgen_anon_1: DISPLAYCONFIG_VIDEO_SIGNAL_INFO__bindgen_ty_1 {
-                    AdditionalSignalInfo: unsafe {
-                        mem::transmute::<__BindgenBitfieldUnit<[u8; 4]>, DISPLAYCONFIG_VIDEO_SIGNAL_INFO__bindgen_ty_1__bindgen_ty_1>(
-                            DISPLAYCONFIG_VIDEO_SIGNAL_INFO__bindgen_ty_1__bindgen_ty_1::new_bitfield_1(
-                                255, 1, 0,
-                            ),
-                        )
-                    },
-                },
-            },
+            targetVideoSignalInfo: signal_info(timing, 1),
         },
 
         ..Default::default()
@@ -228,28 +303,28 @@ pub extern "C-unwind" fn monitor_query_modes(
     p_in_args: *const IDARG_IN_QUERYTARGETMODES,
     p_out_args: *mut IDARG_OUT_QUERYTARGETMODES,
 ) -> NTSTATUS {
-    // find out which monitor this belongs too
-
-    let Ok(monitors) = MONITOR_MODES.lock() else {
-        error!("MONITOR_MODES mutex poisoned");
-        return NTSTATUS::STATUS_DRIVER_INTERNAL_ERROR;
+    let Some(monitor_ptr) = NonNull::new(monitor_object) else {
+        error!("Monitor ptr was null");
+        return NTSTATUS::STATUS_INVALID_ADDRESS;
     };
 
     // we have stored the monitor object per id, so we should be able to compare pointers
-    let Some(monitor) = monitors
-        .iter()
-        .find(|&m| m.object.is_some_and(|p| p.as_ptr() == monitor_object))
-    else {
-        error!("Failed to find monitor object in cache for {monitor_object:?}");
-        return NTSTATUS::STATUS_DRIVER_INTERNAL_ERROR;
+    let modes = match STATE.modes_of_object(MonitorObject(monitor_ptr)) {
+        Ok(Some(modes)) => modes,
+
+        Ok(None) => {
+            error!("Failed to find monitor object in cache for {monitor_object:?}");
+            return NTSTATUS::STATUS_DRIVER_INTERNAL_ERROR;
+        }
+
+        Err(e) => {
+            error!("{e}");
+            return NTSTATUS::STATUS_DRIVER_INTERNAL_ERROR;
+        }
     };
 
-    let number_of_modes = monitor
-        .data
-        .modes
-        .iter()
-        .map(|m| u32::try_from(m.refresh_rates.len()).expect("Cannot use > u32::MAX modes"))
-        .sum();
+    let number_of_modes =
+        u32::try_from(modes.flatten().count()).expect("Cannot use > u32::MAX modes");
 
     // Create a set of modes supported for frame processing and scan-out. These are typically not based on the
     // monitor's descriptor and instead are based on the static processing capability of the device. The OS will
@@ -270,13 +345,8 @@ pub extern "C-unwind" fn monitor_query_modes(
             )
         };
 
-        for (mode, out_target) in monitor
-            .data
-            .modes
-            .flatten()
-            .zip(out_target_modes.iter_mut())
-        {
-            let target_mode = target_mode(mode.width, mode.height, mode.refresh_rate);
+        for (timing, out_target) in modes.flatten().zip(out_target_modes.iter_mut()) {
+            let target_mode = target_mode(&timing);
 
             out_target.write(target_mode);
         }
@@ -287,8 +357,35 @@ pub extern "C-unwind" fn monitor_query_modes(
 
 pub extern "C-unwind" fn adapter_commit_modes(
     _adapter_object: *mut IDDCX_ADAPTER__,
-    _p_in_args: *const IDARG_IN_COMMITMODES,
+    p_in_args: *const IDARG_IN_COMMITMODES,
 ) -> NTSTATUS {
+    let in_args = unsafe { &*p_in_args };
+
+    let paths = if in_args.PathCount == 0 || in_args.pPaths.is_null() {
+        &[][..]
+    } else {
+        unsafe { slice::from_raw_parts(in_args.pPaths, in_args.PathCount as usize) }
+    };
+
+    // swap chain processors pace themselves by the refresh rate of the committed mode
+    for path in paths {
+        let active = path.Flags.0 & IDDCX_PATH_FLAGS::IDDCX_PATH_FLAGS_ACTIVE.0 != 0;
+        let v_sync_freq = path.TargetVideoSignalInfo.vSyncFreq;
+
+        let refresh_rate = (active && v_sync_freq.Denominator != 0)
+            .then(|| f64::from(v_sync_freq.Numerator) / f64::from(v_sync_freq.Denominator));
+
+        let res = unsafe {
+            MonitorContext::get_mut(path.MonitorObject.cast(), |context| {
+                context.set_refresh_rate(refresh_rate);
+            })
+        };
+
+        if let Err(e) = res {
+            error!("Failed to store committed refresh rate: {e:?}");
+        }
+    }
+
     NTSTATUS::STATUS_SUCCESS
 }
 
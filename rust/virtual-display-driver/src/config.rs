@@ -0,0 +1,133 @@
+//! Loading of the startup [`Config`] from the device registry key
+//!
+//! The config is read from the `Device Parameters` key of the device, which an inf can fill with
+//! `HKR, "Device Parameters", "Config", %REG_SZ%, "..."`:
+//!
+//! - `ConfigPath` (REG_SZ): path to a json file with the config, takes precedence
+//! - `Config` (REG_SZ): the json config itself
+
+use std::{fs, io, slice};
+
+use driver_ipc::config::{error::ConfigError, Config};
+use wdf_umdf::{
+    WdfDeviceOpenRegistryKey, WdfError, WdfMemoryGetBuffer, WdfObjectDelete, WdfRegistryClose,
+    WdfRegistryQueryMemory,
+};
+use wdf_umdf_sys::{
+    NTSTATUS, PLUGPLAY_REGKEY_DEVICE, POOL_TYPE, REG_SZ, UNICODE_STRING, WDFDEVICE, WDFKEY,
+    WDFMEMORY, WDFOBJECT, WDF_REGKEY_DEVICE_SUBKEY,
+};
+use windows::Win32::System::Registry::KEY_READ;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LoadError {
+    #[error("Failed to read registry: {0}")]
+    Registry(#[from] WdfError),
+    #[error("Registry value {0} is not a string")]
+    NotAString(&'static str),
+    #[error("Failed to read config file {0}: {1}")]
+    File(String, io::Error),
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+}
+
+/// Load the startup config of `device`, `None` if there is none
+pub fn load(device: WDFDEVICE) -> Result<Option<Config>, LoadError> {
+    let Some(key) = Key::open(device)? else {
+        return Ok(None);
+    };
+
+    let json = if let Some(path) = key.string("ConfigPath")? {
+        fs::read_to_string(&path).map_err(|e| LoadError::File(path, e))?
+    } else if let Some(json) = key.string("Config")? {
+        json
+    } else {
+        return Ok(None);
+    };
+
+    Ok(Some(Config::from_json(&json)?))
+}
+
+struct Key(WDFKEY);
+
+impl Key {
+    /// Open the device parameters key, `None` if it does not exist
+    fn open(device: WDFDEVICE) -> Result<Option<Self>, WdfError> {
+        let mut key = std::ptr::null_mut();
+
+        let status = unsafe {
+            WdfDeviceOpenRegistryKey(
+                device,
+                PLUGPLAY_REGKEY_DEVICE | WDF_REGKEY_DEVICE_SUBKEY,
+                KEY_READ.0,
+                None,
+                &mut key,
+            )
+        };
+
+        match status {
+            Ok(_) => Ok(Some(Self(key))),
+            Err(WdfError::CallFailed(NTSTATUS::STATUS_OBJECT_NAME_NOT_FOUND)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Read a string value, `None` if it does not exist
+    fn string(&self, name: &'static str) -> Result<Option<String>, LoadError> {
+        let mut name_buf = name.encode_utf16().collect::<Vec<_>>();
+        #[allow(clippy::cast_possible_truncation)]
+        let len = (name_buf.len() * 2) as u16;
+        let value_name = UNICODE_STRING {
+            Length: len,
+            MaximumLength: len,
+            Buffer: name_buf.as_mut_ptr(),
+        };
+
+        let mut memory: WDFMEMORY = std::ptr::null_mut();
+        let mut value_type = 0;
+
+        let status = unsafe {
+            WdfRegistryQueryMemory(
+                self.0,
+                &value_name,
+                POOL_TYPE::PagedPool,
+                None,
+                &mut memory,
+                Some(&mut value_type),
+            )
+        };
+
+        match status {
+            Ok(_) => (),
+            Err(WdfError::CallFailed(NTSTATUS::STATUS_OBJECT_NAME_NOT_FOUND)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+
+        let value = if value_type == REG_SZ {
+            let mut size = 0;
+
+            unsafe { WdfMemoryGetBuffer(memory, Some(&mut size)) }
+                .map(|buffer| {
+                    // SAFETY: the memory holds `size` bytes of the utf-16 string, and lives until deleted below
+                    let data = unsafe { slice::from_raw_parts(buffer.cast::<u16>(), size / 2) };
+
+                    // strip the nul terminator
+                    let end = data.iter().position(|&c| c == 0).unwrap_or(data.len());
+                    Some(String::from_utf16_lossy(&data[..end]))
+                })
+                .map_err(LoadError::from)
+        } else {
+            Err(LoadError::NotAString(name))
+        };
+
+        _ = unsafe { WdfObjectDelete(memory as WDFOBJECT) };
+
+        value
+    }
+}
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        _ = unsafe { WdfRegistryClose(self.0) };
+    }
+}
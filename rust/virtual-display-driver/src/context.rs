@@ -2,41 +2,40 @@ use std::{
     mem::{self, size_of},
     num::{ParseIntError, TryFromIntError},
     ptr::{addr_of_mut, NonNull},
+    sync::Arc,
 };
 
 use anyhow::anyhow;
-use log::{error, warn};
+use driver_ipc::config;
+use log::{debug, error};
 use wdf_umdf::{
-    IddCxAdapterInitAsync, IddCxError, IddCxMonitorArrival, IddCxMonitorCreate,
-    IddCxMonitorSetupHardwareCursor, WdfError, WdfObjectDelete, WDF_DECLARE_CONTEXT_TYPE,
+    IddCxAdapterInitAsync, IddCxError, IddCxMonitorArrival, IddCxMonitorCreate, WdfError,
+    WdfObjectDelete, WDF_DECLARE_CONTEXT_TYPE,
 };
 use wdf_umdf_sys::{
     DISPLAYCONFIG_VIDEO_OUTPUT_TECHNOLOGY, HANDLE, IDARG_IN_ADAPTER_INIT, IDARG_IN_MONITORCREATE,
-    IDARG_IN_SETUP_HWCURSOR, IDARG_OUT_ADAPTER_INIT, IDARG_OUT_MONITORARRIVAL,
-    IDARG_OUT_MONITORCREATE, IDDCX_ADAPTER, IDDCX_ADAPTER_CAPS, IDDCX_CURSOR_CAPS,
-    IDDCX_ENDPOINT_DIAGNOSTIC_INFO, IDDCX_ENDPOINT_VERSION, IDDCX_FEATURE_IMPLEMENTATION,
-    IDDCX_MONITOR, IDDCX_MONITOR_DESCRIPTION, IDDCX_MONITOR_DESCRIPTION_TYPE, IDDCX_MONITOR_INFO,
-    IDDCX_SWAPCHAIN, IDDCX_TRANSMISSION_TYPE, IDDCX_XOR_CURSOR_SUPPORT, LUID, NTSTATUS, WDFDEVICE,
-    WDFOBJECT, WDF_OBJECT_ATTRIBUTES,
-};
-use windows::{
-    core::{s, w, GUID},
-    Win32::{Foundation::TRUE, System::Threading::CreateEventA},
+    IDARG_OUT_ADAPTER_INIT, IDARG_OUT_MONITORARRIVAL, IDARG_OUT_MONITORCREATE, IDDCX_ADAPTER,
+    IDDCX_ADAPTER_CAPS, IDDCX_ENDPOINT_DIAGNOSTIC_INFO, IDDCX_ENDPOINT_VERSION,
+    IDDCX_FEATURE_IMPLEMENTATION, IDDCX_MONITOR, IDDCX_MONITOR_DESCRIPTION,
+    IDDCX_MONITOR_DESCRIPTION_TYPE, IDDCX_MONITOR_INFO, IDDCX_SWAPCHAIN, IDDCX_TRANSMISSION_TYPE,
+    LUID, NTSTATUS, WDFDEVICE, WDFOBJECT, WDF_OBJECT_ATTRIBUTES,
 };
+use windows::core::{GUID, HSTRING};
 
 use crate::{
+    cursor::CursorProcessor,
     direct_3d_device::Direct3DDevice,
-    edid::Edid,
-    ipc::{startup, MONITOR_MODES},
-    swap_chain_processor::SwapChainProcessor,
+    export::FrameExport,
+    ipc,
+    state::{MonitorObject, StateGone, STATE},
+    swap_chain_processor::{FrameCounters, Pacing, SwapChainProcessor},
 };
 
-// Maximum amount of monitors that can be connected
-pub const MAX_MONITORS: u8 = 16;
-
 pub struct DeviceContext {
     device: WDFDEVICE,
     adapter: Option<IDDCX_ADAPTER>,
+    // identity and limits the adapter is initialized with
+    adapter_config: config::Adapter,
 }
 
 // SAFETY: Raw ptr is managed by external library
@@ -47,7 +46,17 @@ unsafe impl Sync for DeviceContext {}
 #[allow(unused)]
 pub struct MonitorContext {
     device: IDDCX_MONITOR,
+    // id of the monitor, for log context
+    id: u32,
     swap_chain_processor: Option<SwapChainProcessor>,
+    // queries the hardware cursor, which is set up with every swap chain
+    cursor_processor: Option<CursorProcessor>,
+    // kept across swap chains, so restarts are counted
+    frame_counters: Arc<FrameCounters>,
+    // whether the swap chain processors copy the frames into shared memory
+    frame_export: Arc<FrameExport>,
+    // of the mode the os committed last, in hertz
+    refresh_rate: Option<f64>,
 }
 
 // SAFETY: Raw ptr is managed by external library
@@ -71,19 +80,28 @@ pub enum ContextError {
     Wdf(#[from] WdfError),
     #[error("Windows Error: {0:?}")]
     Win(#[from] windows::core::Error),
+    #[error("Failed to access monitor state: {0:?}")]
+    State(#[from] StateGone),
     #[error("{0:?}")]
     Other(#[from] anyhow::Error),
 }
 
 impl DeviceContext {
-    pub fn new(device: WDFDEVICE) -> Self {
+    pub fn new(device: WDFDEVICE, adapter_config: config::Adapter) -> Self {
         Self {
             device,
             adapter: None,
+            adapter_config,
         }
     }
 
     pub fn init_adapter(&mut self) -> Result<(), ContextError> {
+        let adapter = &self.adapter_config;
+
+        let friendly_name = HSTRING::from(adapter.friendly_name.as_str());
+        let manufacturer = HSTRING::from(adapter.manufacturer.as_str());
+        let model = HSTRING::from(adapter.model.as_str());
+
         let mut version = IDDCX_ENDPOINT_VERSION {
             #[allow(clippy::cast_possible_truncation)]
             Size: size_of::<IDDCX_ENDPOINT_VERSION>() as u32,
@@ -98,7 +116,7 @@ impl DeviceContext {
             #[allow(clippy::cast_possible_truncation)]
             Size: size_of::<IDDCX_ADAPTER_CAPS>() as u32,
 
-            MaxMonitorsSupported: u32::from(MAX_MONITORS),
+            MaxMonitorsSupported: adapter.max_monitors,
 
             EndPointDiagnostics: IDDCX_ENDPOINT_DIAGNOSTIC_INFO {
                 #[allow(clippy::cast_possible_truncation)]
@@ -106,9 +124,9 @@ impl DeviceContext {
                 GammaSupport: IDDCX_FEATURE_IMPLEMENTATION::IDDCX_FEATURE_IMPLEMENTATION_NONE,
                 TransmissionType: IDDCX_TRANSMISSION_TYPE::IDDCX_TRANSMISSION_TYPE_WIRED_OTHER,
 
-                pEndPointFriendlyName: w!("Virtual Display Driver Adapter").as_ptr(),
-                pEndPointManufacturerName: w!("Cherry").as_ptr(),
-                pEndPointModelName: w!("Pro").as_ptr(),
+                pEndPointFriendlyName: friendly_name.as_ptr(),
+                pEndPointManufacturerName: manufacturer.as_ptr(),
+                pEndPointModelName: model.as_ptr(),
 
                 pFirmwareVersion: addr_of_mut!(version).cast(),
                 pHardwareVersion: addr_of_mut!(version).cast(),
@@ -137,8 +155,8 @@ impl DeviceContext {
     }
 
     pub fn finish_init() -> NTSTATUS {
-        // start the socket listener to listen for messages from the client
-        startup();
+        // arrive the monitors of the config and the ones clients sent while the adapter was initializing
+        ipc::adapter_ready();
 
         NTSTATUS::STATUS_SUCCESS
     }
@@ -148,7 +166,9 @@ impl DeviceContext {
             WDF_OBJECT_ATTRIBUTES::init_context_type(unsafe { MonitorContext::get_type_info() });
 
         // use the edid serial number to represent the monitor index for later identification
-        let mut edid = Edid::generate_with(index);
+        let mut edid = STATE
+            .build_edid(index)?
+            .ok_or(anyhow!("Failed to find monitor {index}"))?;
 
         let mut monitor_info = IDDCX_MONITOR_INFO {
             #[allow(clippy::cast_possible_truncation)]
@@ -191,23 +211,12 @@ impl DeviceContext {
         };
 
         // store monitor object for later
-        {
-            let mut lock = MONITOR_MODES
-                .lock()
-                .map_err(|_| anyhow!("Failed to lock mutex"))?;
-
-            for monitor in &mut *lock {
-                if monitor.data.id == index {
-                    monitor.object = Some(
-                        NonNull::new(monitor_create_out.MonitorObject)
-                            .ok_or(anyhow!("MonitorObject was null"))?,
-                    );
-                }
-            }
-        }
+        let object = NonNull::new(monitor_create_out.MonitorObject)
+            .ok_or(anyhow!("MonitorObject was null"))?;
+        STATE.arrived(index, MonitorObject(object), edid);
 
         unsafe {
-            let context = MonitorContext::new(monitor_create_out.MonitorObject);
+            let context = MonitorContext::new(monitor_create_out.MonitorObject, index);
             context.init(monitor_create_out.MonitorObject as WDFOBJECT)?;
         }
 
@@ -219,15 +228,22 @@ impl DeviceContext {
             IddCxMonitorArrival(monitor_create_out.MonitorObject, &mut arrival_out)?;
         }
 
+        debug!(monitor_id = index; "Monitor arrived");
+
         Ok(())
     }
 }
 
 impl MonitorContext {
-    pub fn new(device: IDDCX_MONITOR) -> Self {
+    pub fn new(device: IDDCX_MONITOR, id: u32) -> Self {
         Self {
             device,
+            id,
             swap_chain_processor: None,
+            cursor_processor: None,
+            frame_counters: FrameCounters::register(id),
+            frame_export: FrameExport::get(id),
+            refresh_rate: None,
         }
     }
 
@@ -237,7 +253,8 @@ impl MonitorContext {
         render_adapter: LUID,
         new_frame_event: HANDLE,
     ) {
-        // drop processing thread
+        // drop processing threads
+        drop(self.cursor_processor.take());
         drop(self.swap_chain_processor.take());
 
         // transmute would work, but one less unsafe block, so why not
@@ -248,61 +265,57 @@ impl MonitorContext {
 
         let device = Direct3DDevice::init(luid);
 
-        if let Ok(device) = device {
-            let mut processor = SwapChainProcessor::new();
+        match device {
+            Ok(device) => {
+                let mut processor = SwapChainProcessor::new();
 
-            processor.run(swap_chain, device, new_frame_event);
+                processor.run(
+                    swap_chain,
+                    device,
+                    new_frame_event,
+                    self.frame_counters.clone(),
+                    self.frame_export.clone(),
+                    Pacing::new(self.refresh_rate),
+                );
 
-            self.swap_chain_processor = Some(processor);
+                self.swap_chain_processor = Some(processor);
 
-            self.setup_hw_cursor();
-        } else {
-            // It's important to delete the swap-chain if D3D initialization fails, so that the OS knows to generate a new
-            // swap-chain and try again.
+                debug!(monitor_id = self.id; "Assigned swap chain");
 
-            unsafe {
-                let _ = WdfObjectDelete(swap_chain.cast());
+                self.setup_hw_cursor();
+            }
+
+            Err(e) => {
+                error!(monitor_id = self.id; "Failed to init Direct3D device: {e:?}");
+
+                // It's important to delete the swap-chain if D3D initialization fails, so that the OS knows to generate a new
+                // swap-chain and try again.
+
+                unsafe {
+                    let _ = WdfObjectDelete(swap_chain.cast());
+                }
             }
         }
     }
 
+    /// Store the refresh rate of the mode the os committed, which paces the next swap chain
+    pub fn set_refresh_rate(&mut self, refresh_rate: Option<f64>) {
+        if self.refresh_rate != refresh_rate {
+            debug!(monitor_id = self.id; "Committed refresh rate {refresh_rate:?}");
+        }
+
+        self.refresh_rate = refresh_rate;
+    }
+
     pub fn unassign_swap_chain(&mut self) {
+        self.cursor_processor.take();
         self.swap_chain_processor.take();
     }
 
     pub fn setup_hw_cursor(&mut self) {
-        let mouse_event = unsafe { CreateEventA(None, false, false, s!("vdd_mouse_event")) };
-        let Ok(mouse_event) = mouse_event else {
-            error!("CreateEventA failed: {mouse_event:?}");
-            return;
-        };
-
-        // setup hardware cursor
-        let cursor_info = IDDCX_CURSOR_CAPS {
-            #[allow(clippy::cast_possible_truncation)]
-            Size: std::mem::size_of::<IDDCX_CURSOR_CAPS>() as u32,
-            AlphaCursorSupport: TRUE.0,
-            MaxX: 512,
-            MaxY: 512,
-            ColorXorCursorSupport: IDDCX_XOR_CURSOR_SUPPORT::IDDCX_XOR_CURSOR_SUPPORT_NONE,
-        };
-
-        let hw_cursor = IDARG_IN_SETUP_HWCURSOR {
-            CursorInfo: cursor_info,
-            hNewCursorDataAvailable: mouse_event.0,
-        };
-
-        let res = unsafe { IddCxMonitorSetupHardwareCursor(self.device, &hw_cursor) };
-        let Ok(res) = res else {
-            error!("IddCxMonitorSetupHardwareCursor() failed: {res:?}");
-            return;
-        };
-
-        if res.is_warning() {
-            warn!("IddCxMonitorSetupHardwareCursor() warn: {res:?}");
-        }
-        if res.is_error() {
-            error!("IddCxMonitorSetupHardwareCursor() failed: {res:?}");
+        match CursorProcessor::start(self.device, self.id, self.frame_export.clone()) {
+            Ok(processor) => self.cursor_processor = Some(processor),
+            Err(e) => error!(monitor_id = self.id; "Failed to set up hardware cursor: {e:?}"),
         }
     }
 }
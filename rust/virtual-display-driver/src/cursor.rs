@@ -0,0 +1,317 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, LazyLock, Mutex, OnceLock, PoisonError,
+    },
+    thread::{self, JoinHandle},
+};
+
+use driver_ipc::{
+    config::{HardwareCursor, XorCursor},
+    Cursor, CursorKind, CursorShape, Id,
+};
+use log::{debug, error, warn};
+use tokio::sync::broadcast;
+use wdf_umdf::{IddCxMonitorQueryHardwareCursor, IddCxMonitorSetupHardwareCursor};
+use wdf_umdf_sys::{
+    IDARG_IN_QUERY_HWCURSOR, IDARG_IN_SETUP_HWCURSOR, IDARG_OUT_QUERY_HWCURSOR, IDDCX_CURSOR_CAPS,
+    IDDCX_CURSOR_SHAPE_TYPE, IDDCX_MONITOR, IDDCX_XOR_CURSOR_SUPPORT,
+};
+use windows::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{CloseHandle, HANDLE, TRUE, WAIT_OBJECT_0},
+        System::Threading::{CreateEventW, SetEvent, WaitForSingleObject},
+    },
+};
+
+use crate::{
+    export::{CursorExporter, FrameExport},
+    helpers::Sendable,
+};
+
+// milliseconds to wait for a cursor update, before checking whether the export changed
+const WAIT: u32 = 100;
+
+// shapes the hardware cursors support, the defaults if unset
+static CONFIG: OnceLock<HardwareCursor> = OnceLock::new();
+
+// every cursor update, for the clients which subscribed to them
+static UPDATES: LazyLock<broadcast::Sender<Cursor>> = LazyLock::new(|| broadcast::channel(64).0);
+
+// the last cursor of every monitor, with its shape, by id
+static LATEST: LazyLock<Mutex<BTreeMap<Id, Cursor>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+/// Set the shapes the hardware cursors of the monitors support
+pub fn set_config(config: HardwareCursor) {
+    _ = CONFIG.set(config);
+}
+
+fn config() -> HardwareCursor {
+    CONFIG.get().copied().unwrap_or_default()
+}
+
+/// Receive every cursor update from now on, shapes are only sent when they change
+pub fn subscribe() -> broadcast::Receiver<Cursor> {
+    UPDATES.subscribe()
+}
+
+/// The last cursor of monitor `id` (all if `None`), with its shape
+pub fn latest(id: Option<Id>) -> Vec<Cursor> {
+    LATEST
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .values()
+        .filter(|cursor| id.map_or(true, |id| cursor.id == id))
+        .cloned()
+        .collect()
+}
+
+/// Send an update to the subscribers, and keep it with the last shape
+fn publish(mut cursor: Cursor) {
+    // fails if nobody subscribed
+    _ = UPDATES.send(cursor.clone());
+
+    let mut latest = LATEST.lock().unwrap_or_else(PoisonError::into_inner);
+    if cursor.shape.is_none() {
+        cursor.shape = latest.get(&cursor.id).and_then(|last| last.shape.clone());
+    }
+    latest.insert(cursor.id, cursor);
+}
+
+/// Cursor of a monitor as the cursor thread last queried it
+#[derive(Debug, Default)]
+pub struct CursorState {
+    pub visible: bool,
+    pub x: i32,
+    pub y: i32,
+    pub shape: Option<(frame_export::CursorShape, Vec<u8>)>,
+}
+
+/// Thread which queries the hardware cursor of a monitor whenever the os updates it, and
+/// publishes it to clients and alongside the frame export
+pub struct CursorProcessor {
+    terminate: Arc<AtomicBool>,
+    // signaled by the os on updates, and on drop to stop the thread
+    event: Sendable<HANDLE>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl CursorProcessor {
+    /// Set up the hardware cursor of `monitor`, which has to have a swap chain, and start the
+    /// thread
+    pub fn start(monitor: IDDCX_MONITOR, id: Id, export: Arc<FrameExport>) -> anyhow::Result<Self> {
+        let config = config();
+
+        // unnamed, so monitors and driver instances don't share it
+        let event = unsafe { CreateEventW(None, false, false, PCWSTR::null())? };
+        // SAFETY: event handles may be used from any thread
+        let event = unsafe { Sendable::new(event) };
+
+        let caps = IDDCX_CURSOR_CAPS {
+            #[allow(clippy::cast_possible_truncation)]
+            Size: std::mem::size_of::<IDDCX_CURSOR_CAPS>() as u32,
+            AlphaCursorSupport: TRUE.0,
+            MaxX: config.max_width,
+            MaxY: config.max_height,
+            ColorXorCursorSupport: match config.xor {
+                XorCursor::None => IDDCX_XOR_CURSOR_SUPPORT::IDDCX_XOR_CURSOR_SUPPORT_NONE,
+                XorCursor::Full => IDDCX_XOR_CURSOR_SUPPORT::IDDCX_XOR_CURSOR_SUPPORT_FULL,
+                XorCursor::Emulation => {
+                    IDDCX_XOR_CURSOR_SUPPORT::IDDCX_XOR_CURSOR_SUPPORT_EMULATION
+                }
+            },
+        };
+
+        let setup = IDARG_IN_SETUP_HWCURSOR {
+            CursorInfo: caps,
+            hNewCursorDataAvailable: (*event).0,
+        };
+
+        let res = unsafe { IddCxMonitorSetupHardwareCursor(monitor, &setup) };
+        let res = match res {
+            Ok(res) if !res.is_error() => res,
+            res => {
+                _ = unsafe { CloseHandle(*event) };
+                anyhow::bail!("IddCxMonitorSetupHardwareCursor() failed: {res:?}");
+            }
+        };
+
+        if res.is_warning() {
+            warn!(monitor_id = id; "IddCxMonitorSetupHardwareCursor() warn: {res:?}");
+        }
+
+        let terminate = Arc::new(AtomicBool::new(false));
+
+        // the largest shape, in 32 bit pixels
+        let capacity = config.max_width * config.max_height * 4;
+
+        let thread = {
+            let terminate = terminate.clone();
+            // SAFETY: the monitor outlives the thread, which is joined when its context drops
+            let monitor = unsafe { Sendable::new(monitor) };
+            // SAFETY: see above, the event is closed after the thread is joined
+            let event = unsafe { Sendable::new(*event) };
+
+            thread::spawn(move || {
+                let mut exporter = CursorExporter::new(export, capacity);
+                Self::run_core(*monitor, id, *event, &terminate, &mut exporter, capacity);
+
+                // nothing draws the cursor until the next swap chain is assigned
+                publish(Cursor {
+                    id,
+                    visible: false,
+                    x: 0,
+                    y: 0,
+                    shape: None,
+                });
+            })
+        };
+
+        debug!(monitor_id = id; "Set up hardware cursor");
+
+        Ok(Self {
+            terminate,
+            event,
+            thread: Some(thread),
+        })
+    }
+
+    fn run_core(
+        monitor: IDDCX_MONITOR,
+        id: Id,
+        event: HANDLE,
+        terminate: &AtomicBool,
+        exporter: &mut CursorExporter,
+        capacity: u32,
+    ) {
+        let mut buffer = vec![0u8; capacity as usize];
+        let mut state = CursorState::default();
+        let mut last_shape_id = 0;
+
+        while !terminate.load(Ordering::Relaxed) {
+            let wait_result = unsafe { WaitForSingleObject(event, WAIT) };
+
+            if terminate.load(Ordering::Relaxed) {
+                break;
+            }
+
+            if wait_result != WAIT_OBJECT_0 {
+                // pick up the export being started or stopped
+                exporter.sync(&state);
+                continue;
+            }
+
+            let query = IDARG_IN_QUERY_HWCURSOR {
+                LastShapeId: last_shape_id,
+                ShapeBufferSizeInBytes: capacity,
+                pShapeBuffer: buffer.as_mut_ptr(),
+            };
+            let mut out = IDARG_OUT_QUERY_HWCURSOR::default();
+
+            let res = unsafe { IddCxMonitorQueryHardwareCursor(monitor, &query, &mut out) };
+            match res {
+                Ok(res) if !res.is_error() => (),
+                res => {
+                    error!(monitor_id = id; "IddCxMonitorQueryHardwareCursor() failed: {res:?}");
+                    continue;
+                }
+            }
+
+            state.visible = out.IsCursorVisible != 0;
+            state.x = out.X;
+            state.y = out.Y;
+
+            let mut shape = None;
+            if out.IsCursorShapeUpdated != 0 {
+                let info = out.CursorShapeInfo;
+                last_shape_id = info.ShapeId;
+
+                let size = info.Pitch as usize * info.Height as usize;
+
+                shape = match cursor_kind(info.CursorType) {
+                    Some(kind) if size <= buffer.len() => Some(CursorShape {
+                        id: info.ShapeId,
+                        kind,
+                        width: info.Width,
+                        height: info.Height,
+                        pitch: info.Pitch,
+                        x_hot: info.XHot,
+                        y_hot: info.YHot,
+                        data: buffer[..size].to_vec(),
+                    }),
+
+                    _ => {
+                        warn!(
+                            monitor_id = id;
+                            "Ignoring cursor shape {} of type {:?} and {size} bytes",
+                            info.ShapeId,
+                            info.CursorType
+                        );
+                        None
+                    }
+                };
+            }
+
+            if let Some(shape) = &shape {
+                state.shape = Some((exported_shape(shape), shape.data.clone()));
+            }
+
+            exporter.update(&state, shape.is_some());
+
+            publish(Cursor {
+                id,
+                visible: state.visible,
+                x: state.x,
+                y: state.y,
+                shape,
+            });
+        }
+    }
+}
+
+/// The kind of shapes of IddCx `kind`, `None` if it's unknown
+fn cursor_kind(kind: IDDCX_CURSOR_SHAPE_TYPE) -> Option<CursorKind> {
+    if kind == IDDCX_CURSOR_SHAPE_TYPE::IDDCX_CURSOR_SHAPE_TYPE_ALPHA {
+        Some(CursorKind::Alpha)
+    } else if kind == IDDCX_CURSOR_SHAPE_TYPE::IDDCX_CURSOR_SHAPE_TYPE_MASKED_COLOR {
+        Some(CursorKind::MaskedColor)
+    } else {
+        None
+    }
+}
+
+/// `shape` as it's written alongside the frame export, without its data
+fn exported_shape(shape: &CursorShape) -> frame_export::CursorShape {
+    let kind = match shape.kind {
+        CursorKind::Alpha => frame_export::CursorKind::Alpha,
+        CursorKind::MaskedColor => frame_export::CursorKind::MaskedColor,
+    };
+
+    frame_export::CursorShape {
+        id: shape.id,
+        kind,
+        width: shape.width,
+        height: shape.height,
+        pitch: shape.pitch,
+        x_hot: shape.x_hot,
+        y_hot: shape.y_hot,
+    }
+}
+
+impl Drop for CursorProcessor {
+    fn drop(&mut self) {
+        if let Some(handle) = self.thread.take() {
+            // send signal to end thread, and wake it up
+            self.terminate.store(true, Ordering::Relaxed);
+            _ = unsafe { SetEvent(*self.event) };
+
+            // wait until thread is finished
+            _ = handle.join();
+        }
+
+        _ = unsafe { CloseHandle(*self.event) };
+    }
+}
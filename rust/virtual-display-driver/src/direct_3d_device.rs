@@ -1,3 +1,4 @@
+use driver_ipc::render_adapter::{luid, RenderAdapter};
 use windows::{
     core::Error,
     Win32::{
@@ -10,7 +11,10 @@ use windows::{
                 D3D11_CREATE_DEVICE_PREVENT_ALTERING_LAYER_SETTINGS_FROM_REGISTRY,
                 D3D11_CREATE_DEVICE_SINGLETHREADED, D3D11_SDK_VERSION,
             },
-            Dxgi::{CreateDXGIFactory2, IDXGIAdapter1, IDXGIFactory5, DXGI_CREATE_FACTORY_FLAGS},
+            Dxgi::{
+                CreateDXGIFactory2, IDXGIAdapter1, IDXGIFactory5, DXGI_ADAPTER_FLAG_SOFTWARE,
+                DXGI_CREATE_FACTORY_FLAGS, DXGI_ERROR_NOT_FOUND,
+            },
         },
     },
 };
@@ -35,7 +39,7 @@ pub struct Direct3DDevice {
     _dxgi_factory: IDXGIFactory5,
     _adapter: IDXGIAdapter1,
     pub device: ID3D11Device,
-    _device_context: ID3D11DeviceContext,
+    pub device_context: ID3D11DeviceContext,
 }
 
 impl Direct3DDevice {
@@ -71,7 +75,44 @@ impl Direct3DDevice {
             _dxgi_factory: dxgi_factory,
             _adapter: adapter,
             device,
-            _device_context: device_context,
+            device_context,
         })
     }
 }
+
+/// The gpus which can render the monitors
+pub fn render_adapters() -> Result<Vec<RenderAdapter>, Direct3DError> {
+    let dxgi_factory =
+        unsafe { CreateDXGIFactory2::<IDXGIFactory5>(DXGI_CREATE_FACTORY_FLAGS(0))? };
+
+    let mut adapters = Vec::new();
+
+    for index in 0.. {
+        let adapter = match unsafe { dxgi_factory.EnumAdapters1(index) } {
+            Ok(adapter) => adapter,
+            Err(e) if e.code() == DXGI_ERROR_NOT_FOUND => break,
+            Err(e) => return Err(e.into()),
+        };
+
+        let desc = unsafe { adapter.GetDesc1()? };
+
+        // the software rasterizer can't render indirect displays
+        #[allow(clippy::cast_sign_loss)]
+        if desc.Flags & DXGI_ADAPTER_FLAG_SOFTWARE.0 as u32 != 0 {
+            continue;
+        }
+
+        let len = desc
+            .Description
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(desc.Description.len());
+
+        adapters.push(RenderAdapter {
+            luid: luid(desc.AdapterLuid.LowPart, desc.AdapterLuid.HighPart),
+            description: String::from_utf16_lossy(&desc.Description[..len]),
+        });
+    }
+
+    Ok(adapters)
+}
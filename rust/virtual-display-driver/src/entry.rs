@@ -1,22 +1,20 @@
-use std::time::{Duration, Instant};
-
-use driver_logger::DriverLogger;
+use driver_ipc::config::{pipe_name, Config, LogFile};
+use driver_logger::{default_log_dir, DriverLogger, FileConfig, Format};
 use log::{error, info, Level};
 use wdf_umdf::{
     IddCxDeviceInitConfig, IddCxDeviceInitialize, WdfDeviceCreate,
-    WdfDeviceInitSetPnpPowerEventCallbacks, WdfDeviceSetFailed, WdfDriverCreate,
+    WdfDeviceInitSetPnpPowerEventCallbacks, WdfDriverCreate,
 };
 use wdf_umdf_sys::{
-    IDD_CX_CLIENT_CONFIG, NTSTATUS, WDFDEVICE_INIT, WDFDRIVER__, WDFOBJECT,
-    WDF_DEVICE_FAILED_ACTION, WDF_DRIVER_CONFIG, WDF_OBJECT_ATTRIBUTES,
-    WDF_PNPPOWER_EVENT_CALLBACKS, _DRIVER_OBJECT, _UNICODE_STRING,
+    IDD_CX_CLIENT_CONFIG, NTSTATUS, WDFDEVICE_INIT, WDFDRIVER__, WDFOBJECT, WDF_DRIVER_CONFIG,
+    WDF_OBJECT_ATTRIBUTES, WDF_PNPPOWER_EVENT_CALLBACKS, _DRIVER_OBJECT, _UNICODE_STRING,
 };
 
 use crate::callbacks::{
     adapter_commit_modes, adapter_init_finished, assign_swap_chain, device_d0_entry,
     monitor_get_default_modes, monitor_query_modes, parse_monitor_description, unassign_swap_chain,
 };
-use crate::{context::DeviceContext, helpers::Sendable};
+use crate::{config, context::DeviceContext, ipc, state::STATE};
 
 //
 // Our driver's entry point
@@ -27,85 +25,34 @@ extern "C-unwind" fn DriverEntry(
     driver_object: *mut _DRIVER_OBJECT,
     registry_path: *mut _UNICODE_STRING,
 ) -> NTSTATUS {
-    // During system bootup, `RegisterEventSourceW` fails and causes the driver to not bootup
-    // Pretty unfortunate, therefore, we will run this on a thread until it succeeds and let the rest of
-    // the driver start. I know this is suboptimal considering it's our main code to catch panics.
-    //
-    // It always starts immediately when the computer is already booted up.
-    // If you have a better solution, please by all means open an issue report
-    let init_log = || {
-        let mut logger = DriverLogger::new(if cfg!(debug_assertions) {
-            Level::Debug
-        } else {
-            Level::Info
-        });
-
-        if cfg!(debug_assertions) {
-            logger.debug();
-        } else if logger.name("VirtualDisplayDriver").is_err() {
-            return NTSTATUS::STATUS_UNSUCCESSFUL;
-        }
-
-        let status = logger
-            .init()
-            .map_err(|_| NTSTATUS::STATUS_FAILED_DRIVER_ENTRY)
-            .into();
-
-        if status == NTSTATUS::STATUS_SUCCESS {
-            info!(
-                "Initialized Virtual Display Driver v{} @ {}",
-                env!("CARGO_PKG_VERSION"),
-                env!("VERGEN_GIT_SHA")
-            );
-        }
-
-        status
-    };
+    let mut logger = DriverLogger::new(if cfg!(debug_assertions) {
+        Level::Debug
+    } else {
+        Level::Info
+    });
+
+    // kept for bug reports and sent to subscribers, which clients can request over ipc
+    logger.ring_buffer(1000).subscribers(256);
+
+    if cfg!(debug_assertions) {
+        logger.debug();
+    } else {
+        // During system bootup, `RegisterEventSourceW` fails. The logger retries it while
+        // logging and reports the records up to then once it works, so startup diagnostics
+        // aren't lost
+        logger.name("VirtualDisplayDriver").early_buffer(1000);
+    }
 
-    let status = init_log();
-
-    if !status.is_success() {
-        // Okay, let's try another method then
-        let device = unsafe { Sendable::new(driver_object) };
-
-        std::thread::spawn(move || {
-            #[allow(clippy::redundant_locals)]
-            let device = device;
-            let time_waited = Instant::now();
-            // 5 minutes
-            let timeout_duration = Duration::from_secs(60 * 5);
-            // in ms
-            let sleep_for = 500;
-
-            loop {
-                let status = init_log();
-                std::thread::sleep(Duration::from_millis(sleep_for));
-
-                // if it succeeds, great. if it didn't conclude after 5 minutes
-                // Surely a users system is booted up before then?
-                let timedout = time_waited.elapsed() >= timeout_duration;
-                if status.is_success() || timedout {
-                    if timedout {
-                        // Service took too long to start. Unfortunately, there is no way to log this failure
-                        unsafe {
-                            _ = WdfDeviceSetFailed(
-                                device.cast(),
-                                WDF_DEVICE_FAILED_ACTION::WdfDeviceFailedNoRestart,
-                            );
-                        }
-                    } else {
-                        info!(
-                            "Service took {} seconds to start",
-                            time_waited.elapsed().as_secs()
-                        );
-                    }
-
-                    break;
-                }
-            }
-        });
+    if logger.init().is_err() {
+        return NTSTATUS::STATUS_FAILED_DRIVER_ENTRY;
     }
 
+    info!(
+        "Initialized Virtual Display Driver v{} @ {}",
+        env!("CARGO_PKG_VERSION"),
+        env!("VERGEN_GIT_SHA")
+    );
+
     // set the panic hook to capture and log panics
     crate::panic::set_hook();
 
@@ -177,11 +124,79 @@ extern "C-unwind" fn driver_add(
         return e.into();
     }
 
-    let context = DeviceContext::new(device);
+    let config = match config::load(device) {
+        Ok(config) => config.unwrap_or_default(),
+        Err(e) => {
+            error!("Failed to load startup config: {e}");
+            Config::default()
+        }
+    };
+
+    let Config {
+        monitors,
+        default_modes,
+        adapter,
+        instance,
+        render_adapter,
+        log_file,
+        eco,
+        cursor,
+    } = config;
+
+    let pipe_name = pipe_name(instance.as_deref());
+
+    // crash reports go next to the log files
+    let log_dir = log_file
+        .as_ref()
+        .and_then(|log_file| log_file.dir.clone())
+        .unwrap_or_else(default_log_dir);
+    crate::panic::set_report_path(crate::panic::report_path_in(&log_dir, &pipe_name));
+
+    if let Some(log_file) = log_file {
+        // named like the pipe, so instances don't write to the same files
+        if let Err(e) = driver_logger::set_log_file(Some(file_config(log_file, &pipe_name))) {
+            error!("Failed to open log file: {e}");
+        }
+    }
+
+    STATE.set_limits(adapter.limits());
+
+    if let Some(eco) = eco {
+        crate::swap_chain_processor::set_eco(eco);
+    }
+
+    crate::cursor::set_config(cursor);
+
+    // selected once the adapter is ready
+    if let Err(e) = STATE.set_render_adapter(render_adapter) {
+        error!("Failed to store render adapter preference: {e}");
+    }
+
+    // start the pipe server right away, so clients connecting early (e.g. at boot) don't have to retry
+    // commands and the monitors of the config are stored until the adapter is ready
+    crate::export::set_pipe_name(pipe_name.clone());
+    ipc::startup(pipe_name);
+    ipc::startup_config(monitors, default_modes);
+
+    let context = DeviceContext::new(device, adapter);
 
     unsafe { context.init(device as WDFOBJECT).into() }
 }
 
+fn file_config(log_file: LogFile, name: &str) -> FileConfig {
+    FileConfig {
+        dir: log_file.dir.unwrap_or_else(default_log_dir),
+        name: name.to_owned(),
+        max_size: log_file.max_size,
+        max_files: usize::try_from(log_file.max_files).unwrap_or(usize::MAX),
+        format: if log_file.json {
+            Format::Json
+        } else {
+            Format::Text
+        },
+    }
+}
+
 unsafe extern "C-unwind" fn event_cleanup(wdf_object: WDFOBJECT) {
     _ = unsafe { DeviceContext::drop(wdf_object) };
 }
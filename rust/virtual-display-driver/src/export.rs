@@ -0,0 +1,399 @@
+use std::{
+    collections::BTreeMap,
+    ffi::c_void,
+    slice,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, LazyLock, Mutex, OnceLock, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context as _};
+use driver_ipc::{Id, DEFAULT_PIPE_NAME};
+use frame_export::{
+    cursor_mapping_name, cursor_size, error::WriteError, mapping_name, ring_size, CursorWriter,
+    Format, FrameInfo, Mapping, Producer,
+};
+use log::{error, info};
+use wdf_umdf_sys::IDARG_OUT_RELEASEANDACQUIREBUFFER;
+use windows::{
+    core::Interface,
+    Win32::Graphics::{
+        Direct3D11::{
+            ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D, D3D11_CPU_ACCESS_READ,
+            D3D11_MAPPED_SUBRESOURCE, D3D11_MAP_READ, D3D11_TEXTURE2D_DESC, D3D11_USAGE_STAGING,
+        },
+        Dxgi::{
+            Common::{DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_SAMPLE_DESC},
+            IDXGIResource,
+        },
+    },
+};
+
+use crate::{cursor::CursorState, direct_3d_device::Direct3DDevice, state::STATE};
+
+// frames a ring holds, so consumers may fall behind a little
+const SLOTS: u32 = 3;
+
+// how long to wait before creating a ring again after it failed
+const RETRY: Duration = Duration::from_secs(1);
+
+// the exports of the monitors, by id
+static EXPORTS: LazyLock<Mutex<BTreeMap<Id, Arc<FrameExport>>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+// the shared memory is named after the pipe, so instances don't share it
+static PIPE_NAME: OnceLock<String> = OnceLock::new();
+
+/// Set the name of the pipe of this driver instance, see [`mapping_name`]
+pub fn set_pipe_name(pipe_name: String) {
+    _ = PIPE_NAME.set(pipe_name);
+}
+
+/// Whether the frames of a monitor are exported, shared by the swap chain processors of it
+#[derive(Debug)]
+pub struct FrameExport {
+    id: Id,
+    enabled: AtomicBool,
+}
+
+impl FrameExport {
+    /// The export of the monitor `id`, which is kept across monitors with that id
+    pub fn get(id: Id) -> Arc<Self> {
+        EXPORTS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(id)
+            .or_insert_with(|| {
+                Arc::new(Self {
+                    id,
+                    enabled: AtomicBool::new(false),
+                })
+            })
+            .clone()
+    }
+
+    /// Start or stop exporting the frames of the monitor `id`
+    pub fn set_enabled(id: Id, enabled: bool) {
+        Self::get(id).enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+}
+
+/// Copies the frames of a swap chain into the shared memory of its monitor, while the export is
+/// enabled
+///
+/// The surface is copied into a staging texture and read back on the thread of the swap chain
+/// processor, so exporting costs frame rate.
+pub struct Exporter {
+    export: Arc<FrameExport>,
+    device: ID3D11Device,
+    device_context: ID3D11DeviceContext,
+    // cpu readable copy of the surface, recreated when the surface size changes
+    staging: Option<(ID3D11Texture2D, D3D11_TEXTURE2D_DESC)>,
+    producer: Option<Producer<Mapping>>,
+    // when to create the ring again after it failed
+    retry_at: Option<Instant>,
+    // so the same error isn't logged every frame
+    last_error: Option<String>,
+}
+
+impl Exporter {
+    pub fn new(export: Arc<FrameExport>, device: &Direct3DDevice) -> Self {
+        Self {
+            export,
+            device: device.device.clone(),
+            device_context: device.device_context.clone(),
+            staging: None,
+            producer: None,
+            retry_at: None,
+            last_error: None,
+        }
+    }
+
+    /// Whether the frames are exported
+    pub fn exporting(&self) -> bool {
+        self.export.enabled()
+    }
+
+    /// Drop the ring once the export is disabled, which tells consumers that it stopped
+    pub fn stop_if_disabled(&mut self) {
+        if self.export.enabled() {
+            return;
+        }
+
+        if self.producer.take().is_some() {
+            info!(monitor_id = self.export.id; "Stopped exporting frames");
+        }
+
+        self.staging = None;
+        self.retry_at = None;
+        self.last_error = None;
+    }
+
+    /// Copy the surface of an acquired buffer into the ring, if the export is enabled
+    pub fn export(&mut self, buffer: &IDARG_OUT_RELEASEANDACQUIREBUFFER) {
+        if !self.export.enabled() {
+            return;
+        }
+
+        match self.try_export(buffer) {
+            Ok(()) => self.last_error = None,
+            Err(e) => {
+                let message = format!("{e:?}");
+                if self.last_error.as_ref() != Some(&message) {
+                    error!(monitor_id = self.export.id; "Failed to export frame: {message}");
+                    self.last_error = Some(message);
+                }
+            }
+        }
+    }
+
+    fn try_export(&mut self, buffer: &IDARG_OUT_RELEASEANDACQUIREBUFFER) -> anyhow::Result<()> {
+        if self.producer.is_none() && self.retry_at.is_some_and(|at| Instant::now() < at) {
+            return Ok(());
+        }
+
+        let surface = buffer.MetaData.pSurface.cast::<c_void>();
+        // SAFETY: the surface stays valid until the next buffer is acquired
+        let surface = unsafe { IDXGIResource::from_raw_borrowed(&surface) }
+            .context("Buffer has no surface")?;
+        let texture = surface.cast::<ID3D11Texture2D>()?;
+
+        let mut desc = D3D11_TEXTURE2D_DESC::default();
+        unsafe { texture.GetDesc(&mut desc) };
+
+        if desc.Format != DXGI_FORMAT_B8G8R8A8_UNORM {
+            bail!("Surface format {:?} is not supported", desc.Format);
+        }
+
+        let mut info = FrameInfo::new(Format::Bgra8, desc.Width, desc.Height);
+        info.present_qpc = buffer.MetaData.PresentDisplayQPCTime;
+
+        if self.producer.is_none() {
+            match self.create_ring(&info) {
+                Ok(producer) => self.producer = Some(producer),
+                // fails while consumers still have the previous ring open
+                Err(e) => {
+                    self.retry_at = Some(Instant::now() + RETRY);
+                    return Err(e);
+                }
+            }
+        }
+
+        let staging = self.staging(&desc)?;
+        unsafe { self.device_context.CopyResource(&staging, &texture) };
+
+        let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+        unsafe {
+            self.device_context
+                .Map(&staging, 0, D3D11_MAP_READ, 0, Some(&mut mapped))?;
+        }
+
+        let producer = self.producer.as_mut().expect("created above");
+        let result = producer.write(&info, |rows| {
+            let data = mapped.pData.cast::<u8>();
+
+            for y in 0..info.height {
+                // SAFETY: the mapped texture has `height` rows which are `RowPitch` apart
+                let row = unsafe { data.add(y as usize * mapped.RowPitch as usize) };
+                // SAFETY: see above, a row has at least `row_size` bytes
+                let row = unsafe { slice::from_raw_parts(row, info.row_size()) };
+
+                rows.write(y, row);
+            }
+        });
+
+        unsafe { self.device_context.Unmap(&staging, 0) };
+
+        match result {
+            Ok(_) => Ok(()),
+            // the monitor got a larger mode since the ring was created
+            Err(e @ WriteError::TooLarge { .. }) => {
+                self.producer = None;
+                Err(e.into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Create the ring, with slots for the largest mode of the monitor
+    fn create_ring(&self, info: &FrameInfo) -> anyhow::Result<Producer<Mapping>> {
+        // with a timeout, the state thread might wait for this swap chain processor to stop
+        let largest = STATE
+            .monitors_within(Duration::from_millis(100))
+            .and_then(|monitors| {
+                monitors
+                    .into_iter()
+                    .find(|monitor| monitor.id == self.export.id)
+            })
+            .and_then(|monitor| {
+                monitor
+                    .modes
+                    .iter()
+                    .map(|mode| FrameInfo::new(Format::Bgra8, mode.width, mode.height).size())
+                    .max()
+            });
+
+        let slot_size = largest.unwrap_or(0).max(info.size());
+        let size = ring_size(SLOTS, slot_size).context("Frames are too large")?;
+
+        let pipe_name = PIPE_NAME.get().map_or(DEFAULT_PIPE_NAME, String::as_str);
+        let name = mapping_name(pipe_name, self.export.id);
+
+        let mapping =
+            Mapping::create(&name, size).with_context(|| format!("Failed to create {name}"))?;
+        let producer = Producer::create(mapping, SLOTS, slot_size)?;
+
+        info!(monitor_id = self.export.id; "Exporting frames into {name}");
+
+        Ok(producer)
+    }
+
+    /// The staging texture for surfaces like `desc`
+    fn staging(&mut self, desc: &D3D11_TEXTURE2D_DESC) -> anyhow::Result<ID3D11Texture2D> {
+        if let Some((texture, staging)) = &self.staging {
+            if (staging.Width, staging.Height, staging.Format)
+                == (desc.Width, desc.Height, desc.Format)
+            {
+                return Ok(texture.clone());
+            }
+        }
+
+        let staging = D3D11_TEXTURE2D_DESC {
+            Width: desc.Width,
+            Height: desc.Height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: desc.Format,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Usage: D3D11_USAGE_STAGING,
+            BindFlags: 0,
+            #[allow(clippy::cast_sign_loss)]
+            CPUAccessFlags: D3D11_CPU_ACCESS_READ.0 as u32,
+            MiscFlags: 0,
+        };
+
+        let mut texture = None;
+        unsafe {
+            self.device
+                .CreateTexture2D(&staging, None, Some(&mut texture))?;
+        }
+        let texture = texture.context("No staging texture was created")?;
+
+        self.staging = Some((texture.clone(), staging));
+
+        Ok(texture)
+    }
+}
+
+/// Writes the hardware cursor of a monitor into shared memory, while its frames are exported
+pub struct CursorExporter {
+    export: Arc<FrameExport>,
+    // bytes of the largest shape
+    capacity: u32,
+    writer: Option<CursorWriter<Mapping>>,
+    // when to create the shared memory again after it failed
+    retry_at: Option<Instant>,
+    // so the same error isn't logged every update
+    last_error: Option<String>,
+}
+
+impl CursorExporter {
+    pub fn new(export: Arc<FrameExport>, capacity: u32) -> Self {
+        Self {
+            export,
+            capacity,
+            writer: None,
+            retry_at: None,
+            last_error: None,
+        }
+    }
+
+    /// Write an update of the cursor if the export is enabled, with its shape if it changed
+    pub fn update(&mut self, cursor: &CursorState, new_shape: bool) {
+        self.export(cursor, Some(new_shape));
+    }
+
+    /// Start or stop the export if it was enabled or disabled since the last update
+    pub fn sync(&mut self, cursor: &CursorState) {
+        self.export(cursor, None);
+    }
+
+    /// Write the cursor, and its shape if `new_shape` or the export just started. Without an
+    /// update, it's only written if the export just started.
+    fn export(&mut self, cursor: &CursorState, new_shape: Option<bool>) {
+        if !self.export.enabled() {
+            if self.writer.take().is_some() {
+                info!(monitor_id = self.export.id; "Stopped exporting cursor");
+            }
+
+            self.retry_at = None;
+            self.last_error = None;
+            return;
+        }
+
+        match self.try_export(cursor, new_shape) {
+            Ok(()) => self.last_error = None,
+            Err(e) => {
+                let message = format!("{e:?}");
+                if self.last_error.as_ref() != Some(&message) {
+                    error!(monitor_id = self.export.id; "Failed to export cursor: {message}");
+                    self.last_error = Some(message);
+                }
+            }
+        }
+    }
+
+    fn try_export(&mut self, cursor: &CursorState, new_shape: Option<bool>) -> anyhow::Result<()> {
+        let created = self.writer.is_none();
+        if created {
+            if self.retry_at.is_some_and(|at| Instant::now() < at) {
+                return Ok(());
+            }
+
+            match self.create() {
+                Ok(writer) => self.writer = Some(writer),
+                Err(e) => {
+                    self.retry_at = Some(Instant::now() + RETRY);
+                    return Err(e);
+                }
+            }
+        }
+
+        if !created && new_shape.is_none() {
+            return Ok(());
+        }
+
+        let shape = cursor
+            .shape
+            .as_ref()
+            .filter(|_| created || new_shape == Some(true))
+            .map(|(shape, data)| (shape, data.as_slice()));
+
+        let writer = self.writer.as_mut().expect("created above");
+        writer.write(cursor.visible, cursor.x, cursor.y, shape)?;
+
+        Ok(())
+    }
+
+    fn create(&self) -> anyhow::Result<CursorWriter<Mapping>> {
+        let pipe_name = PIPE_NAME.get().map_or(DEFAULT_PIPE_NAME, String::as_str);
+        let name = cursor_mapping_name(pipe_name, self.export.id);
+
+        let mapping = Mapping::create(&name, cursor_size(self.capacity))
+            .with_context(|| format!("Failed to create {name}"))?;
+        let writer = CursorWriter::create(mapping, self.capacity)?;
+
+        info!(monitor_id = self.export.id; "Exporting cursor into {name}");
+
+        Ok(writer)
+    }
+}
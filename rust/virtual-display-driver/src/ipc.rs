@@ -1,23 +1,36 @@
 use std::{
     mem::size_of,
     ptr::{addr_of_mut, NonNull},
-    sync::{LazyLock, Mutex, OnceLock},
+    sync::{
+        mpsc::{self, Receiver},
+        LazyLock, OnceLock,
+    },
     thread,
 };
 
 use driver_ipc::{
-    Dimen, DriverCommand, EventCommand, Mode, Monitor, RefreshRate, ReplyCommand, RequestCommand,
-    ServerCommand,
+    config,
+    defaults::DefaultModes,
+    render_adapter::{luid_parts, Preference},
+    timing::Timing,
+    Cursor, DriverCommand, DriverError, EventCommand, Id, LogEntry, Mode, Monitor, ReplyCommand,
+    RequestCommand, ServerCommand,
 };
-use log::{error, warn};
+use driver_logger::{unix_millis, Filter, Record};
+use log::{debug, error, info, warn, LevelFilter};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt as _},
     net::windows::named_pipe::{NamedPipeServer, ServerOptions},
     sync::broadcast::{self, error::RecvError, Sender},
     task,
 };
-use wdf_umdf::IddCxMonitorDeparture;
-use wdf_umdf_sys::{IDDCX_ADAPTER__, IDDCX_MONITOR__};
+use wdf_umdf::{
+    IddCxAdapterSetRenderAdapter, IddCxError, IddCxMonitorDeparture, IddCxMonitorUpdateModes,
+};
+use wdf_umdf_sys::{
+    IDARG_IN_ADAPTERSETRENDERADAPTER, IDARG_IN_UPDATEMODES, IDDCX_ADAPTER__, IDDCX_UPDATE_REASON,
+    LUID,
+};
 use windows::Win32::{
     Security::{
         InitializeSecurityDescriptor, SetSecurityDescriptorDacl, PSECURITY_DESCRIPTOR,
@@ -26,34 +39,66 @@ use windows::Win32::{
     System::SystemServices::SECURITY_DESCRIPTOR_REVISION1,
 };
 
-use crate::context::DeviceContext;
+use crate::{
+    callbacks::target_mode,
+    context::DeviceContext,
+    cursor, direct_3d_device,
+    export::FrameExport,
+    panic,
+    state::{Change, MonitorObject, Step, STATE},
+    swap_chain_processor,
+};
 
 pub static ADAPTER: OnceLock<AdapterObject> = OnceLock::new();
-pub static MONITOR_MODES: LazyLock<Mutex<Vec<MonitorObject>>> =
-    LazyLock::new(|| Mutex::new(Vec::new()));
+
+// events for all connected clients, along with the id of the client which caused them (0 for the driver itself)
+static EVENTS: LazyLock<Sender<(usize, EventCommand)>> = LazyLock::new(|| broadcast::channel(16).0);
+
+// the executor thread makes and carries out all plans one after another, so plans are always based on
+// the os calls of earlier ones, e.g. a remove planned between another client's arrival and its
+// create_monitor would never depart it
+static JOBS: LazyLock<mpsc::Sender<Job>> = LazyLock::new(|| {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || run_jobs(&rx));
+    tx
+});
+
+/// Work of the executor thread
+enum Job {
+    // apply a change, answering with the new monitors
+    Change(Change, mpsc::Sender<Vec<Monitor>>),
+    // arrive the monitors stored until the adapter was ready
+    AdapterReady,
+}
 
 #[derive(Debug)]
 pub struct AdapterObject(pub NonNull<IDDCX_ADAPTER__>);
 unsafe impl Sync for AdapterObject {}
 unsafe impl Send for AdapterObject {}
 
-#[derive(Debug)]
-pub struct MonitorObject {
-    pub object: Option<NonNull<IDDCX_MONITOR__>>,
-    pub data: Monitor,
-}
-unsafe impl Sync for MonitorObject {}
-unsafe impl Send for MonitorObject {}
-
 const BUFFER_SIZE: u32 = 4096;
 // EOT
 const EOF: char = '\x04';
 
+/// Log records a client subscribed to
+struct LogSubscription {
+    rx: broadcast::Receiver<Record>,
+    min_level: LevelFilter,
+}
+
+/// Hardware cursors a client subscribed to
+struct CursorSubscription {
+    rx: broadcast::Receiver<Cursor>,
+    // all monitors if `None`
+    id: Option<Id>,
+}
+
 // message processor
 async fn process_message(
     id: usize,
     server: &mut NamedPipeServer,
-    tx: &Sender<(usize, Vec<Monitor>)>,
+    logs: &mut Option<LogSubscription>,
+    cursors: &mut Option<CursorSubscription>,
     buf: &[u8],
     iter: impl Iterator<Item = usize>,
 ) -> Result<(), ()> {
@@ -75,21 +120,99 @@ async fn process_message(
             // driver commands
             ServerCommand::Driver(cmd) => match cmd {
                 DriverCommand::Notify(monitors) => {
-                    notify(monitors.clone());
-                    _ = tx.send((id, monitors));
+                    let limits = match STATE.limits() {
+                        Ok(limits) => limits,
+                        Err(e) => {
+                            error!(client_id = id; "Command::Driver - {e}");
+                            continue;
+                        }
+                    };
+
+                    let valid = config::validate(&monitors)
+                        .and_then(|()| config::check_limits(&monitors, limits));
+                    if let Err(e) = valid {
+                        warn!(client_id = id; "notify(): {e}; update aborted");
+                        send_reply(server, &ReplyCommand::Error(e)).await?;
+                        continue;
+                    }
+
+                    if let Some(monitors) = notify(monitors) {
+                        _ = EVENTS.send((id, EventCommand::Changed(monitors)));
+                    }
                 }
 
                 DriverCommand::Remove(ids) => {
-                    remove(&ids);
-
-                    let lock = MONITOR_MODES.lock().unwrap();
-                    let monitors = lock.iter().map(|m| m.data.clone()).collect();
-                    _ = tx.send((id, monitors));
+                    if let Some(monitors) = apply(Change::Remove(ids)) {
+                        _ = EVENTS.send((id, EventCommand::Changed(monitors)));
+                    }
                 }
 
                 DriverCommand::RemoveAll => {
-                    remove_all();
-                    _ = tx.send((id, Vec::new()));
+                    if let Some(monitors) = apply(Change::RemoveAll) {
+                        _ = EVENTS.send((id, EventCommand::Changed(monitors)));
+                    }
+                }
+
+                DriverCommand::SetDefaultModes(defaults) => {
+                    if let Err(e) = defaults.validate() {
+                        let e = DriverError::InvalidDefaultMode(e);
+                        warn!(client_id = id; "set_default_modes(): {e}; update aborted");
+                        send_reply(server, &ReplyCommand::Error(e)).await?;
+                        continue;
+                    }
+
+                    STATE.set_default_modes(defaults);
+                }
+
+                DriverCommand::SetLogLevel {
+                    level,
+                    module_filter,
+                } => {
+                    info!(
+                        client_id = id;
+                        "Setting log level to {level}{}",
+                        module_filter
+                            .as_deref()
+                            .map(|module| format!(" for {module}"))
+                            .unwrap_or_default()
+                    );
+
+                    // a module keeps the other modules at the current default level
+                    let filter = match module_filter {
+                        Some(module) => Filter {
+                            module: Some((module, level.into())),
+                            ..driver_logger::filter()
+                        },
+                        None => Filter::new(level.into()),
+                    };
+
+                    driver_logger::set_filter(filter);
+                }
+
+                DriverCommand::SetFrameExport {
+                    id: monitor_id,
+                    enabled,
+                } => {
+                    info!(
+                        client_id = id, monitor_id;
+                        "{} frame export",
+                        if enabled { "Starting" } else { "Stopping" }
+                    );
+
+                    FrameExport::set_enabled(monitor_id, enabled);
+                }
+
+                DriverCommand::SetRenderAdapter(preference) => {
+                    match STATE.set_render_adapter(preference.clone()) {
+                        // otherwise it's selected once the adapter is ready
+                        Ok(true) => {
+                            if let Some(preference) = preference {
+                                select_render_adapter(&preference);
+                            }
+                        }
+                        Ok(false) => (),
+                        Err(e) => error!(client_id = id; "Command::Driver - {e}"),
+                    }
                 }
 
                 _ => (),
@@ -97,27 +220,140 @@ async fn process_message(
 
             // request commands
             ServerCommand::Request(RequestCommand::State) => {
-                let mut data = {
-                    let lock = MONITOR_MODES.lock().unwrap();
-                    let monitors = lock.iter().map(|m| m.data.clone()).collect();
-                    let command = ReplyCommand::State(monitors);
-
-                    let Ok(serialized) = serde_json::to_string(&command) else {
-                        error!("Command::Request - failed to serialize reply");
-                        break;
-                    };
+                let monitors = match STATE.monitors() {
+                    Ok(monitors) => monitors,
+                    Err(e) => {
+                        error!(client_id = id; "Command::Request - {e}");
+                        continue;
+                    }
+                };
+
+                send_reply(server, &ReplyCommand::State(monitors)).await?;
+            }
+
+            ServerCommand::Request(RequestCommand::Limits) => {
+                let limits = match STATE.limits() {
+                    Ok(limits) => limits,
+                    Err(e) => {
+                        error!(client_id = id; "Command::Request - {e}");
+                        continue;
+                    }
+                };
+
+                send_reply(server, &ReplyCommand::Limits(limits)).await?;
+            }
 
-                    serialized
+            ServerCommand::Request(RequestCommand::DefaultModes) => {
+                let defaults = match STATE.default_modes() {
+                    Ok(defaults) => defaults,
+                    Err(e) => {
+                        error!(client_id = id; "Command::Request - {e}");
+                        continue;
+                    }
                 };
 
-                data.push(EOF);
+                send_reply(server, &ReplyCommand::DefaultModes(defaults)).await?;
+            }
 
-                if server.write_all(data.as_bytes()).await.is_err() {
-                    // a server error means we should completely stop trying
-                    return Err(());
+            ServerCommand::Request(RequestCommand::ListRenderAdapters) => {
+                let adapters = match direct_3d_device::render_adapters() {
+                    Ok(adapters) => adapters,
+                    Err(e) => {
+                        error!(
+                            client_id = id;
+                            "Command::Request - failed to list render adapters: {e}"
+                        );
+                        continue;
+                    }
+                };
+
+                send_reply(server, &ReplyCommand::RenderAdapters(adapters)).await?;
+            }
+
+            ServerCommand::Request(RequestCommand::RecentLogs { since, min_level }) => {
+                let entries = driver_logger::recent_records(since, min_level.into())
+                    .into_iter()
+                    .map(log_entry)
+                    .collect();
+
+                send_reply(server, &ReplyCommand::RecentLogs(entries)).await?;
+            }
+
+            ServerCommand::Request(RequestCommand::SubscribeLogs { min_level }) => {
+                let Some(rx) = driver_logger::subscribe() else {
+                    warn!(
+                        client_id = id;
+                        "Command::Request - the logger doesn't send records to subscribers"
+                    );
+                    continue;
+                };
+
+                *logs = Some(LogSubscription {
+                    rx,
+                    min_level: min_level.into(),
+                });
+            }
+
+            ServerCommand::Request(RequestCommand::SubscribeCursor { id: monitor_id }) => {
+                // subscribe before taking the current cursors, so no update is missed
+                *cursors = Some(CursorSubscription {
+                    rx: cursor::subscribe(),
+                    id: monitor_id,
+                });
+
+                for cursor in cursor::latest(monitor_id) {
+                    send_event(server, &EventCommand::Cursor(cursor)).await?;
                 }
             }
 
+            ServerCommand::Request(RequestCommand::CrashReport) => {
+                let report = match panic::last_report() {
+                    Ok(report) => report,
+                    Err(e) => {
+                        error!(
+                            client_id = id;
+                            "Command::Request - failed to read crash report: {e}"
+                        );
+                        continue;
+                    }
+                };
+
+                send_reply(server, &ReplyCommand::CrashReport(report)).await?;
+            }
+
+            ServerCommand::Request(RequestCommand::FrameStats) => {
+                let monitors = match STATE.monitors() {
+                    Ok(monitors) => monitors,
+                    Err(e) => {
+                        error!(client_id = id; "Command::Request - {e}");
+                        continue;
+                    }
+                };
+
+                // disabled monitors departed, they aren't rendered to
+                let ids = monitors
+                    .iter()
+                    .filter(|monitor| monitor.enabled)
+                    .map(|monitor| monitor.id)
+                    .collect::<Vec<_>>();
+
+                let stats = swap_chain_processor::frame_stats(&ids);
+
+                send_reply(server, &ReplyCommand::FrameStats(stats)).await?;
+            }
+
+            ServerCommand::Request(RequestCommand::GetEdid(monitor_id)) => {
+                let edid = match STATE.edid(monitor_id) {
+                    Ok(edid) => edid,
+                    Err(e) => {
+                        error!(client_id = id, monitor_id; "Command::Request - {e}");
+                        continue;
+                    }
+                };
+
+                send_reply(server, &ReplyCommand::Edid(monitor_id, edid)).await?;
+            }
+
             // Everything else is an invalid command
             _ => (),
         }
@@ -126,8 +362,71 @@ async fn process_message(
     Ok(())
 }
 
+pub fn log_entry(record: Record) -> LogEntry {
+    LogEntry {
+        seq: record.seq,
+        timestamp: unix_millis(record.time),
+        level: record.level.to_level_filter().into(),
+        target: record.target,
+        message: record.message,
+        fields: record.fields,
+    }
+}
+
+/// The next record of a subscription, never resolves without one
+async fn next_log(logs: &mut Option<LogSubscription>) -> Result<Record, RecvError> {
+    let Some(logs) = logs else {
+        return std::future::pending().await;
+    };
+
+    loop {
+        match logs.rx.recv().await {
+            Ok(record) if record.level > logs.min_level => continue,
+            result => break result,
+        }
+    }
+}
+
+/// The next cursor update of a subscription, never resolves without one
+async fn next_cursor(cursors: &mut Option<CursorSubscription>) -> Result<Cursor, RecvError> {
+    let Some(cursors) = cursors else {
+        return std::future::pending().await;
+    };
+
+    loop {
+        match cursors.rx.recv().await {
+            Ok(cursor) if cursors.id.is_some_and(|id| id != cursor.id) => continue,
+            result => break result,
+        }
+    }
+}
+
+async fn send_event(server: &mut NamedPipeServer, command: &EventCommand) -> Result<(), ()> {
+    let Ok(mut data) = serde_json::to_string(command) else {
+        error!("Command::Request - failed to serialize event");
+        return Ok(());
+    };
+
+    data.push(EOF);
+
+    // a server error means we should completely stop trying
+    server.write_all(data.as_bytes()).await.map_err(|_| ())
+}
+
+async fn send_reply(server: &mut NamedPipeServer, command: &ReplyCommand) -> Result<(), ()> {
+    let Ok(mut data) = serde_json::to_string(command) else {
+        error!("Command::Request - failed to serialize reply");
+        return Ok(());
+    };
+
+    data.push(EOF);
+
+    // a server error means we should completely stop trying
+    server.write_all(data.as_bytes()).await.map_err(|_| ())
+}
+
 #[allow(clippy::too_many_lines)]
-pub fn startup() {
+pub fn startup(pipe_name: String) {
     thread::spawn(move || {
         // These security attributes will allow anyone access, so local account does not need admin privileges to use it
 
@@ -160,8 +459,6 @@ pub fn startup() {
 
         // async time!
         let pipe_server = async {
-            let (tx, _rx) = broadcast::channel(1);
-
             let mut id = 0usize;
 
             loop {
@@ -174,7 +471,7 @@ pub fn startup() {
                         .out_buffer_size(BUFFER_SIZE)
                         // default is unlimited instances
                         .create_with_security_attributes_raw(
-                            r"\\.\pipe\virtualdisplaydriver",
+                            format!(r"\\.\pipe\{pipe_name}"),
                             std::ptr::from_mut::<SECURITY_ATTRIBUTES>(&mut sa).cast(),
                         )
                         .unwrap()
@@ -185,11 +482,13 @@ pub fn startup() {
                 }
 
                 id += 1;
+                debug!(client_id = id; "Client connected");
 
                 let mut msg_buf: Vec<u8> = Vec::with_capacity(BUFFER_SIZE as usize);
                 let mut buf = vec![0; BUFFER_SIZE as usize];
-                let tx = tx.clone();
-                let mut rx = tx.subscribe();
+                let mut rx = EVENTS.subscribe();
+                let mut logs = None;
+                let mut cursors = None;
 
                 task::spawn(async move {
                     loop {
@@ -212,7 +511,7 @@ pub fn startup() {
                                     }
                                 });
 
-                                if process_message(id, &mut server, &tx, &msg_buf, eof_iter.clone()).await.is_err() {
+                                if process_message(id, &mut server, &mut logs, &mut cursors, &msg_buf, eof_iter.clone()).await.is_err() {
                                     break;
                                 }
 
@@ -230,7 +529,7 @@ pub fn startup() {
                                     // ignore if this value was sent for the current client (current client doesn't need notification)
                                     Ok((client_id, _)) if client_id == id => continue,
 
-                                    Ok((_, data)) => EventCommand::Changed(data),
+                                    Ok((_, command)) => command,
 
                                     Err(RecvError::Lagged(_)) => continue,
 
@@ -239,7 +538,10 @@ pub fn startup() {
                                 };
 
                                 let Ok(mut serialized) = serde_json::to_string(&command) else {
-                                    error!("Command::Request - failed to serialize reply");
+                                    error!(
+                                        client_id = id;
+                                        "Command::Request - failed to serialize reply"
+                                    );
                                     break;
                                 };
 
@@ -249,8 +551,75 @@ pub fn startup() {
                                     break;
                                 }
                             }
+
+                            val = next_log(&mut logs) => {
+                                let record = match val {
+                                    Ok(record) => record,
+
+                                    // the client was too slow, those records are gone
+                                    Err(RecvError::Lagged(_)) => continue,
+
+                                    // the logger is gone, there won't be any more records
+                                    Err(RecvError::Closed) => {
+                                        logs = None;
+                                        continue;
+                                    }
+                                };
+
+                                let command = EventCommand::Log {
+                                    level: record.level.to_level_filter().into(),
+                                    target: record.target,
+                                    message: record.message,
+                                    timestamp: unix_millis(record.time),
+                                    fields: record.fields,
+                                };
+
+                                let Ok(mut serialized) = serde_json::to_string(&command) else {
+                                    error!(
+                                        client_id = id;
+                                        "Command::Request - failed to serialize log event"
+                                    );
+                                    break;
+                                };
+
+                                serialized.push(EOF);
+
+                                if server.write_all(serialized.as_bytes()).await.is_err() {
+                                    break;
+                                }
+                            }
+
+                            val = next_cursor(&mut cursors) => {
+                                let updates = match val {
+                                    Ok(cursor) => vec![cursor],
+
+                                    // the client was too slow and might have missed a shape, send the current cursors again
+                                    Err(RecvError::Lagged(_)) => {
+                                        cursor::latest(cursors.as_ref().and_then(|cursors| cursors.id))
+                                    }
+
+                                    Err(RecvError::Closed) => {
+                                        cursors = None;
+                                        continue;
+                                    }
+                                };
+
+                                let mut sent = true;
+                                for cursor in updates {
+                                    if send_event(&mut server, &EventCommand::Cursor(cursor)).await.is_err() {
+                                        sent = false;
+                                        break;
+                                    }
+                                }
+
+                                if !sent {
+                                    break;
+                                }
+                            }
                         }
                     }
+
+                    debug!(client_id = id; "Client disconnected");
                 });
             }
         };
@@ -263,212 +632,229 @@ pub fn startup() {
     });
 }
 
-/// used to check the validity of a Vec<Monitor>
-/// the validity invariants are:
-/// 1. unique monitor ids
-/// 2. unique monitor modes (width+height must be unique per array element)
-/// 3. unique refresh rates per monitor mode
-fn has_duplicates(monitors: &[Monitor]) -> bool {
-    let mut monitor_iter = monitors.iter();
-    while let Some(monitor) = monitor_iter.next() {
-        let duplicate_id = monitor_iter.clone().any(|b| monitor.id == b.id);
-        if duplicate_id {
-            warn!("Found duplicate monitor id {}", monitor.id);
-            return true;
-        }
+/// Notifies driver of new system monitor state
+///
+/// Adds, updates, or removes monitors as needed, returning the new state
+///
+/// Only detaches/reattaches if required
+/// e.g. only a monitor name update would not detach/arrive a monitor, and mode changes of an enabled monitor
+/// are applied in place if the framework and the monitor description allow it (see [`driver_ipc::reconcile::plan`])
+///
+/// The monitors must be [validated](config::validate) already
+fn notify(monitors: Vec<Monitor>) -> Option<Vec<Monitor>> {
+    apply(Change::Notify(monitors))
+}
 
-        let mut mode_iter = monitor.modes.iter();
-        while let Some(mode) = mode_iter.next() {
-            let duplicate_mode = mode_iter
-                .clone()
-                .any(|m| mode.height == m.height && mode.width == m.width);
-            if duplicate_mode {
-                warn!(
-                    "Found duplicate mode {}x{} on monitor {}",
-                    mode.width, mode.height, monitor.id
-                );
-                return true;
-            }
+/// Applies a change to the monitor state and carries out the os calls it needs, returning the new state
+fn apply(change: Change) -> Option<Vec<Monitor>> {
+    let (tx, rx) = mpsc::channel();
 
-            let mut refresh_iter = mode.refresh_rates.iter().copied();
-            while let Some(rr) = refresh_iter.next() {
-                let duplicate_rr = refresh_iter.clone().any(|r| rr == r);
-                if duplicate_rr {
-                    warn!(
-                        "Found duplicate refresh rate {rr} on mode {}x{} for monitor {}",
-                        mode.width, mode.height, monitor.id
-                    );
-                    return true;
+    if JOBS.send(Job::Change(change, tx)).is_err() {
+        error!("Failed to change monitors: executor thread is gone");
+        return None;
+    }
+
+    // the executor only drops `tx` without answering if the change failed, which it logged
+    rx.recv().ok()
+}
+
+fn run_jobs(rx: &Receiver<Job>) {
+    for job in rx {
+        match job {
+            Job::Change(change, tx) => match STATE.change(change) {
+                Ok(plan) => {
+                    execute(plan.steps);
+                    _ = tx.send(plan.monitors);
+                }
+                Err(e) => error!("Failed to change monitors: {e}"),
+            },
+
+            Job::AdapterReady => {
+                match STATE.adapter_ready() {
+                    Ok(plan) => execute(plan.steps),
+                    Err(e) => error!("Failed to arrive stored monitors: {e}"),
                 }
+
+                _ = EVENTS.send((0, EventCommand::AdapterReady));
             }
         }
     }
-
-    false
 }
 
-/// Notifies driver of new system monitor state
+/// Stores the monitors and default modes of the startup config, to be arrived once the adapter is ready
 ///
-/// Adds, updates, or removes monitors as needed
-///
-/// Note that updated monitors causes a detach, update, and reattach. (Required for windows to see the changes)
-///
-/// Only detaches/reattaches if required
-/// e.g. only a monitor name update would not detach/arrive a monitor
-fn notify(monitors: Vec<Monitor>) {
-    // Duplicated id's will not cause any issue, however duplicated resolutions/refresh rates are possible
-    // They should all be unique anyways. So warn + noop if the sender sent incorrect data
-    if has_duplicates(&monitors) {
-        warn!("notify(): Duplicate data was detected; update aborted");
+/// Clients which connected early win, the config is skipped if they already sent monitors
+pub fn startup_config(monitors: Vec<Monitor>, default_modes: Option<DefaultModes>) {
+    match STATE.monitors() {
+        Ok(current) if current.is_empty() => (),
+
+        Ok(_) => {
+            info!("Skipping startup config, a client already sent monitors");
+            return;
+        }
+
+        Err(e) => {
+            error!("Failed to apply startup config: {e}");
+            return;
+        }
+    }
+
+    if let Some(defaults) = default_modes {
+        STATE.set_default_modes(defaults);
+    }
+
+    if monitors.is_empty() {
         return;
     }
 
-    let adapter = ADAPTER.get().unwrap().0.as_ptr();
+    if let Some(monitors) = apply(Change::Notify(monitors)) {
+        _ = EVENTS.send((0, EventCommand::Changed(monitors)));
+    }
+}
 
-    let mut lock = MONITOR_MODES.lock().unwrap();
+/// Called once the adapter finished initializing. Arrives the monitors clients sent until now and lets
+/// them know the adapter is ready, both on the executor thread
+pub fn adapter_ready() {
+    match STATE.render_adapter() {
+        Ok(Some(preference)) => select_render_adapter(&preference),
+        Ok(None) => (),
+        Err(e) => error!("Failed to select render adapter: {e}"),
+    }
 
-    // Remove monitors from internal list which are missing from the provided list
+    if JOBS.send(Job::AdapterReady).is_err() {
+        error!("Failed to arrive stored monitors: executor thread is gone");
+    }
+}
 
-    lock.retain_mut(|mon| {
-        let id = mon.data.id;
-        let found = monitors.iter().any(|m| m.id == id);
+/// Makes the preferred gpu render the monitors, keeping the os's choice if none matches
+fn select_render_adapter(preference: &Preference) {
+    let Some(adapter) = ADAPTER.get() else {
+        error!("Failed to select render adapter: adapter is not ready");
+        return;
+    };
 
-        // if it doesn't exist, then add to removal list
-        if !found {
-            // monitor not found in monitors list, so schedule to remove it
-            if let Some(mut obj) = mon.object.take() {
-                // remove any monitors scheduled for removal
-                let obj = unsafe { obj.as_mut() };
-                if let Err(e) = unsafe { IddCxMonitorDeparture(obj) } {
-                    error!("Failed to remove monitor: {e:?}");
-                }
-            }
+    let adapters = match direct_3d_device::render_adapters() {
+        Ok(adapters) => adapters,
+        Err(e) => {
+            error!("Failed to list render adapters: {e}");
+            return;
         }
+    };
 
-        found
-    });
-
-    let should_arrive = monitors
-        .into_iter()
-        .map(|monitor| {
-            let id = monitor.id;
+    let Some(render_adapter) = preference.find(&adapters) else {
+        warn!("No render adapter matches {preference:?}, keeping the current one");
+        return;
+    };
 
-            let should_arrive;
+    let (low_part, high_part) = luid_parts(render_adapter.luid);
+    let args = IDARG_IN_ADAPTERSETRENDERADAPTER {
+        PreferredRenderAdapter: LUID {
+            LowPart: low_part,
+            HighPart: high_part,
+        },
+    };
 
-            let cur_mon = lock.iter_mut().find(|mon| mon.data.id == id);
+    match unsafe { IddCxAdapterSetRenderAdapter(adapter.0.as_ptr(), &args) } {
+        Ok(()) => info!("Selected render adapter {}", render_adapter.description),
+        Err(e) => error!("Failed to select render adapter: {e:?}"),
+    }
+}
 
-            if let Some(mon) = cur_mon {
-                let modes_changed = mon.data.modes != monitor.modes;
+/// Carries out the os calls of a plan, only called on the executor thread right after the plan was made
+fn execute(steps: Vec<Step>) {
+    let mut arrivals = Vec::new();
 
-                #[allow(clippy::nonminimal_bool)]
-                {
-                    should_arrive =
-                        // previously was disabled, and it was just enabled
-                        (!mon.data.enabled && monitor.enabled) ||
-                        // OR monitor is enabled and the display modes changed
-                        (monitor.enabled && modes_changed) ||
-                        // OR monitor is enabled and the monitor was disconnected
-                        (monitor.enabled && mon.object.is_none());
-                }
+    for step in steps {
+        match step {
+            Step::Depart(object) => depart(object),
 
-                // should only detach if modes changed, or if state is false
-                if modes_changed || !monitor.enabled {
-                    if let Some(mut obj) = mon.object.take() {
-                        let obj = unsafe { obj.as_mut() };
-                        if let Err(e) = unsafe { IddCxMonitorDeparture(obj) } {
-                            error!("Failed to remove monitor: {e:?}");
-                        }
-                    }
+            Step::UpdateModes(object, monitor) => {
+                if !update_modes(object, &monitor) {
+                    depart(object);
+                    STATE.departed(monitor.id);
+                    arrivals.push(monitor.id);
                 }
-
-                // update monitor data
-                mon.data = monitor;
-            } else {
-                should_arrive = monitor.enabled;
-
-                lock.push(MonitorObject {
-                    object: None,
-                    data: monitor,
-                });
             }
 
-            (id, should_arrive)
-        })
-        .collect::<Vec<_>>();
+            Step::Arrive(id) => arrivals.push(id),
+        }
+    }
 
-    // context.create_monitor locks again, so this avoids deadlock
-    drop(lock);
+    if !arrivals.is_empty() {
+        // the state only plans arrivals once the adapter is ready
+        let Some(adapter) = ADAPTER.get() else {
+            error!("Failed to create monitors: adapter is not ready");
+            return;
+        };
+        let adapter = adapter.0.as_ptr();
 
-    let cb = |context: &mut DeviceContext| {
-        // arrive any monitors that need arriving
-        for (id, arrive) in should_arrive {
-            if arrive {
+        let cb = |context: &mut DeviceContext| {
+            for id in arrivals {
                 if let Err(e) = context.create_monitor(id) {
-                    error!("Failed to create monitor: {e:?}");
+                    error!(monitor_id = id; "Failed to create monitor: {e:?}");
                 }
             }
-        }
-    };
+        };
 
-    unsafe {
-        DeviceContext::get_mut(adapter.cast(), cb).unwrap();
+        unsafe {
+            DeviceContext::get_mut(adapter.cast(), cb).unwrap();
+        }
     }
 }
 
-fn remove_all() {
-    let mut lock = MONITOR_MODES.lock().unwrap();
-
-    for monitor in lock.drain(..) {
-        if let Some(mut monitor_object) = monitor.object {
-            let obj = unsafe { monitor_object.as_mut() };
-            if let Err(e) = unsafe { IddCxMonitorDeparture(obj) } {
-                error!("Failed to remove monitor: {e:?}");
-            }
-        }
+fn depart(object: MonitorObject) {
+    if let Err(e) = unsafe { IddCxMonitorDeparture(object.0.as_ptr()) } {
+        error!("Failed to remove monitor: {e:?}");
     }
 }
 
-fn remove(ids: &[u32]) {
-    let mut lock = MONITOR_MODES.lock().unwrap();
+/// Replaces the target modes of an arrived monitor in place, which avoids windows rearranging and
+/// flashing the displays like a depart/arrive does
+///
+/// Returns whether the modes were updated
+fn update_modes(object: MonitorObject, monitor: &Monitor) -> bool {
+    let mut target_modes = monitor
+        .modes
+        .flatten()
+        .map(|timing| target_mode(&timing))
+        .collect::<Vec<_>>();
 
-    for &id in ids {
-        lock.retain_mut(|monitor| {
-            if id == monitor.data.id {
-                if let Some(mut monitor_object) = monitor.object.take() {
-                    let obj = unsafe { monitor_object.as_mut() };
-                    if let Err(e) = unsafe { IddCxMonitorDeparture(obj) } {
-                        error!("Failed to remove monitor: {e:?}");
-                    }
-                }
+    let Ok(target_mode_count) = u32::try_from(target_modes.len()) else {
+        return false;
+    };
 
-                false
-            } else {
-                true
-            }
-        });
+    let args = IDARG_IN_UPDATEMODES {
+        Reason: IDDCX_UPDATE_REASON::IDDCX_UPDATE_REASON_OTHER,
+        TargetModeCount: target_mode_count,
+        pTargetModes: target_modes.as_mut_ptr(),
+    };
+
+    match unsafe { IddCxMonitorUpdateModes(object.0.as_ptr(), &args) } {
+        Ok(_) => true,
+
+        // older frameworks need a depart/arrive
+        Err(IddCxError::IddCxFunctionNotAvailable(_)) => false,
+
+        Err(e) => {
+            error!(
+                monitor_id = monitor.id;
+                "Failed to update modes of monitor {}: {e:?}",
+                monitor.id
+            );
+            false
+        }
     }
 }
 
 pub trait FlattenModes {
-    fn flatten(&self) -> impl Iterator<Item = ModeItem>;
+    fn flatten(&self) -> impl Iterator<Item = Timing>;
 }
 
-#[derive(Copy, Clone)]
-pub struct ModeItem {
-    pub width: Dimen,
-    pub height: Dimen,
-    pub refresh_rate: RefreshRate,
-}
-
-/// Takes a slice of modes and creates a flattened structure that can be iterated over
+/// Takes a slice of modes and creates a flattened structure of their timings that can be iterated over
+///
+/// Modes which no timing can be generated for are skipped, just like in the edid
 impl FlattenModes for Vec<Mode> {
-    fn flatten(&self) -> impl Iterator<Item = ModeItem> {
-        self.iter().flat_map(|m| {
-            m.refresh_rates.iter().map(|&rr| ModeItem {
-                width: m.width,
-                height: m.height,
-                refresh_rate: rr,
-            })
-        })
+    fn flatten(&self) -> impl Iterator<Item = Timing> {
+        self.iter().flat_map(Mode::timings)
     }
 }
@@ -1,12 +1,15 @@
 mod helpers;
 
 mod callbacks;
+mod config;
 mod context;
+mod cursor;
 mod direct_3d_device;
-mod edid;
 mod entry;
+mod export;
 mod ipc;
 mod panic;
+mod state;
 mod swap_chain_processor;
 
 use wdf_umdf_sys::{NTSTATUS, PUNICODE_STRING, PVOID};
@@ -1,20 +1,161 @@
-#[cfg(debug_assertions)]
-use std::backtrace::Backtrace;
-use std::panic;
+//! Panic hook, which logs panics and writes a crash report for the last one
+//!
+//! The report is written next to the log files, so it survives the driver host restarting the
+//! driver, and can be requested over ipc afterwards.
 
-use log::error;
+use std::{
+    fs, io,
+    panic::{self, PanicHookInfo},
+    path::{Path, PathBuf},
+    sync::{PoisonError, RwLock},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use backtrace::{Backtrace, BacktraceFrame};
+use driver_ipc::{CrashReport, DEFAULT_PIPE_NAME};
+use driver_logger::{default_log_dir, unix_millis};
+use log::{error, LevelFilter};
+
+use crate::{ipc::log_entry, state::STATE};
+
+// where the report is written, see `set_report_path`
+static REPORT_PATH: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+// most recent log entries a report contains
+const REPORT_LOGS: usize = 200;
+
+// how long to wait for the monitors, the state thread might be the one which panicked
+const MONITORS_TIMEOUT: Duration = Duration::from_secs(1);
 
 pub fn set_hook() {
     panic::set_hook(Box::new(|v| {
         // debug mode, get full backtrace
         #[cfg(debug_assertions)]
         {
-            let backtrace = Backtrace::force_capture();
+            let backtrace = std::backtrace::Backtrace::force_capture();
             error!("{v}\n\nstack backtrace:\n{backtrace}");
         }
 
-        // otherwise just print the panic since we don't have a backtrace
+        // otherwise just print the panic, the symbols are stripped so the backtrace is only
+        // useful in the crash report
         #[cfg(not(debug_assertions))]
         error!("{v}");
+
+        // built after logging, so the logs of the report end with the panic
+        let report = report(v);
+
+        let path = report_path();
+        match write_report(&path, &report) {
+            Ok(()) => error!("Wrote crash report to {}", path.display()),
+            Err(e) => error!("Failed to write crash report to {}: {e}", path.display()),
+        }
     }));
 }
+
+/// Write crash reports to `path` from now on, instead of the default one in
+/// [`default_log_dir`]
+pub fn set_report_path(path: PathBuf) {
+    *REPORT_PATH.write().unwrap_or_else(PoisonError::into_inner) = Some(path);
+}
+
+/// The path of the crash report in `dir` for the driver instance with pipe `name`
+pub fn report_path_in(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{name}.crash.json"))
+}
+
+fn report_path() -> PathBuf {
+    REPORT_PATH
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .unwrap_or_else(|| report_path_in(&default_log_dir(), DEFAULT_PIPE_NAME))
+}
+
+/// The report written when the driver last panicked, `None` if it didn't panic yet
+pub fn last_report() -> io::Result<Option<CrashReport>> {
+    let data = match fs::read(report_path()) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    Ok(Some(serde_json::from_slice(&data)?))
+}
+
+fn write_report(path: &Path, report: &CrashReport) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(path, serde_json::to_vec_pretty(report)?)
+}
+
+fn report(info: &PanicHookInfo) -> CrashReport {
+    let payload = info.payload();
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|s| (*s).to_owned())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".to_owned());
+
+    let mut logs = driver_logger::recent_records(None, LevelFilter::Trace);
+    logs.drain(..logs.len().saturating_sub(REPORT_LOGS));
+
+    CrashReport {
+        timestamp: unix_millis(SystemTime::now()),
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        git_sha: env!("VERGEN_GIT_SHA").to_owned(),
+        thread: thread::current().name().map(ToOwned::to_owned),
+        message,
+        location: info.location().map(ToString::to_string),
+        backtrace: backtrace(),
+        monitors: STATE.monitors_within(MONITORS_TIMEOUT),
+        logs: logs.into_iter().map(log_entry).collect(),
+    }
+}
+
+/// The frames of the current stack, symbolized where the symbols are available
+fn backtrace() -> Vec<String> {
+    let backtrace = Backtrace::new();
+    let mut frames = Vec::new();
+
+    for frame in backtrace.frames() {
+        let symbols = frame.symbols();
+
+        if symbols.iter().all(|symbol| symbol.name().is_none()) {
+            frames.push(address(frame));
+            continue;
+        }
+
+        // inlined functions share a frame, innermost first
+        for symbol in symbols {
+            let name = symbol
+                .name()
+                .map_or_else(|| "<unknown>".to_owned(), |name| name.to_string());
+
+            match (symbol.filename(), symbol.lineno()) {
+                (Some(file), Some(line)) => {
+                    frames.push(format!("{name} at {}:{line}", file.display()));
+                }
+                _ => frames.push(name),
+            }
+        }
+    }
+
+    frames
+}
+
+/// A frame without symbols, with its offset into the module so it can be symbolized later
+fn address(frame: &BacktraceFrame) -> String {
+    let ip = frame.ip() as usize;
+
+    match frame.module_base_address() {
+        Some(base) => format!(
+            "{ip:#x} (module base {:#x} + {:#x})",
+            base as usize,
+            ip.wrapping_sub(base as usize)
+        ),
+        None => format!("{ip:#x}"),
+    }
+}
@@ -0,0 +1,536 @@
+//! Owner of the monitor state of the driver
+//!
+//! All monitors live on a single thread, which the ipc server and the iddcx callbacks talk to over a
+//! channel, so there are no locks to order or to get poisoned. The state thread never calls into the
+//! os itself: those calls re-enter the driver through callbacks, which in turn need the state thread
+//! to answer. Instead, changes hand back the [`Step`]s the caller has to carry out. Plans assume the
+//! steps of earlier plans were carried out already, so making and carrying out a plan must not
+//! interleave with another one. The ipc server leaves both to a single executor thread.
+//!
+//! Until the adapter is ready, monitors can't arrive yet. Changes are still stored, and the arrivals
+//! are handed back by [`State::adapter_ready`] instead.
+
+use std::{
+    ptr::NonNull,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        LazyLock,
+    },
+    thread,
+    time::Duration,
+};
+
+use driver_ipc::{
+    config::Adapter,
+    defaults::DefaultModes,
+    edid::EdidBuilder,
+    reconcile::{self, Action, Current},
+    render_adapter::Preference,
+    timing::Timing,
+    Id, Limits, Mode, Monitor,
+};
+use log::error;
+use wdf_umdf_sys::IDDCX_MONITOR__;
+
+pub static STATE: LazyLock<State> = LazyLock::new(State::spawn);
+
+/// Os object of an arrived monitor
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MonitorObject(pub NonNull<IDDCX_MONITOR__>);
+unsafe impl Sync for MonitorObject {}
+unsafe impl Send for MonitorObject {}
+
+/// A change of the monitors requested by a client
+#[derive(Debug)]
+pub enum Change {
+    Notify(Vec<Monitor>),
+    Remove(Vec<Id>),
+    RemoveAll,
+}
+
+/// Os call needed to carry out a [`Change`]
+#[derive(Debug)]
+pub enum Step {
+    Depart(MonitorObject),
+    // replace the target modes with the ones of the monitor, falling back to depart/arrive
+    UpdateModes(MonitorObject, Monitor),
+    Arrive(Id),
+}
+
+/// The result of a [`Change`]
+#[derive(Debug)]
+pub struct Plan {
+    // steps to carry out in order
+    pub steps: Vec<Step>,
+    // monitors after the change
+    pub monitors: Vec<Monitor>,
+}
+
+enum Command {
+    Change(Change, Sender<Plan>),
+    Monitors(Sender<Vec<Monitor>>),
+    Edid(Id, Sender<Option<Vec<u8>>>),
+    BuildEdid(Id, Sender<Option<Vec<u8>>>),
+    ModesOfId(Id, Sender<Option<Vec<Mode>>>),
+    ModesOfObject(MonitorObject, Sender<Option<Vec<Mode>>>),
+    Arrived(Id, MonitorObject, Vec<u8>),
+    Departed(Id),
+    DefaultModes(Sender<DefaultModes>),
+    SetDefaultModes(DefaultModes),
+    AdapterReady(Sender<Plan>),
+    Limits(Sender<Limits>),
+    SetLimits(Limits),
+    RenderAdapter(Sender<Option<Preference>>),
+    SetRenderAdapter(Option<Preference>, Sender<bool>),
+}
+
+/// Handle to the state thread
+pub struct State {
+    tx: Sender<Command>,
+}
+
+/// The state thread is gone, which only happens if it panicked
+#[derive(Debug, thiserror::Error)]
+#[error("Monitor state thread is gone")]
+pub struct StateGone;
+
+impl State {
+    fn spawn() -> Self {
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || run(&rx));
+
+        Self { tx }
+    }
+
+    fn send(&self, command: Command) {
+        if self.tx.send(command).is_err() {
+            error!("{StateGone}");
+        }
+    }
+
+    fn query<T>(&self, command: impl FnOnce(Sender<T>) -> Command) -> Result<T, StateGone> {
+        let (tx, rx) = mpsc::channel();
+        self.tx.send(command(tx)).map_err(|_| StateGone)?;
+        rx.recv().map_err(|_| StateGone)
+    }
+
+    /// Apply a change to the stored monitors, returning the os calls it needs
+    pub fn change(&self, change: Change) -> Result<Plan, StateGone> {
+        self.query(|tx| Command::Change(change, tx))
+    }
+
+    pub fn monitors(&self) -> Result<Vec<Monitor>, StateGone> {
+        self.query(Command::Monitors)
+    }
+
+    /// The monitors, `None` if the state thread doesn't answer within `timeout`, e.g. because it's
+    /// the thread asking
+    pub fn monitors_within(&self, timeout: Duration) -> Option<Vec<Monitor>> {
+        let (tx, rx) = mpsc::channel();
+        self.tx.send(Command::Monitors(tx)).ok()?;
+        rx.recv_timeout(timeout).ok()
+    }
+
+    /// The edid a monitor was created with, `None` if the monitor does not exist or isn't plugged in
+    pub fn edid(&self, id: Id) -> Result<Option<Vec<u8>>, StateGone> {
+        self.query(|tx| Command::Edid(id, tx))
+    }
+
+    /// Generate the edid to create a monitor with, `None` if the monitor does not exist
+    pub fn build_edid(&self, id: Id) -> Result<Option<Vec<u8>>, StateGone> {
+        self.query(|tx| Command::BuildEdid(id, tx))
+    }
+
+    /// The modes of a monitor, `None` if the monitor does not exist
+    pub fn modes_of_id(&self, id: Id) -> Result<Option<Vec<Mode>>, StateGone> {
+        self.query(|tx| Command::ModesOfId(id, tx))
+    }
+
+    /// The modes of an arrived monitor, `None` if no monitor has this object
+    pub fn modes_of_object(&self, object: MonitorObject) -> Result<Option<Vec<Mode>>, StateGone> {
+        self.query(|tx| Command::ModesOfObject(object, tx))
+    }
+
+    /// Store the os object of a monitor which was created, and the edid it was created with
+    pub fn arrived(&self, id: Id, object: MonitorObject, edid: Vec<u8>) {
+        self.send(Command::Arrived(id, object, edid));
+    }
+
+    /// Forget the os object of a monitor which departed outside of a [`Step::Depart`]
+    pub fn departed(&self, id: Id) {
+        self.send(Command::Departed(id));
+    }
+
+    pub fn default_modes(&self) -> Result<DefaultModes, StateGone> {
+        self.query(Command::DefaultModes)
+    }
+
+    pub fn set_default_modes(&self, defaults: DefaultModes) {
+        self.send(Command::SetDefaultModes(defaults));
+    }
+
+    pub fn limits(&self) -> Result<Limits, StateGone> {
+        self.query(Command::Limits)
+    }
+
+    /// Set the limits of the adapter, which are fixed once it is initialized
+    pub fn set_limits(&self, limits: Limits) {
+        self.send(Command::SetLimits(limits));
+    }
+
+    pub fn render_adapter(&self) -> Result<Option<Preference>, StateGone> {
+        self.query(Command::RenderAdapter)
+    }
+
+    /// Set the preferred render adapter, returning whether the adapter is ready already. Otherwise the
+    /// preference is picked up once it is
+    pub fn set_render_adapter(&self, preference: Option<Preference>) -> Result<bool, StateGone> {
+        self.query(|tx| Command::SetRenderAdapter(preference, tx))
+    }
+
+    /// Mark the adapter as ready, returning the arrivals of the monitors which were stored before
+    pub fn adapter_ready(&self) -> Result<Plan, StateGone> {
+        self.query(Command::AdapterReady)
+    }
+}
+
+struct Entry {
+    object: Option<MonitorObject>,
+    // timings of the monitor description (edid) the monitor arrived with
+    description: Vec<Timing>,
+    // the edid itself, renames and in place mode updates don't send a new one
+    edid: Option<Vec<u8>>,
+    data: Monitor,
+}
+
+struct Monitors {
+    entries: Vec<Entry>,
+    // modes of monitors which were sent without any
+    default_modes: DefaultModes,
+    // monitors can only arrive once the adapter is ready
+    adapter_ready: bool,
+    limits: Limits,
+    // gpu which should render the monitors
+    render_adapter: Option<Preference>,
+}
+
+impl Default for Monitors {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            default_modes: DefaultModes::default(),
+            adapter_ready: false,
+            limits: Adapter::default().limits(),
+            render_adapter: None,
+        }
+    }
+}
+
+fn run(rx: &Receiver<Command>) {
+    let mut monitors = Monitors::default();
+
+    // a send error only means the asking side gave up waiting, nothing to do about that
+    for command in rx {
+        match command {
+            Command::Change(change, tx) => _ = tx.send(monitors.change(change)),
+
+            Command::Monitors(tx) => _ = tx.send(monitors.data()),
+
+            Command::Edid(id, tx) => {
+                let edid = monitors.find(id).and_then(|e| e.edid.clone());
+                _ = tx.send(edid);
+            }
+
+            Command::BuildEdid(id, tx) => {
+                let edid = monitors
+                    .find(id)
+                    .map(|e| EdidBuilder::from_monitor(&e.data).build());
+                _ = tx.send(edid);
+            }
+
+            Command::ModesOfId(id, tx) => {
+                let modes = monitors.find(id).map(|e| e.data.modes.clone());
+                _ = tx.send(modes);
+            }
+
+            Command::ModesOfObject(object, tx) => {
+                let modes = monitors
+                    .entries
+                    .iter()
+                    .find(|e| e.object == Some(object))
+                    .map(|e| e.data.modes.clone());
+                _ = tx.send(modes);
+            }
+
+            Command::Arrived(id, object, edid) => {
+                if let Some(entry) = monitors.find_mut(id) {
+                    entry.object = Some(object);
+                    entry.description = entry.data.modes.iter().flat_map(Mode::timings).collect();
+                    entry.edid = Some(edid);
+                } else {
+                    error!("Monitor {id} arrived, but it is unknown");
+                }
+            }
+
+            Command::Departed(id) => {
+                if let Some(entry) = monitors.find_mut(id) {
+                    entry.object = None;
+                    entry.edid = None;
+                }
+            }
+
+            Command::DefaultModes(tx) => _ = tx.send(monitors.default_modes.clone()),
+
+            Command::SetDefaultModes(defaults) => monitors.default_modes = defaults,
+
+            Command::AdapterReady(tx) => _ = tx.send(monitors.adapter_ready()),
+
+            Command::Limits(tx) => _ = tx.send(monitors.limits),
+
+            Command::SetLimits(limits) => monitors.limits = limits,
+
+            Command::RenderAdapter(tx) => _ = tx.send(monitors.render_adapter.clone()),
+
+            Command::SetRenderAdapter(preference, tx) => {
+                monitors.render_adapter = preference;
+                _ = tx.send(monitors.adapter_ready);
+            }
+        }
+    }
+}
+
+impl Monitors {
+    fn find(&self, id: Id) -> Option<&Entry> {
+        self.entries.iter().find(|e| e.data.id == id)
+    }
+
+    fn find_mut(&mut self, id: Id) -> Option<&mut Entry> {
+        self.entries.iter_mut().find(|e| e.data.id == id)
+    }
+
+    fn data(&self) -> Vec<Monitor> {
+        self.entries.iter().map(|e| e.data.clone()).collect()
+    }
+
+    fn change(&mut self, change: Change) -> Plan {
+        let desired = match change {
+            Change::Notify(mut monitors) => {
+                self.default_modes.fill(&mut monitors);
+                monitors
+            }
+
+            Change::Remove(ids) => self
+                .data()
+                .into_iter()
+                .filter(|m| !ids.contains(&m.id))
+                .collect(),
+
+            Change::RemoveAll => Vec::new(),
+        };
+
+        let actions = {
+            let current = self
+                .entries
+                .iter()
+                .map(|e| Current {
+                    monitor: &e.data,
+                    arrived: e.object.is_some(),
+                    description: &e.description,
+                })
+                .collect::<Vec<_>>();
+
+            reconcile::plan(&current, &desired)
+        };
+
+        let steps = actions
+            .into_iter()
+            .filter_map(|action| match action {
+                // departed monitors lose their object right away
+                Action::Depart(id) => self
+                    .find_mut(id)
+                    .and_then(|e| {
+                        e.edid = None;
+                        e.object.take()
+                    })
+                    .map(Step::Depart),
+
+                Action::UpdateModes(id) => {
+                    let object = self.find(id)?.object?;
+                    let monitor = desired.iter().find(|m| m.id == id)?.clone();
+                    Some(Step::UpdateModes(object, monitor))
+                }
+
+                // only the data changes, which is stored below
+                Action::Rename(_) => None,
+
+                // arrives later in `adapter_ready`
+                Action::Arrive(id) => self.adapter_ready.then_some(Step::Arrive(id)),
+            })
+            .collect();
+
+        // keep the os objects of the remaining monitors
+        let mut previous = std::mem::take(&mut self.entries);
+        self.entries = desired
+            .iter()
+            .map(|data| {
+                let idx = previous.iter().position(|e| e.data.id == data.id);
+
+                match idx {
+                    Some(idx) => Entry {
+                        data: data.clone(),
+                        ..previous.swap_remove(idx)
+                    },
+
+                    None => Entry {
+                        object: None,
+                        description: Vec::new(),
+                        edid: None,
+                        data: data.clone(),
+                    },
+                }
+            })
+            .collect();
+
+        Plan {
+            steps,
+            monitors: desired,
+        }
+    }
+
+    fn adapter_ready(&mut self) -> Plan {
+        self.adapter_ready = true;
+
+        let steps = self
+            .entries
+            .iter()
+            .filter(|e| e.data.enabled && e.object.is_none())
+            .map(|e| Step::Arrive(e.data.id))
+            .collect();
+
+        Plan {
+            steps,
+            monitors: self.data(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use driver_ipc::edid::Edid;
+
+    use super::*;
+
+    fn monitor(id: Id, modes: Vec<Mode>) -> Monitor {
+        Monitor {
+            id,
+            name: None,
+            enabled: true,
+            modes,
+        }
+    }
+
+    fn object(addr: usize) -> MonitorObject {
+        MonitorObject(NonNull::new(addr as *mut IDDCX_MONITOR__).unwrap())
+    }
+
+    fn arrive(monitors: &mut Monitors, id: Id, object: MonitorObject) {
+        let entry = monitors.find_mut(id).unwrap();
+        entry.object = Some(object);
+        entry.description = entry.data.modes.iter().flat_map(Mode::timings).collect();
+        entry.edid = Some(EdidBuilder::from_monitor(&entry.data).build());
+    }
+
+    #[test]
+    fn change() {
+        let mut monitors = Monitors {
+            adapter_ready: true,
+            ..Default::default()
+        };
+
+        // monitors without modes get the default ones
+        let plan = monitors.change(Change::Notify(vec![monitor(1, vec![]), monitor(2, vec![])]));
+        assert!(matches!(plan.steps[..], [Step::Arrive(1), Step::Arrive(2)]));
+        assert_eq!(plan.monitors[0].modes, DefaultModes::default().modes());
+        assert_eq!(monitors.data(), plan.monitors);
+
+        arrive(&mut monitors, 1, object(8));
+        arrive(&mut monitors, 2, object(16));
+
+        // a mode of the description is updated in place
+        let mut modes = DefaultModes::default().modes();
+        modes.truncate(1);
+        let plan = monitors.change(Change::Notify(vec![
+            monitor(1, modes),
+            plan.monitors[1].clone(),
+        ]));
+        assert!(matches!(plan.steps[..], [Step::UpdateModes(o, _)] if o == object(8)));
+        assert_eq!(monitors.find(1).unwrap().object, Some(object(8)));
+
+        // removing departs and forgets the object
+        let plan = monitors.change(Change::Remove(vec![1]));
+        assert!(matches!(plan.steps[..], [Step::Depart(o)] if o == object(8)));
+        assert!(monitors.find(1).is_none());
+
+        let plan = monitors.change(Change::RemoveAll);
+        assert!(matches!(plan.steps[..], [Step::Depart(o)] if o == object(16)));
+        assert!(monitors.entries.is_empty() && plan.monitors.is_empty());
+    }
+
+    #[test]
+    fn edid_is_kept_until_departure() {
+        let mut monitors = Monitors {
+            adapter_ready: true,
+            ..Default::default()
+        };
+
+        let mut named = Monitor {
+            name: Some("Before".to_owned()),
+            ..monitor(1, vec![])
+        };
+        monitors.change(Change::Notify(vec![named.clone()]));
+        arrive(&mut monitors, 1, object(8));
+
+        let edid = monitors.find(1).unwrap().edid.clone().unwrap();
+        assert_eq!(Edid::parse(&edid).unwrap().name(), Some("Before"));
+
+        // windows only got the edid the monitor was created with
+        named.name = Some("After".to_owned());
+        let plan = monitors.change(Change::Notify(vec![named.clone()]));
+        assert!(plan.steps.is_empty());
+        assert_eq!(monitors.find(1).unwrap().edid.as_ref(), Some(&edid));
+
+        named.enabled = false;
+        let plan = monitors.change(Change::Notify(vec![named]));
+        assert!(matches!(plan.steps[..], [Step::Depart(o)] if o == object(8)));
+        assert!(monitors.find(1).unwrap().edid.is_none());
+    }
+
+    #[test]
+    fn queued_until_adapter_ready() {
+        let mut monitors = Monitors::default();
+
+        let mut disabled = monitor(3, vec![]);
+        disabled.enabled = false;
+
+        // stored, but nothing can arrive yet
+        let plan = monitors.change(Change::Notify(vec![
+            monitor(1, vec![]),
+            monitor(2, vec![]),
+            disabled,
+        ]));
+        assert!(plan.steps.is_empty());
+        assert_eq!(plan.monitors.len(), 3);
+
+        let plan = monitors.change(Change::Remove(vec![2]));
+        assert!(plan.steps.is_empty());
+
+        let plan = monitors.adapter_ready();
+        assert!(matches!(plan.steps[..], [Step::Arrive(1)]));
+        assert_eq!(plan.monitors, monitors.data());
+
+        // from now on changes arrive right away
+        let plan = monitors.change(Change::Notify(vec![monitor(1, vec![]), monitor(2, vec![])]));
+        assert!(matches!(plan.steps[..], [Step::Arrive(1), Step::Arrive(2)]));
+    }
+}
@@ -1,11 +1,15 @@
 use std::{
+    collections::BTreeMap,
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, LazyLock, Mutex, OnceLock, PoisonError,
     },
     thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
 };
 
+use driver_ipc::{config::Eco, FrameStats, Id};
+use driver_logger::unix_millis;
 use log::{debug, error};
 use wdf_umdf::{
     IddCxSwapChainFinishedProcessingFrame, IddCxSwapChainReleaseAndAcquireBuffer,
@@ -16,7 +20,7 @@ use wdf_umdf_sys::{
     NTSTATUS, WAIT_TIMEOUT, WDFOBJECT,
 };
 use windows::{
-    core::{w, Interface},
+    core::{Interface, HSTRING, PCWSTR},
     Win32::{
         Foundation::HANDLE as WHANDLE,
         Graphics::Dxgi::IDXGIDevice,
@@ -26,7 +30,154 @@ use windows::{
     },
 };
 
-use crate::{direct_3d_device::Direct3DDevice, helpers::Sendable};
+use crate::{
+    direct_3d_device::Direct3DDevice,
+    export::{Exporter, FrameExport},
+    helpers::Sendable,
+};
+
+// counters of the monitors which were created, by id
+static COUNTERS: LazyLock<Mutex<BTreeMap<Id, Arc<FrameCounters>>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+// how long frames are counted to get the frames per second
+const FPS_WINDOW: Duration = Duration::from_secs(1);
+
+// milliseconds to wait for a frame before polling again, if no mode was committed
+const DEFAULT_WAIT: u32 = 16;
+
+// longest sleep while throttled, so stopping the processor isn't delayed
+const THROTTLE_STEP: Duration = Duration::from_millis(50);
+
+// throttling of monitors whose frames aren't exported, unset if they aren't throttled
+static ECO: OnceLock<Eco> = OnceLock::new();
+
+/// Throttle the monitors whose frames aren't exported
+pub fn set_eco(eco: Eco) {
+    _ = ECO.set(eco);
+}
+
+/// How a swap chain processor paces itself, from the refresh rate its monitor was committed with
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Pacing {
+    // in hertz, `None` if no mode was committed
+    refresh_rate: Option<f64>,
+}
+
+impl Pacing {
+    pub fn new(refresh_rate: Option<f64>) -> Self {
+        Self {
+            refresh_rate: refresh_rate.filter(|hz| hz.is_finite() && *hz > 0.0),
+        }
+    }
+
+    /// Milliseconds to wait for a frame before polling again, a refresh period
+    fn wait_timeout(self) -> u32 {
+        self.refresh_rate.map_or(DEFAULT_WAIT, |hz| {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let ms = (1000.0 / hz).ceil() as u32;
+            ms.clamp(1, 100)
+        })
+    }
+
+    /// Multimedia Scheduler Service task of the thread, higher refresh rates get a higher priority
+    fn mmcss_task(self) -> &'static str {
+        match self.refresh_rate {
+            Some(hz) if hz >= 100.0 => "Games",
+            Some(hz) if hz < 50.0 => "Playback",
+            _ => "Distribution",
+        }
+    }
+}
+
+/// Frame counters of a monitor, shared by the swap chain processors of it
+///
+/// The processors only touch atomics, so counting doesn't slow down the frame loop.
+#[derive(Debug, Default)]
+pub struct FrameCounters {
+    frames: AtomicU64,
+    timeouts: AtomicU64,
+    pending_waits: AtomicU64,
+    restarts: AtomicU64,
+    // milliseconds since the unix epoch, 0 before the first frame
+    last_frame: AtomicU64,
+    // bits of the f64 frames per second of the last window
+    fps: AtomicU64,
+}
+
+impl FrameCounters {
+    /// New counters for the monitor `id`, which replace the ones of an earlier monitor with that id
+    pub fn register(id: Id) -> Arc<Self> {
+        let counters = Arc::new(Self::default());
+
+        COUNTERS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(id, counters.clone());
+
+        counters
+    }
+
+    fn set_fps(&self, fps: f64) {
+        self.fps.store(fps.to_bits(), Ordering::Relaxed);
+    }
+
+    fn stats(&self, id: Id) -> FrameStats {
+        let last_frame = self.last_frame.load(Ordering::Relaxed);
+
+        FrameStats {
+            id,
+            frames: self.frames.load(Ordering::Relaxed),
+            fps: f64::from_bits(self.fps.load(Ordering::Relaxed)),
+            timeouts: self.timeouts.load(Ordering::Relaxed),
+            pending_waits: self.pending_waits.load(Ordering::Relaxed),
+            restarts: self.restarts.load(Ordering::Relaxed),
+            last_frame: (last_frame != 0).then_some(last_frame),
+        }
+    }
+}
+
+/// The frame statistics of the monitors `ids`, in that order
+///
+/// Counters of other monitors are dropped, those monitors departed.
+pub fn frame_stats(ids: &[Id]) -> Vec<FrameStats> {
+    let mut counters = COUNTERS.lock().unwrap_or_else(PoisonError::into_inner);
+
+    counters.retain(|id, _| ids.contains(id));
+
+    ids.iter()
+        .filter_map(|&id| counters.get(&id).map(|counters| counters.stats(id)))
+        .collect()
+}
+
+/// Frames counted since the window started, to get the frames per second
+struct FpsWindow {
+    start: Instant,
+    frames: u64,
+}
+
+impl FpsWindow {
+    fn new(counters: &FrameCounters) -> Self {
+        Self {
+            start: Instant::now(),
+            frames: counters.frames.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Update the frames per second once the window is over, and start the next one
+    fn update(&mut self, counters: &FrameCounters) {
+        let elapsed = self.start.elapsed();
+        if elapsed < FPS_WINDOW {
+            return;
+        }
+
+        let frames = counters.frames.load(Ordering::Relaxed);
+        #[allow(clippy::cast_precision_loss)]
+        counters.set_fps((frames - self.frames) as f64 / elapsed.as_secs_f64());
+
+        *self = Self::new(counters);
+    }
+}
 
 pub struct SwapChainProcessor {
     terminate: Arc<AtomicBool>,
@@ -49,6 +200,9 @@ impl SwapChainProcessor {
         swap_chain: IDDCX_SWAPCHAIN,
         device: Direct3DDevice,
         available_buffer_event: HANDLE,
+        counters: Arc<FrameCounters>,
+        export: Arc<FrameExport>,
+        pacing: Pacing,
     ) {
         let available_buffer_event = unsafe { Sendable::new(available_buffer_event) };
         let swap_chain = unsafe { Sendable::new(swap_chain) };
@@ -57,14 +211,28 @@ impl SwapChainProcessor {
         let join_handle = thread::spawn(move || {
             // It is very important to prioritize this thread by making use of the Multimedia Scheduler Service.
             // It will intelligently prioritize the thread for improved throughput in high CPU-load scenarios.
+            let task = HSTRING::from(pacing.mmcss_task());
             let mut av_task = 0u32;
-            let res = unsafe { AvSetMmThreadCharacteristicsW(w!("Distribution"), &mut av_task) };
+            let res = unsafe { AvSetMmThreadCharacteristicsW(PCWSTR(task.as_ptr()), &mut av_task) };
             let Ok(av_handle) = res else {
                 error!("Failed to prioritize thread: {res:?}");
                 return;
             };
 
-            Self::run_core(*swap_chain, &device, *available_buffer_event, &terminate);
+            let mut exporter = Exporter::new(export, &device);
+
+            Self::run_core(
+                *swap_chain,
+                &device,
+                *available_buffer_event,
+                &terminate,
+                &counters,
+                &mut exporter,
+                pacing,
+            );
+
+            // no frames are acquired until the next swap chain is assigned
+            counters.set_fps(0.0);
 
             let res = unsafe { WdfObjectDelete(*swap_chain as WDFOBJECT) };
             if let Err(e) = res {
@@ -87,6 +255,9 @@ impl SwapChainProcessor {
         device: &Direct3DDevice,
         available_buffer_event: HANDLE,
         terminate: &AtomicBool,
+        counters: &FrameCounters,
+        exporter: &mut Exporter,
+        pacing: Pacing,
     ) {
         let dxgi_device = device.device.cast::<IDXGIDevice>();
         let Ok(dxgi_device) = dxgi_device else {
@@ -104,7 +275,15 @@ impl SwapChainProcessor {
             return;
         }
 
+        let mut fps_window = FpsWindow::new(counters);
+        let eco_interval = ECO
+            .get()
+            .map(|eco| Duration::from_secs(1) / eco.max_fps.max(1));
+
         loop {
+            fps_window.update(counters);
+            exporter.stop_if_disabled();
+
             let mut buffer = IDARG_OUT_RELEASEANDACQUIREBUFFER::default();
             let hr: NTSTATUS =
                 unsafe { IddCxSwapChainReleaseAndAcquireBuffer(swap_chain, &mut buffer).into() };
@@ -112,8 +291,19 @@ impl SwapChainProcessor {
             #[allow(clippy::items_after_statements)]
             const E_PENDING: u32 = 0x8000_000A;
             if u32::from(hr) == E_PENDING {
-                let wait_result =
-                    unsafe { WaitForSingleObject(WHANDLE(available_buffer_event.cast()), 16).0 };
+                counters.pending_waits.fetch_add(1, Ordering::Relaxed);
+
+                let wait_result = unsafe {
+                    WaitForSingleObject(
+                        WHANDLE(available_buffer_event.cast()),
+                        pacing.wait_timeout(),
+                    )
+                    .0
+                };
+
+                if wait_result == WAIT_TIMEOUT {
+                    counters.timeouts.fetch_add(1, Ordering::Relaxed);
+                }
 
                 // thread requested an end
                 let should_terminate = terminate.load(Ordering::Relaxed);
@@ -130,6 +320,15 @@ impl SwapChainProcessor {
                 // The wait was cancelled or something unexpected happened
                 break;
             } else if hr.is_success() {
+                let acquired = Instant::now();
+
+                counters.frames.fetch_add(1, Ordering::Relaxed);
+                counters
+                    .last_frame
+                    .store(unix_millis(SystemTime::now()), Ordering::Relaxed);
+
+                exporter.export(&buffer);
+
                 // This is the most performance-critical section of code in an IddCx driver. It's important that whatever
                 // is done with the acquired surface be finished as quickly as possible.
                 let hr = unsafe { IddCxSwapChainFinishedProcessingFrame(swap_chain) };
@@ -137,14 +336,39 @@ impl SwapChainProcessor {
                 if hr.is_err() {
                     break;
                 }
+
+                // monitors nobody captures only get the frames eco allows
+                if let Some(interval) = eco_interval.filter(|_| !exporter.exporting()) {
+                    if throttle(acquired, interval, terminate) {
+                        break;
+                    }
+                }
             } else {
                 // The swap-chain was likely abandoned (e.g. DXGI_ERROR_ACCESS_LOST), so exit the processing loop
+                // and let the os assign a new one
+                counters.restarts.fetch_add(1, Ordering::Relaxed);
                 break;
             }
         }
     }
 }
 
+/// Sleep until `interval` passed since `since`, returns whether the processor should terminate
+fn throttle(since: Instant, interval: Duration, terminate: &AtomicBool) -> bool {
+    loop {
+        if terminate.load(Ordering::Relaxed) {
+            return true;
+        }
+
+        match interval.checked_sub(since.elapsed()) {
+            Some(remaining) if !remaining.is_zero() => {
+                thread::sleep(remaining.min(THROTTLE_STEP));
+            }
+            _ => return false,
+        }
+    }
+}
+
 impl Drop for SwapChainProcessor {
     fn drop(&mut self) {
         if let Some(handle) = self.thread.take() {
@@ -156,3 +380,87 @@ impl Drop for SwapChainProcessor {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stats() {
+        let first = FrameCounters::register(1001);
+        let second = FrameCounters::register(1002);
+        FrameCounters::register(1003);
+
+        first.frames.store(120, Ordering::Relaxed);
+        first.last_frame.store(1_700_000_000_000, Ordering::Relaxed);
+        first.set_fps(59.5);
+        second.restarts.store(1, Ordering::Relaxed);
+
+        let stats = frame_stats(&[1002, 1001]);
+        assert_eq!(stats.len(), 2);
+
+        assert_eq!(stats[0].id, 1002);
+        assert_eq!(stats[0].restarts, 1);
+        assert_eq!(stats[0].last_frame, None);
+
+        assert_eq!(stats[1].id, 1001);
+        assert_eq!(stats[1].frames, 120);
+        assert!((stats[1].fps - 59.5).abs() < f64::EPSILON);
+        assert_eq!(stats[1].last_frame, Some(1_700_000_000_000));
+
+        // unknown ids are skipped, the order of the others is kept
+        let ids = frame_stats(&[1004, 1001, 1002])
+            .iter()
+            .map(|stats| stats.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, [1001, 1002]);
+
+        // departed monitors are dropped, every monitor which was left out departed
+        assert!(frame_stats(&[1003]).is_empty());
+        assert!(frame_stats(&[1001, 1002]).is_empty());
+    }
+
+    #[test]
+    fn pacing() {
+        let cases = [
+            (None, DEFAULT_WAIT, "Distribution"),
+            (Some(0.0), DEFAULT_WAIT, "Distribution"),
+            (Some(24.0), 42, "Playback"),
+            (Some(59.94), 17, "Distribution"),
+            (Some(60.0), 17, "Distribution"),
+            (Some(144.0), 7, "Games"),
+            (Some(1.0), 100, "Playback"),
+        ];
+
+        for (refresh_rate, wait, task) in cases {
+            let pacing = Pacing::new(refresh_rate);
+            assert_eq!(pacing.wait_timeout(), wait, "{refresh_rate:?}");
+            assert_eq!(pacing.mmcss_task(), task, "{refresh_rate:?}");
+        }
+    }
+
+    #[test]
+    fn throttling() {
+        let terminate = AtomicBool::new(false);
+
+        let start = Instant::now();
+        assert!(!throttle(start, Duration::from_millis(20), &terminate));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        // already passed
+        let start = Instant::now();
+        assert!(!throttle(
+            start - Duration::from_secs(1),
+            Duration::from_millis(20),
+            &terminate
+        ));
+        assert!(start.elapsed() < Duration::from_millis(20));
+
+        terminate.store(true, Ordering::Relaxed);
+        assert!(throttle(
+            Instant::now(),
+            Duration::from_secs(10),
+            &terminate
+        ));
+    }
+}
@@ -4,11 +4,12 @@
 use std::sync::OnceLock;
 
 use wdf_umdf_sys::{
-    IDARG_IN_ADAPTER_INIT, IDARG_IN_MONITORCREATE, IDARG_IN_QUERY_HWCURSOR,
-    IDARG_IN_SETUP_HWCURSOR, IDARG_IN_SWAPCHAINSETDEVICE, IDARG_OUT_ADAPTER_INIT,
-    IDARG_OUT_MONITORARRIVAL, IDARG_OUT_MONITORCREATE, IDARG_OUT_QUERY_HWCURSOR,
-    IDARG_OUT_RELEASEANDACQUIREBUFFER, IDDCX_ADAPTER, IDDCX_MONITOR, IDDCX_SWAPCHAIN,
-    IDD_CX_CLIENT_CONFIG, NTSTATUS, WDFDEVICE, WDFDEVICE_INIT,
+    IDARG_IN_ADAPTERSETRENDERADAPTER, IDARG_IN_ADAPTER_INIT, IDARG_IN_MONITORCREATE,
+    IDARG_IN_QUERY_HWCURSOR, IDARG_IN_SETUP_HWCURSOR, IDARG_IN_SWAPCHAINSETDEVICE,
+    IDARG_IN_UPDATEMODES, IDARG_OUT_ADAPTER_INIT, IDARG_OUT_MONITORARRIVAL,
+    IDARG_OUT_MONITORCREATE, IDARG_OUT_QUERY_HWCURSOR, IDARG_OUT_RELEASEANDACQUIREBUFFER,
+    IDDCX_ADAPTER, IDDCX_MONITOR, IDDCX_SWAPCHAIN, IDD_CX_CLIENT_CONFIG, NTSTATUS, WDFDEVICE,
+    WDFDEVICE_INIT,
 };
 
 #[derive(Copy, Clone, Debug, thiserror::Error)]
@@ -19,6 +20,15 @@ pub enum IddCxError {
     CallFailed(NTSTATUS),
     #[error("{0}")]
     NtStatus(NTSTATUS),
+    // this is required for success status for ()
+    #[error("This is not an error, ignore it")]
+    _Success,
+}
+
+impl From<()> for IddCxError {
+    fn from((): ()) -> Self {
+        IddCxError::_Success
+    }
 }
 
 impl From<IddCxError> for NTSTATUS {
@@ -29,6 +39,7 @@ impl From<IddCxError> for NTSTATUS {
             IddCxFunctionNotAvailable(_) => Self::STATUS_NOT_FOUND,
             CallFailed(status) => status,
             NtStatus(n) => n,
+            _Success => 0.into(),
         }
     }
 }
@@ -152,6 +163,24 @@ pub unsafe fn IddCxAdapterInitAsync(
     }
 }
 
+/// # Safety
+///
+/// None. User is responsible for safety.
+#[rustfmt::skip]
+pub unsafe fn IddCxAdapterSetRenderAdapter(
+    // in
+    AdapterObject: IDDCX_ADAPTER,
+    // in
+    pInArgs: &IDARG_IN_ADAPTERSETRENDERADAPTER
+) -> Result<(), IddCxError> {
+    IddCxCall!(
+        IddCxAdapterSetRenderAdapter(
+            AdapterObject,
+            pInArgs
+        )
+    )
+}
+
 /// # Safety
 ///
 /// None. User is responsible for safety.
@@ -260,6 +289,29 @@ pub unsafe fn IddCxMonitorDeparture(
     )
 }
 
+/// Replace the target modes of a monitor without it departing.
+///
+/// Returns [`IddCxError::IddCxFunctionNotAvailable`] on frameworks older than
+/// IddCx 1.4.
+///
+/// # Safety
+///
+/// None. User is responsible for safety.
+#[rustfmt::skip]
+pub unsafe fn IddCxMonitorUpdateModes(
+    // in
+    MonitorObject: IDDCX_MONITOR,
+    // in
+    pInArgs: &IDARG_IN_UPDATEMODES
+) -> Result<NTSTATUS, IddCxError> {
+    IddCxCall!(
+        IddCxMonitorUpdateModes(
+            MonitorObject,
+            pInArgs
+        )
+    )
+}
+
 /// # Safety
 ///
 /// None. User is responsible for safety.
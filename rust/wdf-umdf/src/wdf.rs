@@ -5,10 +5,11 @@ use std::ffi::c_void;
 use std::sync::OnceLock;
 
 use wdf_umdf_sys::{
-    DEVPROPTYPE, NTSTATUS, PCUNICODE_STRING, PCWDF_OBJECT_CONTEXT_TYPE_INFO, PDRIVER_OBJECT,
-    POOL_TYPE, PWDFDEVICE_INIT, PWDF_DRIVER_CONFIG, PWDF_OBJECT_ATTRIBUTES, WDFDEVICE, WDFDRIVER,
-    WDFMEMORY, WDFOBJECT, WDF_DEVICE_FAILED_ACTION, WDF_NO_HANDLE, WDF_NO_OBJECT_ATTRIBUTES,
-    WDF_OBJECT_ATTRIBUTES, _WDF_DEVICE_PROPERTY_DATA, _WDF_PNPPOWER_EVENT_CALLBACKS,
+    ACCESS_MASK, DEVPROPTYPE, NTSTATUS, PCUNICODE_STRING, PCWDF_OBJECT_CONTEXT_TYPE_INFO,
+    PDRIVER_OBJECT, POOL_TYPE, PWDFDEVICE_INIT, PWDF_DRIVER_CONFIG, PWDF_OBJECT_ATTRIBUTES, ULONG,
+    WDFDEVICE, WDFDRIVER, WDFKEY, WDFMEMORY, WDFOBJECT, WDF_DEVICE_FAILED_ACTION, WDF_NO_HANDLE,
+    WDF_NO_OBJECT_ATTRIBUTES, WDF_OBJECT_ATTRIBUTES, _WDF_DEVICE_PROPERTY_DATA,
+    _WDF_PNPPOWER_EVENT_CALLBACKS,
 };
 
 #[derive(Copy, Clone, Debug, thiserror::Error)]
@@ -592,3 +593,72 @@ pub unsafe fn WdfMemoryGetBuffer(
         )
     }
 }
+
+/// # Safety
+///
+/// None. User is responsible for safety.
+pub unsafe fn WdfDeviceOpenRegistryKey(
+    // in
+    Device: WDFDEVICE,
+    // in
+    DeviceInstanceKeyType: ULONG,
+    // in
+    DesiredAccess: ACCESS_MASK,
+    // in, optional
+    KeyAttributes: Option<PWDF_OBJECT_ATTRIBUTES>,
+    // out
+    Key: &mut WDFKEY,
+) -> Result<NTSTATUS, WdfError> {
+    WdfCall! {
+        WdfDeviceOpenRegistryKey(
+            Device,
+            DeviceInstanceKeyType,
+            DesiredAccess,
+            KeyAttributes.unwrap_or(WDF_NO_OBJECT_ATTRIBUTES!()),
+            Key
+        )
+    }
+}
+
+/// # Safety
+///
+/// None. User is responsible for safety.
+pub unsafe fn WdfRegistryQueryMemory(
+    // in
+    Key: WDFKEY,
+    // in
+    ValueName: PCUNICODE_STRING,
+    // in
+    PoolType: POOL_TYPE,
+    // in, optional
+    MemoryAttributes: Option<PWDF_OBJECT_ATTRIBUTES>,
+    // out
+    Memory: &mut WDFMEMORY,
+    // out, optional
+    ValueType: Option<&mut ULONG>,
+) -> Result<NTSTATUS, WdfError> {
+    WdfCall! {
+        WdfRegistryQueryMemory(
+            Key,
+            ValueName,
+            PoolType,
+            MemoryAttributes.unwrap_or(WDF_NO_OBJECT_ATTRIBUTES!()),
+            Memory,
+            ValueType.map_or(std::ptr::null_mut(), std::ptr::from_mut)
+        )
+    }
+}
+
+/// # Safety
+///
+/// None. User is responsible for safety.
+pub unsafe fn WdfRegistryClose(
+    // in
+    Key: WDFKEY,
+) -> Result<(), WdfError> {
+    WdfCall! {
+        WdfRegistryClose(
+            Key
+        )
+    }
+}